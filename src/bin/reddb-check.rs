@@ -0,0 +1,65 @@
+//! Checks integrity of a database file and prints a report.
+//!
+//! Usage: `reddb-check [--repair] <database file>`. The file and its log are opened read-only and
+//! are never changed: a file which was not closed properly is recovered in memory. With
+//! `--repair`, the file is opened for writing, and indexes which do not match the rows of their
+//! tables are rebuilt and the file is checked again. Exits with code 1 if problems are found and
+//! with code 2 if the file can not be checked at all.
+
+extern crate reddb;
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+use reddb::database::Database;
+use reddb::database::DatabaseConfiguration;
+use reddb::database::CorruptionPolicy;
+use reddb::Error;
+
+fn check(path: &Path, repair: bool) -> Result<bool, Error> {
+    // Corrupted pages are quarantined, so the rest of the file can still be checked.
+    let mut cfg = DatabaseConfiguration::new();
+    cfg.set_corruption_policy(CorruptionPolicy::Quarantine);
+    cfg.set_checkpoint_interval(None)?;
+    cfg.set_read_only(!repair);
+
+    let database = Database::open_with(path, &cfg)?;
+    let mut report = database.verify()?;
+
+    print!("{}", report);
+
+    if repair && report.has_index_problem() {
+        database.repair_indexes()?;
+        println!("Indexes were rebuilt");
+
+        report = database.verify()?;
+        print!("{}", report);
+    }
+
+    database.close()?;
+
+    Ok(report.is_ok())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let (repair, path) = match args.len() {
+        2 => (false, &args[1]),
+        3 if args[1] == "--repair" => (true, &args[2]),
+        _ => {
+            eprintln!("Usage: reddb-check [--repair] <database file>");
+            process::exit(2);
+        }
+    };
+
+    match check(Path::new(path), repair) {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("Unable to check database file '{}': {}", path, e);
+            process::exit(2);
+        }
+    }
+}
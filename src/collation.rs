@@ -0,0 +1,73 @@
+use std::cmp::Ordering;
+
+/// Rules of comparison of VARCHAR values.
+#[derive(Debug, Copy, Clone, Default, Ord, Eq, PartialOrd, PartialEq)]
+pub enum Collation {
+    /// Compares UTF-8 bytes of the strings, so characters are ordered by their code points.
+    #[default]
+    Binary,
+    /// Compares lower case forms of the strings, so "ABC" is equal to "abc".
+    CaseInsensitive,
+}
+
+impl Collation {
+    /// Compares the strings according to the collation.
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Binary => a.as_bytes().cmp(b.as_bytes()),
+            Collation::CaseInsensitive => {
+                a.chars().flat_map(char::to_lowercase).cmp(b.chars().flat_map(char::to_lowercase))
+            }
+        }
+    }
+}
+
+/// Direction in which values are sorted.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum SortOrder {
+    /// From the least value to the greatest.
+    #[default]
+    Ascending,
+    /// From the greatest value to the least.
+    Descending,
+}
+
+impl SortOrder {
+    /// Applies the order to the result of ascending comparison.
+    pub fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+
+    use collation::{Collation, SortOrder};
+
+    #[test]
+    fn compare_strings() {
+        assert_eq!(Collation::Binary.compare("abc", "abd"), Ordering::Less);
+        assert_eq!(Collation::Binary.compare("ab", "a"), Ordering::Greater);
+        assert_eq!(Collation::Binary.compare("B", "a"), Ordering::Less);
+        assert_eq!(Collation::Binary.compare("Straße", "Straße"), Ordering::Equal);
+        assert_eq!(Collation::Binary.compare("z", "é"), Ordering::Less);
+
+        assert_eq!(Collation::CaseInsensitive.compare("ABC", "abc"), Ordering::Equal);
+        assert_eq!(Collation::CaseInsensitive.compare("B", "a"), Ordering::Greater);
+        assert_eq!(Collation::CaseInsensitive.compare("ÄB", "äb"), Ordering::Equal);
+        assert_eq!(Collation::CaseInsensitive.compare("a", "AB"), Ordering::Less);
+        assert_eq!(Collation::default(), Collation::Binary);
+    }
+
+    #[test]
+    fn apply_sort_order() {
+        assert_eq!(SortOrder::Ascending.apply(Ordering::Less), Ordering::Less);
+        assert_eq!(SortOrder::Descending.apply(Ordering::Less), Ordering::Greater);
+        assert_eq!(SortOrder::Descending.apply(Ordering::Equal), Ordering::Equal);
+        assert_eq!(SortOrder::default(), SortOrder::Ascending);
+    }
+}
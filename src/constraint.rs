@@ -0,0 +1,425 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_type::DataType;
+use value::Value;
+use protocol::serialize_stream::SerializeStream;
+use protocol::deserialize_stream::DeserializeStream;
+use error::Error;
+
+/// Default value of the column, which the inserted row gets if it has no value for the column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefaultValue {
+    /// Row gets NULL.
+    Null,
+    /// Row gets the constant value.
+    Constant(Value),
+    /// Row gets the time of the insert as the number of milliseconds since the Unix epoch.
+    /// Only BIGINT column can have such default value.
+    CurrentTimestamp,
+}
+
+impl DefaultValue {
+    /// Gets value for the inserted row.
+    pub fn evaluate(&self) -> Value {
+        match *self {
+            DefaultValue::Null => Value::Null,
+            DefaultValue::Constant(ref value) => value.clone(),
+            DefaultValue::CurrentTimestamp => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                Value::Bigint(now.as_millis() as i64)
+            }
+        }
+    }
+
+    /// Gets data type of the values, `None` if the value is always NULL.
+    pub fn data_type(&self) -> Option<DataType> {
+        match *self {
+            DefaultValue::Null => None,
+            DefaultValue::Constant(ref value) => value.data_type(),
+            DefaultValue::CurrentTimestamp => Some(DataType::BIGINT),
+        }
+    }
+
+    /// Converts constant integer value to the wider integer type, see `Value::widen`.
+    pub fn widen(self, data_type: DataType) -> DefaultValue {
+        match self {
+            DefaultValue::Constant(value) => DefaultValue::Constant(value.widen(data_type)),
+            default => default,
+        }
+    }
+
+    /// Gets number of bytes needed to serialize the default value.
+    pub fn serialized_len(&self) -> usize {
+        match *self {
+            DefaultValue::Constant(ref value) => 2 + value.serialized_len(),
+            _ => 2,
+        }
+    }
+
+    /// Writes code of the kind of the default value followed by the constant, if any.
+    /// Constant is written as a value of the column type.
+    pub fn write(&self, stream: &mut SerializeStream) -> Result<(), Error> {
+        match *self {
+            DefaultValue::Null => stream.write_smallint(0),
+            DefaultValue::Constant(ref value) => {
+                stream.write_smallint(1)?;
+                stream.write_value(value)
+            }
+            DefaultValue::CurrentTimestamp => stream.write_smallint(2),
+        }
+    }
+
+    /// Reads default value of the column of the type.
+    pub fn read(stream: &mut DeserializeStream, data_type: DataType)
+                -> Result<DefaultValue, Error> {
+        match stream.read_smallint()? {
+            0 => Ok(DefaultValue::Null),
+            1 => Ok(DefaultValue::Constant(stream.read_value(data_type)?)),
+            2 => Ok(DefaultValue::CurrentTimestamp),
+            code => Err(Error::Corruption(format!("Unknown kind of default value: {}", code))),
+        }
+    }
+}
+
+/// CHECK constraint of the column: condition, which every value of the column must satisfy.
+/// NULL satisfies any condition, NOT NULL constraint is used to forbid it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    Equal(Value),
+    NotEqual(Value),
+    Less(Value),
+    LessOrEqual(Value),
+    Greater(Value),
+    GreaterOrEqual(Value),
+    /// Value is equal to one of the values.
+    In(Vec<Value>),
+    /// Length of VARCHAR value in characters or of VARBINARY value in bytes does not exceed
+    /// the limit.
+    MaxLength(u32),
+    Not(Box<Check>),
+    /// Every condition is satisfied.
+    All(Vec<Check>),
+    /// At least one condition is satisfied.
+    Any(Vec<Check>),
+}
+
+impl Check {
+    /// Check if the value satisfies the condition.
+    pub fn test(&self, value: &Value) -> bool {
+        if value.is_null() {
+            return true;
+        }
+
+        match *self {
+            Check::Equal(ref c) => value == c,
+            Check::NotEqual(ref c) => value != c,
+            Check::Less(ref c) => value < c,
+            Check::LessOrEqual(ref c) => value <= c,
+            Check::Greater(ref c) => value > c,
+            Check::GreaterOrEqual(ref c) => value >= c,
+            Check::In(ref values) => values.iter().any(|c| value == c),
+            Check::MaxLength(max) => {
+                match *value {
+                    Value::Varchar(ref s) => s.chars().count() <= max as usize,
+                    Value::Varbinary(ref b) => b.len() <= max as usize,
+                    _ => false,
+                }
+            }
+            Check::Not(ref check) => !check.test(value),
+            Check::All(ref checks) => checks.iter().all(|check| check.test(value)),
+            Check::Any(ref checks) => checks.iter().any(|check| check.test(value)),
+        }
+    }
+
+    /// Check if the condition can be applied to values of the type: constants are not NULL and
+    /// are comparable with the values, length is limited only for VARCHAR and VARBINARY.
+    pub fn applies_to(&self, data_type: DataType) -> bool {
+        let comparable = |c: &Value| match c.data_type() {
+            Some(t) if is_integer(t) => is_integer(data_type),
+            Some(t) => t == data_type,
+            None => false,
+        };
+
+        match *self {
+            Check::Equal(ref c) |
+            Check::NotEqual(ref c) |
+            Check::Less(ref c) |
+            Check::LessOrEqual(ref c) |
+            Check::Greater(ref c) |
+            Check::GreaterOrEqual(ref c) => comparable(c),
+            Check::In(ref values) => values.iter().all(comparable),
+            Check::MaxLength(_) => {
+                data_type == DataType::VARCHAR || data_type == DataType::VARBINARY
+            }
+            Check::Not(ref check) => check.applies_to(data_type),
+            Check::All(ref checks) |
+            Check::Any(ref checks) => checks.iter().all(|check| check.applies_to(data_type)),
+        }
+    }
+
+    /// Gets number of bytes needed to serialize the condition.
+    pub fn serialized_len(&self) -> usize {
+        let constant_len = |c: &Value| 2 + c.serialized_len();
+
+        2 +
+        match *self {
+            Check::Equal(ref c) |
+            Check::NotEqual(ref c) |
+            Check::Less(ref c) |
+            Check::LessOrEqual(ref c) |
+            Check::Greater(ref c) |
+            Check::GreaterOrEqual(ref c) => constant_len(c),
+            Check::In(ref values) => 4 + values.iter().map(constant_len).sum::<usize>(),
+            Check::MaxLength(_) => 4,
+            Check::Not(ref check) => check.serialized_len(),
+            Check::All(ref checks) |
+            Check::Any(ref checks) => 4 + checks.iter().map(Check::serialized_len).sum::<usize>(),
+        }
+    }
+
+    /// Writes code of the condition followed by its operands. Every constant is written as
+    /// code of its data type followed by the value, lists are prefixed with their length.
+    pub fn write(&self, stream: &mut SerializeStream) -> Result<(), Error> {
+        let write_constant = |stream: &mut SerializeStream, c: &Value| {
+            // Constants are never NULL, see `applies_to`.
+            stream.write_smallint(c.data_type().unwrap().code() as i16)?;
+            stream.write_value(c)
+        };
+
+        stream.write_smallint(self.code())?;
+
+        match *self {
+            Check::Equal(ref c) |
+            Check::NotEqual(ref c) |
+            Check::Less(ref c) |
+            Check::LessOrEqual(ref c) |
+            Check::Greater(ref c) |
+            Check::GreaterOrEqual(ref c) => write_constant(stream, c),
+            Check::In(ref values) => {
+                stream.write_int(values.len() as i32)?;
+
+                for c in values {
+                    write_constant(stream, c)?;
+                }
+
+                Ok(())
+            }
+            Check::MaxLength(max) => stream.write_int(max as i32),
+            Check::Not(ref check) => check.write(stream),
+            Check::All(ref checks) |
+            Check::Any(ref checks) => {
+                stream.write_int(checks.len() as i32)?;
+
+                for check in checks {
+                    check.write(stream)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Gets code of the kind of the condition, which is written before its operands.
+    fn code(&self) -> i16 {
+        match *self {
+            Check::Equal(_) => 1,
+            Check::NotEqual(_) => 2,
+            Check::Less(_) => 3,
+            Check::LessOrEqual(_) => 4,
+            Check::Greater(_) => 5,
+            Check::GreaterOrEqual(_) => 6,
+            Check::In(_) => 7,
+            Check::MaxLength(_) => 8,
+            Check::Not(_) => 9,
+            Check::All(_) => 10,
+            Check::Any(_) => 11,
+        }
+    }
+
+    /// Reads condition written by `write`.
+    pub fn read(stream: &mut DeserializeStream) -> Result<Check, Error> {
+        let read_constant = |stream: &mut DeserializeStream| {
+            let code = stream.read_smallint()? as u16;
+
+            match DataType::from_code(code) {
+                Some(data_type) => stream.read_value(data_type),
+                None => Err(Error::Corruption(format!("Unknown data type of constant: {}", code))),
+            }
+        };
+
+        Ok(match stream.read_smallint()? {
+            1 => Check::Equal(read_constant(stream)?),
+            2 => Check::NotEqual(read_constant(stream)?),
+            3 => Check::Less(read_constant(stream)?),
+            4 => Check::LessOrEqual(read_constant(stream)?),
+            5 => Check::Greater(read_constant(stream)?),
+            6 => Check::GreaterOrEqual(read_constant(stream)?),
+            7 => {
+                let len = stream.read_int()?;
+                Check::In((0..len).map(|_| read_constant(stream)).collect::<Result<_, _>>()?)
+            }
+            8 => Check::MaxLength(stream.read_int()? as u32),
+            9 => Check::Not(Box::new(Check::read(stream)?)),
+            10 => Check::All(Check::read_list(stream)?),
+            11 => Check::Any(Check::read_list(stream)?),
+            code => return Err(Error::Corruption(format!("Unknown kind of check: {}", code))),
+        })
+    }
+
+    fn read_list(stream: &mut DeserializeStream) -> Result<Vec<Check>, Error> {
+        let len = stream.read_int()?;
+        (0..len).map(|_| Check::read(stream)).collect()
+    }
+}
+
+/// Formats the condition like an SQL expression over the column value.
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter, checks: &[Check], sep: &str| {
+            write!(f, "(")?;
+
+            for (i, check) in checks.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", sep)?;
+                }
+
+                write!(f, "{}", check)?;
+            }
+
+            write!(f, ")")
+        };
+
+        match *self {
+            Check::Equal(ref c) => write!(f, "VALUE = {:?}", c),
+            Check::NotEqual(ref c) => write!(f, "VALUE <> {:?}", c),
+            Check::Less(ref c) => write!(f, "VALUE < {:?}", c),
+            Check::LessOrEqual(ref c) => write!(f, "VALUE <= {:?}", c),
+            Check::Greater(ref c) => write!(f, "VALUE > {:?}", c),
+            Check::GreaterOrEqual(ref c) => write!(f, "VALUE >= {:?}", c),
+            Check::In(ref values) => write!(f, "VALUE IN {:?}", values),
+            Check::MaxLength(max) => write!(f, "LENGTH(VALUE) <= {}", max),
+            Check::Not(ref check) => write!(f, "NOT ({})", check),
+            Check::All(ref checks) => join(f, checks, "AND"),
+            Check::Any(ref checks) => join(f, checks, "OR"),
+        }
+    }
+}
+
+/// Action of the foreign key, which is taken when the referenced row is deleted or its key
+/// is changed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReferentialAction {
+    /// Change fails if there are rows which refer to the row.
+    Restrict,
+    /// Rows which refer to the row are deleted, or their keys are changed with the row.
+    Cascade,
+    /// Columns of the foreign key are set to NULL in the rows which refer to the row.
+    SetNull,
+    /// Columns of the foreign key are set to their default values in the rows which refer
+    /// to the row. Rows with the default values must exist.
+    SetDefault,
+}
+
+impl ReferentialAction {
+    /// Get code of the action, stored in the catalog.
+    pub fn code(self) -> i16 {
+        match self {
+            ReferentialAction::Restrict => 1,
+            ReferentialAction::Cascade => 2,
+            ReferentialAction::SetNull => 3,
+            ReferentialAction::SetDefault => 4,
+        }
+    }
+
+    /// Get action by its code.
+    pub fn from_code(code: i16) -> Option<ReferentialAction> {
+        match code {
+            1 => Some(ReferentialAction::Restrict),
+            2 => Some(ReferentialAction::Cascade),
+            3 => Some(ReferentialAction::SetNull),
+            4 => Some(ReferentialAction::SetDefault),
+            _ => None,
+        }
+    }
+}
+
+fn is_integer(data_type: DataType) -> bool {
+    matches!(data_type, DataType::SMALLINT | DataType::INTEGER | DataType::BIGINT)
+}
+
+#[cfg(test)]
+mod test {
+    use constraint::{Check, DefaultValue};
+    use data_type::DataType;
+    use value::Value;
+    use storage::MemoryPage;
+    use protocol::serialize_stream::SerializeStream;
+    use protocol::deserialize_stream::DeserializeStream;
+
+    #[test]
+    fn test_values() {
+        let check = Check::All(vec![Check::GreaterOrEqual(Value::from(0i64)),
+                                    Check::Not(Box::new(Check::In(vec![Value::from(13)])))]);
+
+        assert!(check.test(&Value::from(7i16)));
+        assert!(check.test(&Value::Null));
+        assert!(!check.test(&Value::from(-1)));
+        assert!(!check.test(&Value::from(13i64)));
+
+        assert!(Check::MaxLength(3).test(&Value::from("äöü")));
+        assert!(!Check::MaxLength(3).test(&Value::from(vec![0u8; 4])));
+        assert!(Check::Any(vec![Check::Less(Value::from(0.0)), Check::Equal(Value::from(1.0))])
+            .test(&Value::from(1.0)));
+
+        assert_eq!(check.to_string(),
+                   "(VALUE >= Bigint(0) AND NOT (VALUE IN [Integer(13)]))");
+    }
+
+    #[test]
+    fn validate_checks() {
+        assert!(Check::Less(Value::from(10i16)).applies_to(DataType::BIGINT));
+        assert!(Check::MaxLength(10).applies_to(DataType::VARBINARY));
+        assert!(!Check::MaxLength(10).applies_to(DataType::INTEGER));
+        assert!(!Check::Equal(Value::from("a")).applies_to(DataType::INTEGER));
+        assert!(!Check::Equal(Value::Null).applies_to(DataType::INTEGER));
+        assert!(!Check::Any(vec![Check::In(vec![Value::from(1), Value::from(1.0)])])
+            .applies_to(DataType::INTEGER));
+    }
+
+    #[test]
+    fn write_read_checks() {
+        let check = Check::Any(vec![Check::All(vec![Check::Greater(Value::from(1)),
+                                                    Check::LessOrEqual(Value::from(5i64))]),
+                                    Check::In(vec![Value::from(-1i16), Value::from(100)]),
+                                    Check::Not(Box::new(Check::NotEqual(Value::from(7)))),
+                                    Check::MaxLength(3)]);
+
+        let mut page = MemoryPage::new(check.serialized_len());
+        check.write(&mut SerializeStream::new(&mut page, 0)).unwrap();
+
+        let mut stream = DeserializeStream::new(&page, 0);
+        let read = Check::read(&mut stream).unwrap();
+
+        assert_eq!(read, check);
+        assert_eq!(read.to_string(), check.to_string());
+        assert_eq!(stream.position(), page.data().len());
+    }
+
+    #[test]
+    fn evaluate_defaults() {
+        assert!(DefaultValue::Null.evaluate().is_null());
+        assert_eq!(DefaultValue::Constant(Value::from("a")).evaluate(), Value::from("a"));
+        assert!(DefaultValue::CurrentTimestamp.evaluate() > Value::from(1_500_000_000_000i64));
+
+        let default = DefaultValue::Constant(Value::from(3i16)).widen(DataType::BIGINT);
+
+        let mut page = MemoryPage::new(default.serialized_len());
+        default.write(&mut SerializeStream::new(&mut page, 0)).unwrap();
+
+        let read = DefaultValue::read(&mut DeserializeStream::new(&page, 0), DataType::BIGINT)
+            .unwrap();
+        assert_eq!(read.data_type(), Some(DataType::BIGINT));
+        assert_eq!(read, default);
+    }
+}
@@ -22,4 +22,40 @@ impl DataType {
             DataType::FLOAT => 8,
         }
     }
+
+    /// Check if every value of the type can be converted to the other type without loss:
+    /// SMALLINT to INTEGER or BIGINT and INTEGER to BIGINT.
+    pub fn widens_to(self, other: DataType) -> bool {
+        matches!((self, other),
+                 (DataType::SMALLINT, DataType::INTEGER) |
+                 (DataType::SMALLINT, DataType::BIGINT) |
+                 (DataType::INTEGER, DataType::BIGINT))
+    }
+
+    /// Get code of the data type, stored in the catalog.
+    pub fn code(self) -> u16 {
+        match self {
+            DataType::VARCHAR => 1,
+            DataType::VARBINARY => 2,
+            DataType::BOOLEAN => 3,
+            DataType::SMALLINT => 4,
+            DataType::INTEGER => 5,
+            DataType::BIGINT => 6,
+            DataType::FLOAT => 7,
+        }
+    }
+
+    /// Get data type by its code.
+    pub fn from_code(code: u16) -> Option<DataType> {
+        match code {
+            1 => Some(DataType::VARCHAR),
+            2 => Some(DataType::VARBINARY),
+            3 => Some(DataType::BOOLEAN),
+            4 => Some(DataType::SMALLINT),
+            5 => Some(DataType::INTEGER),
+            6 => Some(DataType::BIGINT),
+            7 => Some(DataType::FLOAT),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,462 @@
+use std::sync::Arc;
+use data_type::DataType;
+use database::{Database, MAX_SCHEMA_VERSIONS};
+use database::schema::{Column, Schema};
+use database::index::IndexDefinition;
+use database::foreign_key::ForeignKeyDefinition;
+use database::table::Table;
+use error::Error;
+
+/// Changes of the table schema, which are validated as they are added. Created by
+/// `Table::alter` and applied by `Database::alter_table` as a new schema version.
+#[derive(Debug, Clone)]
+pub struct AlterTable {
+    table: String,
+    /// Schema version the changes are based on.
+    version: u32,
+    next_column_id: u32,
+    /// Columns of the new schema version in the record order.
+    columns: Vec<Arc<Column>>,
+    /// Indexes of the table, their key columns can not be dropped.
+    indexes: Vec<IndexDefinition>,
+    /// Foreign keys of the table, their columns can be neither dropped nor widened.
+    foreign_keys: Vec<ForeignKeyDefinition>,
+    /// Columns referenced by foreign keys of other tables with names of the keys, they can not
+    /// be widened.
+    referenced: Vec<(u32, String)>,
+}
+
+impl AlterTable {
+    /// Adds new column. Rows written before the column was added get the default value of
+    /// the column evaluated now, so it must satisfy the constraints of the column: NOT NULL
+    /// column can be added only with a default value.
+    pub fn add_column(&mut self, column: Column) -> Result<(), Error> {
+        if self.position(&column.name).is_some() {
+            return Err(Error::DuplicateColumn {
+                table: self.table.clone(),
+                column: column.name,
+            });
+        }
+
+        column.validate(&self.table)?;
+
+        let initial = column.default.evaluate();
+        column.check_value(&self.table, &initial)?;
+
+        self.columns.push(Arc::new(Column {
+            id: self.next_column_id,
+            initial,
+            ..column
+        }));
+
+        self.next_column_id += 1;
+        Ok(())
+    }
+
+    /// Drops column. Values of the column are removed from the rows when they are updated.
+    /// Column of an index or of a foreign key can not be dropped.
+    pub fn drop_column(&mut self, name: &str) -> Result<(), Error> {
+        let pos = self.user_column(name)?;
+        let id = self.columns[pos].id;
+
+        if let Some(index) = self.indexes.iter().find(|index| index.columns.contains(&id)) {
+            return Err(Error::InvalidArgument(format!("Column '{}' of table '{}' is used by \
+                                                       index '{}'",
+                                                      name,
+                                                      self.table,
+                                                      index.name)));
+        }
+
+        self.check_foreign_keys(pos)?;
+        self.columns.remove(pos);
+
+        Ok(())
+    }
+
+    /// Drops foreign key of the table by its name.
+    pub fn drop_foreign_key(&mut self, name: &str) -> Result<(), Error> {
+        match self.foreign_keys.iter().position(|key| key.name == name) {
+            Some(pos) => {
+                self.foreign_keys.remove(pos);
+                Ok(())
+            }
+            None => {
+                Err(Error::ForeignKeyNotFound {
+                    table: self.table.clone(),
+                    foreign_key: name.to_owned(),
+                })
+            }
+        }
+    }
+
+    /// Renames column.
+    pub fn rename_column(&mut self, name: &str, new_name: &str) -> Result<(), Error> {
+        let pos = self.user_column(name)?;
+
+        if self.position(new_name).is_some() {
+            return Err(Error::DuplicateColumn {
+                table: self.table.clone(),
+                column: new_name.to_owned(),
+            });
+        }
+
+        self.columns[pos] = Arc::new(Column {
+            name: new_name.to_owned(),
+            ..(*self.columns[pos]).clone()
+        });
+
+        Ok(())
+    }
+
+    /// Widens type of the column: SMALLINT to INTEGER or BIGINT, INTEGER to BIGINT.
+    /// Values stored in the rows are widened when they are read. Indexes with the column
+    /// are rebuilt. Column of a foreign key or referenced by one can not be widened.
+    pub fn widen_column(&mut self, name: &str, data_type: DataType) -> Result<(), Error> {
+        let pos = self.user_column(name)?;
+        self.check_foreign_keys(pos)?;
+
+        let id = self.columns[pos].id;
+
+        if let Some((_, key)) = self.referenced.iter().find(|r| r.0 == id) {
+            return Err(Error::InvalidArgument(format!("Column '{}' of table '{}' is referenced \
+                                                       by foreign key '{}'",
+                                                      name,
+                                                      self.table,
+                                                      key)));
+        }
+
+        let column = (*self.columns[pos]).clone();
+
+        if !column.data_type.widens_to(data_type) {
+            return Err(Error::InvalidArgument(format!("Unable to change type of column '{}' in \
+                                                       table '{}' from {:?} to {:?}",
+                                                      name,
+                                                      self.table,
+                                                      column.data_type,
+                                                      data_type)));
+        }
+
+        self.columns[pos] = Arc::new(Column {
+            data_type,
+            default: column.default.widen(data_type),
+            initial: column.initial.widen(data_type),
+            ..column
+        });
+
+        Ok(())
+    }
+
+    /// Check that the column at the position is not used by a foreign key.
+    fn check_foreign_keys(&self, pos: usize) -> Result<(), Error> {
+        let column = &self.columns[pos];
+
+        match self.foreign_keys.iter().find(|key| key.columns.contains(&column.id)) {
+            Some(key) => {
+                Err(Error::InvalidArgument(format!("Column '{}' of table '{}' is used by foreign \
+                                                    key '{}'",
+                                                   column.name,
+                                                   self.table,
+                                                   key.name)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Gets position of the column by its name.
+    fn position(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    /// Gets position of the non-system column by its name.
+    fn user_column(&self, name: &str) -> Result<usize, Error> {
+        match self.position(name) {
+            Some(pos) if !self.columns[pos].system => Ok(pos),
+            _ => {
+                Err(Error::ColumnNotFound {
+                    table: self.table.clone(),
+                    column: name.to_owned(),
+                })
+            }
+        }
+    }
+}
+
+impl Table {
+    /// Starts changing schema of the table. Changes are applied by `Database::alter_table`.
+    pub fn alter(&self) -> AlterTable {
+        let schema = self.schema();
+
+        AlterTable {
+            table: self.name.clone(),
+            version: schema.version(),
+            next_column_id: schema.next_column_id(),
+            columns: schema.record_columns().to_vec(),
+            indexes: self.index_definitions(),
+            foreign_keys: self.foreign_key_definitions(),
+            referenced: self.referenced_columns(),
+        }
+    }
+}
+
+impl Database {
+    /// Applies changes of the table schema. Rows of the table are not rewritten, see
+    /// `AlterTable`. Fails if the table was altered after the changes were created.
+    pub fn alter_table(&mut self, alter: AlterTable) -> Result<(), Error> {
+        let table = self.user_table(&alter.table)?;
+        let schema = table.schema();
+
+        // Indexes created after the changes were made may use the columns they drop.
+        if schema.version() != alter.version || table.index_definitions() != alter.indexes {
+            return Err(Error::InvalidArgument(format!("Table '{}' was altered after the changes \
+                                                       were created",
+                                                      alter.table)));
+        }
+
+        let foreign_keys = table.foreign_key_definitions();
+
+        let dropped: Vec<_> = foreign_keys.iter()
+            .filter(|key| !alter.foreign_keys.contains(key))
+            .map(|key| key.id)
+            .collect();
+
+        let altered = alter.columns != schema.record_columns();
+
+        if !altered && dropped.is_empty() {
+            return Ok(());
+        }
+
+        if altered && schema.versions.len() >= MAX_SCHEMA_VERSIONS {
+            return Err(Error::OutOfSpace(format!("Table '{}' has too many schema versions",
+                                                 alter.table)));
+        }
+
+        let txn = self.storage.begin();
+
+        // New version and foreign keys are published while the transaction is active, so no
+        // operation of the table can start in between.
+        if altered {
+            let mut versions = schema.versions.clone();
+            versions.push(alter.columns);
+
+            *table.schema.write().unwrap() = Arc::new(Schema::with_versions(versions));
+        }
+
+        *table.foreign_keys.write().unwrap() = alter.foreign_keys;
+
+        // Indexes are rebuilt if types of their key columns were widened.
+        let res = if altered {
+            self.insert_column_rows(&txn, &table, schema.version() + 1)
+                .and_then(|_| table.rebuild_indexes(&txn))
+        } else {
+            Ok(())
+        };
+
+        let res = res.and_then(|_| {
+                self.delete_catalog_rows(&txn,
+                                         &[("_foreign_keys", "id", &dropped),
+                                           ("_foreign_key_columns", "foreign_key_id", &dropped)])
+            });
+
+        let res = match res {
+            Ok(()) => txn.commit(),
+            Err(e) => {
+                txn.rollback()
+                    .and_then(|pages| self.refresh_system_tables(&pages))
+                    .and(Err(e))
+            }
+        };
+
+        // Rows of the table were not changed and trees of the indexes were restored by
+        // the rollback, so only the previous version and foreign keys have to be restored.
+        if res.is_err() {
+            *table.schema.write().unwrap() = schema;
+            *table.foreign_keys.write().unwrap() = foreign_keys;
+            table.refresh_indexes();
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_type::DataType;
+    use row::Row;
+    use database::Database;
+    use database::schema::{Column, ColumnBuilder};
+    use database::table::TableConfiguration;
+    use error::Error;
+
+    #[test]
+    fn alter_table_columns() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::SMALLINT, false)).expect("should not fail");
+        cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let text = "text".repeat(5000);
+        let old = table.insert(&Row::new().with("foo", 1i16).with("bar", text.clone()))
+            .expect("should not fail");
+        let pages = table.storage.pages(table.id).len();
+
+        let mut alter = table.alter();
+        let baz = ColumnBuilder::new("baz", DataType::VARCHAR).default_value("default").build();
+        alter.add_column(baz).expect("should not fail");
+        alter.drop_column("bar").expect("should not fail");
+        alter.rename_column("foo", "qux").expect("should not fail");
+        alter.widen_column("qux", DataType::BIGINT).expect("should not fail");
+
+        database.alter_table(alter).expect("should not fail");
+
+        // Handle obtained before the change sees the new schema, the row is not rewritten.
+        let row = table.get(old).expect("should not fail");
+        assert_eq!(row, Row::new().with("baz", "default").with("qux", 1i64));
+        assert_eq!(row["qux"].data_type(), Some(DataType::BIGINT));
+        assert_eq!(table.storage.pages(table.id).len(), pages);
+
+        let new = table.insert(&Row::new().with("qux", 2i64)).expect("should not fail");
+        table.insert(&Row::new().with("qux", 3i16)).unwrap_err();
+        table.insert(&Row::new().with("bar", "bar")).unwrap_err();
+
+        let rows: Vec<_> = table.scan()
+            .expect("should not fail")
+            .map(|r| r.expect("should not fail"))
+            .collect();
+        assert_eq!(rows,
+                   vec![Row::new().with("baz", "default").with("qux", 1i64),
+                        Row::new().with("baz", "default").with("qux", 2i64)]);
+
+        // Row is upgraded to the current version on update, so the value of the dropped column
+        // and its overflow pages are removed.
+        table.update(old, &Row::new().with("baz", "changed")).expect("should not fail");
+        assert_eq!(table.storage.pages(table.id).len(), 1);
+
+        assert_eq!(table.get(old).expect("should not fail"),
+                   Row::new().with("baz", "changed").with("qux", 1i64));
+        assert_eq!(table.get(new).expect("should not fail"),
+                   Row::new().with("baz", "default").with("qux", 2i64));
+
+        assert!(database.verify().expect("should not fail").is_ok());
+
+        // Unchanged schema does not create a new version.
+        let alter = table.alter();
+        database.alter_table(alter).expect("should not fail");
+        assert_eq!(table.schema().version(), 1);
+    }
+
+    #[test]
+    fn alter_table_errors() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+        let mut alter = table.alter();
+
+        match alter.add_column(Column::new("foo", DataType::INTEGER, false)) {
+            Err(Error::DuplicateColumn { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let column = ColumnBuilder::new("baz", DataType::INTEGER).default_value("baz").build();
+
+        match alter.add_column(column) {
+            Err(Error::TypeMismatch { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match alter.drop_column("_flags") {
+            Err(Error::ColumnNotFound { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match alter.rename_column("foo", "bar") {
+            Err(Error::DuplicateColumn { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match alter.rename_column("baz", "qux") {
+            Err(Error::ColumnNotFound { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match alter.widen_column("foo", DataType::SMALLINT) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        alter.drop_column("bar").expect("should not fail");
+
+        // Changes based on an outdated schema are rejected.
+        let mut other = table.alter();
+        other.rename_column("bar", "baz").expect("should not fail");
+
+        database.alter_table(alter).expect("should not fail");
+
+        match database.alter_table(other) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        database.drop_table("SomeTable").expect("should not fail");
+
+        match database.alter_table(table.alter()) {
+            Err(Error::TableNotFound(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn persist_altered_tables() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("persist_altered_tables");
+
+        {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            let mut cfg = TableConfiguration::new("SomeTable");
+            cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+            cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+
+            let table = database.create_table(cfg).expect("should not fail");
+            table.insert(&Row::new().with("foo", 1).with("bar", "bar")).expect("should not fail");
+
+            let mut alter = table.alter();
+            alter.drop_column("foo").expect("should not fail");
+            let foo = ColumnBuilder::new("foo", DataType::BIGINT).default_value(7i64).build();
+            alter.add_column(foo).expect("should not fail");
+            database.alter_table(alter).expect("should not fail");
+
+            table.insert(&Row::new().with("foo", 2i64)).expect("should not fail");
+
+            let mut alter = table.alter();
+            alter.rename_column("bar", "baz").expect("should not fail");
+            database.alter_table(alter).expect("should not fail");
+        }
+
+        {
+            let database = Database::open(&path).expect("should not fail");
+
+            let table = database.table("SomeTable").expect("should not fail");
+            let rows: Vec<_> = table.scan()
+                .expect("should not fail")
+                .map(|r| r.expect("should not fail"))
+                .collect();
+
+            // Column which was dropped and added again does not see the old values.
+            assert_eq!(rows,
+                       vec![Row::new().with("baz", "bar").with("foo", 7i64),
+                            Row::new().with("baz", None::<String>).with("foo", 2i64)]);
+            assert_eq!(table.schema().version(), 2);
+            assert!(database.verify().expect("should not fail").is_ok());
+        }
+
+        remove_database(&path);
+    }
+}
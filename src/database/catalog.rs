@@ -0,0 +1,729 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use data_type::DataType;
+use value::Value;
+use row::Row;
+use constraint::{Check, DefaultValue, ReferentialAction};
+use collation::SortOrder;
+use storage::Storage;
+use storage::MemoryPage;
+use storage::Transaction;
+use protocol::serialize_stream::SerializeStream;
+use protocol::deserialize_stream::DeserializeStream;
+use database::{Database, SharedTables};
+use database::schema::{Column, Schema};
+use database::index::IndexDefinition;
+use database::foreign_key::ForeignKeyDefinition;
+use database::table::{Table, TableConfiguration};
+use error::Error;
+
+/// System tables with their columns. Id of the system table is its position in the list plus
+/// one, user tables get larger ids.
+///
+/// `_tables` holds id and name of every user table. `_columns` holds every column of every
+/// schema version of the user tables with its position in the record. `_indexes` holds indexes
+/// of the user tables with root pages of their trees and `_index_columns` holds key columns of
+/// every index with their positions in the key and sort orders. `_foreign_keys` holds foreign
+/// keys of the user tables with the referenced tables and their unique indexes, and
+/// `_foreign_key_columns` holds columns of every foreign key with their positions in
+/// the referenced key.
+pub const SYSTEM_TABLES: [(&str, &[(&str, DataType)]); 6] = [
+    ("_tables", &[("id", DataType::INTEGER), ("name", DataType::VARCHAR)]),
+    ("_columns",
+     &[("table_id", DataType::INTEGER),
+       ("version", DataType::INTEGER),
+       ("position", DataType::INTEGER),
+       ("id", DataType::INTEGER),
+       ("name", DataType::VARCHAR),
+       ("data_type", DataType::SMALLINT),
+       ("system", DataType::BOOLEAN),
+       ("nullable", DataType::BOOLEAN),
+       ("default", DataType::VARBINARY),
+       ("initial", DataType::VARBINARY),
+       ("checks", DataType::VARBINARY)]),
+    ("_indexes",
+     &[("id", DataType::INTEGER),
+       ("table_id", DataType::INTEGER),
+       ("name", DataType::VARCHAR),
+       ("unique", DataType::BOOLEAN),
+       ("primary", DataType::BOOLEAN),
+       ("root_page", DataType::INTEGER)]),
+    ("_index_columns",
+     &[("index_id", DataType::INTEGER),
+       ("position", DataType::INTEGER),
+       ("column_id", DataType::INTEGER),
+       ("descending", DataType::BOOLEAN)]),
+    ("_foreign_keys",
+     &[("id", DataType::INTEGER),
+       ("table_id", DataType::INTEGER),
+       ("name", DataType::VARCHAR),
+       ("referenced_table_id", DataType::INTEGER),
+       ("referenced_index_id", DataType::INTEGER),
+       ("on_delete", DataType::SMALLINT),
+       ("on_update", DataType::SMALLINT)]),
+    ("_foreign_key_columns",
+     &[("foreign_key_id", DataType::INTEGER),
+       ("position", DataType::INTEGER),
+       ("column_id", DataType::INTEGER)]),
+];
+
+/// Description of the user table stored in the catalog.
+#[derive(Debug)]
+pub struct CatalogEntry {
+    pub id: u32,
+    pub name: String,
+    pub schema: Schema,
+    pub indexes: Vec<IndexDefinition>,
+    pub foreign_keys: Vec<ForeignKeyDefinition>,
+}
+
+impl Table {
+    /// Creates system table with the columns.
+    pub(super) fn system_table(id: u32,
+                    name: &str,
+                    columns: &[(&str, DataType)],
+                    storage: Arc<Storage>,
+                    tables: SharedTables)
+                    -> Table {
+        let mut cfg = TableConfiguration::new(name);
+
+        for &(name, data_type) in columns {
+            cfg.add_column(Column::new(name, data_type, false))
+                .expect("Columns of the system table are unique");
+        }
+
+        Table {
+            system: true,
+            ..Table::new(id, cfg, 0, storage, tables)
+                .expect("Columns of the system table are unique")
+        }
+    }
+
+    /// Gets system table of the database by its name.
+    pub(super) fn catalog_table(&self, name: &str) -> Result<Arc<Table>, Error> {
+        let tables = self.tables.read().unwrap();
+
+        match tables.values().find(|table| table.system && table.name == name) {
+            Some(table) => Ok(table.clone()),
+            None => Err(Error::Corruption(format!("System table '{}' does not exist", name))),
+        }
+    }
+
+    /// Rolls back the transaction which changed the system tables and records free space of
+    /// their restored pages.
+    pub(super) fn rollback_catalog(&self, txn: Transaction) -> Result<(), Error> {
+        let pages = txn.rollback()?;
+        let tables: Vec<_> = self.tables.read().unwrap().values().cloned().collect();
+
+        for table in tables.iter().filter(|table| table.system) {
+            table.refresh_free_space(&pages)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes rows of the system table with one of the ids in the column.
+    pub(super) fn delete_catalog_rows(&self, txn: &Transaction, column: &str, ids: &[u32])
+                           -> Result<(), Error> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        for (id, row) in self.rows()? {
+            if ids.contains(&(catalog_id(&row, column)?)) {
+                self.delete_row(txn, id)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Database {
+    /// Adds id and name of the table to `_tables`.
+    pub(super) fn insert_table_row(&self, txn: &Transaction, table: &Table) -> Result<(), Error> {
+        let row = Row::new().with("id", table.id as i32).with("name", table.name.as_str());
+        self.tables["_tables"].insert_row(txn, &row)?;
+
+        Ok(())
+    }
+
+    /// Adds columns of the schema versions of the table, starting from the version, to
+    /// `_columns`. Every column of every version is stored as a separate row with its position
+    /// in the record. Default value, initial value and the list of CHECK constraints are
+    /// stored serialized, if they are present, otherwise they are NULL.
+    pub(super) fn insert_column_rows(&self, txn: &Transaction, table: &Table, from: u32)
+                          -> Result<(), Error> {
+        let schema = table.schema();
+
+        for version in from..schema.version() + 1 {
+            for (pos, column) in schema.version_columns(version)?.iter().enumerate() {
+                let default = match column.default {
+                    DefaultValue::Null => None,
+                    ref default => {
+                        Some(serialize(default.serialized_len(), |s| default.write(s))?)
+                    }
+                };
+
+                let initial = match column.initial {
+                    Value::Null => None,
+                    ref initial => {
+                        Some(serialize(initial.serialized_len(), |s| s.write_value(initial))?)
+                    }
+                };
+
+                let checks = if column.checks.is_empty() {
+                    None
+                } else {
+                    let len = 4 + column.checks.iter().map(Check::serialized_len).sum::<usize>();
+
+                    Some(serialize(len, |stream| {
+                        stream.write_int(column.checks.len() as i32)?;
+                        column.checks.iter().try_for_each(|check| check.write(stream))
+                    })?)
+                };
+
+                let row = Row::new()
+                    .with("table_id", table.id as i32)
+                    .with("version", version as i32)
+                    .with("position", pos as i32)
+                    .with("id", column.id as i32)
+                    .with("name", column.name.as_str())
+                    .with("data_type", column.data_type.code() as i16)
+                    .with("system", column.system)
+                    .with("nullable", column.nullable)
+                    .with("default", default)
+                    .with("initial", initial)
+                    .with("checks", checks);
+
+                self.tables["_columns"].insert_row(txn, &row)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds indexes of the table to `_indexes` and their key columns to `_index_columns`.
+    pub(super) fn insert_index_rows(&self, txn: &Transaction, table: &Table) -> Result<(), Error> {
+        let (indexes, columns) = (&self.tables["_indexes"], &self.tables["_index_columns"]);
+
+        for index in table.index_definitions() {
+            index.insert_rows(txn, table.id, indexes, columns)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds foreign keys of the table to `_foreign_keys` and their columns to
+    /// `_foreign_key_columns`.
+    pub(super) fn insert_foreign_key_rows(&self, txn: &Transaction, table: &Table)
+        -> Result<(), Error> {
+        for key in table.foreign_key_definitions() {
+            let row = Row::new()
+                .with("id", key.id as i32)
+                .with("table_id", table.id as i32)
+                .with("name", key.name.as_str())
+                .with("referenced_table_id", key.table as i32)
+                .with("referenced_index_id", key.index as i32)
+                .with("on_delete", key.on_delete.code())
+                .with("on_update", key.on_update.code());
+
+            self.tables["_foreign_keys"].insert_row(txn, &row)?;
+
+            for (pos, &column) in key.columns.iter().enumerate() {
+                let row = Row::new()
+                    .with("foreign_key_id", key.id as i32)
+                    .with("position", pos as i32)
+                    .with("column_id", column as i32);
+
+                self.tables["_foreign_key_columns"].insert_row(txn, &row)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes description of the table, its indexes and its foreign keys from the system
+    /// tables.
+    pub(super) fn delete_table_rows(&self, txn: &Transaction, table: &Table) -> Result<(), Error> {
+        let tables = [table.id];
+        let indexes: Vec<_> = table.index_definitions().iter().map(|index| index.id).collect();
+        let keys: Vec<_> = table.foreign_key_definitions().iter().map(|key| key.id).collect();
+
+        self.delete_catalog_rows(txn,
+                                 &[("_tables", "id", &tables),
+                                   ("_columns", "table_id", &tables),
+                                   ("_indexes", "table_id", &tables),
+                                   ("_index_columns", "index_id", &indexes),
+                                   ("_foreign_keys", "table_id", &tables),
+                                   ("_foreign_key_columns", "foreign_key_id", &keys)])
+    }
+
+    /// Deletes rows of the system tables with one of the ids in the column.
+    pub(super) fn delete_catalog_rows(&self, txn: &Transaction, rows: &[(&str, &str, &[u32])])
+                           -> Result<(), Error> {
+        for &(name, column, ids) in rows {
+            self.tables[name].delete_catalog_rows(txn, column, ids)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the system tables and loads all user tables described in them.
+    /// No user table is loaded if the operation fails.
+    pub(super) fn load_catalog(&mut self) -> Result<(), Error> {
+        for table in self.tables.values() {
+            table.load()?;
+        }
+
+        let mut tables = self.tables.clone();
+
+        for entry in self.read_catalog()? {
+            let table = Table::with_schema(entry.id,
+                                           entry.name.clone(),
+                                           entry.schema,
+                                           entry.indexes,
+                                           self.storage.clone(),
+                                           self.registry.clone());
+
+            *table.foreign_keys.write().unwrap() = entry.foreign_keys;
+
+            table.load()?;
+
+            tables.insert(entry.name, Arc::new(table));
+        }
+
+        self.update_registry(&tables);
+        self.tables = tables;
+
+        Ok(())
+    }
+
+    /// Reads every user table with its schema, indexes and foreign keys from the system tables.
+    pub(super) fn read_catalog(&self) -> Result<Vec<CatalogEntry>, Error> {
+        let mut indexes = self.read_indexes()?;
+        let mut foreign_keys = self.read_foreign_keys()?;
+
+        // Columns by id of the table, schema version and position in the record.
+        let mut columns = BTreeMap::new();
+
+        for row in self.tables["_columns"].scan()? {
+            let row = row?;
+            let table_id = catalog_id(&row, "table_id")?;
+            let version = catalog_id(&row, "version")?;
+            let pos = catalog_id(&row, "position")?;
+
+            columns.insert((table_id, version, pos), Arc::new(Database::read_column(&row)?));
+        }
+
+        let mut entries = Vec::new();
+
+        for row in self.tables["_tables"].scan()? {
+            let row = row?;
+            let id = catalog_id(&row, "id")?;
+            let name = catalog_value::<String>(&row, "name")?;
+            let mut versions: Vec<Vec<Arc<Column>>> = Vec::new();
+
+            let all_columns = (id, 0, 0)..=(id, u32::MAX, u32::MAX);
+
+            for (&(_, version, pos), column) in columns.range(all_columns) {
+                if version as usize == versions.len() {
+                    versions.push(Vec::new());
+                }
+
+                let in_order = version as usize + 1 == versions.len() &&
+                               versions[version as usize].len() == pos as usize;
+
+                if !in_order {
+                    return Err(Error::Corruption(format!("Column {} of version {} of table '{}' \
+                                                          follows a missing column or version",
+                                                         pos,
+                                                         version,
+                                                         name)));
+                }
+
+                versions[version as usize].push(column.clone());
+            }
+
+            if versions.is_empty() {
+                return Err(Error::Corruption(format!("Table '{}' has no schema versions", name)));
+            }
+
+            let schema = Schema::with_versions(versions);
+            let indexes = indexes.remove(&id).unwrap_or_default();
+
+            let missing = indexes.iter().find(|index| index.key_columns(&schema).is_none());
+
+            if let Some(index) = missing {
+                return Err(Error::Corruption(format!("Key column of index '{}' does not exist \
+                                                      in table '{}'",
+                                                     index.name,
+                                                     name)));
+            }
+
+            let foreign_keys = foreign_keys.remove(&id).unwrap_or_default();
+
+            if let Some(key) = foreign_keys.iter()
+                .find(|key| key.columns.iter().any(|&id| schema.column(id).is_none())) {
+                return Err(Error::Corruption(format!("Column of foreign key '{}' does not exist \
+                                                      in table '{}'",
+                                                     key.name,
+                                                     name)));
+            }
+
+            entries.push(CatalogEntry {
+                id,
+                name,
+                schema,
+                indexes,
+                foreign_keys,
+            });
+        }
+
+        for entry in &entries {
+            for key in &entry.foreign_keys {
+                let index = entries.iter()
+                    .filter(|referenced| referenced.id == key.table)
+                    .flat_map(|referenced| &referenced.indexes)
+                    .find(|index| index.id == key.index);
+
+                match index {
+                    Some(index) if index.columns.len() == key.columns.len() => (),
+                    _ => {
+                        return Err(Error::Corruption(format!("Key referenced by foreign key \
+                                                              '{}' does not exist",
+                                                             key.name)))
+                    }
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Reads descriptions of the foreign keys from the system tables. Returns foreign keys
+    /// ordered by their ids by id of the table.
+    fn read_foreign_keys(&self) -> Result<BTreeMap<u32, Vec<ForeignKeyDefinition>>, Error> {
+        // Columns by id of the foreign key and position in the referenced key.
+        let mut columns = BTreeMap::new();
+
+        for row in self.tables["_foreign_key_columns"].scan()? {
+            let row = row?;
+            let key_id = catalog_id(&row, "foreign_key_id")?;
+            let pos = catalog_id(&row, "position")?;
+
+            columns.insert((key_id, pos), catalog_id(&row, "column_id")?);
+        }
+
+        let action = |row: &Row, column: &str| {
+            let code = catalog_value::<i16>(row, column)?;
+
+            ReferentialAction::from_code(code).ok_or_else(|| {
+                Error::Corruption(format!("Unknown referential action: {}", code))
+            })
+        };
+
+        let mut foreign_keys: BTreeMap<u32, Vec<ForeignKeyDefinition>> = BTreeMap::new();
+
+        for row in self.tables["_foreign_keys"].scan()? {
+            let row = row?;
+            let id = catalog_id(&row, "id")?;
+            let name = catalog_value::<String>(&row, "name")?;
+            let key = columns.range((id, 0)..=(id, u32::MAX));
+
+            if key.clone().enumerate().any(|(i, (&(_, pos), _))| i as u32 != pos) {
+                return Err(Error::Corruption(format!("Column of foreign key '{}' is missing",
+                                                     name)));
+            }
+
+            let key = ForeignKeyDefinition {
+                id,
+                name,
+                columns: key.map(|(_, &column)| column).collect(),
+                table: catalog_id(&row, "referenced_table_id")?,
+                index: catalog_id(&row, "referenced_index_id")?,
+                on_delete: action(&row, "on_delete")?,
+                on_update: action(&row, "on_update")?,
+            };
+
+            let table_id = catalog_id(&row, "table_id")?;
+            foreign_keys.entry(table_id).or_default().push(key);
+        }
+
+        for keys in foreign_keys.values_mut() {
+            keys.sort_by_key(|key| key.id);
+        }
+
+        Ok(foreign_keys)
+    }
+
+    /// Reads descriptions of the indexes from the system tables. Returns indexes ordered by their
+    /// ids by id of the table.
+    fn read_indexes(&self) -> Result<BTreeMap<u32, Vec<IndexDefinition>>, Error> {
+        // Key columns with their sort orders by id of the index and position in the key.
+        let mut columns = BTreeMap::new();
+
+        for row in self.tables["_index_columns"].scan()? {
+            let row = row?;
+            let index_id = catalog_id(&row, "index_id")?;
+            let pos = catalog_id(&row, "position")?;
+            let column = catalog_id(&row, "column_id")?;
+
+            let order = if catalog_value(&row, "descending")? {
+                SortOrder::Descending
+            } else {
+                SortOrder::Ascending
+            };
+
+            columns.insert((index_id, pos), (column, order));
+        }
+
+        let mut indexes: BTreeMap<u32, Vec<IndexDefinition>> = BTreeMap::new();
+
+        for row in self.tables["_indexes"].scan()? {
+            let row = row?;
+            let id = catalog_id(&row, "id")?;
+            let name = catalog_value::<String>(&row, "name")?;
+            let key = columns.range((id, 0)..=(id, u32::MAX));
+
+            if key.clone().enumerate().any(|(i, (&(_, pos), _))| i as u32 != pos) {
+                return Err(Error::Corruption(format!("Key column of index '{}' is missing",
+                                                     name)));
+            }
+
+            let index = IndexDefinition {
+                id,
+                name,
+                unique: catalog_value(&row, "unique")?,
+                primary: catalog_value(&row, "primary")?,
+                columns: key.clone().map(|(_, &(column, _))| column).collect(),
+                orders: key.map(|(_, &(_, order))| order).collect(),
+                root: catalog_id(&row, "root_page")?,
+            };
+
+            if index.columns.is_empty() {
+                return Err(Error::Corruption(format!("Index '{}' has no key columns",
+                                                     index.name)));
+            }
+
+            let table_id = catalog_id(&row, "table_id")?;
+            indexes.entry(table_id).or_default().push(index);
+        }
+
+        for indexes in indexes.values_mut() {
+            indexes.sort_by_key(|index| index.id);
+        }
+
+        Ok(indexes)
+    }
+
+    /// Reads description of the column from the row of `_columns`.
+    fn read_column(row: &Row) -> Result<Column, Error> {
+        let name = catalog_value::<String>(row, "name")?;
+        let code = catalog_value::<i16>(row, "data_type")? as u16;
+
+        let data_type = match DataType::from_code(code) {
+            Some(data_type) => data_type,
+            None => {
+                return Err(Error::Corruption(format!("Column '{}' has unknown data type: {}",
+                                                     name,
+                                                     code)))
+            }
+        };
+
+        let default = match catalog_bytes(row, "default")? {
+            Some(data) => deserialize(&data, |stream| DefaultValue::read(stream, data_type))?,
+            None => DefaultValue::Null,
+        };
+
+        let initial = match catalog_bytes(row, "initial")? {
+            Some(data) => deserialize(&data, |stream| stream.read_value(data_type))?,
+            None => Value::Null,
+        };
+
+        let checks = match catalog_bytes(row, "checks")? {
+            Some(data) => {
+                deserialize(&data, |stream| {
+                    let len = stream.read_int()?;
+                    (0..len).map(|_| Check::read(stream)).collect()
+                })?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Column {
+            id: catalog_id(row, "id")?,
+            name,
+            data_type,
+            system: catalog_value(row, "system")?,
+            nullable: catalog_value(row, "nullable")?,
+            default,
+            initial,
+            checks,
+        })
+    }
+}
+
+/// Gets VARBINARY value of the column of the system table row, `None` if it is NULL.
+fn catalog_bytes(row: &Row, column: &str) -> Result<Option<Vec<u8>>, Error> {
+    match row.get(column) {
+        Some(&Value::Null) => Ok(None),
+        _ => catalog_value(row, column).map(Some),
+    }
+}
+
+/// Serializes data stored in a VARBINARY column of the system table.
+fn serialize<F>(len: usize, write: F) -> Result<Vec<u8>, Error>
+    where F: FnOnce(&mut SerializeStream) -> Result<(), Error>
+{
+    let mut page = MemoryPage::new(len);
+    write(&mut SerializeStream::new(&mut page, 0))?;
+
+    Ok(page.data().to_vec())
+}
+
+/// Deserializes data stored in a VARBINARY column of the system table.
+fn deserialize<T, F>(data: &[u8], read: F) -> Result<T, Error>
+    where F: FnOnce(&mut DeserializeStream) -> Result<T, Error>
+{
+    let mut page = MemoryPage::new(data.len());
+    page.data_mut().copy_from_slice(data);
+
+    read(&mut DeserializeStream::new(&page, 0))
+}
+
+/// Gets value of the column of the system table row converted to the type.
+pub fn catalog_value<T>(row: &Row, column: &str) -> Result<T, Error>
+    where T: TryFrom<Value, Error = Error>
+{
+    let value = row.get(column).cloned().unwrap_or(Value::Null);
+
+    T::try_from(value).map_err(|e| {
+        Error::Corruption(format!("Column '{}' of the system table is invalid: {}", column, e))
+    })
+}
+
+/// Gets id, position or version stored in the INTEGER column of the system table row.
+fn catalog_id(row: &Row, column: &str) -> Result<u32, Error> {
+    let value = catalog_value::<i32>(row, column)?;
+
+    u32::try_from(value).map_err(|_| {
+        Error::Corruption(format!("Column '{}' of the system table is negative: {}", column, value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use data_type::DataType;
+    use value::Value;
+    use row::Row;
+    use database::Database;
+    use database::schema::{Column, ColumnBuilder};
+    use database::table::TableConfiguration;
+    use error::Error;
+
+    #[test]
+    fn query_system_tables() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let tables = database.table("_tables").expect("should not fail");
+        let columns = database.table("_columns").expect("should not fail");
+
+        assert!(tables.is_system());
+        assert!(!table.is_system());
+        assert_eq!(database.tables().len(), 1);
+
+        let rows: Vec<_> = tables.scan()
+            .expect("should not fail")
+            .map(|r| r.expect("should not fail"))
+            .collect();
+        assert_eq!(rows, vec![Row::new().with("id", table.id as i32).with("name", "SomeTable")]);
+        assert_eq!(columns.scan().expect("should not fail").count(), 2);
+
+        database.rename_table("SomeTable", "OtherTable").expect("should not fail");
+
+        let rows: Vec<_> = tables.scan()
+            .expect("should not fail")
+            .map(|r| r.expect("should not fail"))
+            .collect();
+        assert_eq!(rows[0]["name"], Value::from("OtherTable"));
+
+        let table = database.table("OtherTable").expect("should not fail");
+        let mut alter = table.alter();
+        alter.add_column(ColumnBuilder::new("bar", DataType::BIGINT).default_value(7i64).build())
+            .expect("should not fail");
+        database.alter_table(alter).expect("should not fail");
+
+        // Every schema version is described by its own rows.
+        let rows: Vec<_> = columns.scan()
+            .expect("should not fail")
+            .map(|r| r.expect("should not fail"))
+            .collect();
+        assert_eq!(rows.len(), 5);
+
+        let bar = rows.iter().find(|row| row["name"] == Value::from("bar")).unwrap();
+        assert_eq!(bar["version"], Value::from(1));
+        assert_eq!(bar["position"], Value::from(2));
+        assert!(!bar["default"].is_null());
+
+        // System tables are changed only by the database itself.
+        match tables.insert(&Row::new().with("id", 100).with("name", "Fake")) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match database.drop_table("_columns") {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match database.create_table(TableConfiguration::new("_indexes")).map(|_| ()) {
+            Err(Error::DuplicateTable(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        database.drop_table("OtherTable").expect("should not fail");
+
+        assert_eq!(tables.scan().expect("should not fail").count(), 0);
+        assert_eq!(columns.scan().expect("should not fail").count(), 0);
+        assert!(database.verify().expect("should not fail").is_ok());
+    }
+
+    #[test]
+    fn detect_missing_schema_version() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("detect_missing_schema_version");
+
+        {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            let mut cfg = TableConfiguration::new("SomeTable");
+            cfg.add_column(Column::new("foo", DataType::INTEGER, false))
+                .expect("should not fail");
+            database.create_table(cfg).expect("should not fail");
+
+            // The only column of the table is moved to version 1, so version 0 is missing.
+            let columns = &database.tables["_columns"];
+            let (id, _) = columns.rows().expect("should not fail").remove(0);
+
+            let txn = database.storage.begin();
+            columns.update_row(&txn, id, &Row::new().with("version", 1))
+                .expect("should not fail");
+            txn.commit().expect("should not fail");
+        }
+
+        match Database::open(&path) {
+            Err(Error::Corruption(_)) => (),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        remove_database(&path);
+    }
+}
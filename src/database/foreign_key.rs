@@ -0,0 +1,843 @@
+use std::ops::Bound;
+use std::sync::Arc;
+use value::Value;
+use row::Row;
+use constraint::ReferentialAction;
+use storage::Transaction;
+use database::{Database, RowId};
+use database::schema::Schema;
+use database::index::key_values;
+use database::table::Table;
+use error::Error;
+
+/// Foreign key of the table: values of its columns in every row either contain NULL or match
+/// the primary key or a unique key of a row of the referenced table. Actions taken when
+/// the referenced row is deleted or its key is changed are RESTRICT unless they are set.
+#[derive(Debug, Clone)]
+pub struct ForeignKey {
+    pub(super) columns: Vec<String>,
+    table: String,
+    pub(super) referenced: Vec<String>,
+    on_delete: ReferentialAction,
+    on_update: ReferentialAction,
+}
+
+impl ForeignKey {
+    /// Creates foreign key of the columns, which refers to the columns of the table.
+    pub fn new(columns: &[&str], table: &str, referenced: &[&str]) -> ForeignKey {
+        ForeignKey {
+            columns: columns.iter().map(|&name| name.to_owned()).collect(),
+            table: table.to_owned(),
+            referenced: referenced.iter().map(|&name| name.to_owned()).collect(),
+            on_delete: ReferentialAction::Restrict,
+            on_update: ReferentialAction::Restrict,
+        }
+    }
+
+    /// Sets action taken when the referenced row is deleted.
+    pub fn on_delete(mut self, action: ReferentialAction) -> ForeignKey {
+        self.on_delete = action;
+        self
+    }
+
+    /// Sets action taken when the key of the referenced row is changed.
+    pub fn on_update(mut self, action: ReferentialAction) -> ForeignKey {
+        self.on_update = action;
+        self
+    }
+}
+
+/// Description of the foreign key of the table stored in the catalog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKeyDefinition {
+    /// Id of the foreign key, which is unique in the database.
+    pub id: u32,
+    pub name: String,
+    /// Ids of the columns in the order of the key columns of the referenced index.
+    pub columns: Vec<u32>,
+    /// Id of the referenced table.
+    pub table: u32,
+    /// Id of the unique index of the referenced table, which holds the referenced keys.
+    pub index: u32,
+    pub on_delete: ReferentialAction,
+    pub on_update: ReferentialAction,
+}
+
+impl Table {
+    /// Gets ids of the columns referenced by foreign keys of the tables with names of the keys.
+    pub(super) fn referenced_columns(&self) -> Vec<(u32, String)> {
+        let indexes = self.index_definitions();
+        let tables: Vec<_> = self.tables.read().unwrap().values().cloned().collect();
+
+        tables.iter()
+            .flat_map(|table| table.foreign_key_definitions())
+            .filter(|key| key.table == self.id)
+            .flat_map(|key| {
+                let columns = indexes.iter()
+                    .find(|index| index.id == key.index)
+                    .map_or(vec![], |index| index.columns.clone());
+
+                columns.into_iter().map(move |id| (id, key.name.clone()))
+            })
+            .collect()
+    }
+
+    /// Gets foreign keys of the table.
+    pub(super) fn foreign_key_definitions(&self) -> Vec<ForeignKeyDefinition> {
+        self.foreign_keys.read().unwrap().clone()
+    }
+
+    /// Gets table of the database by its id.
+    fn related_table(&self, id: u32) -> Result<Arc<Table>, Error> {
+        match self.tables.read().unwrap().get(&id) {
+            Some(table) => Ok(table.clone()),
+            None => {
+                Err(Error::Corruption(format!("Table {} related to table '{}' does not exist",
+                                              id,
+                                              self.name)))
+            }
+        }
+    }
+
+    /// Checks that the row refers to existing rows by its foreign keys. Foreign key with NULL
+    /// in any of its columns is not checked.
+    pub(super) fn check_references(&self, schema: &Schema, row: &Row) -> Result<(), Error> {
+        for key in self.foreign_key_definitions() {
+            let values = match key_values(schema, &key.columns, row) {
+                Some(values) => values,
+                None => continue,
+            };
+
+            let referenced = self.related_table(key.table)?;
+
+            if !referenced.has_key(key.index, values)? {
+                return Err(Error::ForeignKeyViolation {
+                    table: self.name.clone(),
+                    foreign_key: key.name,
+                    referenced_table: referenced.name.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if a row with the key exists in the index. Values of the key are widened to
+    /// the types of the key columns.
+    fn has_key(&self, index: u32, key: Vec<Value>) -> Result<bool, Error> {
+        let indexes = self.indexes.read().unwrap();
+
+        let index = match indexes.list.iter().find(|i| i.definition.id == index) {
+            Some(table_index) => &table_index.tree,
+            None => {
+                return Err(Error::Corruption(format!("Index {} of table '{}' does not exist",
+                                                     index,
+                                                     self.name)))
+            }
+        };
+
+        let key: Vec<_> = key.into_iter()
+            .zip(index.columns())
+            .map(|(value, column)| value.widen(column.data_type))
+            .collect();
+
+        Ok(!index.get(&key)?.is_empty())
+    }
+
+    /// Takes actions of the foreign keys which refer to the row once it was deleted or updated.
+    /// Nothing is done if the referenced key of the row was not changed.
+    pub(super) fn apply_referential_actions(&self,
+                                 txn: &Transaction,
+                                 schema: &Schema,
+                                 old: &Row,
+                                 new: Option<&Row>)
+                                 -> Result<(), Error> {
+        let tables: Vec<_> = self.tables.read().unwrap().values().cloned().collect();
+        let indexes = self.index_definitions();
+
+        for table in tables {
+            for key in table.foreign_key_definitions().into_iter().filter(|k| k.table == self.id) {
+                let index = match indexes.iter().find(|index| index.id == key.index) {
+                    Some(index) => index,
+                    None => {
+                        return Err(Error::Corruption(format!("Index {} of table '{}' does not \
+                                                              exist",
+                                                             key.index,
+                                                             self.name)))
+                    }
+                };
+
+                let old_key = match index.key(schema, old) {
+                    Some(key) => key,
+                    None => continue,
+                };
+
+                // New key may have NULL values, which are propagated by CASCADE.
+                let new_key: Option<Vec<Value>> = new.map(|row| {
+                    index.columns
+                        .iter()
+                        .map(|&id| {
+                            schema.column(id)
+                                .and_then(|column| row.get(&column.name))
+                                .cloned()
+                                .unwrap_or(Value::Null)
+                        })
+                        .collect()
+                });
+
+                if new_key.as_ref() == Some(&old_key) {
+                    continue;
+                }
+
+                let rows = table.referencing_rows(&key, &old_key)?;
+
+                if rows.is_empty() {
+                    continue;
+                }
+
+                let action = if new.is_some() { key.on_update } else { key.on_delete };
+
+                let table_schema = table.schema();
+                let columns: Vec<_> = key.columns
+                    .iter()
+                    .map(|&id| table_schema.column(id).expect("Columns of foreign key exist"))
+                    .collect();
+
+                let values = match (action, new_key) {
+                    (ReferentialAction::Restrict, _) => {
+                        return Err(Error::ForeignKeyViolation {
+                            table: table.name.clone(),
+                            foreign_key: key.name,
+                            referenced_table: self.name.clone(),
+                        })
+                    }
+                    (ReferentialAction::Cascade, Some(new_key)) => new_key,
+                    (ReferentialAction::Cascade, None) => {
+                        for id in rows {
+                            table.delete_row(txn, id)?;
+                        }
+
+                        continue;
+                    }
+                    (ReferentialAction::SetNull, _) => vec![Value::Null; columns.len()],
+                    (ReferentialAction::SetDefault, _) => {
+                        columns.iter().map(|column| column.default.evaluate()).collect()
+                    }
+                };
+
+                let changes = columns.iter()
+                    .zip(values)
+                    .fold(Row::new(), |row, (column, value)| row.with(&column.name, value));
+
+                for id in rows {
+                    table.update_row(txn, id, &changes)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets ids of the rows which refer to the key by the foreign key. Rows are found by
+    /// an index whose leading columns are the columns of the foreign key, such an index is
+    /// created together with the foreign key and can not be dropped.
+    fn referencing_rows(&self, key: &ForeignKeyDefinition, values: &[Value])
+                        -> Result<Vec<RowId>, Error> {
+        let indexes = self.indexes.read().unwrap();
+
+        match indexes.list.iter().find(|index| index.definition.leads_with(&key.columns)) {
+            Some(index) => {
+                // Values are in the order of the columns of the foreign key.
+                let prefix: Vec<_> = index.definition.columns[..key.columns.len()]
+                    .iter()
+                    .map(|id| values[key.columns.iter().position(|c| c == id).unwrap()].clone())
+                    .collect();
+
+                index.tree.range(Bound::Included(&prefix), Bound::Included(&prefix))?.collect()
+            }
+            None => {
+                Err(Error::Corruption(format!("Foreign key '{}' of table '{}' has no index",
+                                              key.name,
+                                              self.name)))
+            }
+        }
+    }
+}
+
+impl Database {
+    /// Gets id for a new foreign key.
+    pub(super) fn next_foreign_key_id(&self) -> u32 {
+        self.tables
+            .values()
+            .flat_map(|table| table.foreign_key_definitions())
+            .map(|key| key.id + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Resolves foreign key of the new table. Referenced columns must be the key of a unique
+    /// index of the referenced table and have the same types as the columns. Columns which are
+    /// set to NULL by an action of the foreign key must accept NULL values.
+    pub(super) fn resolve_foreign_key(&self, table: &Table, key: &ForeignKey, id: u32)
+                           -> Result<ForeignKeyDefinition, Error> {
+        let referenced: &Table = if key.table == table.name {
+            table
+        } else {
+            match self.tables.get(&key.table) {
+                Some(referenced) if referenced.system => {
+                    return Err(Error::InvalidArgument(format!("System table '{}' can not be \
+                                                               referenced",
+                                                              key.table)))
+                }
+                Some(referenced) => referenced,
+                None => return Err(Error::TableNotFound(key.table.clone())),
+            }
+        };
+
+        let schema = table.schema();
+        let referenced_schema = referenced.schema();
+        let mut ids = Vec::new();
+
+        for name in &key.referenced {
+            match referenced_schema.columns.get(name) {
+                Some(column) if !column.system => ids.push(column.id),
+                _ => {
+                    return Err(Error::ColumnNotFound {
+                        table: referenced.name.clone(),
+                        column: name.clone(),
+                    })
+                }
+            }
+        }
+
+        let index = referenced.index_definitions().into_iter().find(|index| {
+            index.unique && index.columns.len() == ids.len() &&
+            index.columns.iter().all(|id| ids.contains(id))
+        });
+
+        let index = match index {
+            Some(index) => index,
+            None => {
+                return Err(Error::InvalidArgument(format!("Columns ({}) of table '{}' are \
+                                                           neither a primary key nor a unique \
+                                                           key",
+                                                          key.referenced.join(", "),
+                                                          referenced.name)))
+            }
+        };
+
+        let set_null = key.on_delete == ReferentialAction::SetNull ||
+                       key.on_update == ReferentialAction::SetNull;
+
+        let mut columns = Vec::new();
+
+        // Columns are stored in the order of the referenced key.
+        for &referenced_id in &index.columns {
+            let pos = ids.iter().position(|&id| id == referenced_id).unwrap();
+            let column = &schema.columns[&key.columns[pos]];
+            let expected = referenced_schema.column(referenced_id).unwrap().data_type;
+
+            if column.data_type != expected {
+                return Err(Error::TypeMismatch {
+                    table: table.name.clone(),
+                    column: column.name.clone(),
+                    expected,
+                    actual: column.data_type,
+                });
+            }
+
+            if set_null && !column.nullable {
+                return Err(Error::InvalidArgument(format!("Column '{}' of table '{}' can not be \
+                                                           set to NULL by foreign key",
+                                                          column.name,
+                                                          table.name)));
+            }
+
+            columns.push(column.id);
+        }
+
+        Ok(ForeignKeyDefinition {
+            id,
+            name: format!("{}_{}_fkey", table.name, key.columns.join("_")),
+            columns,
+            table: referenced.id,
+            index: index.id,
+            on_delete: key.on_delete,
+            on_update: key.on_update,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_type::DataType;
+    use value::Value;
+    use row::Row;
+    use constraint::ReferentialAction;
+    use collation::SortOrder;
+    use database::Database;
+    use database::schema::{Column, ColumnBuilder};
+    use database::foreign_key::ForeignKey;
+    use database::table::{Table, TableConfiguration};
+    use error::Error;
+
+    #[test]
+    fn enforce_foreign_keys() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("Parent");
+        cfg.add_column(Column::new("id", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("name", DataType::VARCHAR, false)).expect("should not fail");
+        cfg.set_primary_key(&["id"]).expect("should not fail");
+
+        let parent = database.create_table(cfg).expect("should not fail");
+
+        let mut cfg = TableConfiguration::new("Child");
+        cfg.add_column(Column::new("id", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("parent", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_foreign_key(ForeignKey::new(&["parent"], "Parent", &["id"]))
+            .expect("should not fail");
+
+        let child = database.create_table(cfg).expect("should not fail");
+
+        let first = parent.insert(&Row::new().with("id", 1).with("name", "first"))
+            .expect("should not fail");
+        let second = parent.insert(&Row::new().with("id", 2).with("name", "second"))
+            .expect("should not fail");
+
+        let id = child.insert(&Row::new().with("id", 1).with("parent", 1))
+            .expect("should not fail");
+        child.insert(&Row::new().with("id", 2)).expect("should not fail");
+
+        match child.insert(&Row::new().with("id", 3).with("parent", 3)) {
+            Err(Error::ForeignKeyViolation {
+                ref table,
+                ref foreign_key,
+                ref referenced_table,
+            }) => {
+                assert_eq!(table, "Child");
+                assert_eq!(foreign_key, "Child_parent_fkey");
+                assert_eq!(referenced_table, "Parent");
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match child.update(id, &Row::new().with("parent", 3)) {
+            Err(Error::ForeignKeyViolation { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match parent.delete(first) {
+            Err(Error::ForeignKeyViolation { ref table, .. }) => assert_eq!(table, "Child"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match parent.update(first, &Row::new().with("id", 10)) {
+            Err(Error::ForeignKeyViolation { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Referenced key is not changed.
+        parent.update(first, &Row::new().with("name", "renamed")).expect("should not fail");
+        parent.delete(second).expect("should not fail");
+
+        child.update(id, &Row::new().with("parent", None::<i32>)).expect("should not fail");
+        parent.delete(first).expect("should not fail");
+
+        assert_eq!(parent.scan().expect("should not fail").count(), 0);
+        assert_eq!(child.scan().expect("should not fail").count(), 2);
+    }
+
+    #[test]
+    fn apply_referential_actions() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("Parent");
+        cfg.add_column(Column::new("id", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("code", DataType::VARCHAR, false)).expect("should not fail");
+        cfg.set_primary_key(&["id"]).expect("should not fail");
+        cfg.add_unique(&["code"]).expect("should not fail");
+
+        let parent = database.create_table(cfg).expect("should not fail");
+
+        let actions = [("Cascade", ReferentialAction::Cascade),
+                       ("SetNull", ReferentialAction::SetNull),
+                       ("SetDefault", ReferentialAction::SetDefault)];
+
+        let children: Vec<_> = actions.iter()
+            .map(|&(name, action)| {
+                let mut cfg = TableConfiguration::new(name);
+                cfg.add_column(ColumnBuilder::new("code", DataType::VARCHAR)
+                        .default_value("none")
+                        .build())
+                    .expect("should not fail");
+                cfg.add_foreign_key(ForeignKey::new(&["code"], "Parent", &["code"])
+                        .on_delete(action)
+                        .on_update(action))
+                    .expect("should not fail");
+
+                database.create_table(cfg).expect("should not fail")
+            })
+            .collect();
+
+        let codes = |table: &Table| -> Vec<Value> {
+            table.scan()
+                .expect("should not fail")
+                .map(|row| row.expect("should not fail")["code"].clone())
+                .collect()
+        };
+
+        parent.insert(&Row::new().with("id", 0).with("code", "none")).expect("should not fail");
+        let a = parent.insert(&Row::new().with("id", 1).with("code", "a"))
+            .expect("should not fail");
+        let b = parent.insert(&Row::new().with("id", 2).with("code", "b"))
+            .expect("should not fail");
+
+        for child in &children {
+            for &code in &["a", "b", "b"] {
+                child.insert(&Row::new().with("code", code)).expect("should not fail");
+            }
+        }
+
+        parent.update(b, &Row::new().with("code", "c")).expect("should not fail");
+
+        assert_eq!(codes(&children[0]), vec![Value::from("a"), Value::from("c"), Value::from("c")]);
+        assert_eq!(codes(&children[1]), vec![Value::from("a"), Value::Null, Value::Null]);
+        assert_eq!(codes(&children[2]),
+                   vec![Value::from("a"), Value::from("none"), Value::from("none")]);
+
+        parent.delete(a).expect("should not fail");
+
+        assert_eq!(codes(&children[0]), vec![Value::from("c"), Value::from("c")]);
+        assert_eq!(codes(&children[1]), vec![Value::Null, Value::Null, Value::Null]);
+        assert_eq!(codes(&children[2]), vec![Value::from("none"); 3]);
+
+        // Default value must refer to an existing row, so the whole change is rolled back.
+        let none = parent.find_by_primary_key(&[Value::from(0)])
+            .expect("should not fail")
+            .expect("row exists")
+            .0;
+
+        match parent.update(none, &Row::new().with("code", "other")) {
+            Err(Error::ForeignKeyViolation { ref table, .. }) => assert_eq!(table, "SetDefault"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert_eq!(codes(&children[2]), vec![Value::from("none"); 3]);
+        assert!(parent.find_by_primary_key(&[Value::from(0)]).expect("should not fail").is_some());
+
+        parent.insert(&Row::new().with("id", 3).with("code", "a")).expect("should not fail");
+        assert_eq!(parent.scan().expect("should not fail").count(), 3);
+        assert!(database.verify().expect("should not fail").is_ok());
+    }
+
+    #[test]
+    fn cascade_self_references() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("Tree");
+        cfg.add_column(Column::new("id", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("parent", DataType::INTEGER, false)).expect("should not fail");
+        cfg.set_primary_key(&["id"]).expect("should not fail");
+        cfg.add_foreign_key(ForeignKey::new(&["parent"], "Tree", &["id"])
+                .on_delete(ReferentialAction::Cascade))
+            .expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        // Row may refer to itself.
+        let root = table.insert(&Row::new().with("id", 0).with("parent", 0))
+            .expect("should not fail");
+
+        for id in 1..20 {
+            table.insert(&Row::new().with("id", id).with("parent", (id - 1) / 2))
+                .expect("should not fail");
+        }
+
+        let node = table.find_by_primary_key(&[Value::from(1)])
+            .expect("should not fail")
+            .expect("row exists")
+            .0;
+
+        // Nodes 1, 3, 4, 7, 8, 9, 10, 15, 16, 17, 18 and 19.
+        table.delete(node).expect("should not fail");
+        assert_eq!(table.scan().expect("should not fail").count(), 8);
+
+        table.delete(root).expect("should not fail");
+        assert_eq!(table.scan().expect("should not fail").count(), 0);
+    }
+
+    #[test]
+    fn index_foreign_keys() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("Parent");
+        cfg.add_column(Column::new("id", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("code", DataType::VARCHAR, false)).expect("should not fail");
+        cfg.set_primary_key(&["id", "code"]).expect("should not fail");
+
+        let parent = database.create_table(cfg).expect("should not fail");
+
+        // Key which is not indexed gets an index, one which leads another index does not.
+        let mut cfg = TableConfiguration::new("Child");
+        cfg.add_column(Column::new("code", DataType::VARCHAR, false)).expect("should not fail");
+        cfg.add_column(Column::new("parent", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("other", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("seq", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_index(&[("parent", SortOrder::Descending),
+                        ("code", SortOrder::Ascending),
+                        ("seq", SortOrder::Ascending)])
+            .expect("should not fail");
+        cfg.add_foreign_key(ForeignKey::new(&["code", "parent"], "Parent", &["code", "id"])
+                .on_delete(ReferentialAction::Cascade))
+            .expect("should not fail");
+        cfg.add_foreign_key(ForeignKey::new(&["other", "code"], "Parent", &["id", "code"]))
+            .expect("should not fail");
+
+        let child = database.create_table(cfg).expect("should not fail");
+
+        assert_eq!(child.index_names(),
+                   vec!["Child_parent_desc_code_seq_idx", "Child_other_code_idx"]);
+
+        for id in 0..10 {
+            parent.insert(&Row::new().with("id", id).with("code", "a")).expect("should not fail");
+        }
+
+        for seq in 0..100 {
+            let row = Row::new()
+                .with("code", "a")
+                .with("parent", seq % 10)
+                .with("other", seq % 3)
+                .with("seq", seq);
+
+            child.insert(&row).expect("should not fail");
+        }
+
+        let find = |id: i32| {
+            parent.find_by_primary_key(&[Value::from(id), Value::from("a")])
+                .expect("should not fail")
+                .expect("row exists")
+                .0
+        };
+
+        parent.delete(find(7)).expect("should not fail");
+        assert_eq!(child.scan().expect("should not fail").count(), 90);
+
+        match parent.delete(find(2)) {
+            Err(Error::ForeignKeyViolation { ref foreign_key, .. }) => {
+                assert_eq!(foreign_key, "Child_other_code_fkey")
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        parent.delete(find(5)).expect("should not fail");
+        assert_eq!(child.scan().expect("should not fail").count(), 80);
+
+        assert!(database.verify().expect("should not fail").is_ok());
+    }
+
+    #[test]
+    fn validate_foreign_keys() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("Parent");
+        cfg.add_column(Column::new("id", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("name", DataType::VARCHAR, false)).expect("should not fail");
+        cfg.set_primary_key(&["id"]).expect("should not fail");
+
+        database.create_table(cfg).expect("should not fail");
+
+        let child = |key: ForeignKey| {
+            let mut cfg = TableConfiguration::new("Child");
+            cfg.add_column(ColumnBuilder::new("foo", DataType::INTEGER).not_null().build())
+                .expect("should not fail");
+            cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+            cfg.add_foreign_key(key).map(|_| cfg)
+        };
+
+        match child(ForeignKey::new(&["foo"], "Parent", &["id", "name"])) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        match child(ForeignKey::new(&["baz"], "Parent", &["id"])) {
+            Err(Error::ColumnNotFound { ref column, .. }) => assert_eq!(column, "baz"),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let cfg = child(ForeignKey::new(&["foo"], "Missing", &["id"])).expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::TableNotFound(ref name)) => assert_eq!(name, "Missing"),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let cfg = child(ForeignKey::new(&["bar"], "Parent", &["name"])).expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let cfg = child(ForeignKey::new(&["bar"], "Parent", &["id"])).expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::TypeMismatch { ref column, .. }) => assert_eq!(column, "bar"),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let cfg = child(ForeignKey::new(&["foo"], "Parent", &["id"])
+                .on_delete(ReferentialAction::SetNull))
+            .expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let cfg = child(ForeignKey::new(&["foo"], "_tables", &["id"])).expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        // Both keys are named "Child_foo_fkey".
+        let mut cfg = child(ForeignKey::new(&["foo"], "Parent", &["id"]))
+            .expect("should not fail");
+        cfg.add_foreign_key(ForeignKey::new(&["foo"], "Child", &["foo"]))
+            .expect("should not fail");
+        cfg.add_unique(&["foo"]).expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::DuplicateForeignKey { ref table, ref foreign_key }) => {
+                assert_eq!(table, "Child");
+                assert_eq!(foreign_key, "Child_foo_fkey");
+            }
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        assert_eq!(database.tables().len(), 1);
+
+        let cfg = child(ForeignKey::new(&["foo"], "Parent", &["id"])).expect("should not fail");
+        let table = database.create_table(cfg).expect("should not fail");
+
+        match database.drop_table("Parent") {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut alter = table.alter();
+
+        match alter.drop_column("foo") {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match alter.widen_column("foo", DataType::BIGINT) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match alter.drop_foreign_key("Child_bar_fkey") {
+            Err(Error::ForeignKeyNotFound { ref foreign_key, .. }) => {
+                assert_eq!(foreign_key, "Child_bar_fkey")
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Index created for the foreign key is kept until the key is dropped.
+        match table.drop_index("Child_foo_idx") {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        alter.drop_foreign_key("Child_foo_fkey").expect("should not fail");
+        database.alter_table(alter).expect("should not fail");
+        table.drop_index("Child_foo_idx").expect("should not fail");
+
+        let mut alter = table.alter();
+        alter.drop_column("foo").expect("should not fail");
+        database.alter_table(alter).expect("should not fail");
+
+        table.insert(&Row::new().with("bar", "a")).expect("should not fail");
+        database.drop_table("Parent").expect("should not fail");
+
+        assert!(database.verify().expect("should not fail").is_ok());
+    }
+
+    #[test]
+    fn persist_foreign_keys() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("persist_foreign_keys");
+
+        {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            let mut cfg = TableConfiguration::new("Parent");
+            cfg.add_column(Column::new("a", DataType::SMALLINT, false)).expect("should not fail");
+            cfg.add_column(Column::new("b", DataType::VARCHAR, false)).expect("should not fail");
+            cfg.set_primary_key(&["a", "b"]).expect("should not fail");
+
+            let parent = database.create_table(cfg).expect("should not fail");
+            parent.insert(&Row::new().with("a", 1i16).with("b", "x")).expect("should not fail");
+
+            // Columns refer to the key in another order.
+            let mut cfg = TableConfiguration::new("Child");
+            cfg.add_column(Column::new("x", DataType::VARCHAR, false)).expect("should not fail");
+            cfg.add_column(Column::new("y", DataType::SMALLINT, false)).expect("should not fail");
+            cfg.add_foreign_key(ForeignKey::new(&["x", "y"], "Parent", &["b", "a"])
+                    .on_update(ReferentialAction::Cascade))
+                .expect("should not fail");
+
+            let child = database.create_table(cfg).expect("should not fail");
+            child.insert(&Row::new().with("x", "x").with("y", 1i16)).expect("should not fail");
+            child.insert(&Row::new().with("x", "y").with("y", 1i16)).unwrap_err();
+
+            database.rename_table("Parent", "Renamed").expect("should not fail");
+
+            let mut alter = database.table("Renamed").expect("should not fail").alter();
+
+            match alter.widen_column("a", DataType::INTEGER) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+
+        {
+            let database = Database::open(&path).expect("should not fail");
+            let parent = database.table("Renamed").expect("should not fail");
+            let child = database.table("Child").expect("should not fail");
+
+            match child.insert(&Row::new().with("x", "x").with("y", 2i16)) {
+                Err(Error::ForeignKeyViolation { ref referenced_table, .. }) => {
+                    assert_eq!(referenced_table, "Renamed")
+                }
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            child.insert(&Row::new().with("x", "x").with("y", 1i16)).expect("should not fail");
+
+            let (id, _) = parent.find_by_primary_key(&[Value::from(1i16), Value::from("x")])
+                .expect("should not fail")
+                .expect("row exists");
+
+            parent.update(id, &Row::new().with("b", "z")).expect("should not fail");
+
+            let values: Vec<_> = child.scan().expect("should not fail")
+                .map(|row| row.expect("should not fail")["x"].clone())
+                .collect();
+            assert_eq!(values, vec![Value::from("z"), Value::from("z")]);
+
+            assert_eq!(database.table("_foreign_key_columns")
+                           .expect("should not fail")
+                           .scan().expect("should not fail")
+                           .count(),
+                       2);
+            assert!(database.verify().expect("should not fail").is_ok());
+        }
+
+        remove_database(&path);
+    }
+}
@@ -0,0 +1,1479 @@
+use std::ops::Bound;
+use std::sync::Arc;
+use value::Value;
+use row::Row;
+use collation::SortOrder;
+use storage::Storage;
+use storage::Transaction;
+use indexing::{BTree, KeyColumn};
+use database::RowId;
+use database::schema::Schema;
+use database::table::Table;
+use error::Error;
+
+/// Description of the index of the table stored in the catalog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexDefinition {
+    /// Id of the index, which is unique in the database.
+    pub id: u32,
+    pub name: String,
+    /// Index does not allow two rows with the same key.
+    pub unique: bool,
+    /// Index of the primary key.
+    pub primary: bool,
+    /// Ids of the key columns in the key order.
+    pub columns: Vec<u32>,
+    /// Sort orders of the key columns.
+    pub orders: Vec<SortOrder>,
+    /// Root page of the index tree, which never changes.
+    pub root: u32,
+}
+
+impl IndexDefinition {
+    /// Gets key columns of the index with their types in the schema, `None` if a key column
+    /// does not exist in the schema.
+    pub fn key_columns(&self, schema: &Schema) -> Option<Vec<KeyColumn>> {
+        self.columns
+            .iter()
+            .zip(&self.orders)
+            .map(|(&id, &order)| {
+                schema.column(id).map(|column| {
+                    KeyColumn { order, ..KeyColumn::new(column.data_type) }
+                })
+            })
+            .collect()
+    }
+
+    /// Gets key of the row in the index: values of the key columns. Returns `None` if any of
+    /// the values is NULL, such rows are not indexed.
+    pub fn key(&self, schema: &Schema, row: &Row) -> Option<Vec<Value>> {
+        key_values(schema, &self.columns, row)
+    }
+
+    /// Checks that the leading columns of the index are the columns in any order, so rows
+    /// with the values of the columns are found by the index.
+    pub fn leads_with(&self, columns: &[u32]) -> bool {
+        self.columns.len() >= columns.len() &&
+        self.columns[..columns.len()].iter().all(|id| columns.contains(id))
+    }
+
+    /// Adds the index of the table to `_indexes` and its key columns to `_index_columns`.
+    pub fn insert_rows(&self, txn: &Transaction, table_id: u32, indexes: &Table, columns: &Table)
+                   -> Result<(), Error> {
+        let row = Row::new()
+            .with("id", self.id as i32)
+            .with("table_id", table_id as i32)
+            .with("name", self.name.as_str())
+            .with("unique", self.unique)
+            .with("primary", self.primary)
+            .with("root_page", self.root as i32);
+
+        indexes.insert_row(txn, &row)?;
+
+        for (pos, (&column, &order)) in self.columns.iter().zip(&self.orders).enumerate() {
+            let row = Row::new()
+                .with("index_id", self.id as i32)
+                .with("position", pos as i32)
+                .with("column_id", column as i32)
+                .with("descending", order == SortOrder::Descending);
+
+            columns.insert_row(txn, &row)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Index of the table with its description.
+#[derive(Debug)]
+pub struct TableIndex {
+    pub definition: IndexDefinition,
+    pub tree: BTree,
+}
+
+impl TableIndex {
+    /// Opens tree of the index with keys of the types in the schema. Key columns must exist
+    /// in the schema.
+    pub fn open(schema: &Schema, definition: IndexDefinition, storage: &Arc<Storage>)
+                -> TableIndex {
+        let columns = definition.key_columns(schema).expect("Key columns exist in the schema");
+
+        TableIndex {
+            tree: BTree::open(storage.clone(), definition.root, columns),
+            definition,
+        }
+    }
+}
+
+/// Indexes of the table, which are stored as trees in pages of the table. Changes made by
+/// the failed operation are reverted by the rollback of its transaction.
+#[derive(Debug, Default)]
+pub struct Indexes {
+    pub list: Vec<TableIndex>,
+}
+
+/// Options of the index created by `Table::create_index`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct IndexOptions {
+    unique: bool,
+}
+
+impl IndexOptions {
+    /// Creates options of a non-unique index.
+    pub fn new() -> IndexOptions {
+        IndexOptions::default()
+    }
+
+    /// Sets whether the index allows two rows with the same key.
+    pub fn unique(mut self, unique: bool) -> IndexOptions {
+        self.unique = unique;
+        self
+    }
+}
+
+impl Table {
+    /// Gets descriptions of the indexes of the table.
+    pub(super) fn index_definitions(&self) -> Vec<IndexDefinition> {
+        self.indexes.read().unwrap().list.iter().map(|index| index.definition.clone()).collect()
+    }
+
+    /// Creates trees of the indexes of the new table.
+    pub(super) fn create_trees(&self, txn: &Transaction) -> Result<(), Error> {
+        let mut indexes = self.indexes.write().unwrap();
+
+        for index in &mut indexes.list {
+            let columns = index.tree.columns().to_vec();
+
+            index.tree = BTree::create(txn, self.storage.clone(), self.id, columns)?;
+            index.definition.root = index.tree.root();
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds indexes whose key columns were widened, so their keys have types of the key
+    /// columns in the current schema.
+    pub(super) fn rebuild_indexes(&self, txn: &Transaction) -> Result<(), Error> {
+        let schema = self.schema();
+        let mut indexes = self.indexes.write().unwrap();
+
+        for index in &mut indexes.list {
+            if index.definition.key_columns(&schema).as_deref() == Some(index.tree.columns()) {
+                continue;
+            }
+
+            index.tree.clear(txn)?;
+            *index = TableIndex::open(&schema, index.definition.clone(), &self.storage);
+
+            self.fill_index(txn, &schema, index)?;
+        }
+
+        Ok(())
+    }
+
+    /// Opens trees of the indexes with types of the key columns in the current schema, e.g.
+    /// after the trees were restored by a rollback.
+    pub(super) fn refresh_indexes(&self) {
+        let schema = self.schema();
+        let mut indexes = self.indexes.write().unwrap();
+
+        for index in &mut indexes.list {
+            *index = TableIndex::open(&schema, index.definition.clone(), &self.storage);
+        }
+    }
+
+    /// Checks that keys of the row are not used by other rows in the unique indexes.
+    /// Row with the id, if any, is the row being updated.
+    pub(super) fn check_unique(&self, schema: &Schema, row: &Row, id: Option<RowId>)
+        -> Result<(), Error> {
+        let indexes = self.indexes.read().unwrap();
+
+        for index in indexes.list.iter().filter(|index| index.definition.unique) {
+            let key = match index.definition.key(schema, row) {
+                Some(key) => key,
+                None => continue,
+            };
+
+            if index.tree.get(&key)?.into_iter().any(|other| Some(other) != id) {
+                let columns = index.definition
+                    .columns
+                    .iter()
+                    .filter_map(|&id| schema.column(id).map(|c| c.name.clone()))
+                    .collect();
+
+                return Err(Error::UniqueViolation {
+                    table: self.name.clone(),
+                    index: index.definition.name.clone(),
+                    columns,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces entries of the row in the indexes: entries of the old row are removed and
+    /// entries of the new row are added. Changes are reverted by the rollback of
+    /// the transaction if the operation fails.
+    pub(super) fn update_indexes(&self,
+                      txn: &Transaction,
+                      schema: &Schema,
+                      old: Option<(RowId, &Row)>,
+                      new: Option<(RowId, &Row)>)
+                      -> Result<(), Error> {
+        let indexes = self.indexes.write().unwrap();
+
+        for index in &indexes.list {
+            let definition = &index.definition;
+            let old = old.and_then(|(id, row)| definition.key(schema, row).map(|key| (id, key)));
+            let new = new.and_then(|(id, row)| definition.key(schema, row).map(|key| (id, key)));
+
+            if old == new {
+                continue;
+            }
+
+            if let Some((row, key)) = old {
+                index.tree.remove(txn, &key, row)?;
+            }
+
+            if let Some((row, key)) = new {
+                index.tree.add(txn, &key, row)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds row by values of the primary key columns in the key order. Returns `None` if there
+    /// is no such row. Row is found in O(log n) by the index of the primary key.
+    pub fn find_by_primary_key(&self, key: &[Value]) -> Result<Option<(RowId, Row)>, Error> {
+        self.check_attached()?;
+
+        let schema = self.schema();
+
+        let ids = {
+            let indexes = self.indexes.read().unwrap();
+
+            let index = match indexes.list.iter().find(|index| index.definition.primary) {
+                Some(index) => index,
+                None => {
+                    return Err(Error::InvalidArgument(format!("Table '{}' has no primary key",
+                                                              self.name)))
+                }
+            };
+
+            // NULL is not equal to any key.
+            if !self.check_key(&schema, &index.definition, key, false)? {
+                return Ok(None);
+            }
+
+            index.tree.get(key)?
+        };
+
+        match ids.first() {
+            Some(&id) => Ok(Some((id, self.read(id)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets names of the indexes of the table.
+    pub fn index_names(&self) -> Vec<String> {
+        let indexes = self.indexes.read().unwrap();
+        indexes.list.iter().map(|index| index.definition.name.clone()).collect()
+    }
+
+    /// Creates index with the name on the columns sorted in the orders and fills it with keys
+    /// of all rows. Index is kept in sync with the rows by every insert, update and delete
+    /// from now on. Unique index can not be created if two rows have the same key.
+    pub fn create_index(&self,
+                        name: &str,
+                        columns: &[(&str, SortOrder)],
+                        options: IndexOptions)
+                        -> Result<(), Error> {
+        let txn = self.storage.begin();
+
+        // Table is checked once the transaction is started, so neither the table nor its
+        // schema can change until the index is published.
+        self.check_writable()?;
+
+        let definition = self.new_index(name, columns, options)?;
+
+        let res = self.create_tree(&txn, definition).and_then(|index| {
+            let indexes = self.catalog_table("_indexes")?;
+            let columns = self.catalog_table("_index_columns")?;
+            index.definition.insert_rows(&txn, self.id, &indexes, &columns)?;
+
+            Ok(index)
+        });
+
+        // Pages of the tree are freed by the rollback.
+        let index = match res {
+            Ok(index) => index,
+            Err(e) => return self.rollback_catalog(txn).and(Err(e)),
+        };
+
+        let id = index.definition.id;
+        self.indexes.write().unwrap().list.push(index);
+
+        let res = txn.commit();
+
+        if res.is_err() {
+            self.indexes.write().unwrap().list.retain(|index| index.definition.id != id);
+        }
+
+        res
+    }
+
+    /// Drops the index with the name, pages of its tree are freed. Index of
+    /// the primary key, indexes referred by foreign keys and the only index which finds rows
+    /// of a foreign key of the table can not be dropped. Dropping the index of a unique
+    /// constraint drops the constraint.
+    pub fn drop_index(&self, name: &str) -> Result<(), Error> {
+        let txn = self.storage.begin();
+
+        self.check_writable()?;
+
+        let definition = match self.index_definitions().into_iter().find(|i| i.name == name) {
+            Some(definition) => definition,
+            None => {
+                return Err(Error::IndexNotFound {
+                    table: self.name.clone(),
+                    index: name.to_owned(),
+                })
+            }
+        };
+
+        if definition.primary {
+            return Err(Error::InvalidArgument(format!("Index '{}' of the primary key of table \
+                                                       '{}' can not be dropped",
+                                                      name,
+                                                      self.name)));
+        }
+
+        let indexes = self.index_definitions();
+
+        for key in self.foreign_key_definitions() {
+            let covered = |index: &IndexDefinition| index.leads_with(&key.columns);
+
+            if covered(&definition) &&
+               !indexes.iter().any(|index| index.id != definition.id && covered(index)) {
+                return Err(Error::InvalidArgument(format!("Index '{}' of table '{}' is used by \
+                                                           foreign key '{}'",
+                                                          name,
+                                                          self.name,
+                                                          key.name)));
+            }
+        }
+
+        let tables: Vec<_> = self.tables.read().unwrap().values().cloned().collect();
+
+        for table in tables {
+            let keys = table.foreign_key_definitions();
+            let key = keys.iter().find(|k| k.table == self.id && k.index == definition.id);
+
+            if let Some(key) = key {
+                return Err(Error::InvalidArgument(format!("Index '{}' of table '{}' is used by \
+                                                           foreign key '{}' of table '{}'",
+                                                          name,
+                                                          self.name,
+                                                          key.name,
+                                                          table.name)));
+            }
+        }
+
+        let ids = [definition.id];
+
+        let res = {
+            let indexes = self.indexes.read().unwrap();
+            let index = indexes.list.iter().find(|i| i.definition.id == definition.id).unwrap();
+
+            index.tree.free_pages(&txn)
+        };
+
+        let res = res.and_then(|_| self.catalog_table("_indexes"))
+            .and_then(|indexes| indexes.delete_catalog_rows(&txn, "id", &ids))
+            .and_then(|_| self.catalog_table("_index_columns"))
+            .and_then(|columns| columns.delete_catalog_rows(&txn, "index_id", &ids));
+
+        if let Err(e) = res {
+            return self.rollback_catalog(txn).and(Err(e));
+        }
+
+        let removed = {
+            let mut indexes = self.indexes.write().unwrap();
+            let pos = indexes.list.iter().position(|i| i.definition.id == definition.id).unwrap();
+            (pos, indexes.list.remove(pos))
+        };
+
+        let res = txn.commit();
+
+        if res.is_err() {
+            let (pos, index) = removed;
+            self.indexes.write().unwrap().list.insert(pos, index);
+        }
+
+        res
+    }
+
+    /// Creates description of the new index of the table. Name must not be used by another
+    /// index of the table, columns must exist and be used once.
+    fn new_index(&self, name: &str, columns: &[(&str, SortOrder)], options: IndexOptions)
+                 -> Result<IndexDefinition, Error> {
+        let definitions = self.index_definitions();
+
+        if name.is_empty() {
+            return Err(Error::InvalidArgument(format!("Index of table '{}' must have a name",
+                                                      self.name)));
+        }
+
+        if definitions.iter().any(|index| index.name == name) {
+            return Err(Error::DuplicateIndex {
+                table: self.name.clone(),
+                index: name.to_owned(),
+            });
+        }
+
+        if columns.is_empty() {
+            return Err(Error::InvalidArgument(format!("Key of index '{}' must have columns",
+                                                      name)));
+        }
+
+        let schema = self.schema();
+        let mut ids = Vec::with_capacity(columns.len());
+
+        for (i, &(column, _)) in columns.iter().enumerate() {
+            let id = match schema.columns.get(column) {
+                Some(column) if !column.system => column.id,
+                _ => {
+                    return Err(Error::ColumnNotFound {
+                        table: self.name.clone(),
+                        column: column.to_owned(),
+                    })
+                }
+            };
+
+            if columns[..i].iter().any(|&(other, _)| other == column) {
+                return Err(Error::DuplicateColumn {
+                    table: self.name.clone(),
+                    column: column.to_owned(),
+                });
+            }
+
+            ids.push(id);
+        }
+
+        // Ids of the indexes are unique in the database.
+        let id = self.tables
+            .read()
+            .unwrap()
+            .values()
+            .flat_map(|table| table.index_definitions())
+            .map(|index| index.id + 1)
+            .max()
+            .unwrap_or(1);
+
+        Ok(IndexDefinition {
+            id,
+            name: name.to_owned(),
+            unique: options.unique,
+            primary: false,
+            columns: ids,
+            orders: columns.iter().map(|&(_, order)| order).collect(),
+            // Tree is created together with the index, see `create_tree`.
+            root: 0,
+        })
+    }
+
+    /// Creates tree of the new index in pages of the table and fills it with keys of all rows.
+    fn create_tree(&self, txn: &Transaction, mut definition: IndexDefinition)
+                   -> Result<TableIndex, Error> {
+        let schema = self.schema();
+        let columns = definition.key_columns(&schema).expect("Key columns exist in the schema");
+
+        let tree = BTree::create(txn, self.storage.clone(), self.id, columns)?;
+        definition.root = tree.root();
+
+        let index = TableIndex { definition, tree };
+        self.fill_index(txn, &schema, &index)?;
+
+        Ok(index)
+    }
+
+    /// Fills the empty index with keys of all rows, which are sorted and loaded into its tree
+    /// at once. Fails if the index is unique and two rows have the same key.
+    pub(super) fn fill_index(&self, txn: &Transaction, schema: &Schema, index: &TableIndex)
+                  -> Result<(), Error> {
+        let (definition, tree) = (&index.definition, &index.tree);
+
+        let mut entries: Vec<_> = self.rows()?
+            .into_iter()
+            .filter_map(|(id, row)| definition.key(schema, &row).map(|key| (key, id)))
+            .collect();
+
+        entries.sort_by(|a, b| tree.compare_keys(&a.0, &b.0).then(a.1.cmp(&b.1)));
+
+        let duplicate = entries.windows(2).any(|w| tree.compare_keys(&w[0].0, &w[1].0).is_eq());
+
+        if definition.unique && duplicate {
+            return Err(Error::UniqueViolation {
+                table: self.name.clone(),
+                index: definition.name.clone(),
+                columns: definition.columns
+                    .iter()
+                    .map(|&id| schema.column(id).unwrap().name.clone())
+                    .collect(),
+            });
+        }
+
+        tree.bulk_load(txn, entries)
+    }
+
+    /// Gets ids of the rows with keys of the index between the bounds. Bounds have values of
+    /// all key columns in the key order or of the leading ones, see `Index::range`: with
+    /// the same leading values in both bounds the range is scanned on the next column, e.g.
+    /// index on `(tenant_id, created_at)` finds rows of a tenant created in a time range.
+    /// Ids are sorted by the keys in the order of the index, rows with the same key by their
+    /// ids. Rows are read by `get`.
+    pub fn find_by_index(&self,
+                         index: &str,
+                         from: Bound<&[Value]>,
+                         to: Bound<&[Value]>)
+                         -> Result<Vec<RowId>, Error> {
+        self.check_attached()?;
+
+        let schema = self.schema();
+        let indexes = self.indexes.read().unwrap();
+
+        let index = match indexes.list.iter().find(|i| i.definition.name == index) {
+            Some(index) => index,
+            None => {
+                return Err(Error::IndexNotFound {
+                    table: self.name.clone(),
+                    index: index.to_owned(),
+                })
+            }
+        };
+
+        for bound in &[from, to] {
+            match *bound {
+                Bound::Included(key) | Bound::Excluded(key) => {
+                    // NULL is neither less nor greater than any key.
+                    if !self.check_key(&schema, &index.definition, key, true)? {
+                        return Ok(Vec::new());
+                    }
+                }
+                Bound::Unbounded => (),
+            }
+        }
+
+        index.tree.range(from, to)?.collect()
+    }
+
+    /// Checks that the key has values of all columns of the index in the key order, or of
+    /// the leading columns if it is a prefix. Returns `false` if some of the values is NULL.
+    fn check_key(&self, schema: &Schema, index: &IndexDefinition, key: &[Value], prefix: bool)
+                 -> Result<bool, Error> {
+        let columns = &index.columns;
+
+        if key.len() > columns.len() || key.is_empty() ||
+           (!prefix && key.len() != columns.len()) {
+            return Err(Error::InvalidArgument(format!("Key of index '{}' has {} columns, got {} \
+                                                       values",
+                                                      index.name,
+                                                      columns.len(),
+                                                      key.len())));
+        }
+
+        for (value, &id) in key.iter().zip(columns) {
+            let column = schema.column(id).unwrap();
+
+            match value.data_type() {
+                None => return Ok(false),
+                Some(actual) if actual != column.data_type => {
+                    return Err(Error::TypeMismatch {
+                        table: self.name.clone(),
+                        column: column.name.clone(),
+                        expected: column.data_type,
+                        actual,
+                    })
+                }
+                Some(_) => (),
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Gets values of the columns with the ids in the row. Returns `None` if any of the values
+/// is NULL.
+pub fn key_values(schema: &Schema, columns: &[u32], row: &Row) -> Option<Vec<Value>> {
+    columns.iter()
+        .map(|&id| {
+            schema.column(id)
+                .and_then(|column| row.get(&column.name))
+                .filter(|value| !value.is_null())
+                .cloned()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::ops::Bound;
+    use data_type::DataType;
+    use value::Value;
+    use row::Row;
+    use collation::SortOrder;
+    use storage::PageType;
+    use verify::Problem;
+    use database::{Database, DatabaseConfiguration, RowId};
+    use database::schema::Column;
+    use database::index::IndexOptions;
+    use database::foreign_key::ForeignKey;
+    use database::table::{Table, TableConfiguration};
+    use error::Error;
+
+    #[test]
+    fn enforce_primary_key_and_unique_constraints() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("id", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("email", DataType::VARCHAR, false)).expect("should not fail");
+        cfg.add_column(Column::new("data", DataType::VARBINARY, false)).expect("should not fail");
+        cfg.set_primary_key(&["id"]).expect("should not fail");
+        cfg.add_unique(&["email"]).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let ids: Vec<_> = (0..50)
+            .map(|i| {
+                let row = Row::new().with("id", i).with("email", format!("user{}", i));
+                table.insert(&row.with("data", vec![0u8; 100])).expect("should not fail")
+            })
+            .collect();
+
+        let (id, row) = table.find_by_primary_key(&[Value::from(7)])
+            .expect("should not fail")
+            .expect("row exists");
+        assert_eq!(id, ids[7]);
+        assert_eq!(row["email"], Value::from("user7"));
+
+        assert!(table.find_by_primary_key(&[Value::from(50)]).expect("should not fail").is_none());
+
+        match table.insert(&Row::new().with("id", 7).with("email", "other")) {
+            Err(Error::UniqueViolation { ref table, ref index, ref columns }) => {
+                assert_eq!(table, "SomeTable");
+                assert_eq!(index, "SomeTable_pkey");
+                assert_eq!(*columns, vec!["id".to_owned()]);
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match table.insert(&Row::new().with("id", 50).with("email", "user3")) {
+            Err(Error::UniqueViolation { ref index, .. }) => {
+                assert_eq!(index, "SomeTable_email_key")
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match table.insert(&Row::new().with("email", "other")) {
+            Err(Error::NotNullViolation { ref column, .. }) => assert_eq!(column, "id"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // NULL is not equal to any value, so several rows may have it in a unique column.
+        table.insert(&Row::new().with("id", 100)).expect("should not fail");
+        table.insert(&Row::new().with("id", 101)).expect("should not fail");
+
+        match table.update(ids[1], &Row::new().with("id", 2)) {
+            Err(Error::UniqueViolation { ref index, .. }) => assert_eq!(index, "SomeTable_pkey"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Row keeps its own key.
+        table.update(ids[1], &Row::new().with("id", 1).with("email", "user1"))
+            .expect("should not fail");
+
+        // Key of the row is changed and the row is moved to another page.
+        let moved = table.update(ids[1], &Row::new().with("id", 60).with("data", vec![1u8; 1000]))
+            .expect("should not fail");
+        assert!(moved != ids[1]);
+
+        assert!(table.find_by_primary_key(&[Value::from(1)]).expect("should not fail").is_none());
+        assert_eq!(table.find_by_primary_key(&[Value::from(60)])
+                       .expect("should not fail")
+                       .map(|(id, _)| id),
+                   Some(moved));
+
+        // Key of the deleted row can be used again.
+        table.delete(ids[2]).expect("should not fail");
+        assert!(table.find_by_primary_key(&[Value::from(2)]).expect("should not fail").is_none());
+
+        table.insert(&Row::new().with("id", 2).with("email", "user2")).expect("should not fail");
+        table.insert(&Row::new().with("id", 1).with("email", "user61")).expect("should not fail");
+
+        assert_eq!(table.scan().expect("should not fail").count(), 53);
+    }
+
+    #[test]
+    fn reject_duplicate_index_names() {
+        let mut database = Database::new();
+
+        // Both unique constraints are named "SomeTable_a_b_c_key".
+        let mut cfg = TableConfiguration::new("SomeTable");
+
+        for name in &["a", "b", "c", "a_b", "b_c"] {
+            cfg.add_column(Column::new(name, DataType::INTEGER, true)).expect("should not fail");
+        }
+
+        cfg.add_unique(&["a_b", "c"]).expect("should not fail");
+        cfg.add_unique(&["a", "b_c"]).expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::DuplicateIndex { ref table, ref index }) => {
+                assert_eq!(table, "SomeTable");
+                assert_eq!(index, "SomeTable_a_b_c_key");
+            }
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+
+        // Descending "x" and ascending "x_desc" are both named "SomeTable_x_desc_idx".
+        let new_cfg = || {
+            let mut cfg = TableConfiguration::new("SomeTable");
+
+            for name in &["id", "x", "x_desc"] {
+                cfg.add_column(Column::new(name, DataType::INTEGER, true))
+                    .expect("should not fail");
+            }
+
+            cfg.set_primary_key(&["id"]).expect("should not fail");
+            cfg.add_index(&[("x", SortOrder::Descending)]).expect("should not fail");
+            cfg
+        };
+
+        let mut cfg = new_cfg();
+        cfg.add_index(&[("x_desc", SortOrder::Ascending)]).expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::DuplicateIndex { ref index, .. }) => {
+                assert_eq!(index, "SomeTable_x_desc_idx")
+            }
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        // So is the index created for the foreign key.
+        let mut cfg = new_cfg();
+        cfg.add_foreign_key(ForeignKey::new(&["x_desc"], "SomeTable", &["id"]))
+            .expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::DuplicateIndex { ref index, .. }) => {
+                assert_eq!(index, "SomeTable_x_desc_idx")
+            }
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        assert!(database.tables().is_empty());
+    }
+
+    #[test]
+    fn index_every_type() {
+        let mut database = Database::new();
+
+        let columns = [("s", DataType::VARCHAR),
+                       ("b", DataType::VARBINARY),
+                       ("flag", DataType::BOOLEAN),
+                       ("small", DataType::SMALLINT),
+                       ("big", DataType::BIGINT),
+                       ("f", DataType::FLOAT)];
+
+        let mut cfg = TableConfiguration::new("Values");
+
+        for &(name, data_type) in &columns {
+            cfg.add_column(Column::new(name, data_type, false)).expect("should not fail");
+            cfg.add_unique(&[name]).expect("should not fail");
+        }
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        table.insert(&Row::new()
+                .with("s", "a")
+                .with("b", vec![1u8])
+                .with("flag", true)
+                .with("small", 1i16)
+                .with("big", 1i64)
+                .with("f", f64::NAN))
+            .expect("should not fail");
+
+        table.insert(&Row::new().with("s", "A").with("b", vec![1u8, 0]).with("f", -0.0))
+            .expect("should not fail");
+
+        let duplicates = [Row::new().with("s", "a"),
+                          Row::new().with("b", vec![1u8]),
+                          Row::new().with("flag", true),
+                          Row::new().with("small", 1i16),
+                          Row::new().with("big", 1i64),
+                          Row::new().with("f", f64::NAN),
+                          Row::new().with("f", 0.0)];
+
+        for (row, &(name, _)) in duplicates.iter().zip(columns.iter().chain(&columns[5..])) {
+            match table.insert(row) {
+                Err(Error::UniqueViolation { ref columns, .. }) => assert_eq!(columns, &[name]),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+        }
+
+        table.insert(&Row::new().with("flag", false).with("f", f64::INFINITY))
+            .expect("should not fail");
+        assert_eq!(table.scan().expect("should not fail").count(), 3);
+    }
+
+    #[test]
+    fn find_rows_by_index() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("Events");
+        cfg.add_column(Column::new("id", DataType::BIGINT, false)).expect("should not fail");
+        cfg.add_column(Column::new("name", DataType::VARCHAR, false)).expect("should not fail");
+        cfg.set_primary_key(&["id"]).expect("should not fail");
+        cfg.add_unique(&["name"]).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+        assert_eq!(table.index_names(), vec!["Events_pkey", "Events_name_key"]);
+
+        for id in (0..100i64).rev() {
+            table.insert(&Row::new().with("id", id).with("name", format!("event{:02}", id)))
+                .expect("should not fail");
+        }
+
+        table.insert(&Row::new().with("id", 100i64)).expect("should not fail");
+
+        let ids = |from: Bound<i64>, to: Bound<i64>| -> Vec<i64> {
+            let key = |id| vec![Value::from(id)];
+            let (from, to) = (from.map(key), to.map(key));
+
+            table.find_by_index("Events_pkey",
+                               from.as_ref().map(Vec::as_slice),
+                               to.as_ref().map(Vec::as_slice))
+                .expect("should not fail")
+                .into_iter()
+                .map(|id| {
+                    let row = table.get(id).expect("should not fail");
+                    i64::try_from(row["id"].clone()).expect("should not fail")
+                })
+                .collect()
+        };
+
+        assert_eq!(ids(Bound::Included(10), Bound::Excluded(15)), vec![10, 11, 12, 13, 14]);
+        assert_eq!(ids(Bound::Excluded(97), Bound::Unbounded), vec![98, 99, 100]);
+        assert_eq!(ids(Bound::Unbounded, Bound::Included(1)), vec![0, 1]);
+        assert_eq!(ids(Bound::Unbounded, Bound::Unbounded).len(), 101);
+        assert!(ids(Bound::Included(50), Bound::Included(49)).is_empty());
+
+        let name = [Value::from("event42")];
+        let found = table.find_by_index("Events_name_key",
+                                        Bound::Included(&name),
+                                        Bound::Included(&name))
+            .expect("should not fail");
+        assert_eq!(table.get(found[0]).expect("should not fail")["id"], Value::from(42i64));
+
+        // Rows without name are not in the index, rows are in reverse order.
+        let from = [Value::from("event95")];
+        let names: Vec<_> = table.find_by_index("Events_name_key",
+                                                Bound::Excluded(&from),
+                                                Bound::Unbounded)
+            .expect("should not fail")
+            .into_iter()
+            .rev()
+            .map(|id| table.get(id).expect("should not fail")["name"].clone())
+            .collect();
+        assert_eq!(names,
+                   vec![Value::from("event99"),
+                        Value::from("event98"),
+                        Value::from("event97"),
+                        Value::from("event96")]);
+
+        let null = [Value::Null];
+        assert!(table.find_by_index("Events_name_key", Bound::Included(&null), Bound::Unbounded)
+            .expect("should not fail")
+            .is_empty());
+
+        let key = [Value::from(1)];
+
+        match table.find_by_index("Events_name_key", Bound::Included(&key), Bound::Unbounded) {
+            Err(Error::TypeMismatch { ref column, .. }) => assert_eq!(column, "name"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match table.find_by_index("Events_pkey", Bound::Included(&[]), Bound::Unbounded) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match table.find_by_index("Missing", Bound::Unbounded, Bound::Unbounded) {
+            Err(Error::IndexNotFound { ref index, .. }) => assert_eq!(index, "Missing"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn composite_indexes() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("composite_indexes");
+
+        {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            let mut cfg = TableConfiguration::new("Events");
+            cfg.add_column(Column::new("tenant_id", DataType::INTEGER, false))
+                .expect("should not fail");
+            cfg.add_column(Column::new("created_at", DataType::BIGINT, false))
+                .expect("should not fail");
+            cfg.add_index(&[("tenant_id", SortOrder::Ascending),
+                              ("created_at", SortOrder::Descending)])
+                .expect("should not fail");
+
+            match cfg.add_index(&[("tenant_id", SortOrder::Ascending),
+                                  ("created_at", SortOrder::Descending)]) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            match cfg.add_index(&[("missing", SortOrder::Ascending)]) {
+                Err(Error::ColumnNotFound { ref column, .. }) => assert_eq!(column, "missing"),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            cfg.add_index(&[("created_at", SortOrder::Ascending)]).expect("should not fail");
+
+            let table = database.create_table(cfg).expect("should not fail");
+            assert_eq!(table.index_names(),
+                       vec!["Events_tenant_id_created_at_desc_idx", "Events_created_at_idx"]);
+
+            for i in 0..60i64 {
+                table.insert(&Row::new().with("tenant_id", (i % 3) as i32).with("created_at", i))
+                    .expect("should not fail");
+            }
+
+            // Rows without the key are not indexed, but the index is not unique.
+            table.insert(&Row::new().with("tenant_id", 1)).expect("should not fail");
+            table.insert(&Row::new().with("tenant_id", 1).with("created_at", 4i64))
+                .expect("should not fail");
+        }
+
+        {
+            let database = Database::open(&path).expect("should not fail");
+            let table = database.table("Events").expect("should not fail");
+
+            let times = |from: Bound<&[Value]>, to: Bound<&[Value]>| -> Vec<i64> {
+                table.find_by_index("Events_tenant_id_created_at_desc_idx", from, to)
+                    .expect("should not fail")
+                    .into_iter()
+                    .map(|id| {
+                        let row = table.get(id).expect("should not fail");
+                        i64::try_from(row["created_at"].clone()).expect("should not fail")
+                    })
+                    .collect()
+            };
+
+            let tenant = [Value::from(1)];
+            let all = times(Bound::Included(&tenant), Bound::Included(&tenant));
+            assert_eq!(all.len(), 21);
+            assert_eq!(&all[..3], &[58, 55, 52]);
+            assert_eq!(&all[19..], &[4, 1]);
+
+            // Latest rows of the tenant created before 10.
+            let from = [Value::from(1), Value::from(10i64)];
+            assert_eq!(times(Bound::Excluded(&from), Bound::Included(&tenant)), vec![7, 4, 4, 1]);
+
+            let to = [Value::from(1), Value::from(40i64)];
+            assert_eq!(times(Bound::Included(&to), Bound::Excluded(&from)),
+                       vec![40, 37, 34, 31, 28, 25, 22, 19, 16, 13]);
+
+            let ids = table.find_by_index("Events_created_at_idx",
+                                          Bound::Unbounded,
+                                          Bound::Unbounded)
+                .expect("should not fail");
+            assert_eq!(ids.len(), 61);
+
+            let key = [Value::from(1), Value::from(4i64), Value::from(1)];
+
+            match table.find_by_index("Events_tenant_id_created_at_desc_idx",
+                                      Bound::Included(&key),
+                                      Bound::Unbounded) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            let columns = database.table("_index_columns").expect("should not fail");
+            let descending: Vec<_> = columns.scan().expect("should not fail")
+                .map(|row| row.expect("should not fail")["descending"].clone())
+                .collect();
+            assert_eq!(descending,
+                       vec![Value::from(false), Value::from(true), Value::from(false)]);
+
+            assert!(database.verify().expect("should not fail").is_ok());
+        }
+
+        remove_database(&path);
+    }
+
+    #[test]
+    fn create_and_drop_indexes() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("create_and_drop_indexes");
+
+        {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            let mut cfg = TableConfiguration::new("Orders");
+            cfg.add_column(Column::new("id", DataType::INTEGER, false)).expect("should not fail");
+            cfg.add_column(Column::new("tenant", DataType::INTEGER, false))
+                .expect("should not fail");
+            cfg.add_column(Column::new("code", DataType::VARCHAR, false)).expect("should not fail");
+            cfg.set_primary_key(&["id"]).expect("should not fail");
+
+            let table = database.create_table(cfg).expect("should not fail");
+
+            for id in 0..50 {
+                table.insert(&Row::new()
+                        .with("id", id)
+                        .with("tenant", id % 5)
+                        .with("code", format!("code{}", id / 2)))
+                    .expect("should not fail");
+            }
+
+            let alter = table.alter();
+
+            // Index is filled with keys of the existing rows.
+            table.create_index("by_tenant",
+                              &[("tenant", SortOrder::Ascending), ("id", SortOrder::Descending)],
+                              IndexOptions::new())
+                .expect("should not fail");
+
+            let tenant = |id: i32| -> Vec<RowId> {
+                let key = [Value::from(id)];
+                table.find_by_index("by_tenant", Bound::Included(&key), Bound::Included(&key))
+                    .expect("should not fail")
+            };
+
+            let ids = tenant(3);
+            assert_eq!(ids.len(), 10);
+            assert_eq!(table.get(ids[0]).expect("should not fail")["id"], Value::from(48));
+
+            // Changes made after the index was created are rejected.
+            match database.alter_table(alter) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            match table.create_index("by_code", &[("code", SortOrder::Ascending)],
+                                     IndexOptions::new().unique(true)) {
+                Err(Error::UniqueViolation { ref index, ref columns, .. }) => {
+                    assert_eq!(index, "by_code");
+                    assert_eq!(columns, &["code"]);
+                }
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            match table.create_index("by_tenant", &[("code", SortOrder::Ascending)],
+                                     IndexOptions::new()) {
+                Err(Error::DuplicateIndex { ref index, .. }) => assert_eq!(index, "by_tenant"),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            match table.create_index("by_missing", &[("missing", SortOrder::Ascending)],
+                                     IndexOptions::new()) {
+                Err(Error::ColumnNotFound { ref column, .. }) => assert_eq!(column, "missing"),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            match table.create_index("by_nothing", &[], IndexOptions::new()) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            let system = database.table("_indexes").expect("should not fail");
+
+            match system.create_index("by_name", &[("name", SortOrder::Ascending)],
+                                      IndexOptions::new()) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            assert_eq!(table.index_names(), vec!["Orders_pkey", "by_tenant"]);
+
+            // Unique index is created once the duplicates are removed.
+            for id in (1..50).step_by(2) {
+                table.delete(table.find_by_primary_key(&[Value::from(id)])
+                        .expect("should not fail")
+                        .expect("row exists")
+                        .0)
+                    .expect("should not fail");
+            }
+
+            table.create_index("by_code", &[("code", SortOrder::Ascending)],
+                              IndexOptions::new().unique(true))
+                .expect("should not fail");
+            assert_eq!(tenant(3).len(), 5);
+
+            // Indexes are maintained by inserts, updates and deletes and restored if they fail.
+            let id = table.insert(&Row::new().with("id", 100).with("tenant", 3).with("code", "new"))
+                .expect("should not fail");
+            assert_eq!(tenant(3).len(), 6);
+
+            match table.insert(&Row::new().with("id", 101).with("tenant", 3).with("code", "new")) {
+                Err(Error::UniqueViolation { ref index, .. }) => assert_eq!(index, "by_code"),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            match table.update(id, &Row::new().with("tenant", 4).with("code", "code0")) {
+                Err(Error::UniqueViolation { ref index, .. }) => assert_eq!(index, "by_code"),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            assert_eq!(tenant(3).len(), 6);
+
+            let id = table.update(id, &Row::new().with("tenant", 4)).expect("should not fail");
+            assert_eq!(tenant(3).len(), 5);
+            assert_eq!(tenant(4).len(), 6);
+            assert_eq!(tenant(4)[0], id);
+
+            table.delete(id).expect("should not fail");
+            assert_eq!(tenant(4).len(), 5);
+        }
+
+        {
+            let mut database = Database::open(&path).expect("should not fail");
+            let table = database.table("Orders").expect("should not fail");
+
+            assert_eq!(table.index_names(), vec!["Orders_pkey", "by_tenant", "by_code"]);
+
+            let code = [Value::from("code3")];
+            let ids = table.find_by_index("by_code", Bound::Included(&code), Bound::Included(&code))
+                .expect("should not fail");
+            assert_eq!(ids.len(), 1);
+
+            match table.drop_index("Orders_pkey") {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            let mut cfg = TableConfiguration::new("Items");
+            cfg.add_column(Column::new("code", DataType::VARCHAR, false)).expect("should not fail");
+            cfg.add_foreign_key(ForeignKey::new(&["code"], "Orders", &["code"]))
+                .expect("should not fail");
+
+            database.create_table(cfg).expect("should not fail");
+
+            match table.drop_index("by_code") {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            database.drop_table("Items").expect("should not fail");
+
+            table.drop_index("by_code").expect("should not fail");
+            table.drop_index("by_tenant").expect("should not fail");
+
+            match table.drop_index("by_tenant") {
+                Err(Error::IndexNotFound { ref index, .. }) => assert_eq!(index, "by_tenant"),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            match table.find_by_index("by_code", Bound::Unbounded, Bound::Unbounded) {
+                Err(Error::IndexNotFound { .. }) => (),
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            // Duplicate codes are allowed once the unique index is dropped.
+            table.insert(&Row::new().with("id", 101).with("code", "code3"))
+                .expect("should not fail");
+
+            assert_eq!(table.index_names(), vec!["Orders_pkey"]);
+            let index_columns = database.table("_index_columns").expect("should not fail");
+            assert_eq!(index_columns.scan().expect("should not fail").count(), 1);
+            assert!(database.verify().expect("should not fail").is_ok());
+        }
+
+        {
+            let database = Database::open(&path).expect("should not fail");
+            let table = database.table("Orders").expect("should not fail");
+
+            assert_eq!(table.index_names(), vec!["Orders_pkey"]);
+            assert_eq!(table.scan().expect("should not fail").count(), 26);
+            assert!(database.verify().expect("should not fail").is_ok());
+        }
+
+        remove_database(&path);
+    }
+
+    #[test]
+    fn persist_index_trees() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("persist_index_trees");
+
+        let mut cfg = DatabaseConfiguration::new();
+        cfg.set_page_size(512).expect("should not fail");
+        cfg.set_buffer_pool_size(4).expect("should not fail");
+
+        let index_pages = |table: &Table| -> Vec<u32> {
+            table.storage
+                .pages(table.id)
+                .into_iter()
+                .filter(|&page_id| {
+                    let page = table.storage.get_page(page_id).expect("should not fail").unwrap();
+                    let page_type = page.lock().page_type();
+                    page_type == Some(PageType::Index)
+                })
+                .collect()
+        };
+
+        let find = |table: &Table, rank: Value| -> Vec<RowId> {
+            let key = [rank];
+            table.find_by_index("by_rank", Bound::Included(&key), Bound::Included(&key))
+                .expect("should not fail")
+        };
+
+        {
+            let mut database = Database::create_with(&path, &cfg).expect("should not fail");
+
+            let mut table_cfg = TableConfiguration::new("Events");
+            table_cfg.add_column(Column::new("id", DataType::INTEGER, false))
+                .expect("should not fail");
+            table_cfg.add_column(Column::new("rank", DataType::SMALLINT, false))
+                .expect("should not fail");
+            table_cfg.set_primary_key(&["id"]).expect("should not fail");
+
+            let table = database.create_table(table_cfg).expect("should not fail");
+
+            for id in 0..1000 {
+                table.insert(&Row::new().with("id", id).with("rank", (id % 100) as i16))
+                    .expect("should not fail");
+            }
+
+            table.create_index("by_rank", &[("rank", SortOrder::Ascending)], IndexOptions::new())
+                .expect("should not fail");
+
+            // Trees of the indexes have several levels.
+            let pages = index_pages(&table).len();
+            assert!(pages > 10);
+
+            for id in 0..500 {
+                let (row, _) = table.find_by_primary_key(&[Value::from(id)])
+                    .expect("should not fail")
+                    .expect("row exists");
+                table.delete(row).expect("should not fail");
+            }
+
+            // Trees shrink as their entries are removed.
+            assert!(index_pages(&table).len() < pages);
+            assert_eq!(find(&table, Value::from(7i16)).len(), 5);
+
+            // Changes of the trees are recovered from the log.
+            database.simulate_crash();
+        }
+
+        {
+            let mut database = Database::open_with(&path, &cfg).expect("should not fail");
+            let table = database.table("Events").expect("should not fail");
+
+            let (_, row) = table.find_by_primary_key(&[Value::from(700)])
+                .expect("should not fail")
+                .expect("row exists");
+            assert_eq!(row["rank"], Value::from(0i16));
+            assert!(table.find_by_primary_key(&[Value::from(300)])
+                .expect("should not fail")
+                .is_none());
+            assert_eq!(find(&table, Value::from(7i16)).len(), 5);
+
+            // Index is rebuilt with keys of the widened type.
+            let mut alter = table.alter();
+            alter.widen_column("rank", DataType::INTEGER).expect("should not fail");
+            database.alter_table(alter).expect("should not fail");
+
+            assert_eq!(find(&table, Value::from(7)).len(), 5);
+            assert!(database.verify().expect("should not fail").is_ok());
+
+            let pages = index_pages(&table);
+            table.drop_index("by_rank").expect("should not fail");
+            assert!(index_pages(&table).len() < pages.len());
+
+            // Index page which does not belong to any tree.
+            let txn = database.storage.begin();
+            let orphan = txn.allocate_page(PageType::Index, table.id).expect("should not fail");
+            txn.commit().expect("should not fail");
+
+            let report = database.verify().expect("should not fail");
+
+            match report.problems[..] {
+                [Problem::InvalidPage { page_id, .. }] => assert_eq!(page_id, orphan),
+                ref problems => panic!("Unexpected problems: {:?}", problems),
+            }
+        }
+
+        remove_database(&path);
+    }
+
+    #[test]
+    fn validate_keys() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+
+        match cfg.set_primary_key(&["baz"]) {
+            Err(Error::ColumnNotFound { ref column, .. }) => assert_eq!(column, "baz"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match cfg.add_unique(&["foo", "foo"]) {
+            Err(Error::DuplicateColumn { ref column, .. }) => assert_eq!(column, "foo"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match cfg.add_unique(&[]) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        cfg.set_primary_key(&["foo", "bar"]).expect("should not fail");
+        cfg.add_unique(&["bar"]).expect("should not fail");
+
+        match cfg.set_primary_key(&["bar"]) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match cfg.add_unique(&["bar"]) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        match table.find_by_primary_key(&[Value::from(1)]) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match table.find_by_primary_key(&[Value::from(1i64), Value::from("a")]) {
+            Err(Error::TypeMismatch { ref column, .. }) => assert_eq!(column, "foo"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert!(table.find_by_primary_key(&[Value::Null, Value::from("a")])
+            .expect("should not fail")
+            .is_none());
+
+        let mut alter = table.alter();
+
+        match alter.drop_column("bar") {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut cfg = TableConfiguration::new("OtherTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+
+        let other = database.create_table(cfg).expect("should not fail");
+
+        match other.find_by_primary_key(&[Value::from(1)]) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn persist_keys() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("persist_keys");
+
+        {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            let mut cfg = TableConfiguration::new("SomeTable");
+            cfg.add_column(Column::new("foo", DataType::SMALLINT, false)).expect("should not fail");
+            cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+            cfg.add_column(Column::new("baz", DataType::FLOAT, false)).expect("should not fail");
+            cfg.set_primary_key(&["bar", "foo"]).expect("should not fail");
+            cfg.add_unique(&["baz"]).expect("should not fail");
+
+            let table = database.create_table(cfg).expect("should not fail");
+
+            for i in 0..10i16 {
+                let row = Row::new().with("foo", i % 2).with("bar", format!("{}", i / 2));
+                table.insert(&row.with("baz", i as f64)).expect("should not fail");
+            }
+
+            let mut cfg = TableConfiguration::new("DroppedTable");
+            cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+            cfg.set_primary_key(&["foo"]).expect("should not fail");
+
+            database.create_table(cfg).expect("should not fail");
+            database.drop_table("DroppedTable").expect("should not fail");
+
+            // Index is rebuilt with the widened type.
+            let mut alter = table.alter();
+            alter.widen_column("foo", DataType::INTEGER).expect("should not fail");
+            alter.rename_column("bar", "name").expect("should not fail");
+            database.alter_table(alter).expect("should not fail");
+
+            let key = [Value::from("2"), Value::from(1)];
+            let (_, row) = table.find_by_primary_key(&key)
+                .expect("should not fail")
+                .expect("row exists");
+            assert_eq!(row["baz"], Value::from(5.0));
+        }
+
+        {
+            let database = Database::open(&path).expect("should not fail");
+            let table = database.table("SomeTable").expect("should not fail");
+
+            let indexes: Vec<_> = database.table("_indexes")
+                .expect("should not fail")
+                .scan().expect("should not fail")
+                .map(|row| row.expect("should not fail")["name"].clone())
+                .collect();
+            assert_eq!(indexes,
+                       vec![Value::from("SomeTable_pkey"), Value::from("SomeTable_baz_key")]);
+
+            let key = [Value::from("4"), Value::from(0)];
+            let (_, row) = table.find_by_primary_key(&key)
+                .expect("should not fail")
+                .expect("row exists");
+            assert_eq!(row["baz"], Value::from(8.0));
+
+            match table.insert(&Row::new().with("foo", 0).with("name", "4")) {
+                Err(Error::UniqueViolation { ref columns, .. }) => {
+                    assert_eq!(*columns, vec!["name".to_owned(), "foo".to_owned()])
+                }
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            match table.insert(&Row::new().with("foo", 0).with("name", "5").with("baz", 1.0)) {
+                Err(Error::UniqueViolation { ref index, .. }) => {
+                    assert_eq!(index, "SomeTable_baz_key")
+                }
+                res => panic!("Unexpected result: {:?}", res),
+            }
+
+            table.insert(&Row::new().with("foo", 0).with("name", "5")).expect("should not fail");
+
+            let index_columns = database.table("_index_columns").expect("should not fail");
+            assert_eq!(index_columns.scan().expect("should not fail").count(), 3);
+            assert!(database.verify().expect("should not fail").is_ok());
+        }
+
+        remove_database(&path);
+    }
+}
@@ -0,0 +1,1126 @@
+use std::collections::BTreeMap;
+use std::cmp;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use row::Row;
+use storage::Storage;
+use storage::DEFAULT_PAGE_SIZE;
+use storage::DEFAULT_BUFFER_POOL_SIZE;
+use storage::MIN_PAGE_SIZE;
+use storage::BufferPoolStats;
+use storage::Transaction;
+use storage::Checkpointer;
+use database::schema::Schema;
+use database::catalog::{SYSTEM_TABLES, catalog_value};
+use error::Error;
+
+mod schema;
+mod index;
+mod foreign_key;
+mod table;
+mod alter;
+mod catalog;
+mod verify;
+
+pub use self::schema::Column;
+pub use self::schema::ColumnBuilder;
+pub use self::foreign_key::ForeignKey;
+pub use self::index::IndexOptions;
+pub use self::table::TableConfiguration;
+pub use self::alter::AlterTable;
+pub use self::table::Table;
+pub use self::table::Scan;
+pub use storage::CorruptPage;
+pub use storage::CorruptionPolicy;
+
+/// Schema of the table, which is shared by its handles and scans, so they see schema changes.
+type SharedSchema = Arc<RwLock<Arc<Schema>>>;
+
+/// Tables of the database by their ids, which are shared by the tables, so they can enforce
+/// foreign keys.
+type SharedTables = Arc<RwLock<BTreeMap<u32, Arc<Table>>>>;
+
+/// Flag of the deleted row in the `_flags` system column.
+const ROW_DELETED: i32 = 0x1;
+
+/// Schema version of the row is stored in the high bits of the `_flags` system column.
+const VERSION_SHIFT: u32 = 16;
+
+/// Maximal number of schema versions of a table.
+const MAX_SCHEMA_VERSIONS: usize = 1 << 15;
+
+/// Default interval between checkpoints of the database file.
+pub const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Identifier of the row in the table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RowId {
+    page: u32,
+    slot: u32,
+}
+
+impl RowId {
+    /// Creates id of the row in the slot of the page.
+    pub fn new(page: u32, slot: u32) -> RowId {
+        RowId { page, slot }
+    }
+
+    /// Gets id of the page of the row.
+    pub fn page(&self) -> u32 {
+        self.page
+    }
+
+    /// Gets slot of the row in its page.
+    pub fn slot(&self) -> u32 {
+        self.slot
+    }
+}
+
+/// Configuration of the database file.
+#[derive(Debug, Clone)]
+pub struct DatabaseConfiguration {
+    page_size: usize,
+    buffer_pool_size: usize,
+    checkpoint_interval: Option<Duration>,
+    corruption_policy: CorruptionPolicy,
+    read_only: bool,
+}
+
+impl Default for DatabaseConfiguration {
+    fn default() -> Self {
+        DatabaseConfiguration::new()
+    }
+}
+
+impl DatabaseConfiguration {
+    /// Creates new DatabaseConfiguration with default settings.
+    pub fn new() -> DatabaseConfiguration {
+        DatabaseConfiguration {
+            page_size: DEFAULT_PAGE_SIZE,
+            buffer_pool_size: DEFAULT_BUFFER_POOL_SIZE,
+            checkpoint_interval: Some(DEFAULT_CHECKPOINT_INTERVAL),
+            corruption_policy: CorruptionPolicy::Fail,
+            read_only: false,
+        }
+    }
+
+    /// Sets size of the single page in bytes. It is used only when a new file is created.
+    pub fn set_page_size(&mut self, page_size: usize) -> Result<(), Error> {
+        if page_size < MIN_PAGE_SIZE || !page_size.is_power_of_two() {
+            return Err(Error::InvalidArgument(format!("Page size must be a power of two not \
+                                                       less than {}, got {}",
+                                                      MIN_PAGE_SIZE,
+                                                      page_size)));
+        }
+
+        self.page_size = page_size;
+        Ok(())
+    }
+
+    /// Sets maximal number of pages cached in memory.
+    pub fn set_buffer_pool_size(&mut self, pages: usize) -> Result<(), Error> {
+        if pages == 0 {
+            return Err(Error::InvalidArgument("Buffer pool size must be positive".to_owned()));
+        }
+
+        self.buffer_pool_size = pages;
+        Ok(())
+    }
+
+    /// Sets interval between checkpoints written in the background. Recovery after a crash
+    /// replays only the changes made since the last checkpoint. `None` disables background
+    /// checkpoints, so they are written only by `Database::checkpoint` and on close.
+    pub fn set_checkpoint_interval(&mut self, interval: Option<Duration>) -> Result<(), Error> {
+        if interval == Some(Duration::from_secs(0)) {
+            return Err(Error::InvalidArgument("Checkpoint interval must be positive".to_owned()));
+        }
+
+        self.checkpoint_interval = interval;
+        Ok(())
+    }
+
+    /// Sets what to do when a corrupted page is read from the database file: either fail
+    /// the operation, or quarantine the page, so the rest of the database stays available.
+    /// Rows stored in a quarantined page are lost. If a quarantined page makes the system
+    /// tables unreadable, the database is opened without user tables and new tables can not
+    /// be created, so the file can still be verified.
+    pub fn set_corruption_policy(&mut self, policy: CorruptionPolicy) {
+        self.corruption_policy = policy;
+    }
+
+    /// Sets whether the database file is opened read-only. Read-only database never changes its
+    /// file and log: a file which was not closed properly is recovered in memory, and changes of
+    /// the database fail. It is used only when an existing file is opened.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+}
+
+/// Database. Either lives in memory only or is stored in a database file.
+///
+/// Tables are described in the catalog, which consists of the system tables stored like any
+/// other table, see `SYSTEM_TABLES`. System tables are updated in the same transaction as
+/// the tables they describe.
+///
+/// Changes of the database file are written to the write-ahead log. Checkpoints, which allow
+/// to discard the log, are written periodically in the background.
+#[derive(Debug)]
+pub struct Database {
+    storage: Arc<Storage>,
+    tables: BTreeMap<String, Arc<Table>>,
+    /// Tables by their ids, which are shared with the tables.
+    registry: SharedTables,
+    checkpointer: Option<Checkpointer>,
+    /// Why the system tables could not be read when the database was opened.
+    catalog_error: Option<String>,
+}
+
+impl Database {
+    /// Creates new in-memory Database.
+    pub fn new() -> Database {
+        Database::with_storage(Storage::new())
+    }
+
+    /// Creates new Database stored in the file. Fails if the file already exists.
+    pub fn create(path: &Path) -> Result<Database, Error> {
+        Database::create_with(path, &DatabaseConfiguration::new())
+    }
+
+    /// Creates new Database stored in the file using provided configuration.
+    /// Fails if the file already exists.
+    pub fn create_with(path: &Path, cfg: &DatabaseConfiguration) -> Result<Database, Error> {
+        let storage = Storage::create(path,
+                                      cfg.page_size,
+                                      cfg.buffer_pool_size,
+                                      cfg.corruption_policy)?;
+
+        let mut database = Database::with_storage(storage);
+        database.start_checkpointer(cfg)?;
+
+        Ok(database)
+    }
+
+    /// Opens Database stored in the file.
+    pub fn open(path: &Path) -> Result<Database, Error> {
+        Database::open_with(path, &DatabaseConfiguration::new())
+    }
+
+    /// Opens Database stored in the file using provided configuration.
+    /// Page size of the existing file is used rather than the configured one.
+    pub fn open_with(path: &Path, cfg: &DatabaseConfiguration) -> Result<Database, Error> {
+        let storage = if cfg.read_only {
+            Storage::open_read_only(path, cfg.buffer_pool_size, cfg.corruption_policy)?
+        } else {
+            Storage::open(path, cfg.buffer_pool_size, cfg.corruption_policy)?
+        };
+
+        let mut database = Database::with_storage(storage);
+
+        match database.load_catalog() {
+            Ok(()) => (),
+            Err(Error::Corruption(reason)) if cfg.corruption_policy ==
+                                             CorruptionPolicy::Quarantine => {
+                database.catalog_error = Some(reason);
+            }
+            Err(e) => return Err(e),
+        }
+
+        if !cfg.read_only {
+            database.start_checkpointer(cfg)?;
+        }
+
+        Ok(database)
+    }
+
+    /// Gets ids of the corrupted pages which were quarantined.
+    pub fn quarantined_pages(&self) -> Vec<u32> {
+        self.storage.quarantined_pages()
+    }
+
+    /// Gets statistics of the buffer pool, which caches pages of the database file.
+    pub fn buffer_pool_stats(&self) -> BufferPoolStats {
+        self.storage.buffer_pool_stats()
+    }
+
+    fn with_storage(storage: Storage) -> Database {
+        let storage = Arc::new(storage);
+        let registry = SharedTables::default();
+
+        let tables = SYSTEM_TABLES.iter()
+            .enumerate()
+            .map(|(i, &(name, columns))| {
+                let table = Table::system_table(i as u32 + 1,
+                                                name,
+                                                columns,
+                                                storage.clone(),
+                                                registry.clone());
+                (name.to_owned(), Arc::new(table))
+            })
+            .collect();
+
+        let database = Database {
+            storage,
+            tables,
+            registry,
+            checkpointer: None,
+            catalog_error: None,
+        };
+
+        database.update_registry(&database.tables);
+        database
+    }
+
+    /// Sets the tables shared with the tables.
+    fn update_registry(&self, tables: &BTreeMap<String, Arc<Table>>) {
+        *self.registry.write().unwrap() = tables.values()
+            .map(|table| (table.id, table.clone()))
+            .collect();
+    }
+
+    /// Starts background checkpoints, if they are enabled in the configuration.
+    fn start_checkpointer(&mut self, cfg: &DatabaseConfiguration) -> Result<(), Error> {
+        if let Some(interval) = cfg.checkpoint_interval {
+            self.checkpointer = Some(Checkpointer::start(self.storage.clone(), interval)?);
+        }
+
+        Ok(())
+    }
+
+    /// Writes all changes to the database file.
+    pub fn flush(&self) -> Result<(), Error> {
+        self.storage.flush()
+    }
+
+    /// Writes a checkpoint: all changes are written to the database file and the log
+    /// is discarded. Waits until the active operation, if any, is finished.
+    pub fn checkpoint(&self) -> Result<(), Error> {
+        self.storage.checkpoint()
+    }
+
+    /// Gets description of the error of the last failed background checkpoint, `None` if
+    /// the last checkpoint has succeeded. Changes are kept in the log until a checkpoint
+    /// succeeds, so the log grows meanwhile.
+    pub fn checkpoint_error(&self) -> Option<String> {
+        self.checkpointer.as_ref().and_then(Checkpointer::last_error)
+    }
+
+    /// Closes the database: stops background checkpoints and writes a checkpoint, so the file
+    /// does not need recovery when it is opened next time. Unlike dropping the database,
+    /// returns the error of the last failed background checkpoint or of closing the file.
+    pub fn close(mut self) -> Result<(), Error> {
+        let stopped = match self.checkpointer.take() {
+            Some(checkpointer) => checkpointer.stop(),
+            None => Ok(()),
+        };
+
+        let closed = self.storage.close();
+
+        stopped.and(closed)
+    }
+
+    /// Drops the database without writing anything to the database file,
+    /// as if the process has crashed.
+    #[cfg(test)]
+    fn simulate_crash(mut self) {
+        self.checkpointer = None;
+        self.storage.abandon();
+    }
+
+    /// Creates new table in Database using provided configuration.
+    /// Fails if a table with the same name already exists, unless the configuration allows
+    /// to return the existing table.
+    pub fn create_table(&mut self, cfg: TableConfiguration) -> Result<Arc<Table>, Error> {
+        if let Some(table) = self.tables.get(&cfg.name) {
+            if cfg.if_not_exists {
+                return Ok(table.clone());
+            }
+
+            return Err(Error::DuplicateTable(cfg.name));
+        }
+
+        if let Some(ref reason) = self.catalog_error {
+            return Err(Error::Corruption(format!("System tables can not be read: {}", reason)));
+        }
+
+        let name = cfg.name.clone();
+        let id = self.next_table_id();
+
+        let index_id = self.next_index_id();
+        let foreign_keys = cfg.foreign_keys.clone();
+
+        let table = Arc::new(Table::new(id,
+                                        cfg,
+                                        index_id,
+                                        self.storage.clone(),
+                                        self.registry.clone())?);
+
+        let key_id = self.next_foreign_key_id();
+
+        let foreign_keys = foreign_keys.iter()
+            .enumerate()
+            .map(|(i, key)| self.resolve_foreign_key(&table, key, key_id + i as u32))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Names are generated from the names of the columns, so different keys may get one name.
+        for (i, key) in foreign_keys.iter().enumerate() {
+            if foreign_keys[..i].iter().any(|other| other.name == key.name) {
+                return Err(Error::DuplicateForeignKey {
+                    table: name,
+                    foreign_key: key.name.clone(),
+                });
+            }
+        }
+
+        *table.foreign_keys.write().unwrap() = foreign_keys;
+
+        let mut tables = self.tables.clone();
+        tables.insert(name, table.clone());
+
+        self.save_tables(tables, None, |db, txn| {
+            txn.set_next_table_id(id + 1)?;
+            table.create_trees(txn)?;
+            db.insert_table_row(txn, &table)?;
+            db.insert_column_rows(txn, &table, 0)?;
+            db.insert_index_rows(txn, &table)?;
+            db.insert_foreign_key_rows(txn, &table)
+        })?;
+
+        Ok(table)
+    }
+
+    /// Gets id for a new table. Ids of the dropped tables are not reused, so a stale handle of
+    /// a dropped table never refers to pages of another table.
+    fn next_table_id(&self) -> u32 {
+        let max = self.tables.values().map(|t| t.id).max().unwrap();
+
+        cmp::max(self.storage.next_table_id(), max + 1)
+    }
+
+    /// Gets id for a new index.
+    fn next_index_id(&self) -> u32 {
+        self.tables
+            .values()
+            .flat_map(|table| table.index_definitions())
+            .map(|index| index.id + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Gets table by its name. System tables can be read like user tables, but they can not be
+    /// changed.
+    pub fn table(&self, name: &str) -> Result<Arc<Table>, Error> {
+        match self.tables.get(name) {
+            Some(table) => Ok(table.clone()),
+            None => Err(Error::TableNotFound(name.to_owned())),
+        }
+    }
+
+    /// Gets all user tables ordered by their names.
+    pub fn tables(&self) -> Vec<Arc<Table>> {
+        self.tables.values().filter(|t| !t.system).cloned().collect()
+    }
+
+    /// Gets user table by its name for a schema change.
+    fn user_table(&self, name: &str) -> Result<Arc<Table>, Error> {
+        let table = self.table(name)?;
+
+        if table.system {
+            return Err(Error::InvalidArgument(format!("System table '{}' can not be changed",
+                                                      name)));
+        }
+
+        Ok(table)
+    }
+
+    /// Drops table and frees all its pages. Handles of the table obtained earlier can no longer
+    /// be used. Table referenced by foreign keys of other tables can not be dropped, the foreign
+    /// keys must be dropped first.
+    pub fn drop_table(&mut self, name: &str) -> Result<(), Error> {
+        let table = self.user_table(name)?;
+
+        for other in self.tables.values().filter(|other| other.id != table.id) {
+            let keys = other.foreign_key_definitions();
+
+            if let Some(key) = keys.iter().find(|key| key.table == table.id) {
+                return Err(Error::InvalidArgument(format!("Table '{}' is referenced by foreign \
+                                                           key '{}' of table '{}'",
+                                                          name,
+                                                          key.name,
+                                                          other.name)));
+            }
+        }
+
+        let mut tables = self.tables.clone();
+        tables.remove(name);
+
+        self.save_tables(tables,
+                         Some((&table, true)),
+                         |db, txn| db.delete_table_rows(txn, &table))
+    }
+
+    /// Renames table and returns its new handle. Rows of the table are kept. Handles of the table
+    /// obtained earlier can no longer be used.
+    pub fn rename_table(&mut self, name: &str, new_name: &str) -> Result<Arc<Table>, Error> {
+        let table = self.user_table(name)?;
+
+        if self.tables.contains_key(new_name) {
+            return Err(Error::DuplicateTable(new_name.to_owned()));
+        }
+
+        let renamed = Arc::new(table.renamed(new_name));
+
+        let mut tables = self.tables.clone();
+        tables.remove(name);
+        tables.insert(new_name.to_owned(), renamed.clone());
+
+        self.save_tables(tables, Some((&table, false)), |db, txn| {
+            for (id, row) in db.tables["_tables"].rows()? {
+                if catalog_value::<i32>(&row, "id")? as u32 == table.id {
+                    db.tables["_tables"].update_row(txn, id, &Row::new().with("name", new_name))?;
+                }
+            }
+
+            Ok(())
+        })?;
+
+        Ok(renamed)
+    }
+
+    /// Records free space of the pages of the system tables after they were restored
+    /// by a rollback.
+    fn refresh_system_tables(&self, pages: &[u32]) -> Result<(), Error> {
+        for table in self.tables.values().filter(|t| t.system) {
+            table.refresh_free_space(pages)?;
+        }
+
+        Ok(())
+    }
+
+    /// Updates the system tables and sets the new set of tables. Handle of the removed table,
+    /// if any, is detached and, if the table is dropped, its pages are freed.
+    fn save_tables<F>(&mut self,
+                      tables: BTreeMap<String, Arc<Table>>,
+                      removed: Option<(&Table, bool)>,
+                      update: F)
+                      -> Result<(), Error>
+        where F: FnOnce(&Database, &Transaction) -> Result<(), Error>
+    {
+        let txn = self.storage.begin();
+
+        let res = match removed {
+            Some((table, true)) => table.drop_pages(&txn),
+            _ => Ok(()),
+        };
+
+        if let Err(e) = res.and_then(|_| update(self, &txn)) {
+            let pages = txn.rollback()?;
+
+            self.refresh_system_tables(&pages)?;
+
+            if let Some((table, _)) = removed {
+                table.refresh_free_space(&pages)?;
+            }
+
+            return Err(e);
+        }
+
+        // Handle is detached while the transaction is active, so no operation of the table
+        // can start in between.
+        if let Some((table, _)) = removed {
+            table.detached.store(true, Ordering::SeqCst);
+        }
+
+        self.update_registry(&tables);
+
+        if let Err(e) = txn.commit() {
+            if let Some((table, _)) = removed {
+                table.detached.store(false, Ordering::SeqCst);
+            }
+
+            self.update_registry(&self.tables);
+
+            return Err(e);
+        }
+
+        self.tables = tables;
+
+        Ok(())
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Database::new()
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        // Checkpointer is stopped first, so it does not race with closing.
+        self.checkpointer = None;
+
+        // Tables refer to each other through the registry.
+        self.registry.write().unwrap().clear();
+
+        let _ = self.storage.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use data_type::DataType;
+    use value::Value;
+    use row::Row;
+    use database::{Database, DatabaseConfiguration, RowId};
+    use database::schema::Column;
+    use database::table::TableConfiguration;
+    use error::Error;
+
+    #[test]
+    fn create_empty_table() {
+        let mut database = Database::new();
+
+        let cfg = TableConfiguration::new("Table1");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        assert!(table.name == "Table1");
+    }
+
+    #[test]
+    fn create_table_with_columns() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("bar", DataType::BOOLEAN, false)).expect("should not fail");
+        cfg.add_column(Column::new("baz", DataType::VARCHAR, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        assert!(table.name == "SomeTable");
+    }
+
+    #[test]
+    fn create_table_with_existing_name() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+        let id = table.insert(&Row::new().with("foo", 1)).expect("should not fail");
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("bar", DataType::BOOLEAN, false)).expect("should not fail");
+
+        match database.create_table(cfg.clone()) {
+            Err(Error::DuplicateTable(ref name)) => assert_eq!(name, "SomeTable"),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        // Existing table is returned as it is.
+        cfg.set_if_not_exists(true);
+        let existing = database.create_table(cfg).expect("should not fail");
+
+        assert_eq!(existing.id, table.id);
+        assert_eq!(existing.get(id).expect("should not fail"), Row::new().with("foo", 1));
+    }
+
+    #[test]
+    fn list_and_drop_tables() {
+        let mut database = Database::new();
+
+        for name in &["B", "A", "C"] {
+            let mut cfg = TableConfiguration::new(name);
+            cfg.add_column(Column::new("foo", DataType::VARCHAR, false)).expect("should not fail");
+            database.create_table(cfg).expect("should not fail");
+        }
+
+        let names: Vec<_> = database.tables().iter().map(|t| t.name().to_owned()).collect();
+        assert_eq!(names, vec!["A", "B", "C"]);
+
+        let table = database.table("B").expect("should not fail");
+
+        for i in 0..100 {
+            let row = Row::new().with("foo", format!("{:01000}", i));
+            table.insert(&row).expect("should not fail");
+        }
+
+        let pages = table.storage.pages(table.id);
+        assert!(pages.len() > 1);
+
+        database.drop_table("B").expect("should not fail");
+
+        assert!(table.storage.pages(table.id).is_empty());
+        assert_eq!(database.tables().len(), 2);
+
+        match database.table("B") {
+            Err(Error::TableNotFound(ref name)) => assert_eq!(name, "B"),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        match database.drop_table("B") {
+            Err(Error::TableNotFound(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Handle of the dropped table can not change the database.
+        match table.insert(&Row::new().with("foo", "bar")) {
+            Err(Error::TableNotFound(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Pages of the dropped table are reused.
+        let other = database.table("A").expect("should not fail");
+        let id = other.insert(&Row::new().with("foo", "bar")).expect("should not fail");
+        assert!(id.page <= *pages.iter().max().unwrap());
+
+        assert!(database.verify().expect("should not fail").is_ok());
+    }
+
+    #[test]
+    fn rename_table() {
+        let mut database = Database::new();
+
+        for name in &["First", "Second"] {
+            let mut cfg = TableConfiguration::new(name);
+            cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+            database.create_table(cfg).expect("should not fail");
+        }
+
+        let table = database.table("First").expect("should not fail");
+        let id = table.insert(&Row::new().with("foo", 1)).expect("should not fail");
+
+        match database.rename_table("First", "Second") {
+            Err(Error::DuplicateTable(ref name)) => assert_eq!(name, "Second"),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let renamed = database.rename_table("First", "Third").expect("should not fail");
+
+        assert_eq!(renamed.name(), "Third");
+        assert_eq!(renamed.get(id).expect("should not fail"), Row::new().with("foo", 1));
+        database.table("First").unwrap_err();
+
+        match table.delete(id) {
+            Err(Error::TableNotFound(ref name)) => assert_eq!(name, "First"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        renamed.delete(id).expect("should not fail");
+    }
+
+    #[test]
+    fn persist_tables() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("persist_tables");
+
+        {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            let mut cfg = TableConfiguration::new("First");
+            cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+            cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+
+            let first = database.create_table(cfg).expect("should not fail");
+
+            let mut cfg = TableConfiguration::new("Second");
+            cfg.add_column(Column::new("baz", DataType::FLOAT, false)).expect("should not fail");
+
+            let second = database.create_table(cfg).expect("should not fail");
+
+            for i in 0..500 {
+                first.insert(&Row::new().with("foo", i).with("bar", i.to_string()))
+                    .expect("should not fail");
+            }
+
+            second.insert(&Row::new().with("baz", 0.5)).expect("should not fail");
+            second.insert(&Row::new().with("baz", None::<f64>)).expect("should not fail");
+        }
+
+        Database::create(&path).unwrap_err();
+
+        {
+            let database = Database::open(&path).expect("should not fail");
+
+            let first = database.table("First").expect("should not fail");
+            let rows: Vec<_> = first.scan()
+                .expect("should not fail")
+                .map(|r| r.expect("should not fail"))
+                .collect();
+
+            assert_eq!(rows.len(), 500);
+            for (i, row) in rows.iter().enumerate() {
+                assert_eq!(row, &Row::new().with("bar", i.to_string()).with("foo", i as i32));
+            }
+
+            let second = database.table("Second").expect("should not fail");
+            let rows: Vec<_> = second.scan()
+                .expect("should not fail")
+                .map(|r| r.expect("should not fail"))
+                .collect();
+
+            assert_eq!(rows,
+                       vec![Row::new().with("baz", 0.5), Row::new().with("baz", None::<f64>)]);
+
+            // Rows of one table are not visible through another one.
+            let page = first.storage.pages(first.id)[0];
+            first.get(RowId { page, slot: 0 }).expect("should not fail");
+            second.get(RowId { page, slot: 0 }).unwrap_err();
+
+            // Free space of the loaded pages is reused.
+            second.insert(&Row::new().with("baz", 1.5)).expect("should not fail");
+            assert_eq!(second.storage.pages(second.id).len(), 1);
+        }
+
+        Database::open(&path).expect("should not fail");
+
+        remove_database(&path);
+    }
+
+    #[test]
+    fn persist_dropped_and_renamed_tables() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("persist_dropped_and_renamed_tables");
+
+        {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            for name in &["First", "Second"] {
+                let mut cfg = TableConfiguration::new(name);
+                cfg.add_column(Column::new("foo", DataType::INTEGER, false))
+                    .expect("should not fail");
+
+                let table = database.create_table(cfg).expect("should not fail");
+                table.insert(&Row::new().with("foo", 1)).expect("should not fail");
+            }
+
+            database.drop_table("First").expect("should not fail");
+            database.rename_table("Second", "First").expect("should not fail");
+        }
+
+        {
+            let database = Database::open(&path).expect("should not fail");
+
+            let names: Vec<_> = database.tables().iter().map(|t| t.name().to_owned()).collect();
+            assert_eq!(names, vec!["First"]);
+
+            let table = database.table("First").expect("should not fail");
+            let rows: Vec<_> = table.scan()
+                .expect("should not fail")
+                .map(|r| r.expect("should not fail"))
+                .collect();
+
+            assert_eq!(rows, vec![Row::new().with("foo", 1)]);
+            assert!(database.verify().expect("should not fail").is_ok());
+        }
+
+        remove_database(&path);
+    }
+
+    #[test]
+    fn table_ids_are_not_reused() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("table_ids_are_not_reused");
+
+        let create = |database: &mut Database, name: &str| {
+            let mut cfg = TableConfiguration::new(name);
+            cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+
+            let table = database.create_table(cfg).expect("should not fail");
+            table.insert(&Row::new().with("foo", 1)).expect("should not fail");
+
+            table
+        };
+
+        let (first, second) = {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            let first = create(&mut database, "First");
+            let old = create(&mut database, "Second");
+
+            database.drop_table("Second").expect("should not fail");
+
+            let second = create(&mut database, "Second");
+            assert!(second.id > old.id);
+
+            // Stale handle of the dropped table does not see rows of the new one.
+            match old.scan() {
+                Err(Error::TableNotFound(ref name)) => assert_eq!(name, "Second"),
+                res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+            }
+
+            database.drop_table("Second").expect("should not fail");
+
+            (first.id, second.id)
+        };
+
+        {
+            let mut database = Database::open(&path).expect("should not fail");
+
+            let third = create(&mut database, "Third");
+            assert!(third.id > second && second > first);
+
+            assert!(database.verify().expect("should not fail").is_ok());
+        }
+
+        remove_database(&path);
+    }
+
+    #[test]
+    fn open_invalid_database() {
+        use std::fs;
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("open_invalid_database");
+
+        Database::open(&path).unwrap_err();
+
+        fs::write(&path, vec![0u8; 8192]).unwrap();
+        Database::open(&path).unwrap_err();
+
+        remove_database(&path);
+    }
+
+    #[test]
+    fn evict_pages_of_large_table() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("evict_pages_of_large_table");
+
+        let mut cfg = DatabaseConfiguration::new();
+        cfg.set_page_size(512).expect("should not fail");
+        cfg.set_buffer_pool_size(4).expect("should not fail");
+
+        cfg.set_page_size(1000).unwrap_err();
+        cfg.set_buffer_pool_size(0).unwrap_err();
+
+        {
+            let mut database = Database::create_with(&path, &cfg).expect("should not fail");
+
+            let mut table_cfg = TableConfiguration::new("SomeTable");
+            table_cfg.add_column(Column::new("foo", DataType::INTEGER, false))
+                .expect("should not fail");
+            table_cfg.add_column(Column::new("bar", DataType::VARCHAR, false))
+                .expect("should not fail");
+
+            let table = database.create_table(table_cfg).expect("should not fail");
+
+            let ids: Vec<_> = (0..1000)
+                .map(|i| {
+                    table.insert(&Row::new().with("foo", i).with("bar", i.to_string()))
+                        .expect("should not fail")
+                })
+                .collect();
+
+            assert!(table.storage.pages(table.id).len() > 4);
+            assert!(database.buffer_pool_stats().evictions > 0);
+
+            for (i, id) in ids.iter().enumerate().step_by(7) {
+                assert_eq!(table.get(*id).unwrap()["foo"], Value::Integer(i as i32));
+            }
+
+            assert!(database.buffer_pool_stats().misses > 0);
+            assert_eq!(table.scan().expect("should not fail").count(), 1000);
+        }
+
+        let database = Database::open_with(&path, &cfg).expect("should not fail");
+        let table = database.table("SomeTable").expect("should not fail");
+
+        let rows: Vec<_> = table.scan()
+            .expect("should not fail")
+            .map(|r| r.expect("should not fail"))
+            .collect();
+
+        assert_eq!(rows.len(), 1000);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row, &Row::new().with("bar", i.to_string()).with("foo", i as i32));
+        }
+
+        let stats = database.buffer_pool_stats();
+        assert!(stats.hits + stats.misses > 0);
+
+        drop(database);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn recover_tables_after_crash() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("recover_tables_after_crash");
+
+        let mut cfg = DatabaseConfiguration::new();
+        cfg.set_page_size(512).expect("should not fail");
+        cfg.set_buffer_pool_size(4).expect("should not fail");
+
+        {
+            let mut database = Database::create_with(&path, &cfg).expect("should not fail");
+
+            let mut table_cfg = TableConfiguration::new("SomeTable");
+            table_cfg.add_column(Column::new("foo", DataType::INTEGER, false))
+                .expect("should not fail");
+
+            let table = database.create_table(table_cfg).expect("should not fail");
+
+            let ids: Vec<_> = (0..300)
+                .map(|i| table.insert(&Row::new().with("foo", i)).expect("should not fail"))
+                .collect();
+
+            for id in &ids[100..200] {
+                table.delete(*id).expect("should not fail");
+            }
+
+            table.update(ids[0], &Row::new().with("foo", -1)).expect("should not fail");
+
+            // Some pages reach the file through evictions, the rest exists only in the log.
+            assert!(database.buffer_pool_stats().evictions > 0);
+
+            database.simulate_crash();
+        }
+
+        let database = Database::open_with(&path, &cfg).expect("should not fail");
+        let table = database.table("SomeTable").expect("should not fail");
+
+        let values: Vec<_> = table.scan()
+            .expect("should not fail")
+            .map(|r| r.expect("should not fail")["foo"].clone())
+            .collect();
+
+        let expected: Vec<_> = Some(-1)
+            .into_iter()
+            .chain((1..100).chain(200..300))
+            .map(Value::Integer)
+            .collect();
+
+        assert_eq!(values, expected);
+
+        drop(database);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn open_read_only_database() {
+        use std::fs;
+        use storage::wal_path;
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("open_read_only_database");
+
+        let mut cfg = DatabaseConfiguration::new();
+        cfg.set_page_size(512).expect("should not fail");
+        cfg.set_buffer_pool_size(4).expect("should not fail");
+
+        {
+            let mut database = Database::create_with(&path, &cfg).expect("should not fail");
+
+            let mut table_cfg = TableConfiguration::new("SomeTable");
+            table_cfg.add_column(Column::new("foo", DataType::INTEGER, false))
+                .expect("should not fail");
+
+            let table = database.create_table(table_cfg).expect("should not fail");
+
+            for i in 0..300 {
+                table.insert(&Row::new().with("foo", i)).expect("should not fail");
+            }
+
+            database.simulate_crash();
+        }
+
+        let file = fs::read(&path).unwrap();
+        let log = fs::read(wal_path(&path)).unwrap();
+
+        cfg.set_read_only(true);
+
+        {
+            // File is recovered in memory only.
+            let mut database = Database::open_with(&path, &cfg).expect("should not fail");
+            let table = database.table("SomeTable").expect("should not fail");
+
+            assert_eq!(table.scan().expect("should not fail").count(), 300);
+            assert!(database.verify().expect("should not fail").is_ok());
+
+            table.insert(&Row::new().with("foo", 300)).unwrap_err();
+            database.drop_table("SomeTable").unwrap_err();
+
+            assert_eq!(table.scan().expect("should not fail").count(), 300);
+            assert_eq!(database.tables().len(), 1);
+
+            database.close().expect("should not fail");
+        }
+
+        assert_eq!(fs::read(&path).unwrap(), file);
+        assert_eq!(fs::read(wal_path(&path)).unwrap(), log);
+
+        // Missing log is not created.
+        cfg.set_read_only(false);
+        let database = Database::open_with(&path, &cfg).expect("should not fail");
+        database.close().expect("should not fail");
+        fs::remove_file(wal_path(&path)).unwrap();
+
+        cfg.set_read_only(true);
+        let database = Database::open_with(&path, &cfg).expect("should not fail");
+        database.close().expect("should not fail");
+        assert!(!wal_path(&path).exists());
+
+        remove_database(&path);
+    }
+
+    #[test]
+    fn checkpoint_database() {
+        use std::fs;
+        use storage::wal_path;
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("checkpoint_database");
+
+        let mut cfg = DatabaseConfiguration::new();
+        cfg.set_checkpoint_interval(None).expect("should not fail");
+
+        cfg.set_checkpoint_interval(Some(Duration::from_secs(0))).unwrap_err();
+
+        {
+            let mut database = Database::create_with(&path, &cfg).expect("should not fail");
+
+            let mut table_cfg = TableConfiguration::new("SomeTable");
+            table_cfg.add_column(Column::new("foo", DataType::INTEGER, false))
+                .expect("should not fail");
+
+            let table = database.create_table(table_cfg).expect("should not fail");
+
+            for i in 0..100 {
+                table.insert(&Row::new().with("foo", i)).expect("should not fail");
+            }
+
+            database.checkpoint().expect("should not fail");
+            assert_eq!(fs::metadata(wal_path(&path)).unwrap().len(), 16);
+
+            for i in 100..200 {
+                table.insert(&Row::new().with("foo", i)).expect("should not fail");
+            }
+
+            // Only the changes made after the checkpoint are in the log.
+            assert!(fs::metadata(wal_path(&path)).unwrap().len() > 16);
+
+            database.simulate_crash();
+        }
+
+        let database = Database::open_with(&path, &cfg).expect("should not fail");
+        let table = database.table("SomeTable").expect("should not fail");
+
+        assert_eq!(table.scan().expect("should not fail").count(), 200);
+        assert_eq!(database.checkpoint_error(), None);
+
+        database.close().expect("should not fail");
+        assert_eq!(fs::metadata(wal_path(&path)).unwrap().len(), 16);
+
+        remove_database(&path);
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use data_type::DataType;
+use value::Value;
+use constraint::{Check, DefaultValue};
+use database::VERSION_SHIFT;
+use error::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// Id of the column in the table. It is kept when the column is renamed or widened.
+    pub(super) id: u32,
+    pub(super) name: String,
+    pub(super) data_type: DataType,
+    pub(super) system: bool,
+    /// Column accepts NULL values.
+    pub(super) nullable: bool,
+    /// Value of the column in the inserted rows which do not have it.
+    pub(super) default: DefaultValue,
+    /// Value of the column in the rows written before the column was added.
+    pub(super) initial: Value,
+    /// CHECK constraints, which every value of the column satisfies.
+    pub(super) checks: Vec<Check>,
+}
+
+impl Column {
+    pub(super) fn new(name: &str, data_type: DataType, system: bool) -> Column {
+        Column {
+            id: 0,
+            name: name.to_owned(),
+            data_type,
+            system,
+            nullable: true,
+            default: DefaultValue::Null,
+            initial: Value::Null,
+            checks: Vec::new(),
+        }
+    }
+
+    /// Checks that the default value and the CHECK constraints match type of the column.
+    /// Constant default value must satisfy the CHECK constraints.
+    pub(super) fn validate(&self, table: &str) -> Result<(), Error> {
+        match self.default.data_type() {
+            Some(actual) if actual != self.data_type => {
+                return Err(Error::TypeMismatch {
+                    table: table.to_owned(),
+                    column: self.name.clone(),
+                    expected: self.data_type,
+                    actual,
+                })
+            }
+            _ => (),
+        }
+
+        if let Some(check) = self.checks.iter().find(|c| !c.applies_to(self.data_type)) {
+            return Err(Error::InvalidArgument(format!("Check constraint {} of column '{}' in \
+                                                       table '{}' does not match type {:?}",
+                                                      check,
+                                                      self.name,
+                                                      table,
+                                                      self.data_type)));
+        }
+
+        match self.default {
+            DefaultValue::Constant(ref value) => self.check_value(table, value),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the value satisfies NOT NULL and CHECK constraints of the column.
+    pub(super) fn check_value(&self, table: &str, value: &Value) -> Result<(), Error> {
+        if value.is_null() && !self.nullable {
+            return Err(Error::NotNullViolation {
+                table: table.to_owned(),
+                column: self.name.clone(),
+            });
+        }
+
+        match self.checks.iter().find(|check| !check.test(value)) {
+            Some(check) => {
+                Err(Error::CheckViolation {
+                    table: table.to_owned(),
+                    column: self.name.clone(),
+                    check: check.clone(),
+                })
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Builder of the column of a new table or of a column added to an existing table.
+/// Constraints of the column are validated when the column is added.
+#[derive(Debug, Clone)]
+pub struct ColumnBuilder {
+    column: Column,
+}
+
+impl ColumnBuilder {
+    /// Creates builder of the column, which accepts NULL values and has neither default value
+    /// nor constraints.
+    pub fn new(name: &str, data_type: DataType) -> ColumnBuilder {
+        ColumnBuilder { column: Column::new(name, data_type, false) }
+    }
+
+    /// Sets whether the column accepts NULL values.
+    pub fn nullable(mut self, nullable: bool) -> ColumnBuilder {
+        self.column.nullable = nullable;
+        self
+    }
+
+    /// Adds NOT NULL constraint.
+    pub fn not_null(self) -> ColumnBuilder {
+        self.nullable(false)
+    }
+
+    /// Sets constant default value.
+    pub fn default_value<V: Into<Value>>(self, value: V) -> ColumnBuilder {
+        let value = value.into();
+
+        if value.is_null() {
+            self.default(DefaultValue::Null)
+        } else {
+            self.default(DefaultValue::Constant(value))
+        }
+    }
+
+    /// Sets default value, which may be generated for every inserted row.
+    pub fn default(mut self, default: DefaultValue) -> ColumnBuilder {
+        self.column.default = default;
+        self
+    }
+
+    /// Adds CHECK constraint.
+    pub fn check(mut self, check: Check) -> ColumnBuilder {
+        self.column.checks.push(check);
+        self
+    }
+
+    /// Creates the column.
+    pub fn build(self) -> Column {
+        self.column
+    }
+}
+
+/// Schema of the table with all its versions.
+///
+/// Every row is stored under the schema version which was current when the row was written.
+/// Rows of older versions are converted to the current version when they are read and are
+/// stored under the current version when they are updated, so altering the table does not
+/// rewrite its rows.
+#[derive(Debug, PartialEq)]
+pub struct Schema {
+    /// Columns of every version in the order they are stored in a row: system columns first.
+    pub versions: Vec<Vec<Arc<Column>>>,
+    /// Columns of the current version by their names.
+    pub columns: BTreeMap<String, Arc<Column>>,
+}
+
+impl Schema {
+    /// Creates schema of the new table. User columns are stored in the order of their names.
+    pub fn new(columns: &BTreeMap<String, Arc<Column>>) -> Schema {
+        let system = columns.values().filter(|c| c.system);
+        let user = columns.values().filter(|c| !c.system);
+
+        let columns = system.chain(user)
+            .enumerate()
+            .map(|(id, column)| {
+                Arc::new(Column {
+                    id: id as u32,
+                    ..(**column).clone()
+                })
+            })
+            .collect();
+
+        Schema::with_versions(vec![columns])
+    }
+
+    /// Creates schema with the versions, the last one is the current version.
+    pub fn with_versions(versions: Vec<Vec<Arc<Column>>>) -> Schema {
+        let columns = versions.last()
+            .unwrap()
+            .iter()
+            .map(|column| (column.name.clone(), column.clone()))
+            .collect();
+
+        Schema { versions, columns }
+    }
+
+    /// Gets current version of the schema.
+    pub fn version(&self) -> u32 {
+        self.versions.len() as u32 - 1
+    }
+
+    /// Gets columns of the current version in the order they are stored in a row.
+    pub fn record_columns(&self) -> &[Arc<Column>] {
+        self.versions.last().unwrap()
+    }
+
+    /// Gets columns of the version in the order they are stored in a row.
+    pub fn version_columns(&self, version: u32) -> Result<&[Arc<Column>], Error> {
+        match self.versions.get(version as usize) {
+            Some(columns) => Ok(columns),
+            None => Err(Error::Corruption(format!("Schema version {} does not exist", version))),
+        }
+    }
+
+    /// Gets column of the current version by its id.
+    pub fn column(&self, id: u32) -> Option<&Arc<Column>> {
+        self.record_columns().iter().find(|c| c.id == id)
+    }
+
+    /// Gets id for a new column. Ids of the dropped columns are not reused.
+    pub fn next_column_id(&self) -> u32 {
+        self.versions.iter().flatten().map(|c| c.id + 1).max().unwrap_or(0)
+    }
+}
+
+/// Gets schema version of the row by value of its `_flags` column.
+pub fn row_version(flags: i32) -> u32 {
+    flags as u32 >> VERSION_SHIFT
+}
+
+/// Gets length of the null bitmap of the record with the columns.
+pub fn null_bitmap_len(columns: &[Arc<Column>]) -> usize {
+    columns.iter().filter(|c| !c.system).count().div_ceil(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use data_type::DataType;
+    use value::Value;
+    use row::Row;
+    use constraint::{Check, DefaultValue};
+    use database::Database;
+    use database::schema::{Column, ColumnBuilder};
+    use database::table::TableConfiguration;
+    use error::Error;
+
+    #[test]
+    fn enforce_column_constraints() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(ColumnBuilder::new("foo", DataType::INTEGER)
+                .not_null()
+                .check(Check::Greater(Value::from(0)))
+                .build())
+            .expect("should not fail");
+        cfg.add_column(ColumnBuilder::new("bar", DataType::VARCHAR)
+                .default_value("none")
+                .check(Check::MaxLength(4))
+                .build())
+            .expect("should not fail");
+        cfg.add_column(ColumnBuilder::new("created", DataType::BIGINT)
+                .default(DefaultValue::CurrentTimestamp)
+                .build())
+            .expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let id = table.insert(&Row::new().with("foo", 1)).expect("should not fail");
+        let row = table.get(id).expect("should not fail");
+
+        assert_eq!(row["bar"], Value::from("none"));
+        assert!(row["created"] > Value::from(0i64));
+
+        // Explicit NULL is not replaced by the default value.
+        let other = table.insert(&Row::new().with("foo", 2).with("bar", None::<String>))
+            .expect("should not fail");
+        assert!(table.get(other).expect("should not fail")["bar"].is_null());
+
+        match table.insert(&Row::new().with("bar", "a")) {
+            Err(Error::NotNullViolation { ref table, ref column }) => {
+                assert_eq!((table.as_str(), column.as_str()), ("SomeTable", "foo"))
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match table.insert(&Row::new().with("foo", 0)) {
+            Err(Error::CheckViolation { ref column, ref check, .. }) => {
+                assert_eq!(column, "foo");
+                assert_eq!(*check, Check::Greater(Value::from(0)));
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match table.update(id, &Row::new().with("bar", "too long")) {
+            Err(Error::CheckViolation { ref column, .. }) => assert_eq!(column, "bar"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match table.update(id, &Row::new().with("foo", None::<i32>)) {
+            Err(Error::NotNullViolation { ref column, .. }) => assert_eq!(column, "foo"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert_eq!(table.get(id).expect("should not fail"), row);
+        assert_eq!(table.scan().expect("should not fail").count(), 2);
+    }
+
+    #[test]
+    fn validate_column_constraints() {
+        let mut database = Database::new();
+        let mut cfg = TableConfiguration::new("SomeTable");
+
+        let foo = ColumnBuilder::new("foo", DataType::INTEGER).default_value("a").build();
+
+        match cfg.add_column(foo) {
+            Err(Error::TypeMismatch { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let column = ColumnBuilder::new("foo", DataType::INTEGER)
+            .default(DefaultValue::CurrentTimestamp)
+            .build();
+
+        match cfg.add_column(column) {
+            Err(Error::TypeMismatch { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match cfg.add_column(ColumnBuilder::new("foo", DataType::INTEGER)
+            .check(Check::MaxLength(1))
+            .build()) {
+            Err(Error::InvalidArgument(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let column = ColumnBuilder::new("foo", DataType::INTEGER)
+            .default_value(-1)
+            .check(Check::GreaterOrEqual(Value::from(0)))
+            .build();
+
+        match cfg.add_column(column) {
+            Err(Error::CheckViolation { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+        let id = table.insert(&Row::new().with("foo", 1)).expect("should not fail");
+
+        // Rows written before the column was added must satisfy its constraints.
+        let mut alter = table.alter();
+
+        match alter.add_column(ColumnBuilder::new("bar", DataType::INTEGER).not_null().build()) {
+            Err(Error::NotNullViolation { .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        alter.add_column(ColumnBuilder::new("bar", DataType::BIGINT)
+                .not_null()
+                .default(DefaultValue::CurrentTimestamp)
+                .build())
+            .expect("should not fail");
+        database.alter_table(alter).expect("should not fail");
+
+        // Old row gets the time of the change, new rows get the time of the insert.
+        let initial = table.get(id).expect("should not fail")["bar"].clone();
+        let other = table.insert(&Row::new().with("foo", 2)).expect("should not fail");
+
+        assert!(initial > Value::from(0i64));
+        assert!(table.get(other).expect("should not fail")["bar"] >= initial);
+        assert_eq!(table.get(id).expect("should not fail")["bar"], initial);
+    }
+
+    #[test]
+    fn persist_column_constraints() {
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("persist_column_constraints");
+
+        {
+            let mut database = Database::create(&path).expect("should not fail");
+
+            let mut cfg = TableConfiguration::new("SomeTable");
+            cfg.add_column(ColumnBuilder::new("foo", DataType::SMALLINT)
+                    .not_null()
+                    .default_value(5i16)
+                    .check(Check::Any(vec![Check::Less(Value::from(10i16)),
+                                           Check::In(vec![Value::from(100i16)])]))
+                    .build())
+                .expect("should not fail");
+
+            let table = database.create_table(cfg).expect("should not fail");
+            table.insert(&Row::new()).expect("should not fail");
+
+            let mut alter = table.alter();
+            alter.widen_column("foo", DataType::BIGINT).expect("should not fail");
+            database.alter_table(alter).expect("should not fail");
+        }
+
+        {
+            let database = Database::open(&path).expect("should not fail");
+            let table = database.table("SomeTable").expect("should not fail");
+
+            table.insert(&Row::new().with("foo", 100i64)).expect("should not fail");
+            table.insert(&Row::new().with("foo", 10i64)).unwrap_err();
+            table.insert(&Row::new().with("foo", None::<i64>)).unwrap_err();
+
+            let id = table.insert(&Row::new()).expect("should not fail");
+            assert_eq!(table.get(id).expect("should not fail")["foo"], Value::Bigint(5));
+
+            assert_eq!(table.scan().expect("should not fail").count(), 3);
+            assert!(database.verify().expect("should not fail").is_ok());
+        }
+
+        remove_database(&path);
+    }
+}
@@ -0,0 +1,1437 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use data_type::DataType;
+use value::Value;
+use row::Row;
+use collation::SortOrder;
+use storage::Storage;
+use storage::MemoryPage;
+use storage::PageType;
+use storage::SlottedPage;
+use storage::SLOT_LEN;
+use storage::PageHandle;
+use storage::Transaction;
+use storage::OverflowWriter;
+use storage::OVERFLOW_REF_LEN;
+use storage::overflow_threshold;
+use protocol::serialize_stream::SerializeStream;
+use protocol::deserialize_stream::DeserializeStream;
+use database::{ROW_DELETED, RowId, SharedSchema, SharedTables, VERSION_SHIFT};
+use database::schema::{Column, Schema, null_bitmap_len, row_version};
+use database::index::{IndexDefinition, Indexes, TableIndex};
+use database::foreign_key::{ForeignKey, ForeignKeyDefinition};
+use error::Error;
+
+#[derive(Debug, Clone)]
+pub struct TableConfiguration {
+    pub(super) name: String,
+    columns: BTreeMap<String, Arc<Column>>,
+    /// Columns of the primary key.
+    primary_key: Option<Vec<String>>,
+    /// Columns of every unique constraint.
+    unique: Vec<Vec<String>>,
+    /// Columns of every non-unique index with their sort orders.
+    indexes: Vec<Vec<(String, SortOrder)>>,
+    pub(super) foreign_keys: Vec<ForeignKey>,
+    pub(super) if_not_exists: bool,
+}
+
+impl TableConfiguration {
+    /// Creates new TableConfiguration.
+    pub fn new(name: &str) -> TableConfiguration {
+        TableConfiguration {
+            name: name.to_owned(),
+            columns: BTreeMap::new(),
+            primary_key: None,
+            unique: Vec::new(),
+            indexes: Vec::new(),
+            foreign_keys: Vec::new(),
+            if_not_exists: false,
+        }
+    }
+
+    /// Sets whether `Database::create_table` returns the existing table with the same name
+    /// instead of failing. Columns of the existing table are kept as they are.
+    pub fn set_if_not_exists(&mut self, if_not_exists: bool) {
+        self.if_not_exists = if_not_exists;
+    }
+
+    // Adds new column to the table.
+    pub fn add_column(&mut self, column: Column) -> Result<(), Error> {
+        let name = column.name.clone();
+
+        if self.columns.contains_key(&name) {
+            return Err(Error::DuplicateColumn {
+                table: self.name.clone(),
+                column: name,
+            });
+        }
+
+        column.validate(&self.name)?;
+
+        self.columns.insert(name.clone(), Arc::new(column));
+        Ok(())
+    }
+
+    /// Sets primary key of the table: one or several columns, which identify the row.
+    /// Columns must be added first, they no longer accept NULL values. Primary key is enforced
+    /// by a unique index, so a row is found by its key with `Table::find_by_primary_key`.
+    pub fn set_primary_key(&mut self, columns: &[&str]) -> Result<(), Error> {
+        if self.primary_key.is_some() {
+            return Err(Error::InvalidArgument(format!("Table '{}' already has a primary key",
+                                                      self.name)));
+        }
+
+        let columns = self.key_columns(columns)?;
+
+        for name in &columns {
+            let column = Column {
+                nullable: false,
+                ..(*self.columns[name]).clone()
+            };
+
+            self.columns.insert(name.clone(), Arc::new(column));
+        }
+
+        self.primary_key = Some(columns);
+        Ok(())
+    }
+
+    /// Adds unique constraint: no two rows have the same values of the columns. Columns must be
+    /// added first. Rows with NULL in any of the columns are not checked, as NULL is not equal
+    /// to any value. Constraint is enforced by a unique index named after the table and
+    /// the columns with `_key` suffix, `Database::create_table` fails if two indexes get
+    /// the same name.
+    pub fn add_unique(&mut self, columns: &[&str]) -> Result<(), Error> {
+        let columns = self.key_columns(columns)?;
+
+        if self.unique.contains(&columns) {
+            return Err(Error::InvalidArgument(format!("Unique constraint on columns ({}) \
+                                                       already exists in table '{}'",
+                                                      columns.join(", "),
+                                                      self.name)));
+        }
+
+        self.unique.push(columns);
+        Ok(())
+    }
+
+    /// Adds index on the columns, which are sorted in the orders. Columns must be added first.
+    /// Rows are found by the leading columns of the index, see `Table::find_by_index`.
+    /// Index is named after the table and the columns with `_idx` suffix, descending columns
+    /// are followed by `desc`, e.g. `Events_tenant_id_created_at_desc_idx`.
+    /// `Database::create_table` fails if two indexes get the same name.
+    pub fn add_index(&mut self, columns: &[(&str, SortOrder)]) -> Result<(), Error> {
+        let names: Vec<&str> = columns.iter().map(|&(name, _)| name).collect();
+        self.key_columns(&names)?;
+
+        let columns: Vec<_> = columns.iter()
+            .map(|&(name, order)| (name.to_owned(), order))
+            .collect();
+
+        if self.indexes.contains(&columns) {
+            return Err(Error::InvalidArgument(format!("Index on columns ({}) already exists in \
+                                                       table '{}'",
+                                                      names.join(", "),
+                                                      self.name)));
+        }
+
+        self.indexes.push(columns);
+        Ok(())
+    }
+
+    /// Adds foreign key. Columns must be added first. Referenced columns are checked when
+    /// the table is created: they must be the primary key or a unique constraint of
+    /// the referenced table and have the same types as the columns. Table may refer to itself.
+    /// Unless the columns lead another index of the table, the table gets an index on them,
+    /// named like one added by `add_index`. It is not dropped together with the foreign key.
+    /// `Database::create_table` fails if the name is taken by another index. Foreign key is
+    /// named after the table and the columns with `_fkey` suffix, so keys on the same columns
+    /// are rejected too.
+    pub fn add_foreign_key(&mut self, key: ForeignKey) -> Result<(), Error> {
+        let columns: Vec<&str> = key.columns.iter().map(String::as_str).collect();
+        self.key_columns(&columns)?;
+
+        if key.referenced.len() != key.columns.len() {
+            return Err(Error::InvalidArgument(format!("Foreign key ({}) of table '{}' must \
+                                                       refer to {} columns, got {}",
+                                                      key.columns.join(", "),
+                                                      self.name,
+                                                      key.columns.len(),
+                                                      key.referenced.len())));
+        }
+
+        self.foreign_keys.push(key);
+        Ok(())
+    }
+
+    /// Checks that the key has columns, every one of them exists and is used once.
+    fn key_columns(&self, columns: &[&str]) -> Result<Vec<String>, Error> {
+        if columns.is_empty() {
+            return Err(Error::InvalidArgument(format!("Key of table '{}' must have columns",
+                                                      self.name)));
+        }
+
+        for (i, &name) in columns.iter().enumerate() {
+            if !self.columns.contains_key(name) {
+                return Err(Error::ColumnNotFound {
+                    table: self.name.clone(),
+                    column: name.to_owned(),
+                });
+            }
+
+            if columns[..i].contains(&name) {
+                return Err(Error::DuplicateColumn {
+                    table: self.name.clone(),
+                    column: name.to_owned(),
+                });
+            }
+        }
+
+        Ok(columns.iter().map(|&name| name.to_owned()).collect())
+    }
+}
+
+#[derive(Debug)]
+pub struct Table {
+    pub(super) id: u32,
+    pub(super) name: String,
+    pub(super) schema: SharedSchema,
+    pub(super) storage: Arc<Storage>,
+    /// System table is changed only by the database itself.
+    pub(super) system: bool,
+    /// Set when the table is dropped or renamed, so the handle can no longer change it.
+    pub(super) detached: AtomicBool,
+    /// Indexes of the table, which are shared by its handles.
+    pub(super) indexes: Arc<RwLock<Indexes>>,
+    /// Foreign keys of the table, which are shared by its handles.
+    pub(super) foreign_keys: Arc<RwLock<Vec<ForeignKeyDefinition>>>,
+    /// All tables of the database.
+    pub(super) tables: SharedTables,
+}
+
+impl Table {
+    /// Creates new table. Primary key and unique constraints get unique indexes with ids
+    /// starting from the index id. Foreign keys whose columns are not the leading columns of
+    /// an index get an index on the columns, so referencing rows are found without a scan.
+    /// Foreign keys are added once the table is created, see `Database::resolve_foreign_key`.
+    pub(super) fn new(id: u32,
+           cfg: TableConfiguration,
+           index_id: u32,
+           storage: Arc<Storage>,
+           tables: SharedTables)
+           -> Result<Table, Error> {
+        let mut columns = cfg.columns;
+
+        for column in Table::system_columns() {
+            if columns.contains_key(&column.name) {
+                return Err(Error::DuplicateColumn {
+                    table: cfg.name,
+                    column: column.name,
+                });
+            }
+
+            columns.insert(column.name.clone(), Arc::new(column));
+        }
+
+        let schema = Schema::new(&columns);
+
+        let table = &cfg.name;
+        let ascending = |columns: &[String]| -> Vec<(String, SortOrder)> {
+            columns.iter().map(|name| (name.clone(), SortOrder::Ascending)).collect()
+        };
+
+        // Name, uniqueness, primary flag and key columns of every index.
+        let mut keys: Vec<_> = cfg.primary_key
+            .iter()
+            .map(|columns| (format!("{}_pkey", table), true, true, ascending(columns)))
+            .chain(cfg.unique.iter().map(|columns| {
+                (format!("{}_{}_key", table, columns.join("_")), true, false, ascending(columns))
+            }))
+            .chain(cfg.indexes.iter().map(|columns| {
+                let names: Vec<_> = columns.iter()
+                    .map(|(name, order)| match *order {
+                        SortOrder::Ascending => name.clone(),
+                        SortOrder::Descending => format!("{}_desc", name),
+                    })
+                    .collect();
+
+                (format!("{}_{}_idx", table, names.join("_")), false, false, columns.clone())
+            }))
+            .collect();
+
+        for key in &cfg.foreign_keys {
+            // Columns are resolved in the order of the referenced key, so any order will do.
+            let indexed = keys.iter().any(|(_, _, _, columns)| {
+                columns.len() >= key.columns.len() &&
+                columns[..key.columns.len()].iter().all(|(name, _)| key.columns.contains(name))
+            });
+
+            if !indexed {
+                let name = format!("{}_{}_idx", table, key.columns.join("_"));
+                keys.push((name, false, false, ascending(&key.columns)));
+            }
+        }
+
+        // Names are generated from the names of the columns, so different keys may get one name.
+        for (i, (name, ..)) in keys.iter().enumerate() {
+            if keys[..i].iter().any(|(other, ..)| other == name) {
+                return Err(Error::DuplicateIndex {
+                    table: table.clone(),
+                    index: name.clone(),
+                });
+            }
+        }
+
+        let indexes = keys.into_iter()
+            .enumerate()
+            .map(|(i, (name, unique, primary, columns))| {
+                IndexDefinition {
+                    id: index_id + i as u32,
+                    name,
+                    unique,
+                    primary,
+                    columns: columns.iter().map(|(name, _)| schema.columns[name].id).collect(),
+                    orders: columns.iter().map(|&(_, order)| order).collect(),
+                    // Trees are created together with the table, see `create_trees`.
+                    root: 0,
+                }
+            })
+            .collect();
+
+        Ok(Table::with_schema(id, cfg.name, schema, indexes, storage, tables))
+    }
+
+    pub(super) fn with_schema(id: u32,
+                   name: String,
+                   schema: Schema,
+                   indexes: Vec<IndexDefinition>,
+                   storage: Arc<Storage>,
+                   tables: SharedTables)
+                   -> Table {
+        let indexes = Indexes {
+            list: indexes.into_iter()
+                .map(|index| TableIndex::open(&schema, index, &storage))
+                .collect(),
+        };
+
+        Table {
+            id,
+            name,
+            schema: Arc::new(RwLock::new(Arc::new(schema))),
+            storage,
+            system: false,
+            detached: AtomicBool::new(false),
+            indexes: Arc::new(RwLock::new(indexes)),
+            foreign_keys: Arc::new(RwLock::new(Vec::new())),
+            tables,
+        }
+    }
+
+    /// Restores free space of the pages of the table, which is already stored in the storage.
+    pub(super) fn load(&self) -> Result<(), Error> {
+        for page_id in self.storage.pages(self.id) {
+            // Page is verified when it is read for the first time, so it may be quarantined.
+            let page = match self.storage.get_page(page_id)? {
+                Some(page) => page,
+                None => continue,
+            };
+            let page = page.lock();
+
+            match page.page_type() {
+                Some(PageType::Data) => {
+                    self.storage.update_free_space(page_id, Table::page_free_space(&page)?)
+                }
+                Some(PageType::Overflow) | Some(PageType::Index) => (),
+                _ => {
+                    return Err(Error::Corruption(format!("Page {} of table '{}' is not a data \
+                                                          page, an overflow page or an index \
+                                                          page",
+                                                         page_id,
+                                                         self.name)))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets name of the table.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Creates handle of the same table with another name.
+    pub(super) fn renamed(&self, name: &str) -> Table {
+        Table {
+            id: self.id,
+            name: name.to_owned(),
+            schema: self.schema.clone(),
+            storage: self.storage.clone(),
+            system: self.system,
+            detached: AtomicBool::new(false),
+            indexes: self.indexes.clone(),
+            foreign_keys: self.foreign_keys.clone(),
+            tables: self.tables.clone(),
+        }
+    }
+
+    /// Check if the table is a system table.
+    pub fn is_system(&self) -> bool {
+        self.system
+    }
+
+    /// Gets current schema of the table.
+    pub(super) fn schema(&self) -> Arc<Schema> {
+        self.schema.read().unwrap().clone()
+    }
+
+    /// Check that the table was neither dropped nor renamed since the handle was obtained.
+    pub(super) fn check_attached(&self) -> Result<(), Error> {
+        if self.detached.load(Ordering::SeqCst) {
+            return Err(Error::TableNotFound(self.name.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Check that rows of the table can be changed: the handle is attached and the table is not
+    /// a system table.
+    pub(super) fn check_writable(&self) -> Result<(), Error> {
+        if self.system {
+            return Err(Error::InvalidArgument(format!("System table '{}' can not be changed",
+                                                      self.name)));
+        }
+
+        self.check_attached()
+    }
+
+    /// Gets system columns of the new table. "_flags" must be the first one.
+    fn system_columns() -> Vec<Column> {
+        vec![Column::new("_flags", DataType::INTEGER, true)]
+    }
+
+    /// Gets value for every column of the current schema version in the record order,
+    /// validating provided values. Columns which are missing in the row get their default value.
+    /// Every value must satisfy the constraints of its column.
+    fn record_values(&self, schema: &Schema, row: &Row) -> Result<Vec<Value>, Error> {
+        for (name, value) in row {
+            let column = match schema.columns.get(name) {
+                Some(column) if !column.system => column,
+                _ => {
+                    return Err(Error::ColumnNotFound {
+                        table: self.name.clone(),
+                        column: name.to_string(),
+                    })
+                }
+            };
+
+            if !value.is_null() && value.data_type() != Some(column.data_type) {
+                return Err(Error::TypeMismatch {
+                    table: self.name.clone(),
+                    column: name.to_string(),
+                    expected: column.data_type,
+                    actual: value.data_type().unwrap(),
+                });
+            }
+        }
+
+        schema.record_columns()
+            .iter()
+            .map(|column| if column.system {
+                // "_flags" is the only system column, it holds the schema version of the row.
+                Ok(Value::Integer((schema.version() << VERSION_SHIFT) as i32))
+            } else {
+                let value = match row.get(&column.name) {
+                    Some(value) => value.clone(),
+                    None => column.default.evaluate(),
+                };
+
+                column.check_value(&self.name, &value).and(Ok(value))
+            })
+            .collect()
+    }
+
+    /// Gets number of bytes needed to store record with the values of the columns.
+    /// Values stored in overflow pages take only the space of the reference to them.
+    fn record_len(&self, columns: &[Arc<Column>], values: &[Value]) -> usize {
+        let threshold = overflow_threshold(self.storage.page_size());
+
+        let values_len = values.iter()
+            .map(|value| match value.serialized_len() {
+                len if len > threshold => OVERFLOW_REF_LEN,
+                len => len,
+            })
+            .sum::<usize>();
+
+        null_bitmap_len(columns) + values_len
+    }
+
+    /// Serializes record under the current schema version.
+    /// Record consists of system columns, null bitmap of user columns and values of the user
+    /// columns which are not NULL. Large values are written to overflow pages.
+    fn encode_record(&self,
+                     txn: &Transaction,
+                     schema: &Schema,
+                     values: &[Value])
+                     -> Result<Vec<u8>, Error> {
+        let columns = schema.record_columns();
+        let system = columns.iter().filter(|c| c.system).count();
+
+        let mut bitmap = vec![0u8; null_bitmap_len(columns)];
+
+        for (i, value) in values[system..].iter().enumerate() {
+            if value.is_null() {
+                bitmap[i / 8] |= 1 << (i % 8);
+            }
+        }
+
+        let overflow = OverflowWriter::new(txn, self.id, self.storage.page_size());
+
+        let mut page = MemoryPage::new(self.record_len(columns, values));
+        {
+            let mut stream = SerializeStream::with_overflow(&mut page, 0, &overflow);
+
+            for value in &values[..system] {
+                stream.write_value(value)?;
+            }
+
+            stream.write_raw(&bitmap)?;
+
+            for value in values[system..].iter().filter(|v| !v.is_null()) {
+                stream.write_value(value)?;
+            }
+        }
+
+        Ok(page.data().to_vec())
+    }
+
+    /// Check if the record at the offset is marked as deleted.
+    fn is_deleted(page: &MemoryPage, offset: usize) -> Result<bool, Error> {
+        // System columns are stored first and "_flags" is the first of them.
+        let flags = DeserializeStream::new(page, offset).read_int()?;
+
+        Ok(flags & ROW_DELETED != 0)
+    }
+
+    /// Removes records of deleted rows from the page.
+    fn purge_deleted(page: &mut MemoryPage) -> Result<(), Error> {
+        let mut deleted = Vec::new();
+
+        {
+            let sp = SlottedPage::new(&*page);
+
+            for slot in sp.slots() {
+                if Table::is_deleted(page, sp.record_offset(slot).unwrap())? {
+                    deleted.push(slot);
+                }
+            }
+        }
+
+        let mut sp = SlottedPage::new(page);
+
+        for slot in deleted {
+            sp.delete(slot);
+        }
+
+        Ok(())
+    }
+
+    /// Gets free space of the page, including space of the deleted rows and their slots,
+    /// which are reused once the deleted rows are purged.
+    fn page_free_space(page: &MemoryPage) -> Result<usize, Error> {
+        let sp = SlottedPage::new(page);
+        let mut space = sp.available_space();
+
+        for slot in sp.slots() {
+            if Table::is_deleted(page, sp.record_offset(slot).unwrap())? {
+                space += sp.record(slot).unwrap().len() + SLOT_LEN;
+            }
+        }
+
+        Ok(space)
+    }
+
+    /// Gets first pages of the overflow chains of the record at the offset.
+    pub(super) fn overflow_pages(schema: &Schema,
+                      page: &MemoryPage,
+                      offset: usize)
+                      -> Result<Vec<u32>, Error> {
+        let flags = DeserializeStream::new(page, offset).read_int()?;
+        let columns = schema.version_columns(row_version(flags))?;
+
+        let mut stream = DeserializeStream::new(page, offset);
+        let mut pages = Vec::new();
+
+        for column in columns.iter().filter(|c| c.system) {
+            stream.skip_value(column.data_type)?;
+        }
+
+        let bitmap = stream.read_raw(null_bitmap_len(columns))?;
+
+        for (i, column) in columns.iter().filter(|c| !c.system).enumerate() {
+            if bitmap[i / 8] & (1 << (i % 8)) == 0 {
+                pages.extend(stream.skip_value(column.data_type)?);
+            }
+        }
+
+        Ok(pages)
+    }
+
+    /// Check if the page has rows which are not deleted.
+    fn has_live_rows(page: &MemoryPage) -> Result<bool, Error> {
+        let sp = SlottedPage::new(page);
+
+        for slot in sp.slots() {
+            if !Table::is_deleted(page, sp.record_offset(slot).unwrap())? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Stores record in a page with enough free space or in a new page.
+    fn store_record(&self, txn: &Transaction, record: &[u8]) -> Result<RowId, Error> {
+        let storage = &self.storage;
+
+        let page_id = match storage.find_page_with_space(self.id, record.len() + SLOT_LEN) {
+            Some(page_id) => page_id,
+            None => {
+                let page_id = txn.allocate_page(PageType::Data, self.id)?;
+                let page = storage.get_page(page_id)?.unwrap();
+
+                SlottedPage::init(&mut *txn.write(&page), page_id);
+
+                page_id
+            }
+        };
+
+        let page = storage.get_page(page_id)?.unwrap();
+        let mut page = txn.write(&page);
+
+        if !SlottedPage::new(&*page).fits(record.len()) {
+            Table::purge_deleted(&mut page)?;
+        }
+
+        let slot = match SlottedPage::new(&mut *page).insert(record) {
+            Some(slot) => slot,
+            None => {
+                return Err(Error::Corruption(format!("Free space map is out of sync for page {}",
+                                                     page_id)))
+            }
+        };
+
+        storage.update_free_space(page_id, Table::page_free_space(&page)?);
+
+        Ok(RowId {
+            page: page_id,
+            slot,
+        })
+    }
+
+    /// Check that the record fits into a page of the storage.
+    fn check_record_len(&self, record: &[u8]) -> Result<(), Error> {
+        let page_size = self.storage.page_size();
+
+        if record.len() > SlottedPage::<&MemoryPage>::max_record_len(page_size) {
+            return Err(Error::OutOfSpace(format!("Row of size {} does not fit into a page of \
+                                                  size {} in table '{}'",
+                                                 record.len(),
+                                                 page_size,
+                                                 self.name)));
+        }
+
+        Ok(())
+    }
+
+    /// Finishes transaction of the operation: commits it if the operation has succeeded,
+    /// otherwise rolls it back together with the changes of the indexes.
+    fn finish<T>(&self, txn: Transaction, res: Result<T, Error>) -> Result<T, Error> {
+        match res {
+            Ok(val) => txn.commit().map(|_| val),
+            Err(e) => {
+                let pages = txn.rollback()?;
+
+                // Referential actions change rows of other tables too.
+                let tables: Vec<_> = self.tables.read().unwrap().values().cloned().collect();
+
+                for table in tables {
+                    table.refresh_free_space(&pages)?;
+                }
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Records free space of the pages of the table after they were restored by a rollback.
+    pub(super) fn refresh_free_space(&self, pages: &[u32]) -> Result<(), Error> {
+        for &page_id in pages {
+            if let Some(page) = self.get_page(page_id)? {
+                self.storage.update_free_space(page_id, Table::page_free_space(&page.lock())?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inserts new row into the table and returns its id.
+    /// Row may contain values for non-system columns of the table only.
+    pub fn insert(&self, row: &Row) -> Result<RowId, Error> {
+        let txn = self.storage.begin();
+        let res = self.check_writable().and_then(|_| self.insert_row(&txn, row));
+
+        self.finish(txn, res)
+    }
+
+    pub(super) fn insert_row(&self, txn: &Transaction, row: &Row) -> Result<RowId, Error> {
+        let schema = self.schema();
+        let values = self.record_values(&schema, row)?;
+
+        let row = schema.record_columns()
+            .iter()
+            .zip(&values)
+            .filter(|&(column, _)| !column.system)
+            .fold(Row::new(), |row, (column, value)| row.with(&column.name, value.clone()));
+
+        self.check_unique(&schema, &row, None)?;
+
+        let record = self.encode_record(txn, &schema, &values)?;
+
+        self.check_record_len(&record)?;
+
+        let id = self.store_record(txn, &record)?;
+        self.update_indexes(txn, &schema, None, Some((id, &row)))?;
+
+        // References are checked once the row is stored, so the row may refer to itself.
+        self.check_references(&schema, &row)?;
+
+        Ok(id)
+    }
+
+    /// Gets data page of the table by its id.
+    fn get_page(&self, page_id: u32) -> Result<Option<PageHandle>, Error> {
+        match self.storage.get_page(page_id)? {
+            Some(page) => {
+                let is_data_page = {
+                    let page = page.lock();
+                    page.owner() == self.id && page.page_type() == Some(PageType::Data)
+                };
+
+                Ok(if is_data_page { Some(page) } else { None })
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Reads row which is not deleted.
+    pub(super) fn read(&self, id: RowId) -> Result<Row, Error> {
+        let not_found = || {
+            Error::RowNotFound {
+                table: self.name.clone(),
+                row: id,
+            }
+        };
+
+        let page = self.get_page(id.page)?.ok_or_else(not_found)?;
+        let page = page.lock();
+
+        let offset = SlottedPage::new(&*page).record_offset(id.slot).ok_or_else(not_found)?;
+
+        // Schema is taken once the page is locked, so it has the version of every row there.
+        let schema = self.schema();
+        let mut stream = DeserializeStream::with_storage(&page, offset, &self.storage);
+
+        match read_record(&schema, &mut stream)? {
+            (flags, _) if flags & ROW_DELETED != 0 => Err(not_found()),
+            (_, row) => Ok(row),
+        }
+    }
+
+    /// Gets row by its id.
+    pub fn get(&self, id: RowId) -> Result<Row, Error> {
+        self.check_attached()?;
+        self.read(id)
+    }
+
+    /// Updates values of the columns present in the row. Other columns keep their values.
+    /// Row is updated in place if it still fits into its page, otherwise it is moved and
+    /// gets a new id. Returns id of the updated row.
+    pub fn update(&self, id: RowId, changes: &Row) -> Result<RowId, Error> {
+        let txn = self.storage.begin();
+        let res = self.check_writable().and_then(|_| self.update_row(&txn, id, changes));
+
+        self.finish(txn, res)
+    }
+
+    pub(super) fn update_row(&self, txn: &Transaction, id: RowId, changes: &Row)
+        -> Result<RowId, Error> {
+        let old = self.read(id)?;
+        let mut row = old.clone();
+
+        for (name, value) in changes {
+            row.set(name, value.clone());
+        }
+
+        // Row is stored under the current schema version, even if it was written under
+        // an older one.
+        let schema = self.schema();
+        let values = self.record_values(&schema, &row)?;
+
+        self.check_unique(&schema, &row, Some(id))?;
+
+        let record = self.encode_record(txn, &schema, &values)?;
+
+        self.check_record_len(&record)?;
+
+        let page = self.storage.get_page(id.page)?.unwrap();
+        let mut page = txn.write(&page);
+
+        // Overflow pages of the previous values are replaced by the new ones.
+        let offset = SlottedPage::new(&*page).record_offset(id.slot).unwrap();
+        let overflow = Table::overflow_pages(&schema, &page, offset)?;
+
+        if !SlottedPage::new(&*page).fits(record.len()) {
+            Table::purge_deleted(&mut page)?;
+        }
+
+        let updated = SlottedPage::new(&mut *page).update(id.slot, &record);
+
+        if !updated {
+            SlottedPage::new(&mut *page).delete(id.slot);
+        }
+
+        self.storage.update_free_space(id.page, Table::page_free_space(&page)?);
+
+        // Page is released before the row is stored, as the row may be moved to any page.
+        drop(page);
+
+        let new_id = if updated { id } else { self.store_record(txn, &record)? };
+
+        for first in overflow {
+            txn.free_chain(first)?;
+        }
+
+        self.update_indexes(txn, &schema, Some((id, &old)), Some((new_id, &row)))?;
+        self.check_references(&schema, &row)?;
+        self.apply_referential_actions(txn, &schema, &old, Some(&row))?;
+
+        Ok(new_id)
+    }
+
+    /// Marks row as deleted. Deleted rows are skipped by scans and their space is reclaimed
+    /// when the page runs out of free space. Overflow pages of the row are freed at once.
+    pub fn delete(&self, id: RowId) -> Result<(), Error> {
+        let txn = self.storage.begin();
+        let res = self.check_writable().and_then(|_| self.delete_row(&txn, id));
+
+        self.finish(txn, res)
+    }
+
+    pub(super) fn delete_row(&self, txn: &Transaction, id: RowId) -> Result<(), Error> {
+        let not_found = || {
+            Error::RowNotFound {
+                table: self.name.clone(),
+                row: id,
+            }
+        };
+
+        // Row is read for its keys only if the table has indexes. Table without indexes can not
+        // be referenced by foreign keys.
+        let old = if self.indexes.read().unwrap().list.is_empty() {
+            None
+        } else {
+            Some(self.read(id)?)
+        };
+
+        let page = self.get_page(id.page)?.ok_or_else(not_found)?;
+        let mut page = txn.write(&page);
+
+        let offset = SlottedPage::new(&*page).record_offset(id.slot).ok_or_else(not_found)?;
+
+        let flags = DeserializeStream::new(&page, offset).read_int()?;
+
+        if flags & ROW_DELETED != 0 {
+            return Err(not_found());
+        }
+
+        let overflow = Table::overflow_pages(&self.schema(), &page, offset)?;
+
+        SerializeStream::new(&mut page, offset).write_int(flags | ROW_DELETED)?;
+
+        let live = Table::has_live_rows(&page)?;
+
+        if live {
+            self.storage.update_free_space(id.page, Table::page_free_space(&page)?);
+        }
+
+        // Page is released before it is freed, as freeing rewrites the page.
+        drop(page);
+
+        if !live {
+            txn.free_page(id.page)?;
+        }
+
+        for first in overflow {
+            txn.free_chain(first)?;
+        }
+
+        if let Some(ref old) = old {
+            let schema = self.schema();
+
+            self.update_indexes(txn, &schema, Some((id, old)), None)?;
+            self.apply_referential_actions(txn, &schema, old, None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates iterator over all rows of the table. Fails if the table was dropped or renamed.
+    pub fn scan(&self) -> Result<Scan, Error> {
+        self.check_attached()?;
+
+        Ok(Scan {
+            storage: self.storage.clone(),
+            owner: self.id,
+            schema: self.schema.clone(),
+            pages: self.storage.pages(self.id),
+            page: 0,
+            slot: 0,
+        })
+    }
+
+    /// Reads all rows of the table with their ids.
+    pub(super) fn rows(&self) -> Result<Vec<(RowId, Row)>, Error> {
+        let mut scan = self.scan()?;
+        let mut rows = Vec::new();
+
+        while let Some(row) = scan.next_row() {
+            rows.push(row?);
+        }
+
+        Ok(rows)
+    }
+
+    /// Frees all pages of the table.
+    pub(super) fn drop_pages(&self, txn: &Transaction) -> Result<(), Error> {
+        for page_id in self.storage.pages(self.id) {
+            txn.free_page(page_id)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Iterator over all rows of the table.
+/// Yields values of non-system columns of every row which is not deleted.
+/// Only the page which is being read is pinned in the buffer pool.
+#[derive(Debug)]
+pub struct Scan {
+    storage: Arc<Storage>,
+    owner: u32,
+    schema: SharedSchema,
+    pages: Vec<u32>,
+    page: usize,
+    slot: u32,
+}
+
+impl Scan {
+    /// Reads next row which is not deleted together with its id.
+    fn next_row(&mut self) -> Option<Result<(RowId, Row), Error>> {
+        while self.page < self.pages.len() {
+            let page_id = self.pages[self.page];
+
+            let page = match self.storage.get_page(page_id) {
+                Ok(Some(page)) => page,
+                Ok(None) => {
+                    // Page was freed or quarantined after the scan has started.
+                    self.page += 1;
+                    continue;
+                }
+                Err(e) => return Some(Err(e)),
+            };
+
+            let page = page.lock();
+
+            // Schema is taken once the page is locked, so it has the version of every row there.
+            let schema = self.schema.read().unwrap().clone();
+
+            if page.owner() != self.owner || page.page_type() != Some(PageType::Data) {
+                self.page += 1;
+                continue;
+            }
+
+            let sp = SlottedPage::new(&*page);
+
+            while self.slot < sp.slot_count() {
+                let slot = self.slot;
+                self.slot += 1;
+
+                let offset = match sp.record_offset(slot) {
+                    Some(offset) => offset,
+                    None => continue,
+                };
+
+                let mut stream = DeserializeStream::with_storage(&page, offset, &self.storage);
+
+                match read_record(&schema, &mut stream) {
+                    Ok((flags, _)) if flags & ROW_DELETED != 0 => continue,
+                    Ok((_, row)) => return Some(Ok((RowId { page: page_id, slot }, row))),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            self.page += 1;
+            self.slot = 0;
+        }
+
+        None
+    }
+}
+
+impl Iterator for Scan {
+    type Item = Result<Row, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_row().map(|res| res.map(|(_, row)| row))
+    }
+}
+
+/// Reads record from the stream. Returns value of the `_flags` column and values of the
+/// non-system columns of the current schema version. Record written under an older version
+/// is converted: values of the dropped columns are skipped, columns added later get their
+/// initial values and values of the widened columns are widened.
+/// Values of the deleted row are not read, as its overflow pages are freed.
+pub fn read_record(schema: &Schema, stream: &mut DeserializeStream) -> Result<(i32, Row), Error> {
+    let mut row = Row::new();
+
+    // "_flags" is the first system column of every schema version.
+    let flags = stream.read_int()?;
+
+    if flags & ROW_DELETED != 0 {
+        return Ok((flags, row));
+    }
+
+    let columns = schema.version_columns(row_version(flags))?;
+
+    for column in columns.iter().filter(|c| c.system).skip(1) {
+        stream.skip_value(column.data_type)?;
+    }
+
+    let bitmap = stream.read_raw(null_bitmap_len(columns))?;
+    let mut values = BTreeMap::new();
+
+    for (i, column) in columns.iter().filter(|c| !c.system).enumerate() {
+        let value = if bitmap[i / 8] & (1 << (i % 8)) != 0 {
+            Value::Null
+        } else {
+            stream.read_value(column.data_type)?
+        };
+
+        values.insert(column.id, value);
+    }
+
+    // Values are returned in the order of the column names, whatever the record order is.
+    for column in schema.columns.values().filter(|c| !c.system) {
+        let value = match values.remove(&column.id) {
+            Some(value) => value.widen(column.data_type),
+            None => column.initial.clone(),
+        };
+
+        row.set(&column.name, value);
+    }
+
+    Ok((flags, row))
+}
+
+#[cfg(test)]
+mod tests {
+    use data_type::DataType;
+    use value::Value;
+    use row::Row;
+    use storage::SlottedPage;
+    use database::Database;
+    use database::schema::{Column, null_bitmap_len};
+    use database::table::TableConfiguration;
+    use error::Error;
+
+    #[test]
+    fn insert_rows() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let first = table.insert(&Row::new().with("foo", 1).with("bar", "a"))
+            .expect("should not fail");
+        let second = table.insert(&Row::new().with("bar", "b").with("foo", 2))
+            .expect("should not fail");
+
+        assert!(first != second);
+    }
+
+    #[test]
+    fn report_errors() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+
+        match cfg.add_column(Column::new("foo", DataType::BIGINT, false)) {
+            Err(Error::DuplicateColumn { ref table, ref column }) => {
+                assert_eq!((table.as_str(), column.as_str()), ("SomeTable", "foo"))
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        match table.insert(&Row::new().with("foo", true)) {
+            Err(Error::TypeMismatch { expected, actual, .. }) => {
+                assert_eq!((expected, actual), (DataType::INTEGER, DataType::BOOLEAN))
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        match table.insert(&Row::new().with("baz", 1)) {
+            Err(Error::ColumnNotFound { ref column, .. }) => assert_eq!(column, "baz"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let id = table.insert(&Row::new().with("foo", 1)).expect("should not fail");
+        table.delete(id).expect("should not fail");
+
+        match table.get(id) {
+            Err(Error::RowNotFound { row, .. }) => assert_eq!(row, id),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // System columns can not be redefined.
+        let mut cfg = TableConfiguration::new("OtherTable");
+        cfg.add_column(Column::new("_flags", DataType::INTEGER, false)).expect("should not fail");
+
+        match database.create_table(cfg) {
+            Err(Error::DuplicateColumn { ref column, .. }) => assert_eq!(column, "_flags"),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn read_corrupted_row() {
+        use protocol::pack;
+
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::VARCHAR, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+        let id = table.insert(&Row::new().with("foo", "bar")).expect("should not fail");
+
+        // Length of the value points past the end of the page.
+        {
+            let page = table.get_page(id.page).expect("should not fail").unwrap();
+            let mut page = page.lock();
+            let offset = SlottedPage::new(&*page).record_offset(id.slot).unwrap();
+            let len_offset = offset + 4 + null_bitmap_len(table.schema().record_columns());
+
+            pack::pack_unsigned(&mut page.data_mut()[len_offset..], 0x00FF_FFFF)
+                .expect("should not fail");
+        }
+
+        match table.get(id) {
+            Err(Error::Corruption(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert!(table.scan().expect("should not fail").any(|row| row.is_err()));
+    }
+
+    #[test]
+    fn insert_fills_several_pages() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::BIGINT, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let mut ids = Vec::new();
+        for i in 0..1000i64 {
+            ids.push(table.insert(&Row::new().with("foo", i)).expect("should not fail"));
+        }
+
+        ids.dedup();
+        assert_eq!(ids.len(), 1000);
+        assert!(table.storage.pages(table.id).len() > 1);
+    }
+
+    #[test]
+    fn insert_invalid_rows() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        table.insert(&Row::new().with("foo", true)).unwrap_err();
+        table.insert(&Row::new().with("foo", 1i64)).unwrap_err();
+        table.insert(&Row::new().with("baz", 1)).unwrap_err();
+        table.insert(&Row::new().with("_flags", 1)).unwrap_err();
+    }
+
+    #[test]
+    fn insert_too_large_row() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        let mut row = Row::new();
+
+        // Every value is small enough to be stored in the row, but all together they are not.
+        for i in 0..5 {
+            let name = format!("foo{}", i);
+
+            cfg.add_column(Column::new(&name, DataType::VARBINARY, false))
+                .expect("should not fail");
+            row.set(&name, Value::Varbinary(vec![0u8; 1000]));
+        }
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        table.insert(&row).unwrap_err();
+
+        assert_eq!(table.scan().expect("should not fail").count(), 0);
+        assert_eq!(table.storage.pages(table.id).len(), 0);
+    }
+
+    #[test]
+    fn scan_rows() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        assert_eq!(table.scan().expect("should not fail").count(), 0);
+
+        for i in 0..500 {
+            table.insert(&Row::new().with("foo", i).with("bar", i.to_string()))
+                .expect("should not fail");
+        }
+
+        let rows: Vec<_> = table.scan()
+            .expect("should not fail")
+            .map(|r| r.expect("should not fail"))
+            .collect();
+
+        assert_eq!(rows.len(), 500);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row, &Row::new().with("bar", i.to_string()).with("foo", i as i32));
+        }
+    }
+
+    #[test]
+    fn scan_skips_deleted_rows() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let ids: Vec<_> = (0..10)
+            .map(|i| table.insert(&Row::new().with("foo", i)).expect("should not fail"))
+            .collect();
+
+        table.delete(ids[0]).expect("should not fail");
+        table.delete(ids[5]).expect("should not fail");
+        table.delete(ids[9]).expect("should not fail");
+        table.delete(ids[5]).unwrap_err();
+
+        let values: Vec<_> = table.scan().expect("should not fail")
+            .map(|r| r.expect("should not fail")["foo"].clone())
+            .collect();
+
+        assert_eq!(values,
+                   vec![1, 2, 3, 4, 6, 7, 8].into_iter().map(Value::Integer).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn insert_and_scan_nulls() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        for i in 0..10 {
+            cfg.add_column(Column::new(&format!("col{}", i), DataType::INTEGER, false))
+                .expect("should not fail");
+        }
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        table.insert(&Row::new().with("col0", 0).with("col9", 9)).expect("should not fail");
+        table.insert(&Row::new().with("col3", None::<i32>)).expect("should not fail");
+
+        let rows: Vec<_> = table.scan()
+            .expect("should not fail")
+            .map(|r| r.expect("should not fail"))
+            .collect();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["col0"], Value::Integer(0));
+        assert_eq!(rows[0]["col9"], Value::Integer(9));
+        assert!((1..9).all(|i| rows[0][i].is_null()));
+        assert!(rows[1].values().iter().all(Value::is_null));
+    }
+
+    #[test]
+    fn get_and_update_rows() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::INTEGER, false)).expect("should not fail");
+        cfg.add_column(Column::new("bar", DataType::VARCHAR, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let id = table.insert(&Row::new().with("foo", 1).with("bar", "long value"))
+            .expect("should not fail");
+
+        assert_eq!(table.get(id).unwrap(), Row::new().with("bar", "long value").with("foo", 1));
+
+        // Smaller row is updated in place.
+        assert_eq!(table.update(id, &Row::new().with("bar", "short")).unwrap(), id);
+        assert_eq!(table.get(id).unwrap(), Row::new().with("bar", "short").with("foo", 1));
+
+        table.update(id, &Row::new().with("foo", "text")).unwrap_err();
+        table.update(id, &Row::new().with("baz", 1)).unwrap_err();
+
+        table.delete(id).expect("should not fail");
+
+        table.get(id).unwrap_err();
+        table.update(id, &Row::new().with("foo", 2)).unwrap_err();
+        table.delete(id).unwrap_err();
+    }
+
+    #[test]
+    fn update_moves_row_to_another_page() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::VARBINARY, false)).expect("should not fail");
+        cfg.add_column(Column::new("bar", DataType::VARBINARY, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let ids: Vec<_> = (0..4)
+            .map(|_| table.insert(&Row::new().with("foo", vec![0u8; 990])).unwrap())
+            .collect();
+
+        let moved = table.update(ids[0], &Row::new().with("bar", vec![1u8; 500])).unwrap();
+
+        assert!(moved != ids[0]);
+        assert_eq!(table.get(moved).unwrap()["foo"], Value::Varbinary(vec![0u8; 990]));
+        assert_eq!(table.get(moved).unwrap()["bar"], Value::Varbinary(vec![1u8; 500]));
+        assert_eq!(table.scan().expect("should not fail").count(), 4);
+    }
+
+    #[test]
+    fn deleted_rows_space_is_reused() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::VARBINARY, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        for _ in 0..100 {
+            let id = table.insert(&Row::new().with("foo", vec![0u8; 1000])).unwrap();
+            table.delete(id).unwrap();
+        }
+
+        assert_eq!(table.storage.pages(table.id).len(), 0);
+        assert_eq!(table.scan().expect("should not fail").count(), 0);
+    }
+
+    #[test]
+    fn inserts_fill_free_space() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::VARBINARY, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let ids: Vec<_> = (0..12)
+            .map(|_| table.insert(&Row::new().with("foo", vec![0u8; 990])).unwrap())
+            .collect();
+
+        assert_eq!(table.storage.pages(table.id).len(), 3);
+
+        // Delete some rows from the first two pages, leaving free space for a single row in each.
+        table.delete(ids[0]).unwrap();
+        table.delete(ids[5]).unwrap();
+
+        table.insert(&Row::new().with("foo", vec![1u8; 990])).unwrap();
+        table.insert(&Row::new().with("foo", vec![1u8; 990])).unwrap();
+
+        assert_eq!(table.storage.pages(table.id).len(), 3);
+        assert_eq!(table.scan().expect("should not fail").count(), 12);
+
+        // Page without live rows is freed.
+        for id in &ids[8..] {
+            table.delete(*id).unwrap();
+        }
+
+        assert_eq!(table.storage.pages(table.id).len(), 2);
+        assert_eq!(table.scan().expect("should not fail").count(), 8);
+    }
+
+    #[test]
+    fn store_large_values_in_overflow_pages() {
+        let mut database = Database::new();
+
+        let mut cfg = TableConfiguration::new("SomeTable");
+        cfg.add_column(Column::new("foo", DataType::VARCHAR, false)).expect("should not fail");
+        cfg.add_column(Column::new("bar", DataType::VARBINARY, false)).expect("should not fail");
+
+        let table = database.create_table(cfg).expect("should not fail");
+
+        let text = "text".repeat(5000);
+        let bytes: Vec<u8> = (0..100000).map(|i| i as u8).collect();
+
+        let large = table.insert(&Row::new().with("foo", text.clone()).with("bar", bytes.clone()))
+            .expect("should not fail");
+        let small = table.insert(&Row::new().with("foo", "small")).expect("should not fail");
+
+        // Both rows share a single data page.
+        assert_eq!(large.page, small.page);
+        assert!(table.storage.pages(table.id).len() > 25);
+
+        let row = table.get(large).expect("should not fail");
+        assert_eq!(row["foo"], Value::Varchar(text.clone()));
+        assert_eq!(row["bar"], Value::Varbinary(bytes.clone()));
+
+        assert_eq!(table.scan().expect("should not fail").count(), 2);
+
+        // Overflow pages of the replaced value are freed.
+        let pages = table.storage.pages(table.id).len();
+        table.update(large, &Row::new().with("bar", vec![1u8; 10])).expect("should not fail");
+
+        assert!(table.storage.pages(table.id).len() < pages - 20);
+        assert_eq!(table.get(large).expect("should not fail")["foo"], Value::Varchar(text));
+
+        table.delete(large).expect("should not fail");
+        assert_eq!(table.storage.pages(table.id).len(), 1);
+
+        table.delete(small).expect("should not fail");
+        assert_eq!(table.storage.pages(table.id).len(), 0);
+    }
+}
@@ -0,0 +1,613 @@
+use std::collections::BTreeSet;
+use row::Row;
+use storage::MemoryPage;
+use storage::PageType;
+use storage::SlottedPage;
+use storage::Transaction;
+use verify::Problem;
+use verify::VerifyReport;
+use protocol::deserialize_stream::DeserializeStream;
+use database::{Database, ROW_DELETED, RowId};
+use database::index::TableIndex;
+use database::table::{Table, read_record};
+use error::Error;
+
+impl Table {
+    /// Rebuilds all indexes from the rows of the table. Trees may be broken, so they are not
+    /// read: every index page except the roots is freed and the roots become empty leaves
+    /// before the trees are filled.
+    fn repair_indexes(&self, txn: &Transaction) -> Result<(), Error> {
+        let schema = self.schema();
+        let indexes = self.indexes.read().unwrap();
+        let roots: BTreeSet<_> = indexes.list.iter().map(|index| index.tree.root()).collect();
+
+        for page_id in self.storage.pages(self.id) {
+            if roots.contains(&page_id) {
+                continue;
+            }
+
+            let page_type = match self.storage.get_page(page_id)? {
+                Some(page) => page.lock().page_type(),
+                None => continue,
+            };
+
+            if page_type == Some(PageType::Index) {
+                txn.free_page(page_id)?;
+            }
+        }
+
+        for index in &indexes.list {
+            index.tree.reset(txn)?;
+            self.fill_index(txn, &schema, index)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every row of the table can be read, that every overflow page of the table
+    /// belongs to a row, that trees of the indexes are valid, that every index page belongs
+    /// to a tree and that entries of every index match the rows. Pages which already have
+    /// problems are skipped.
+    fn verify(&self, report: &mut VerifyReport) -> Result<(), Error> {
+        report.tables += 1;
+
+        let mut overflow = BTreeSet::new();
+        let mut referenced = BTreeSet::new();
+        let mut index_pages = BTreeSet::new();
+        let mut rows = Vec::new();
+        // Rows of the skipped pages are unknown, so entries of the indexes which refer to
+        // them can not be checked.
+        let mut all_rows = true;
+
+        for page_id in self.storage.pages(self.id) {
+            if report.has_page_problem(page_id) {
+                all_rows = false;
+                continue;
+            }
+
+            let page = match self.storage.get_page(page_id)? {
+                Some(page) => page,
+                None => {
+                    all_rows = false;
+                    continue;
+                }
+            };
+
+            let page = page.lock();
+
+            match page.page_type() {
+                Some(PageType::Data) => (),
+                Some(PageType::Overflow) => {
+                    overflow.insert(page_id);
+                    continue;
+                }
+                Some(PageType::Index) => {
+                    index_pages.insert(page_id);
+                    continue;
+                }
+                _ => {
+                    report.problems.push(Problem::InvalidPage {
+                        page_id,
+                        reason: format!("page of table '{}' is not a data page, an overflow \
+                                         page or an index page",
+                                        self.name),
+                    });
+                    continue;
+                }
+            }
+
+            let sp = SlottedPage::new(&*page);
+
+            for slot in sp.slots() {
+                let offset = sp.record_offset(slot).unwrap();
+
+                let id = RowId { page: page_id, slot };
+
+                match self.verify_row(&page, offset, &mut referenced) {
+                    Ok(Some(row)) => {
+                        report.rows += 1;
+                        rows.push((id, row));
+                    }
+                    Ok(None) => (),
+                    Err(e) => {
+                        all_rows = false;
+
+                        report.problems.push(Problem::InvalidRow {
+                            table: self.name.clone(),
+                            row: id,
+                            reason: e.to_string(),
+                        })
+                    }
+                }
+            }
+        }
+
+        for &page_id in overflow.difference(&referenced) {
+            report.problems.push(Problem::InvalidPage {
+                page_id,
+                reason: format!("overflow page of table '{}' does not belong to any row",
+                                self.name),
+            });
+        }
+
+        let mut trees_valid = true;
+
+        for index in &self.indexes.read().unwrap().list {
+            match index.tree.verify() {
+                Ok(pages) => {
+                    for page_id in pages {
+                        index_pages.remove(&page_id);
+                    }
+
+                    self.verify_entries(index, &rows, all_rows, report)?;
+                }
+                Err(e) => {
+                    trees_valid = false;
+
+                    report.problems.push(Problem::InvalidIndex {
+                        table: self.name.clone(),
+                        index: index.definition.name.clone(),
+                        reason: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        // Pages of an invalid tree are unknown.
+        if trees_valid {
+            for page_id in index_pages {
+                report.problems.push(Problem::InvalidPage {
+                    page_id,
+                    reason: format!("index page of table '{}' does not belong to any index",
+                                    self.name),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the record and collects its overflow pages.
+    /// Returns `None` if the row is deleted.
+    fn verify_row(&self, page: &MemoryPage, offset: usize, overflow: &mut BTreeSet<u32>)
+                  -> Result<Option<Row>, Error> {
+        let schema = self.schema();
+        let mut stream = DeserializeStream::with_storage(page, offset, &self.storage);
+
+        let (flags, row) = read_record(&schema, &mut stream)?;
+
+        if flags & ROW_DELETED != 0 {
+            return Ok(None);
+        }
+
+        for first in Table::overflow_pages(&schema, page, offset)? {
+            overflow.extend(self.storage.chain_pages(first)?);
+        }
+
+        Ok(Some(row))
+    }
+
+    /// Checks that every row with a key is found in the index by its key and that every entry
+    /// of the index refers to such a row, unless some rows could not be read.
+    fn verify_entries(&self,
+                      index: &TableIndex,
+                      rows: &[(RowId, Row)],
+                      all_rows: bool,
+                      report: &mut VerifyReport)
+                      -> Result<(), Error> {
+        let schema = self.schema();
+        let mut indexed = BTreeSet::new();
+
+        let mismatch = |row: RowId, reason: &str| {
+            Problem::IndexMismatch {
+                table: self.name.clone(),
+                index: index.definition.name.clone(),
+                row,
+                reason: reason.to_owned(),
+            }
+        };
+
+        for &(id, ref row) in rows {
+            let key = match index.definition.key(&schema, row) {
+                Some(key) => key,
+                None => continue,
+            };
+
+            if !index.tree.get(&key)?.contains(&id) {
+                report.problems.push(mismatch(id, "row is missing from the index"));
+            }
+
+            indexed.insert(id);
+        }
+
+        if !all_rows {
+            return Ok(());
+        }
+
+        let mut seen = BTreeSet::new();
+
+        for id in index.tree.iter()? {
+            let id = id?;
+
+            if !indexed.contains(&id) {
+                report.problems.push(mismatch(id, "entry refers to a row which does not exist \
+                                                   or has no key"));
+            } else if !seen.insert(id) {
+                report.problems.push(mismatch(id, "row has several entries in the index"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Database {
+    /// Checks integrity of the database: checksums and headers of all pages, slot directories
+    /// of the data pages, rows of every table and the catalog. Returns a report with all the
+    /// problems found. Waits until the active operation, if any, is finished and blocks other
+    /// operations during the check.
+    pub fn verify(&self) -> Result<VerifyReport, Error> {
+        // Transaction keeps other operations from changing the database during the check.
+        let txn = self.storage.begin();
+        let mut report = VerifyReport::default();
+
+        self.storage.verify_pages(&mut report)?;
+        self.verify_catalog(&mut report)?;
+
+        for table in self.tables.values() {
+            table.verify(&mut report)?;
+        }
+
+        txn.rollback()?;
+
+        Ok(report)
+    }
+
+    /// Rebuilds indexes of all tables from their rows, e.g. after `verify` has found an index
+    /// which does not match the rows or whose tree is broken. Index pages which do not belong
+    /// to any tree are freed. Fails if rows of a table can not be read or violate a unique
+    /// index, nothing is changed then.
+    pub fn repair_indexes(&self) -> Result<(), Error> {
+        let txn = self.storage.begin();
+
+        let res = self.tables.values().try_for_each(|table| table.repair_indexes(&txn));
+
+        match res {
+            Ok(()) => txn.commit(),
+            // Only index pages are changed, so free space of the data pages stays the same.
+            Err(e) => txn.rollback().and(Err(e)),
+        }
+    }
+
+    /// Checks that the system tables describe the user tables and that every allocated page
+    /// belongs to a table.
+    fn verify_catalog(&self, report: &mut VerifyReport) -> Result<(), Error> {
+        let entries = match self.read_catalog() {
+            Ok(entries) => entries,
+            Err(e) => {
+                report.problems.push(Problem::InvalidCatalog(e.to_string()));
+                return Ok(());
+            }
+        };
+
+        for entry in &entries {
+            let matches = |table: &Table| {
+                !table.system && table.id == entry.id && *table.schema() == entry.schema &&
+                table.index_definitions() == entry.indexes &&
+                table.foreign_key_definitions() == entry.foreign_keys
+            };
+
+            match self.tables.get(&entry.name) {
+                Some(table) if matches(table) => (),
+                Some(_) => {
+                    let reason = format!("table '{}' does not match its description", entry.name);
+                    report.problems.push(Problem::InvalidCatalog(reason));
+                }
+                None => {
+                    let reason = format!("table '{}' is not loaded", entry.name);
+                    report.problems.push(Problem::InvalidCatalog(reason));
+                }
+            }
+        }
+
+        for table in self.tables() {
+            if !entries.iter().any(|entry| entry.name == table.name) {
+                let reason = format!("table '{}' is missing", table.name);
+                report.problems.push(Problem::InvalidCatalog(reason));
+            }
+        }
+
+        for owner in self.storage.owners() {
+            if !self.tables.values().any(|t| t.id == owner) {
+                for page_id in self.storage.pages(owner) {
+                    report.problems.push(Problem::OrphanPage { page_id, owner });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+    use data_type::DataType;
+    use value::Value;
+    use row::Row;
+    use collation::SortOrder;
+    use storage::PageType;
+    use verify::Problem;
+    use database::{CorruptionPolicy, Database, DatabaseConfiguration, RowId};
+    use database::schema::Column;
+    use database::index::IndexOptions;
+    use database::table::TableConfiguration;
+    use error::Error;
+
+    #[test]
+    fn repair_indexes() {
+        use storage::PAGE_HEADER_LEN;
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("repair_indexes");
+
+        let mut cfg = DatabaseConfiguration::new();
+        cfg.set_page_size(512).expect("should not fail");
+
+        let mut database = Database::create_with(&path, &cfg).expect("should not fail");
+
+        let mut table_cfg = TableConfiguration::new("Events");
+        table_cfg.add_column(Column::new("id", DataType::INTEGER, false)).expect("should not fail");
+        table_cfg.add_column(Column::new("rank", DataType::SMALLINT, false))
+            .expect("should not fail");
+        table_cfg.set_primary_key(&["id"]).expect("should not fail");
+
+        let table = database.create_table(table_cfg).expect("should not fail");
+
+        for id in 0..500 {
+            table.insert(&Row::new().with("id", id).with("rank", (id % 50) as i16))
+                .expect("should not fail");
+        }
+
+        table.create_index("by_rank", &[("rank", SortOrder::Ascending)], IndexOptions::new())
+            .expect("should not fail");
+
+        assert!(database.verify().expect("should not fail").is_ok());
+
+        let (row, _) = table.find_by_primary_key(&[Value::from(7)])
+            .expect("should not fail")
+            .expect("row exists");
+        let missing = RowId::new(9999, 0);
+
+        // Entry of the row is removed and an entry of a row which does not exist is added.
+        {
+            let indexes = table.indexes.read().unwrap();
+            let index = indexes.list.iter().find(|i| i.definition.name == "by_rank").unwrap();
+
+            let txn = database.storage.begin();
+            assert!(index.tree.remove(&txn, &[Value::from(7i16)], row).expect("should not fail"));
+            index.tree.add(&txn, &[Value::from(3i16)], missing).expect("should not fail");
+            txn.commit().expect("should not fail");
+        }
+
+        let report = database.verify().expect("should not fail");
+        assert!(report.has_index_problem());
+
+        match report.problems[..] {
+            [Problem::IndexMismatch { row: first, .. },
+             Problem::IndexMismatch { row: second, .. }] => {
+                assert_eq!((first, second), (row, missing))
+            }
+            ref problems => panic!("Unexpected problems: {:?}", problems),
+        }
+
+        // Leaf of the primary key is broken and an index page belongs to no tree.
+        let orphan = {
+            let indexes = table.indexes.read().unwrap();
+            let index = indexes.list.iter().find(|i| i.definition.primary).unwrap();
+            let pages = index.tree.pages().expect("should not fail");
+            let leaf = database.storage.get_page(pages[pages.len() - 1]).expect("should not fail");
+
+            let txn = database.storage.begin();
+            txn.write(&leaf.unwrap()).data_mut()[PAGE_HEADER_LEN] = 5;
+            let orphan = txn.allocate_page(PageType::Index, table.id).expect("should not fail");
+            txn.commit().expect("should not fail");
+
+            orphan
+        };
+
+        let report = database.verify().expect("should not fail");
+        assert!(report.problems.iter().any(|p| match *p {
+            Problem::InvalidIndex { ref index, .. } => index != "by_rank",
+            _ => false,
+        }));
+
+        database.repair_indexes().expect("should not fail");
+
+        let report = database.verify().expect("should not fail");
+        assert!(report.is_ok(), "{}", report);
+        assert!(database.storage.get_page(orphan).expect("should not fail").is_none());
+
+        assert_eq!(table.find_by_primary_key(&[Value::from(7)]).expect("should not fail"),
+                   Some((row, Row::new().with("id", 7).with("rank", 7i16))));
+
+        let key = [Value::from(7i16)];
+        let rows = table.find_by_index("by_rank", Bound::Included(&key), Bound::Included(&key))
+            .expect("should not fail");
+        assert_eq!(rows.len(), 10);
+
+        drop(database);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn detect_corrupted_pages() {
+        use std::fs;
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("detect_corrupted_pages");
+
+        let mut cfg = DatabaseConfiguration::new();
+        cfg.set_page_size(512).expect("should not fail");
+
+        {
+            let mut database = Database::create_with(&path, &cfg).expect("should not fail");
+
+            let mut table_cfg = TableConfiguration::new("SomeTable");
+            table_cfg.add_column(Column::new("foo", DataType::INTEGER, false))
+                .expect("should not fail");
+
+            let table = database.create_table(table_cfg).expect("should not fail");
+
+            for i in 0..300 {
+                table.insert(&Row::new().with("foo", i)).expect("should not fail");
+            }
+        }
+
+        // Flip a bit in the middle of the last data page.
+        let mut data = fs::read(&path).expect("should not fail");
+        let page_id = data.len() / 512 - 1;
+        data[page_id * 512 + 256] ^= 0x10;
+        fs::write(&path, &data).expect("should not fail");
+
+        match Database::open_with(&path, &cfg) {
+            Err(Error::CorruptPage(e)) => assert_eq!(e.page_id, page_id as u32),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        // Quarantined page is excluded, the rest of the table is available.
+        cfg.set_corruption_policy(CorruptionPolicy::Quarantine);
+
+        let database = Database::open_with(&path, &cfg).expect("should not fail");
+        assert_eq!(database.quarantined_pages(), vec![page_id as u32]);
+
+        let table = database.table("SomeTable").expect("should not fail");
+        let rows: Vec<_> = table.scan()
+            .expect("should not fail")
+            .map(|r| r.expect("should not fail"))
+            .collect();
+
+        assert!(!rows.is_empty() && rows.len() < 300);
+
+        drop(database);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn verify_database() {
+        use std::fs;
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("verify_database");
+
+        let mut cfg = DatabaseConfiguration::new();
+        cfg.set_page_size(512).expect("should not fail");
+        cfg.set_corruption_policy(CorruptionPolicy::Quarantine);
+
+        {
+            let mut database = Database::create_with(&path, &cfg).expect("should not fail");
+
+            let mut table_cfg = TableConfiguration::new("SomeTable");
+            table_cfg.add_column(Column::new("foo", DataType::INTEGER, false))
+                .expect("should not fail");
+            table_cfg.add_column(Column::new("bar", DataType::VARBINARY, false))
+                .expect("should not fail");
+
+            let table = database.create_table(table_cfg).expect("should not fail");
+
+            let ids: Vec<_> = (0..100)
+                .map(|i| table.insert(&Row::new().with("foo", i)).expect("should not fail"))
+                .collect();
+
+            table.insert(&Row::new().with("foo", 100).with("bar", vec![1u8; 2000]))
+                .expect("should not fail");
+            table.delete(ids[0]).expect("should not fail");
+
+            let report = database.verify().expect("should not fail");
+
+            // System tables are checked too: one row in "_tables" and a row per column
+            // in "_columns".
+            assert!(report.is_ok(), "{}", report);
+            assert_eq!(report.tables, 7);
+            assert_eq!(report.rows, 104);
+            assert!(report.pages > 5);
+
+            // Page which belongs to nobody.
+            let txn = database.storage.begin();
+            let orphan = txn.allocate_page(PageType::Overflow, 42).expect("should not fail");
+            txn.commit().expect("should not fail");
+
+            let report = database.verify().expect("should not fail");
+            assert_eq!(report.problems, vec![Problem::OrphanPage { page_id: orphan, owner: 42 }]);
+        }
+
+        // Flip a bit in the first data page.
+        let mut data = fs::read(&path).expect("should not fail");
+        let page_id = 3;
+        data[page_id * 512 + 300] ^= 0x10;
+        fs::write(&path, &data).expect("should not fail");
+
+        let database = Database::open_with(&path, &cfg).expect("should not fail");
+        let report = database.verify().expect("should not fail");
+
+        match report.problems[0] {
+            Problem::CorruptPage(ref e) => assert_eq!(e.page_id, page_id as u32),
+            ref problem => panic!("Unexpected problem: {}", problem),
+        }
+
+        assert!(report.rows < 100);
+
+        drop(database);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn report_unreadable_catalog() {
+        use std::fs;
+        use test_utils::{temp_path, remove_database};
+
+        let path = temp_path("report_unreadable_catalog");
+
+        let mut cfg = DatabaseConfiguration::new();
+        cfg.set_page_size(512).expect("should not fail");
+
+        let page_id = {
+            let mut database = Database::create_with(&path, &cfg).expect("should not fail");
+
+            let mut table_cfg = TableConfiguration::new("SomeTable");
+            table_cfg.add_column(Column::new("foo", DataType::INTEGER, false))
+                .expect("should not fail");
+
+            database.create_table(table_cfg).expect("should not fail");
+
+            // The only page of "_columns".
+            database.storage.pages(2)[0]
+        };
+
+        let mut data = fs::read(&path).expect("should not fail");
+        data[page_id as usize * 512 + 256] ^= 0x10;
+        fs::write(&path, &data).expect("should not fail");
+
+        cfg.set_corruption_policy(CorruptionPolicy::Quarantine);
+
+        let mut database = Database::open_with(&path, &cfg).expect("should not fail");
+        assert!(database.table("SomeTable").is_err());
+
+        match database.create_table(TableConfiguration::new("OtherTable")) {
+            Err(Error::Corruption(_)) => (),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let report = database.verify().expect("should not fail");
+
+        match report.problems[..] {
+            [Problem::CorruptPage(ref e), Problem::InvalidCatalog(_)] => {
+                assert_eq!(e.page_id, page_id)
+            }
+            ref problems => panic!("Unexpected problems: {:?}", problems),
+        }
+
+        drop(database);
+        remove_database(&path);
+    }
+}
@@ -0,0 +1,166 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use data_type::DataType;
+use database::RowId;
+use constraint::Check;
+use storage::CorruptPage;
+
+/// Error of the database operation.
+#[derive(Debug)]
+pub enum Error {
+    /// There is not enough space for the data: in a buffer, in a page, in the buffer pool.
+    OutOfSpace(String),
+    /// Value does not match the data type of the column.
+    TypeMismatch {
+        table: String,
+        column: String,
+        expected: DataType,
+        actual: DataType,
+    },
+    /// Column with the same name already exists in the table.
+    DuplicateColumn { table: String, column: String },
+    /// Column does not exist in the table.
+    ColumnNotFound { table: String, column: String },
+    /// NULL value of the column which does not accept NULL values.
+    NotNullViolation { table: String, column: String },
+    /// Value of the column does not satisfy the CHECK constraint.
+    CheckViolation {
+        table: String,
+        column: String,
+        check: Check,
+    },
+    /// Key of the row is already used by another row in the unique index: primary key or
+    /// unique constraint.
+    UniqueViolation {
+        table: String,
+        index: String,
+        columns: Vec<String>,
+    },
+    /// Row refers to a row which does not exist by the foreign key, or a row which other rows
+    /// refer to is deleted or its key is changed.
+    ForeignKeyViolation {
+        table: String,
+        foreign_key: String,
+        referenced_table: String,
+    },
+    /// Index with the same name already exists in the table.
+    DuplicateIndex { table: String, index: String },
+    /// Index does not exist in the table.
+    IndexNotFound { table: String, index: String },
+    /// Foreign key with the same name already exists in the table.
+    DuplicateForeignKey { table: String, foreign_key: String },
+    /// Foreign key does not exist in the table.
+    ForeignKeyNotFound { table: String, foreign_key: String },
+    /// Table with the same name already exists in the database.
+    DuplicateTable(String),
+    /// Table does not exist in the database.
+    TableNotFound(String),
+    /// Row does not exist in the table.
+    RowNotFound { table: String, row: RowId },
+    /// Content of the page read from the database file does not match its checksum.
+    CorruptPage(CorruptPage),
+    /// Stored data is invalid, e.g. a value can not be read from a record.
+    Corruption(String),
+    /// Argument of the operation or the configuration is invalid.
+    InvalidArgument(String),
+    /// I/O operation has failed.
+    Io { context: String, error: io::Error },
+}
+
+impl Error {
+    /// Creates I/O error with the description of the failed operation.
+    pub fn io(context: String, error: io::Error) -> Error {
+        Error::Io { context, error }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::OutOfSpace(ref msg) |
+            Error::Corruption(ref msg) |
+            Error::InvalidArgument(ref msg) => write!(f, "{}", msg),
+            Error::TypeMismatch { ref table, ref column, expected, actual } => {
+                write!(f,
+                       "Type mismatch for column '{}' in table '{}': expected={:?}, actual={:?}",
+                       column,
+                       table,
+                       expected,
+                       actual)
+            }
+            Error::DuplicateColumn { ref table, ref column } => {
+                write!(f, "Column with the name '{}' already exists in table '{}'", column, table)
+            }
+            Error::ColumnNotFound { ref table, ref column } => {
+                write!(f, "Column with the name '{}' does not exist in table '{}'", column, table)
+            }
+            Error::NotNullViolation { ref table, ref column } => {
+                write!(f, "Column '{}' in table '{}' can not be NULL", column, table)
+            }
+            Error::CheckViolation { ref table, ref column, ref check } => {
+                write!(f,
+                       "Value of column '{}' in table '{}' violates check constraint: {}",
+                       column,
+                       table,
+                       check)
+            }
+            Error::UniqueViolation { ref table, ref index, ref columns } => {
+                write!(f,
+                       "Key ({}) in table '{}' violates unique index '{}'",
+                       columns.join(", "),
+                       table,
+                       index)
+            }
+            Error::ForeignKeyViolation { ref table, ref foreign_key, ref referenced_table } => {
+                write!(f,
+                       "Foreign key '{}' of table '{}' referencing table '{}' is violated",
+                       foreign_key,
+                       table,
+                       referenced_table)
+            }
+            Error::DuplicateIndex { ref table, ref index } => {
+                write!(f, "Index with the name '{}' already exists in table '{}'", index, table)
+            }
+            Error::IndexNotFound { ref table, ref index } => {
+                write!(f, "Index with the name '{}' does not exist in table '{}'", index, table)
+            }
+            Error::DuplicateForeignKey { ref table, ref foreign_key } => {
+                write!(f,
+                       "Foreign key with the name '{}' already exists in table '{}'",
+                       foreign_key,
+                       table)
+            }
+            Error::ForeignKeyNotFound { ref table, ref foreign_key } => {
+                write!(f,
+                       "Foreign key with the name '{}' does not exist in table '{}'",
+                       foreign_key,
+                       table)
+            }
+            Error::DuplicateTable(ref name) => write!(f, "Table '{}' already exists", name),
+            Error::TableNotFound(ref name) => write!(f, "Table '{}' does not exist", name),
+            Error::RowNotFound { ref table, row } => {
+                write!(f, "Row {:?} does not exist in table '{}'", row, table)
+            }
+            Error::CorruptPage(ref e) => write!(f, "{}", e),
+            Error::Io { ref context, ref error } => write!(f, "{}: {}", context, error),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::CorruptPage(ref e) => Some(e),
+            Error::Io { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<CorruptPage> for Error {
+    fn from(e: CorruptPage) -> Error {
+        Error::CorruptPage(e)
+    }
+}
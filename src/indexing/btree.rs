@@ -0,0 +1,1369 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use data_type::DataType;
+use collation::{Collation, SortOrder};
+use database::RowId;
+use value::Value;
+use storage::MemoryPage;
+use storage::PageType;
+use storage::Storage;
+use storage::Transaction;
+use storage::PAGE_HEADER_LEN;
+use protocol::serialize_stream::SerializeStream;
+use protocol::deserialize_stream::DeserializeStream;
+use error::Error;
+
+/// Length of the node header, which follows the page header: level of the node, number of
+/// its entries, id of the previous leaf and id of the first child of an internal node.
+/// Id of the next leaf is stored in the page header as the next page of the chain.
+const NODE_HEADER_LEN: usize = 16;
+
+/// Part of the node filled by bulk loading, in percent. The rest is left for inserts, so they
+/// do not split every node at once.
+const FILL_FACTOR: usize = 90;
+
+/// Column of the index key: type of its values, collation they are compared with and
+/// the order they are sorted in.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KeyColumn {
+    pub data_type: DataType,
+    pub collation: Collation,
+    pub order: SortOrder,
+}
+
+impl KeyColumn {
+    /// Creates key column with values of the type, compared with the binary collation and
+    /// sorted in ascending order.
+    pub fn new(data_type: DataType) -> Self {
+        KeyColumn {
+            data_type,
+            collation: Collation::Binary,
+            order: SortOrder::Ascending,
+        }
+    }
+}
+
+/// Position of the entry among the entries with the same key.
+#[derive(Debug, Copy, Clone, Ord, Eq, PartialOrd, PartialEq)]
+enum Position {
+    /// Search bound, which precedes all entries with the key.
+    First,
+    /// Entry of the row with the id.
+    Row(RowId),
+    /// Search bound, which follows all entries with the key.
+    Last,
+}
+
+/// Compares keys value by value in the orders of the columns. Only values present in both keys
+/// are compared, so all keys starting with the values of a shorter key are equal to it.
+/// Values must have types of the columns.
+fn compare_keys(columns: &[KeyColumn], a: &[Value], b: &[Value]) -> Ordering {
+    for ((a, b), column) in a.iter().zip(b).zip(columns) {
+        match column.order.apply(a.total_cmp(b, column.collation)) {
+            Ordering::Equal => (),
+            res => return res,
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Checks that the key has values of all columns.
+fn check_len(columns: &[KeyColumn], key: &[Value]) -> Result<(), Error> {
+    if key.len() != columns.len() {
+        return Err(Error::InvalidArgument(format!("Index key must have {} values, got {}",
+                                                  columns.len(),
+                                                  key.len())));
+    }
+
+    Ok(())
+}
+
+/// Checks that the key has values of all columns or of the leading ones and that the values
+/// have types of the columns.
+fn check_key(columns: &[KeyColumn], key: &[Value]) -> Result<(), Error> {
+    if key.is_empty() || key.len() > columns.len() {
+        return Err(Error::InvalidArgument(format!("Index key must have from 1 to {} values, \
+                                                   got {}",
+                                                  columns.len(),
+                                                  key.len())));
+    }
+
+    for (value, column) in key.iter().zip(columns) {
+        if value.data_type() != Some(column.data_type) {
+            return Err(Error::InvalidArgument(format!("Index key value {:?} does not match \
+                                                       type {:?}",
+                                                      value,
+                                                      column.data_type)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Entry of the node: key of the row and id of the row. Entry of an internal node is
+/// a separator, which is the least entry of the child following it.
+#[derive(Debug, Clone)]
+struct NodeEntry {
+    key: Vec<Value>,
+    row: RowId,
+    /// Child of the internal node, zero in a leaf.
+    child: u32,
+}
+
+/// Node of the tree stored in an index page.
+#[derive(Debug)]
+struct Node {
+    /// Zero for a leaf, height above the leaves for an internal node.
+    level: u32,
+    /// Previous leaf, zero for the first leaf and for internal nodes.
+    prev: u32,
+    /// Next leaf, zero for the last leaf and for internal nodes.
+    next: u32,
+    /// Child of the internal node with entries which precede the first separator.
+    first_child: u32,
+    entries: Vec<NodeEntry>,
+}
+
+impl Node {
+    /// Creates empty node at the level.
+    fn new(level: u32) -> Node {
+        Node {
+            level,
+            prev: 0,
+            next: 0,
+            first_child: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Check if the node is a leaf.
+    fn is_leaf(&self) -> bool {
+        self.level == 0
+    }
+
+    /// Gets child of the internal node by its position: the first child goes first, then
+    /// children of the separators.
+    fn child(&self, pos: usize) -> u32 {
+        if pos == 0 {
+            self.first_child
+        } else {
+            self.entries[pos - 1].child
+        }
+    }
+
+    /// Gets serialized length of the entry in the node.
+    fn entry_len(&self, entry: &NodeEntry) -> usize {
+        let key: usize = entry.key.iter().map(Value::serialized_len).sum();
+
+        if self.is_leaf() {
+            key + 8
+        } else {
+            key + 12
+        }
+    }
+
+    /// Gets serialized length of the node without the page header.
+    fn len(&self) -> usize {
+        NODE_HEADER_LEN + self.entries.iter().map(|entry| self.entry_len(entry)).sum::<usize>()
+    }
+
+    /// Splits the overflowed node into two halves of about the same length. Returns the left
+    /// half, the separator of the right half and the right half. The left half keeps links
+    /// of the node.
+    fn split(mut self) -> (Node, NodeEntry, Node) {
+        let lens: Vec<_> = self.entries.iter().map(|entry| self.entry_len(entry)).collect();
+        let half = lens.iter().sum::<usize>() / 2;
+
+        let mut mid = 0;
+        let mut len = 0;
+
+        while mid < lens.len() && len + lens[mid] <= half {
+            len += lens[mid];
+            mid += 1;
+        }
+
+        // Both halves of a leaf have entries, separator of an internal node moves up.
+        let last = if self.is_leaf() { lens.len() - 1 } else { lens.len() - 2 };
+        let mid = mid.min(last).max(1);
+
+        let mut right = Node::new(self.level);
+        right.entries = self.entries.split_off(mid);
+
+        let separator = if self.is_leaf() {
+            NodeEntry { child: 0, ..right.entries[0].clone() }
+        } else {
+            let separator = right.entries.remove(0);
+            right.first_child = separator.child;
+            separator
+        };
+
+        (self, separator, right)
+    }
+
+    /// Reads node from the index page. Values of the keys have types of the columns.
+    fn read(page: &MemoryPage, columns: &[KeyColumn]) -> Result<Node, Error> {
+        if page.page_type() != Some(PageType::Index) {
+            return Err(Error::Corruption(format!("Page {} is not an index page",
+                                                 page.page_id())));
+        }
+
+        let mut stream = DeserializeStream::new(page, PAGE_HEADER_LEN);
+
+        let mut node = Node::new(stream.read_int()? as u32);
+        let count = stream.read_int()? as u32;
+        node.prev = stream.read_int()? as u32;
+        node.first_child = stream.read_int()? as u32;
+        node.next = page.next_page();
+
+        for _ in 0..count {
+            let key = columns.iter()
+                .map(|column| stream.read_value(column.data_type))
+                .collect::<Result<_, _>>()?;
+
+            let row = RowId::new(stream.read_int()? as u32, stream.read_int()? as u32);
+
+            let child = if node.is_leaf() {
+                0
+            } else {
+                stream.read_int()? as u32
+            };
+
+            node.entries.push(NodeEntry { key, row, child });
+        }
+
+        Ok(node)
+    }
+
+    /// Writes node to the index page.
+    fn write(&self, page: &mut MemoryPage) -> Result<(), Error> {
+        page.set_next_page(self.next);
+
+        let mut stream = SerializeStream::new(page, PAGE_HEADER_LEN);
+
+        stream.write_int(self.level as i32)?;
+        stream.write_int(self.entries.len() as i32)?;
+        stream.write_int(self.prev as i32)?;
+        stream.write_int(self.first_child as i32)?;
+
+        for entry in &self.entries {
+            for value in &entry.key {
+                stream.write_value(value)?;
+            }
+
+            stream.write_int(entry.row.page() as i32)?;
+            stream.write_int(entry.row.slot() as i32)?;
+
+            if !self.is_leaf() {
+                stream.write_int(entry.child as i32)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Position of the iterator in a leaf: the leaf, its node and the entry.
+/// Position past the last entry of a leaf is moved to the first entry of the next leaf,
+/// so every position in the tree is unique.
+#[derive(Debug)]
+struct Cursor {
+    page: u32,
+    node: Node,
+    index: usize,
+}
+
+impl Cursor {
+    /// Gets entry at the position, `None` at the end of the tree.
+    fn entry(&self) -> Option<&NodeEntry> {
+        self.node.entries.get(self.index)
+    }
+
+    /// Check if the cursors are at the same position.
+    fn same(&self, other: &Cursor) -> bool {
+        self.page == other.page && self.index == other.index
+    }
+}
+
+/// Iterator over ids of the rows of the tree in the order of their keys. Rows with the same
+/// key are ordered by their ids. Iterator is double-ended: leaves are followed by their links
+/// in both directions. Leaves are read one at a time, so the tree must not be changed while
+/// the iterator is used.
+#[derive(Debug)]
+pub struct BTreeRange<'a> {
+    tree: &'a BTree,
+    front: Cursor,
+    back: Cursor,
+    done: bool,
+}
+
+impl<'a> BTreeRange<'a> {
+    /// Check if the iteration has ended: cursors have met or reading a leaf has failed.
+    fn ended(&mut self) -> bool {
+        self.done = self.done || self.front.same(&self.back);
+        self.done
+    }
+}
+
+impl<'a> Iterator for BTreeRange<'a> {
+    type Item = Result<RowId, Error>;
+
+    fn next(&mut self) -> Option<Result<RowId, Error>> {
+        if self.ended() {
+            return None;
+        }
+
+        let row = match self.front.entry() {
+            Some(entry) => entry.row,
+            None => return None,
+        };
+
+        self.front.index += 1;
+
+        if let Err(e) = self.tree.normalize(&mut self.front) {
+            self.done = true;
+            return Some(Err(e));
+        }
+
+        Some(Ok(row))
+    }
+}
+
+impl<'a> DoubleEndedIterator for BTreeRange<'a> {
+    fn next_back(&mut self) -> Option<Result<RowId, Error>> {
+        if self.ended() {
+            return None;
+        }
+
+        if self.back.index == 0 {
+            let page = self.back.node.prev;
+
+            match self.tree.read_node(page) {
+                Ok(node) => {
+                    self.back = Cursor {
+                        page,
+                        index: node.entries.len(),
+                        node,
+                    };
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        self.back.index -= 1;
+
+        self.back.entry().map(|entry| Ok(entry.row))
+    }
+}
+
+/// Index stored in pages of the storage as a B+tree, so it is not rebuilt when the database
+/// is opened and is not limited by the memory.
+///
+/// Entries are kept in the leaves in the order of their keys, see `Index` for the order and
+/// the lookups. Leaves are linked to their neighbours in both directions for range scans.
+/// Internal nodes hold separators, which are entries too, so rows with the same key may be
+/// spread over several leaves. Node which overflows its page is split in two, node which is
+/// less than a quarter full is merged with its neighbour or takes some of its entries.
+///
+/// Root page never changes, so the tree is found by it: the root is moved into a new page
+/// when it is split and a single child replaces the root when the tree shrinks.
+/// Every change of a page is made by the transaction, so it is logged and is reverted on
+/// rollback. Tree has no locks of its own: readers must not run concurrently with writers.
+#[derive(Debug)]
+pub struct BTree {
+    storage: Arc<Storage>,
+    root: u32,
+    columns: Vec<KeyColumn>,
+}
+
+impl BTree {
+    /// Creates empty tree with keys of the columns in a new page of the owner.
+    pub fn create(txn: &Transaction,
+                  storage: Arc<Storage>,
+                  owner: u32,
+                  columns: Vec<KeyColumn>)
+                  -> Result<BTree, Error> {
+        let root = txn.allocate_page(PageType::Index, owner)?;
+
+        let tree = BTree::open(storage, root, columns);
+        tree.write_node(txn, root, &Node::new(0))?;
+
+        Ok(tree)
+    }
+
+    /// Opens the tree with the root page. Keys of the tree must have types of the columns.
+    pub fn open(storage: Arc<Storage>, root: u32, columns: Vec<KeyColumn>) -> BTree {
+        BTree {
+            storage,
+            root,
+            columns,
+        }
+    }
+
+    /// Gets id of the root page.
+    pub fn root(&self) -> u32 {
+        self.root
+    }
+
+    /// Gets columns of the key.
+    pub fn columns(&self) -> &[KeyColumn] {
+        &self.columns
+    }
+
+    /// Compares keys in the order of the tree.
+    pub fn compare_keys(&self, a: &[Value], b: &[Value]) -> Ordering {
+        compare_keys(&self.columns, a, b)
+    }
+
+    /// Gets number of entries in the tree. All leaves are read.
+    pub fn len(&self) -> Result<usize, Error> {
+        let mut cursor = self.seek(None, false)?;
+        let mut len = 0;
+
+        loop {
+            len += cursor.node.entries.len();
+
+            if cursor.node.next == 0 {
+                return Ok(len);
+            }
+
+            cursor.node = self.read_node(cursor.node.next)?;
+        }
+    }
+
+    /// Check if the tree has no entries. Only the root leaf may be empty.
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        let root = self.read_node(self.root)?;
+        Ok(root.is_leaf() && root.entries.is_empty())
+    }
+
+    /// Adds entry of the row with the key to the tree. Entry which is already in the tree is
+    /// not added again. Serialized key must not exceed a quarter of the page.
+    pub fn add(&self, txn: &Transaction, key: &[Value], row: RowId) -> Result<(), Error> {
+        self.check_entry(key)?;
+
+        let entry = NodeEntry {
+            key: key.to_vec(),
+            row,
+            child: 0,
+        };
+
+        self.insert(txn, self.root, entry).map(|_| ())
+    }
+
+    /// Removes entry of the row with the key. Returns `false` if there is no such entry.
+    pub fn remove(&self, txn: &Transaction, key: &[Value], row: RowId) -> Result<bool, Error> {
+        check_len(&self.columns, key)?;
+        check_key(&self.columns, key)?;
+
+        let (found, _) = self.delete(txn, self.root, key, row)?;
+
+        if found {
+            self.collapse_root(txn)?;
+        }
+
+        Ok(found)
+    }
+
+    /// Gets ids of the rows with the key in the key order. Key may have only leading values,
+    /// then all rows with keys starting with them are found.
+    pub fn get(&self, key: &[Value]) -> Result<Vec<RowId>, Error> {
+        self.range(Bound::Included(key), Bound::Included(key))?.collect()
+    }
+
+    /// Gets ids of the rows with keys between the bounds in the key order, see `Index::range`
+    /// for the bounds.
+    pub fn range(&self, from: Bound<&[Value]>, to: Bound<&[Value]>)
+                 -> Result<BTreeRange<'_>, Error> {
+        // Bounds are placed before or after all entries with the key, see `Index::range`.
+        let from = match from {
+            Bound::Included(key) => Some((key, Position::First)),
+            Bound::Excluded(key) => Some((key, Position::Last)),
+            Bound::Unbounded => None,
+        };
+
+        let to = match to {
+            Bound::Included(key) => Some((key, Position::Last)),
+            Bound::Excluded(key) => Some((key, Position::First)),
+            Bound::Unbounded => None,
+        };
+
+        for &(key, _) in from.iter().chain(&to) {
+            check_key(&self.columns, key)?;
+        }
+
+        let mut range = BTreeRange {
+            tree: self,
+            front: self.seek(from, false)?,
+            back: self.seek(to, true)?,
+            done: false,
+        };
+
+        // Lower bound follows the upper one if the first entry from it is past the upper one.
+        if let (Some(entry), Some((key, position))) = (range.front.entry(), to) {
+            range.done = self.compare(key, position, entry) == Ordering::Less;
+        }
+
+        Ok(range)
+    }
+
+    /// Gets ids of all rows in the key order.
+    pub fn iter(&self) -> Result<BTreeRange<'_>, Error> {
+        self.range(Bound::Unbounded, Bound::Unbounded)
+    }
+
+    /// Fills the empty tree with the entries, which are sorted by their keys and rows with
+    /// the same key by their ids. Leaves are written from left to right and filled up to
+    /// `FILL_FACTOR`, then every upper level is built from the level below it.
+    pub fn bulk_load<I>(&self, txn: &Transaction, entries: I) -> Result<(), Error>
+        where I: IntoIterator<Item = (Vec<Value>, RowId)>
+    {
+        if !self.is_empty()? {
+            return Err(Error::InvalidArgument("Only an empty index can be bulk loaded"
+                .to_owned()));
+        }
+
+        let mut prev: Option<NodeEntry> = None;
+
+        let leaves = entries.into_iter().map(|(key, row)| {
+            self.check_entry(&key)?;
+
+            let entry = NodeEntry { key, row, child: 0 };
+
+            if let Some(ref prev) = prev {
+                if self.compare(&prev.key, Position::Row(prev.row), &entry) != Ordering::Less {
+                    return Err(Error::InvalidArgument("Bulk loaded entries must be unique and \
+                                                       sorted in the key order"
+                        .to_owned()));
+                }
+            }
+
+            prev = Some(entry.clone());
+
+            Ok(entry)
+        });
+
+        let mut children = match self.build_level(txn, 0, leaves)? {
+            Some(children) => children,
+            None => return Ok(()),
+        };
+
+        for level in 1.. {
+            children = match self.build_level(txn, level, children.into_iter().map(Ok))? {
+                Some(children) => children,
+                None => break,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Removes all entries: pages of the tree are freed except the root, which becomes
+    /// an empty leaf.
+    pub fn clear(&self, txn: &Transaction) -> Result<(), Error> {
+        for page_id in self.pages()? {
+            if page_id != self.root {
+                txn.free_page(page_id)?;
+            }
+        }
+
+        self.reset(txn)
+    }
+
+    /// Makes the root an empty leaf without reading the tree, so it works for a broken tree
+    /// too. Other pages of the tree are left as they are, the caller must free them.
+    pub fn reset(&self, txn: &Transaction) -> Result<(), Error> {
+        self.write_node(txn, self.root, &Node::new(0))
+    }
+
+    /// Frees all pages of the tree, including the root.
+    pub fn free_pages(&self, txn: &Transaction) -> Result<(), Error> {
+        for page_id in self.pages()? {
+            txn.free_page(page_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets ids of all pages of the tree.
+    pub fn pages(&self) -> Result<Vec<u32>, Error> {
+        let mut pages = vec![self.root];
+        let mut pos = 0;
+
+        while pos < pages.len() {
+            let node = self.read_node(pages[pos])?;
+
+            if !node.is_leaf() {
+                pages.extend((0..node.entries.len() + 1).map(|i| node.child(i)));
+            }
+
+            pos += 1;
+        }
+
+        Ok(pages)
+    }
+
+    /// Checks structure of the tree: every node is an index page at its level, entries of
+    /// the nodes are sorted and lie between the separators of their parents and the leaves
+    /// are linked in the key order. Returns ids of all pages of the tree.
+    pub fn verify(&self) -> Result<Vec<u32>, Error> {
+        let mut pages = BTreeSet::new();
+        let mut leaves = Vec::new();
+
+        self.verify_node(self.root, None, (None, None), &mut pages, &mut leaves)?;
+
+        for (i, &(page_id, prev, next)) in leaves.iter().enumerate() {
+            let expected_prev = if i == 0 { 0 } else { leaves[i - 1].0 };
+            let expected_next = leaves.get(i + 1).map_or(0, |leaf| leaf.0);
+
+            if prev != expected_prev || next != expected_next {
+                return Err(Error::Corruption(format!("Leaf {} of the index is linked to \
+                                                      leaves {} and {} instead of {} and {}",
+                                                     page_id,
+                                                     prev,
+                                                     next,
+                                                     expected_prev,
+                                                     expected_next)));
+            }
+        }
+
+        Ok(pages.into_iter().collect())
+    }
+
+    /// Checks the node and its children. Entries of the node must not precede the low bound
+    /// and must precede the high bound. Leaves are collected with their links.
+    fn verify_node(&self,
+                   page_id: u32,
+                   level: Option<u32>,
+                   bounds: (Option<&NodeEntry>, Option<&NodeEntry>),
+                   pages: &mut BTreeSet<u32>,
+                   leaves: &mut Vec<(u32, u32, u32)>)
+                   -> Result<(), Error> {
+        let invalid = |reason: &str| {
+            Err(Error::Corruption(format!("Index page {} is invalid: {}", page_id, reason)))
+        };
+
+        if !pages.insert(page_id) {
+            return invalid("page is referenced twice");
+        }
+
+        let node = self.read_node(page_id)?;
+
+        if level.is_some_and(|level| level != node.level) {
+            return invalid("level does not match the level of its parent");
+        }
+
+        let (low, high) = bounds;
+        let entries: Vec<_> = low.into_iter().chain(&node.entries).chain(high).collect();
+
+        if entries.windows(2).any(|w| self.compare_entries(w[0], w[1]) == Ordering::Greater) ||
+           node.entries.windows(2).any(|w| self.compare_entries(&w[0], &w[1]) != Ordering::Less) ||
+           node.entries.last().zip(high)
+            .is_some_and(|(last, high)| self.compare_entries(last, high) != Ordering::Less) {
+            return invalid("entries are out of order");
+        }
+
+        if node.is_leaf() {
+            leaves.push((page_id, node.prev, node.next));
+            return Ok(());
+        }
+
+        for i in 0..node.entries.len() + 1 {
+            let low = if i == 0 { low } else { Some(&node.entries[i - 1]) };
+            let high = node.entries.get(i).or(high);
+
+            self.verify_node(node.child(i), Some(node.level - 1), (low, high), pages, leaves)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the key has values of all columns of the right types and that the entry
+    /// with it fits into a quarter of the page, so every split node has entries in both halves.
+    fn check_entry(&self, key: &[Value]) -> Result<(), Error> {
+        check_len(&self.columns, key)?;
+        check_key(&self.columns, key)?;
+
+        // Entry of an internal node holds id of the child too.
+        let len = key.iter().map(Value::serialized_len).sum::<usize>() + 12;
+        let max_len = (self.capacity() - NODE_HEADER_LEN) / 4;
+
+        if len > max_len {
+            return Err(Error::OutOfSpace(format!("Index entry of size {} exceeds the limit of \
+                                                  {} bytes",
+                                                 len,
+                                                 max_len)));
+        }
+
+        Ok(())
+    }
+
+    /// Gets space of the page available to the node.
+    fn capacity(&self) -> usize {
+        self.storage.page_size() - PAGE_HEADER_LEN
+    }
+
+    /// Gets the least length of the node which does not have to be merged.
+    fn min_len(&self) -> usize {
+        NODE_HEADER_LEN + (self.capacity() - NODE_HEADER_LEN) / 4
+    }
+
+    /// Compares the key at the position with the entry.
+    fn compare(&self, key: &[Value], position: Position, entry: &NodeEntry) -> Ordering {
+        compare_keys(&self.columns, key, &entry.key).then(position.cmp(&Position::Row(entry.row)))
+    }
+
+    /// Compares entries by their keys and rows.
+    fn compare_entries(&self, a: &NodeEntry, b: &NodeEntry) -> Ordering {
+        self.compare(&a.key, Position::Row(a.row), b)
+    }
+
+    /// Gets position of the child of the internal node, which may hold the key at the position:
+    /// the child following the last separator which does not follow the key.
+    fn child_pos(&self, node: &Node, key: &[Value], position: Position) -> usize {
+        node.entries.partition_point(|entry| self.compare(key, position, entry) != Ordering::Less)
+    }
+
+    /// Gets position of the first entry of the leaf which does not precede the key at
+    /// the position.
+    fn entry_pos(&self, node: &Node, key: &[Value], position: Position) -> usize {
+        node.entries
+            .partition_point(|entry| self.compare(key, position, entry) == Ordering::Greater)
+    }
+
+    /// Reads node from the page.
+    fn read_node(&self, page_id: u32) -> Result<Node, Error> {
+        match self.storage.get_page(page_id)? {
+            Some(page) => Node::read(&page.lock(), &self.columns),
+            None => Err(Error::Corruption(format!("Index page {} is not allocated", page_id))),
+        }
+    }
+
+    /// Writes node to the page.
+    fn write_node(&self, txn: &Transaction, page_id: u32, node: &Node) -> Result<(), Error> {
+        let page = self.storage.fetch_page(page_id)?;
+        let mut page = txn.write(&page);
+
+        node.write(&mut page)
+    }
+
+    /// Allocates new page of the tree, which has the owner of the root.
+    fn allocate(&self, txn: &Transaction) -> Result<u32, Error> {
+        let owner = match self.storage.get_page(self.root)? {
+            Some(page) => page.lock().owner(),
+            None => return Err(Error::Corruption(format!("Index page {} is not allocated",
+                                                         self.root))),
+        };
+
+        txn.allocate_page(PageType::Index, owner)
+    }
+
+    /// Sets previous leaf of the leaf.
+    fn set_prev(&self, txn: &Transaction, page_id: u32, prev: u32) -> Result<(), Error> {
+        let mut node = self.read_node(page_id)?;
+        node.prev = prev;
+
+        self.write_node(txn, page_id, &node)
+    }
+
+    /// Finds position of the key in the leaves: the first entry which follows the key at
+    /// the position, or the start or the end of the tree if there is no key.
+    fn seek(&self, key: Option<(&[Value], Position)>, end: bool) -> Result<Cursor, Error> {
+        let mut page = self.root;
+
+        loop {
+            let node = self.read_node(page)?;
+
+            if node.is_leaf() {
+                let index = match key {
+                    Some((key, position)) => self.entry_pos(&node, key, position),
+                    None if end => node.entries.len(),
+                    None => 0,
+                };
+
+                let mut cursor = Cursor { page, node, index };
+                self.normalize(&mut cursor)?;
+
+                return Ok(cursor);
+            }
+
+            let pos = match key {
+                Some((key, position)) => self.child_pos(&node, key, position),
+                None if end => node.entries.len(),
+                None => 0,
+            };
+
+            page = node.child(pos);
+        }
+    }
+
+    /// Moves cursor past the last entry of the leaf to the next leaf, if any.
+    fn normalize(&self, cursor: &mut Cursor) -> Result<(), Error> {
+        while cursor.index == cursor.node.entries.len() && cursor.node.next != 0 {
+            cursor.page = cursor.node.next;
+            cursor.node = self.read_node(cursor.page)?;
+            cursor.index = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Inserts entry into the subtree of the node. Returns separator of the new right
+    /// neighbour of the node if the node was split.
+    fn insert(&self, txn: &Transaction, page_id: u32, entry: NodeEntry)
+              -> Result<Option<NodeEntry>, Error> {
+        let mut node = self.read_node(page_id)?;
+        let position = Position::Row(entry.row);
+
+        if node.is_leaf() {
+            let pos = self.entry_pos(&node, &entry.key, position);
+
+            if node.entries.get(pos).is_some_and(|e| self.compare_entries(&entry, e).is_eq()) {
+                return Ok(None);
+            }
+
+            node.entries.insert(pos, entry);
+        } else {
+            let pos = self.child_pos(&node, &entry.key, position);
+
+            match self.insert(txn, node.child(pos), entry)? {
+                Some(separator) => node.entries.insert(pos, separator),
+                None => return Ok(None),
+            }
+        }
+
+        if node.len() <= self.capacity() {
+            self.write_node(txn, page_id, &node)?;
+            return Ok(None);
+        }
+
+        self.split(txn, page_id, node)
+    }
+
+    /// Splits the overflowed node. Halves of the root are moved to new pages and the root
+    /// becomes their parent, so the tree grows by a level. Otherwise the right half is moved
+    /// to a new page and its separator is returned.
+    fn split(&self, txn: &Transaction, page_id: u32, node: Node)
+             -> Result<Option<NodeEntry>, Error> {
+        let (mut left, separator, mut right) = node.split();
+
+        if page_id == self.root {
+            let left_id = self.allocate(txn)?;
+            let right_id = self.allocate(txn)?;
+
+            // Root has no neighbours.
+            if left.is_leaf() {
+                left.next = right_id;
+                right.prev = left_id;
+            }
+
+            self.write_node(txn, left_id, &left)?;
+            self.write_node(txn, right_id, &right)?;
+
+            let mut root = Node::new(left.level + 1);
+            root.first_child = left_id;
+            root.entries.push(NodeEntry { child: right_id, ..separator });
+
+            self.write_node(txn, self.root, &root)?;
+
+            return Ok(None);
+        }
+
+        let right_id = self.allocate(txn)?;
+
+        if left.is_leaf() {
+            right.prev = page_id;
+            right.next = left.next;
+            left.next = right_id;
+
+            if right.next != 0 {
+                self.set_prev(txn, right.next, right_id)?;
+            }
+        }
+
+        self.write_node(txn, page_id, &left)?;
+        self.write_node(txn, right_id, &right)?;
+
+        Ok(Some(NodeEntry { child: right_id, ..separator }))
+    }
+
+    /// Deletes entry of the row with the key from the subtree of the node. Returns whether
+    /// the entry was found and whether the node is less than a quarter full.
+    fn delete(&self, txn: &Transaction, page_id: u32, key: &[Value], row: RowId)
+              -> Result<(bool, bool), Error> {
+        let mut node = self.read_node(page_id)?;
+        let position = Position::Row(row);
+
+        if node.is_leaf() {
+            let pos = self.entry_pos(&node, key, position);
+
+            match node.entries.get(pos) {
+                Some(entry) if self.compare(key, position, entry) == Ordering::Equal => (),
+                _ => return Ok((false, false)),
+            }
+
+            node.entries.remove(pos);
+        } else {
+            let pos = self.child_pos(&node, key, position);
+            let (found, underflow) = self.delete(txn, node.child(pos), key, row)?;
+
+            if !underflow {
+                return Ok((found, false));
+            }
+
+            self.rebalance(txn, &mut node, pos)?;
+        }
+
+        self.write_node(txn, page_id, &node)?;
+
+        Ok((true, node.len() < self.min_len()))
+    }
+
+    /// Rebalances the underflowed child of the node with its neighbour: they are merged if
+    /// their entries fit into a page, otherwise the entries are split between them evenly.
+    fn rebalance(&self, txn: &Transaction, parent: &mut Node, pos: usize) -> Result<(), Error> {
+        // Single child of the root replaces the root.
+        if parent.entries.is_empty() {
+            return Ok(());
+        }
+
+        let pos = if pos == 0 { 0 } else { pos - 1 };
+        let (left_id, right_id) = (parent.child(pos), parent.child(pos + 1));
+
+        let mut left = self.read_node(left_id)?;
+        let mut right = self.read_node(right_id)?;
+
+        // Separator of the right node moves down to an internal node.
+        if !left.is_leaf() {
+            let separator = parent.entries[pos].clone();
+            left.entries.push(NodeEntry { child: right.first_child, ..separator });
+        }
+
+        left.entries.append(&mut right.entries);
+
+        if left.len() <= self.capacity() {
+            if left.is_leaf() {
+                left.next = right.next;
+
+                if left.next != 0 {
+                    self.set_prev(txn, left.next, left_id)?;
+                }
+            }
+
+            self.write_node(txn, left_id, &left)?;
+            txn.free_page(right_id)?;
+            parent.entries.remove(pos);
+
+            return Ok(());
+        }
+
+        let next = right.next;
+        let (left, separator, mut right) = left.split();
+
+        if right.is_leaf() {
+            right.prev = left_id;
+            right.next = next;
+        }
+
+        self.write_node(txn, left_id, &left)?;
+        self.write_node(txn, right_id, &right)?;
+        parent.entries[pos] = NodeEntry { child: right_id, ..separator };
+
+        Ok(())
+    }
+
+    /// Replaces the root, which has a single child, with the child, so the tree shrinks by
+    /// a level.
+    fn collapse_root(&self, txn: &Transaction) -> Result<(), Error> {
+        loop {
+            let root = self.read_node(self.root)?;
+
+            if root.is_leaf() || !root.entries.is_empty() {
+                return Ok(());
+            }
+
+            // The only child has no neighbours.
+            let child = self.read_node(root.first_child)?;
+
+            self.write_node(txn, self.root, &child)?;
+            txn.free_page(root.first_child)?;
+        }
+    }
+
+    /// Writes nodes of the level from left to right with the items, which are entries of
+    /// the leaves or separators of the children. Returns separators of the written nodes,
+    /// or `None` if all items fit into the root.
+    fn build_level<I>(&self, txn: &Transaction, level: u32, items: I)
+                      -> Result<Option<Vec<NodeEntry>>, Error>
+        where I: Iterator<Item = Result<NodeEntry, Error>>
+    {
+        let limit = self.capacity() * FILL_FACTOR / 100;
+
+        let mut separators = Vec::new();
+        let mut node = Node::new(level);
+        // The least entry of the node, which becomes its separator.
+        let mut low: Option<NodeEntry> = None;
+        // Page of the node, which is allocated once the level has several nodes.
+        let mut page = None;
+
+        for item in items {
+            let item = item?;
+
+            if let Some(first) = low.take() {
+                if node.len() + node.entry_len(&item) <= limit {
+                    node.entries.push(item);
+                    low = Some(first);
+                    continue;
+                }
+
+                let current = match page {
+                    Some(page_id) => page_id,
+                    None => self.allocate(txn)?,
+                };
+
+                let next = self.allocate(txn)?;
+
+                if node.is_leaf() {
+                    node.next = next;
+                }
+
+                self.write_node(txn, current, &node)?;
+                separators.push(NodeEntry { child: current, ..first });
+
+                node = Node::new(level);
+
+                if node.is_leaf() {
+                    node.prev = current;
+                }
+
+                page = Some(next);
+            }
+
+            // Separator of the first child of an internal node moves up.
+            if node.is_leaf() {
+                node.entries.push(item.clone());
+            } else {
+                node.first_child = item.child;
+            }
+
+            low = Some(item);
+        }
+
+        match (page, low) {
+            (Some(page_id), Some(first)) => {
+                self.write_node(txn, page_id, &node)?;
+                separators.push(NodeEntry { child: page_id, ..first });
+
+                Ok(Some(separators))
+            }
+            _ => {
+                self.write_node(txn, self.root, &node)?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Bound;
+    use std::sync::Arc;
+
+    use data_type::DataType;
+    use collation::SortOrder;
+    use database::RowId;
+    use value::Value;
+    use storage::{CorruptionPolicy, Storage};
+    use indexing::{BTree, KeyColumn};
+    use test_utils::{remove_database, temp_path};
+
+    /// Creates tree of integers in the storage with small pages, so it has several levels.
+    fn tree(storage: &Arc<Storage>) -> BTree {
+        let txn = storage.begin();
+        let tree = BTree::create(&txn, storage.clone(), 1, vec![KeyColumn::new(DataType::INTEGER)])
+            .unwrap();
+        txn.commit().unwrap();
+
+        tree
+    }
+
+    fn key(val: i32) -> Vec<Value> {
+        vec![Value::from(val / 2)]
+    }
+
+    fn row(val: i32) -> RowId {
+        RowId::new(val as u32 / 4, val as u32 % 4)
+    }
+
+    fn rows(tree: &BTree) -> Vec<RowId> {
+        tree.iter().unwrap().collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn split_and_merge_nodes() {
+        let storage = Arc::new(Storage::with_page_size(256));
+        let tree = tree(&storage);
+        let n = 500;
+
+        // Entries are added in a scattered order, every key has two rows.
+        let txn = storage.begin();
+
+        for i in 0..n {
+            let val = i * 7 % n;
+            tree.add(&txn, &key(val), row(val)).unwrap();
+        }
+
+        tree.add(&txn, &key(10), row(10)).unwrap();
+        txn.commit().unwrap();
+
+        assert_eq!(tree.len().unwrap(), n as usize);
+        assert_eq!(rows(&tree), (0..n).map(row).collect::<Vec<_>>());
+        assert_eq!(tree.get(&key(100)).unwrap(), vec![row(100), row(101)]);
+        assert_eq!(tree.verify().unwrap().len(), storage.pages(1).len());
+        assert!(storage.pages(1).len() > 30);
+
+        let txn = storage.begin();
+
+        for i in (0..n).filter(|i| i % 3 != 0) {
+            assert!(tree.remove(&txn, &key(i), row(i)).unwrap());
+        }
+
+        assert!(!tree.remove(&txn, &key(1), row(1)).unwrap());
+        assert!(!tree.remove(&txn, &key(2), row(0)).unwrap());
+        txn.commit().unwrap();
+
+        let left: Vec<_> = (0..n).filter(|i| i % 3 == 0).collect();
+        assert_eq!(rows(&tree), left.iter().cloned().map(row).collect::<Vec<_>>());
+        assert_eq!(tree.get(&key(100)).unwrap(), Vec::new());
+        assert_eq!(tree.get(&key(99)).unwrap(), vec![row(99)]);
+        assert_eq!(tree.verify().unwrap().len(), storage.pages(1).len());
+
+        let txn = storage.begin();
+
+        for &i in left.iter().rev() {
+            assert!(tree.remove(&txn, &key(i), row(i)).unwrap());
+        }
+
+        txn.commit().unwrap();
+
+        assert!(tree.is_empty().unwrap());
+        assert_eq!(storage.pages(1), vec![tree.root()]);
+
+        let txn = storage.begin();
+        tree.add(&txn, &[Value::from(1i64)], row(0)).unwrap_err();
+        tree.add(&txn, &[Value::from(1), Value::from(1)], row(0)).unwrap_err();
+        tree.remove(&txn, &[Value::Null], row(0)).unwrap_err();
+        tree.get(&[]).unwrap_err();
+    }
+
+    #[test]
+    fn scan_ranges() {
+        let storage = Arc::new(Storage::with_page_size(256));
+        let tree = tree(&storage);
+        let n = 200;
+
+        let txn = storage.begin();
+        tree.bulk_load(&txn, (0..n).map(|i| (key(i), row(i)))).unwrap();
+        txn.commit().unwrap();
+
+        let range = |from: Bound<i32>, to: Bound<i32>| -> Vec<RowId> {
+            let (from, to) = (from.map(|v| key(v * 2)), to.map(|v| key(v * 2)));
+
+            tree.range(from.as_ref().map(Vec::as_slice), to.as_ref().map(Vec::as_slice))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+
+        let ids: Vec<_> = (0..n).map(row).collect();
+
+        assert_eq!(range(Bound::Included(1), Bound::Included(50)), &ids[2..102]);
+        assert_eq!(range(Bound::Excluded(1), Bound::Included(50)), &ids[4..102]);
+        assert_eq!(range(Bound::Included(1), Bound::Excluded(50)), &ids[2..100]);
+        assert_eq!(range(Bound::Unbounded, Bound::Excluded(70)), &ids[..140]);
+        assert_eq!(range(Bound::Excluded(70), Bound::Unbounded), &ids[142..]);
+        assert_eq!(range(Bound::Unbounded, Bound::Unbounded), ids);
+        assert_eq!(range(Bound::Included(-5), Bound::Included(0)), &ids[..2]);
+        assert_eq!(range(Bound::Excluded(99), Bound::Unbounded), Vec::new());
+        assert_eq!(range(Bound::Unbounded, Bound::Excluded(0)), Vec::new());
+        assert_eq!(range(Bound::Included(60), Bound::Included(20)), Vec::new());
+        assert_eq!(range(Bound::Included(20), Bound::Excluded(20)), Vec::new());
+
+        let reversed: Vec<_> = tree.range(Bound::Included(&key(40)[..]), Bound::Unbounded)
+            .unwrap()
+            .rev()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(reversed, ids[40..].iter().rev().cloned().collect::<Vec<_>>());
+
+        // Iterator is read from both ends until they meet.
+        let mut iter = tree.range(Bound::Included(&key(10)[..]), Bound::Included(&key(150)[..]))
+            .unwrap();
+        let mut both = Vec::new();
+
+        while let (Some(front), Some(back)) = (iter.next(), iter.next_back()) {
+            both.push(front.unwrap());
+            both.push(back.unwrap());
+        }
+
+        assert_eq!(iter.next().is_none(), iter.next_back().is_none());
+        both.sort();
+        assert_eq!(both, &ids[10..152]);
+    }
+
+    #[test]
+    fn scan_composite_keys() {
+        let storage = Arc::new(Storage::with_page_size(256));
+        let columns = vec![KeyColumn::new(DataType::VARCHAR),
+                           KeyColumn {
+                               order: SortOrder::Descending,
+                               ..KeyColumn::new(DataType::BIGINT)
+                           }];
+
+        let txn = storage.begin();
+        let tree = BTree::create(&txn, storage.clone(), 1, columns).unwrap();
+
+        for i in 0..100 {
+            let tenant = ["a", "b", "c"][i % 3];
+            let key = [Value::from(tenant), Value::from(i as i64)];
+            tree.add(&txn, &key, RowId::new(0, i as u32)).unwrap();
+        }
+
+        txn.commit().unwrap();
+
+        let slots = |from: Bound<&[Value]>, to: Bound<&[Value]>| -> Vec<u32> {
+            tree.range(from, to)
+                .unwrap()
+                .map(|id| id.unwrap().slot())
+                .collect()
+        };
+
+        // Rows of every tenant are sorted from the latest to the earliest.
+        let b = [Value::from("b")];
+        let expected: Vec<_> = (0..100).rev().filter(|i| i % 3 == 1).collect();
+        assert_eq!(slots(Bound::Included(&b), Bound::Included(&b)), expected);
+
+        let from = [Value::from("b"), Value::from(50i64)];
+        let to = [Value::from("b"), Value::from(40i64)];
+        assert_eq!(slots(Bound::Included(&from), Bound::Excluded(&to)), vec![49, 46, 43]);
+        assert_eq!(slots(Bound::Included(&to), Bound::Included(&from)), Vec::<u32>::new());
+        assert_eq!(slots(Bound::Excluded(&b), Bound::Unbounded).len(), 33);
+
+        // Key must fit into a quarter of the page.
+        let txn = storage.begin();
+        let long = [Value::from("x".repeat(100).as_str()), Value::from(0i64)];
+        tree.add(&txn, &long, RowId::new(0, 0)).unwrap_err();
+    }
+
+    #[test]
+    fn bulk_load_sorted_entries() {
+        let storage = Arc::new(Storage::with_page_size(256));
+        let tree = tree(&storage);
+        let n = 1000;
+
+        let txn = storage.begin();
+        tree.bulk_load(&txn, (0..n).map(|i| (key(i), row(i)))).unwrap();
+        txn.commit().unwrap();
+
+        assert_eq!(rows(&tree), (0..n).map(row).collect::<Vec<_>>());
+        assert_eq!(tree.verify().unwrap().len(), storage.pages(1).len());
+
+        // Loaded tree is changed as usual.
+        let txn = storage.begin();
+
+        for i in 0..n / 2 {
+            tree.remove(&txn, &key(i * 2), row(i * 2)).unwrap();
+        }
+
+        tree.add(&txn, &key(n), row(n)).unwrap();
+        txn.commit().unwrap();
+
+        assert_eq!(tree.len().unwrap(), n as usize / 2 + 1);
+        tree.verify().unwrap();
+
+        let txn = storage.begin();
+        tree.bulk_load(&txn, vec![(key(0), row(0))]).unwrap_err();
+
+        tree.clear(&txn).unwrap();
+        assert_eq!(storage.pages(1), vec![tree.root()]);
+
+        tree.bulk_load(&txn, vec![(key(2), row(2)), (key(0), row(0))]).unwrap_err();
+        tree.bulk_load(&txn, vec![(key(0), row(0)), (key(0), row(0))]).unwrap_err();
+        tree.bulk_load(&txn, vec![(key(0), row(0))]).unwrap();
+        assert_eq!(rows(&tree), vec![row(0)]);
+    }
+
+    #[test]
+    fn rollback_changes() {
+        let storage = Arc::new(Storage::with_page_size(256));
+        let tree = tree(&storage);
+
+        let txn = storage.begin();
+        tree.bulk_load(&txn, (0..100).map(|i| (key(i), row(i)))).unwrap();
+        txn.commit().unwrap();
+
+        let pages = storage.pages(1);
+
+        let txn = storage.begin();
+
+        for i in 100..300 {
+            tree.add(&txn, &key(i), row(i)).unwrap();
+        }
+
+        for i in 0..50 {
+            tree.remove(&txn, &key(i), row(i)).unwrap();
+        }
+
+        txn.rollback().unwrap();
+
+        assert_eq!(rows(&tree), (0..100).map(row).collect::<Vec<_>>());
+        assert_eq!(storage.pages(1), pages);
+        tree.verify().unwrap();
+
+        let txn = storage.begin();
+        tree.free_pages(&txn).unwrap();
+        txn.commit().unwrap();
+
+        assert!(storage.pages(1).is_empty());
+    }
+
+    #[test]
+    fn persist_tree() {
+        let path = temp_path("persist_tree");
+        let columns = vec![KeyColumn::new(DataType::INTEGER)];
+
+        let root = {
+            let storage = Arc::new(Storage::create(&path, 512, 16, CorruptionPolicy::Fail)
+                .unwrap());
+            let tree = self::tree(&storage);
+
+            let txn = storage.begin();
+
+            for i in 0..500 {
+                tree.add(&txn, &key(i), row(i)).unwrap();
+            }
+
+            txn.commit().unwrap();
+            storage.close().unwrap();
+
+            tree.root()
+        };
+
+        let storage = Arc::new(Storage::open(&path, 16, CorruptionPolicy::Fail).unwrap());
+        let tree = BTree::open(storage.clone(), root, columns);
+
+        assert_eq!(rows(&tree), (0..500).map(row).collect::<Vec<_>>());
+        assert_eq!(tree.get(&key(300)).unwrap(), vec![row(300), row(301)]);
+        tree.verify().unwrap();
+
+        drop(tree);
+        storage.close().unwrap();
+        remove_database(&path);
+    }
+}
@@ -0,0 +1,3 @@
+mod btree;
+
+pub use self::btree::{BTree, KeyColumn};
@@ -1,14 +1,23 @@
 #![allow(dead_code)]
 
-extern crate skiplist;
-
+pub mod error;
 pub mod database;
 pub mod data_type;
+pub mod collation;
+pub mod value;
+pub mod row;
+pub mod constraint;
+pub mod verify;
 
 mod storage;
 mod protocol;
 mod indexing;
 
+pub use error::Error;
+
+#[cfg(test)]
+mod test_utils;
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -1,12 +1,25 @@
+use std::borrow::Cow;
+use std::str;
+
 use data_type::DataType;
 use storage::MemoryPage;
+use storage::Storage;
+use storage::OVERFLOW_FLAG;
+use storage::OVERFLOW_REF_LEN;
+use protocol::unpack;
+use value::Value;
+use error::Error;
 
 /// Reads and deserializes values from MemoryPage.
 /// Always reads values in the architertural endian currently.
+///
+/// VARCHAR and VARBINARY values stored in overflow pages are reassembled from the storage
+/// of the stream.
 #[derive(Debug)]
 pub struct DeserializeStream<'a> {
     position: usize,
     page: &'a MemoryPage,
+    storage: Option<&'a Storage>,
 }
 
 impl<'a> DeserializeStream<'a> {
@@ -14,37 +27,222 @@ impl<'a> DeserializeStream<'a> {
     pub fn new(page: &'a MemoryPage, pos: usize) -> Self {
         DeserializeStream {
             position: pos,
-            page: page,
+            page,
+            storage: None,
+        }
+    }
+
+    /// Creates new stream, which reads values stored in overflow pages of the storage.
+    pub fn with_storage(page: &'a MemoryPage, pos: usize, storage: &'a Storage) -> Self {
+        DeserializeStream {
+            position: pos,
+            page,
+            storage: Some(storage),
         }
     }
 
+    /// Get current position of the stream in the page.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
     /// Check if we have enough memory in page to read a value.
-    fn check_space(&self, len: usize) -> Result<(), String> {
+    fn check_space(&self, len: usize) -> Result<(), Error> {
         let avail: isize = self.page.data().len() as isize - self.position as isize;
 
         if avail < len as isize {
-            Err(format!("Not enough memory to serialize INTEGER value: required={}, available={}",
-                        len,
-                        avail))
+            Err(Error::Corruption(format!("Not enough memory to deserialize value: required={}, \
+                                           available={}",
+                                          len,
+                                          avail)))
         } else {
             Ok(())
         }
     }
 
-    /// Read integer from stream.
-    pub fn read_int(&mut self) -> Result<i32, String> {
-        self.check_space(DataType::INTEGER.static_len())?;
+    /// Check if we have enough memory in page to read a static typed value.
+    fn check_static_type_len(&self, field_type: DataType) -> Result<&'a [u8], Error> {
+        match self.check_space(field_type.static_len()) {
+            Err(e) => Err(Error::Corruption(format!("Unable to read {:?}: {}", field_type, e))),
+            Ok(_) => Ok(&self.page.data()[self.position..]),
+        }
+    }
+
+    /// Check if we have enough memory in page to read a dynamic typed value.
+    fn check_dynamic_type_len(&self, field_type: DataType) -> Result<&'a [u8], Error> {
+        let mem = self.check_static_type_len(field_type)?;
+        let size = unpack::unpack_unsigned(mem)? as usize;
+
+        match self.check_space(field_type.static_len() + size) {
+            Err(e) => Err(Error::Corruption(format!("Unable to read {:?}: {}", field_type, e))),
+            Ok(_) => Ok(mem),
+        }
+    }
+
+    /// Read INTEGER from stream.
+    pub fn read_int(&mut self) -> Result<i32, Error> {
+        let mem = self.check_static_type_len(DataType::INTEGER)?;
 
-        let mem = self.page.data();
-        let p = self.position;
+        self.position += DataType::INTEGER.static_len();
 
-        let res: i32 = (mem[p] as i32 & 0xFFi32) | (mem[p + 1] as i32 & 0xFFi32) << 8 |
-                       (mem[p + 2] as i32 & 0xFFi32) << 16 |
-                       (mem[p + 3] as i32 & 0xFFi32) << 24;
+        unpack::unpack_int(mem)
+    }
+
+    /// Read SMALLINT from stream.
+    pub fn read_smallint(&mut self) -> Result<i16, Error> {
+        let mem = self.check_static_type_len(DataType::SMALLINT)?;
 
-        self.position += 4;
+        self.position += DataType::SMALLINT.static_len();
 
-        Ok(res)
+        unpack::unpack_smallint(mem)
+    }
+
+    /// Read BIGINT from stream.
+    pub fn read_bigint(&mut self) -> Result<i64, Error> {
+        let mem = self.check_static_type_len(DataType::BIGINT)?;
+
+        self.position += DataType::BIGINT.static_len();
+
+        unpack::unpack_bigint(mem)
+    }
+
+    /// Read BOOLEAN from stream.
+    pub fn read_bool(&mut self) -> Result<bool, Error> {
+        let mem = self.check_static_type_len(DataType::BOOLEAN)?;
+
+        self.position += DataType::BOOLEAN.static_len();
+
+        unpack::unpack_bool(mem)
+    }
+
+    /// Read FLOAT from stream.
+    pub fn read_float(&mut self) -> Result<f64, Error> {
+        let mem = self.check_static_type_len(DataType::FLOAT)?;
+
+        self.position += DataType::FLOAT.static_len();
+
+        unpack::unpack_float(mem)
+    }
+
+    /// Read length of the value stored in overflow pages and id of its first overflow page.
+    /// Returns `None` if the value is stored in the stream itself.
+    fn read_overflow_ref(&mut self, field_type: DataType) -> Result<Option<(usize, u32)>, Error> {
+        let len = unpack::unpack_unsigned(self.check_static_type_len(field_type)?)?;
+
+        if len & OVERFLOW_FLAG == 0 {
+            return Ok(None);
+        }
+
+        if let Err(e) = self.check_space(OVERFLOW_REF_LEN) {
+            return Err(Error::Corruption(format!("Unable to read {:?}: {}", field_type, e)));
+        }
+
+        let first = unpack::unpack_unsigned(&self.page.data()[self.position + 4..])?;
+        self.position += OVERFLOW_REF_LEN;
+
+        Ok(Some(((len & !OVERFLOW_FLAG) as usize, first)))
+    }
+
+    /// Read bytes of VARCHAR or VARBINARY value. Bytes of the value stored in the stream itself
+    /// point directly to the page memory, value stored in overflow pages is reassembled.
+    fn read_bytes(&mut self, field_type: DataType) -> Result<Cow<'a, [u8]>, Error> {
+        let (len, first) = match self.read_overflow_ref(field_type)? {
+            Some(overflow) => overflow,
+            None => {
+                let mem = self.check_dynamic_type_len(field_type)?;
+                let val = unpack::unpack_array(mem)?;
+
+                self.position += field_type.static_len() + val.len();
+
+                return Ok(Cow::Borrowed(val));
+            }
+        };
+
+        let storage = match self.storage {
+            Some(storage) => storage,
+            None => {
+                return Err(Error::InvalidArgument(format!("Unable to read {:?}: value is stored \
+                                                           in overflow pages",
+                                                          field_type)))
+            }
+        };
+
+        let val = storage.read_chain(first)?;
+
+        if val.len() != len {
+            return Err(Error::Corruption(format!("Unable to read {:?}: overflow pages hold {} \
+                                                  bytes, expected {}",
+                                                 field_type,
+                                                 val.len(),
+                                                 len)));
+        }
+
+        Ok(Cow::Owned(val))
+    }
+
+    /// Read VARCHAR from stream. Value stored in the page is borrowed from it, value stored
+    /// in overflow pages is read into an owned string.
+    pub fn read_varchar(&mut self) -> Result<Cow<'a, str>, Error> {
+        let res = match self.read_bytes(DataType::VARCHAR)? {
+            Cow::Borrowed(bytes) => {
+                str::from_utf8(bytes).map(Cow::Borrowed).map_err(|e| e.to_string())
+            }
+            Cow::Owned(bytes) => {
+                String::from_utf8(bytes).map(Cow::Owned).map_err(|e| e.to_string())
+            }
+        };
+
+        res.map_err(|e| Error::Corruption(format!("Unable to read VARCHAR: {}", e)))
+    }
+
+    /// Read VARBINARY from stream. Value stored in the page is borrowed from it, value
+    /// stored in overflow pages is read into an owned buffer.
+    pub fn read_varbinary(&mut self) -> Result<Cow<'a, [u8]>, Error> {
+        self.read_bytes(DataType::VARBINARY)
+    }
+
+    /// Skip value of the specified type. Returns id of the first overflow page
+    /// if the value is stored in overflow pages.
+    pub fn skip_value(&mut self, data_type: DataType) -> Result<Option<u32>, Error> {
+        match data_type {
+            DataType::VARCHAR | DataType::VARBINARY => {
+                if let Some((_, first)) = self.read_overflow_ref(data_type)? {
+                    return Ok(Some(first));
+                }
+
+                let mem = self.check_dynamic_type_len(data_type)?;
+                self.position += data_type.static_len() + unpack::unpack_array(mem)?.len();
+            }
+            _ => {
+                self.check_static_type_len(data_type)?;
+                self.position += data_type.static_len();
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Read specified number of raw bytes from stream.
+    pub fn read_raw(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        self.check_space(len)?;
+
+        let val = &self.page.data()[self.position..self.position + len];
+        self.position += len;
+
+        Ok(val)
+    }
+
+    /// Read value of the specified type from stream.
+    pub fn read_value(&mut self, data_type: DataType) -> Result<Value, Error> {
+        Ok(match data_type {
+            DataType::VARCHAR => Value::Varchar(self.read_varchar()?.into_owned()),
+            DataType::VARBINARY => Value::Varbinary(self.read_varbinary()?.into_owned()),
+            DataType::BOOLEAN => Value::Boolean(self.read_bool()?),
+            DataType::SMALLINT => Value::Smallint(self.read_smallint()?),
+            DataType::INTEGER => Value::Integer(self.read_int()?),
+            DataType::BIGINT => Value::Bigint(self.read_bigint()?),
+            DataType::FLOAT => Value::Float(self.read_float()?),
+        })
     }
 }
 
@@ -54,6 +252,9 @@ mod test {
     use storage::MemoryPage;
     use protocol::serialize_stream::SerializeStream;
     use protocol::deserialize_stream::DeserializeStream;
+    use data_type::DataType;
+    use value::Value;
+    use error::Error;
 
     #[test]
     fn write_read_single_int() {
@@ -63,7 +264,7 @@ mod test {
             ws.write_int(42).expect("Should not fail");
         }
 
-        let mut rs = DeserializeStream::new(&mut page, 0);
+        let mut rs = DeserializeStream::new(&page, 0);
         let val = rs.read_int().unwrap();
 
         assert_eq!(val, 42);
@@ -80,9 +281,218 @@ mod test {
             ws.write_int(-1294).expect("Should not fail");
         }
 
-        let mut rs = DeserializeStream::new(&mut page, 0);
+        let mut rs = DeserializeStream::new(&page, 0);
         assert_eq!(rs.read_int().unwrap(), 2093608745);
         assert_eq!(rs.read_int().unwrap(), 0);
         assert_eq!(rs.read_int().unwrap(), -1294);
     }
+
+    #[test]
+    fn write_read_values() {
+        let values = [Value::Varchar("test".to_owned()),
+                      Value::Varbinary(vec![1, 2, 3]),
+                      Value::Boolean(true),
+                      Value::Smallint(-12),
+                      Value::Integer(42),
+                      Value::Bigint(1 << 40),
+                      Value::Float(0.5)];
+
+        let len = values.iter().map(|v| v.serialized_len()).sum();
+
+        let mut page = MemoryPage::new(len);
+        {
+            let mut ws = SerializeStream::new(&mut page, 0);
+
+            for val in values.iter() {
+                ws.write_value(val).expect("Should not fail");
+            }
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        for val in values.iter() {
+            assert_eq!(&rs.read_value(val.data_type().unwrap()).unwrap(), val);
+        }
+
+        assert_eq!(rs.position(), len);
+        rs.read_value(DataType::BOOLEAN).unwrap_err();
+    }
+
+    #[test]
+    fn read_truncated_varchar() {
+        let mut page = MemoryPage::new(4 + 2);
+        {
+            let mut ws = SerializeStream::new(&mut page, 0);
+            ws.write_int(3).expect("Should not fail");
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        rs.read_value(DataType::VARCHAR).unwrap_err();
+    }
+
+    #[test]
+    fn write_read_smallints() {
+        let mut page = MemoryPage::new(2 * 3 + 1);
+        {
+            let mut ws = SerializeStream::new(&mut page, 0);
+
+            ws.write_smallint(11).expect("Should not fail");
+            ws.write_smallint(i16::MIN).expect("Should not fail");
+            ws.write_smallint(-1294).expect("Should not fail");
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        assert_eq!(rs.read_smallint().unwrap(), 11);
+        assert_eq!(rs.read_smallint().unwrap(), i16::MIN);
+        assert_eq!(rs.read_smallint().unwrap(), -1294);
+        rs.read_smallint().unwrap_err();
+    }
+
+    #[test]
+    fn write_read_bigints() {
+        let mut page = MemoryPage::new(8 * 3 + 7);
+        {
+            let mut ws = SerializeStream::new(&mut page, 0);
+
+            ws.write_bigint(i64::MAX).expect("Should not fail");
+            ws.write_bigint(0).expect("Should not fail");
+            ws.write_bigint(-1294).expect("Should not fail");
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        assert_eq!(rs.read_bigint().unwrap(), i64::MAX);
+        assert_eq!(rs.read_bigint().unwrap(), 0);
+        assert_eq!(rs.read_bigint().unwrap(), -1294);
+        rs.read_bigint().unwrap_err();
+    }
+
+    #[test]
+    fn write_read_bools() {
+        let mut page = MemoryPage::new(2);
+        {
+            let mut ws = SerializeStream::new(&mut page, 0);
+
+            ws.write_bool(true).expect("Should not fail");
+            ws.write_bool(false).expect("Should not fail");
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        assert!(rs.read_bool().unwrap());
+        assert!(!rs.read_bool().unwrap());
+        rs.read_bool().unwrap_err();
+    }
+
+    #[test]
+    fn write_read_floats() {
+        let mut page = MemoryPage::new(8 * 3 + 7);
+        {
+            let mut ws = SerializeStream::new(&mut page, 0);
+
+            ws.write_float(11.05).expect("Should not fail");
+            ws.write_float(-0.0).expect("Should not fail");
+            ws.write_float(f64::INFINITY).expect("Should not fail");
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        assert_eq!(rs.read_float().unwrap(), 11.05);
+        assert_eq!(rs.read_float().unwrap().to_bits(), (-0.0f64).to_bits());
+        assert_eq!(rs.read_float().unwrap(), f64::INFINITY);
+        rs.read_float().unwrap_err();
+    }
+
+    #[test]
+    fn write_read_varchars() {
+        let mut page = MemoryPage::new(3 * 4 + 4 + 4 + 3);
+        {
+            let mut ws = SerializeStream::new(&mut page, 0);
+
+            ws.write_varchar("test").expect("Should not fail");
+            ws.write_varchar("💖").expect("Should not fail");
+            ws.write_varchar("").expect("Should not fail");
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        assert_eq!(rs.read_varchar().unwrap(), "test");
+        assert_eq!(rs.read_varchar().unwrap(), "💖");
+        assert_eq!(rs.read_varchar().unwrap(), "");
+        rs.read_varchar().unwrap_err();
+    }
+
+    #[test]
+    fn read_invalid_varchar() {
+        let mut page = MemoryPage::new(4 + 2);
+        {
+            let mut ws = SerializeStream::new(&mut page, 0);
+            ws.write_varbinary(&[0xC3, 0x28]).expect("Should not fail");
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+
+        match rs.read_varchar() {
+            Err(Error::Corruption(_)) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        assert_eq!(&*rs.read_varbinary().unwrap(), &[0xC3, 0x28]);
+    }
+
+    #[test]
+    fn write_read_varbinaries() {
+        let arr: [u8; 5] = [1, 2, 3, 4, 5];
+
+        let mut page = MemoryPage::new(2 * 4 + arr.len() + 3);
+        {
+            let mut ws = SerializeStream::new(&mut page, 0);
+
+            ws.write_varbinary(&arr).expect("Should not fail");
+            ws.write_varbinary(&[]).expect("Should not fail");
+        }
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        assert_eq!(&*rs.read_varbinary().unwrap(), &arr);
+        assert_eq!(&*rs.read_varbinary().unwrap(), &[]);
+        rs.read_varbinary().unwrap_err();
+        assert_eq!(rs.position(), 2 * 4 + arr.len());
+    }
+
+    #[test]
+    fn write_read_overflow_values() {
+        use storage::Storage;
+        use storage::OverflowWriter;
+
+        let storage = Storage::with_page_size(256);
+        let txn = storage.begin();
+        let overflow = OverflowWriter::new(&txn, 1, 256);
+
+        let text = "💖".repeat(100);
+        let bytes: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+
+        let mut page = MemoryPage::new(4 + 5 + 8 + 8);
+        {
+            let mut ws = SerializeStream::with_overflow(&mut page, 0, &overflow);
+
+            ws.write_varchar("small").expect("Should not fail");
+            ws.write_varchar(&text).expect("Should not fail");
+            ws.write_varbinary(&bytes).expect("Should not fail");
+            assert_eq!(ws.position(), 4 + 5 + 8 + 8);
+        }
+
+        assert_eq!(storage.pages(1).len(), 2 + 5);
+
+        let mut rs = DeserializeStream::with_storage(&page, 0, &storage);
+        assert_eq!(rs.read_varchar().unwrap(), "small");
+        assert_eq!(rs.read_varchar().unwrap(), text);
+        assert_eq!(&*rs.read_varbinary().unwrap(), &bytes[..]);
+
+        // Values can not be reassembled without the storage.
+        let mut rs = DeserializeStream::new(&page, 0);
+        rs.read_varchar().unwrap();
+        rs.read_varchar().unwrap_err();
+
+        let mut rs = DeserializeStream::new(&page, 0);
+        assert_eq!(rs.skip_value(DataType::VARCHAR).unwrap(), None);
+        assert!(rs.skip_value(DataType::VARCHAR).unwrap().is_some());
+        assert!(rs.skip_value(DataType::VARBINARY).unwrap().is_some());
+        assert_eq!(rs.position(), 4 + 5 + 8 + 8);
+    }
 }
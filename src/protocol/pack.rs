@@ -1,53 +1,64 @@
-use std::mem::transmute;
+use error::Error;
+
+/// Checks that the buffer has enough space for the value.
+fn check_space(data: &[u8], len: usize, what: &str) -> Result<(), Error> {
+    if data.len() < len {
+        return Err(Error::OutOfSpace(format!("Unable to pack {} into buffer: not enough space, \
+                                              required {}, available {}",
+                                             what,
+                                             len,
+                                             data.len())));
+    }
+
+    Ok(())
+}
 
-pub fn pack_int(data: &mut [u8], value: i32) -> usize {
-    assert!(data.len() >= 4, "Unable to pack i32 into buffer. Not enough space, available {}", data.len());
+pub fn pack_int(data: &mut [u8], value: i32) -> Result<usize, Error> {
+    check_space(data, 4, "i32")?;
 
     data[0] = value as u8;
     data[1] = (value >> 8) as u8;
     data[2] = (value >> 16) as u8;
     data[3] = (value >> 24) as u8;
 
-    4
+    Ok(4)
 }
 
 #[test]
 fn test_pack_int_success() {
     let mut array: [u8; 4] = [0; 4];
-    assert_eq!(4, pack_int(&mut array, 42));
+    assert_eq!(4, pack_int(&mut array, 42).unwrap());
 }
 
 #[test]
-#[should_panic]
 fn test_pack_int_fail() {
     let mut array: [u8; 3] = [0; 3];
-    pack_int(&mut array, 42);
+    pack_int(&mut array, 42).unwrap_err();
 }
 
-pub fn pack_smallint(data: &mut [u8], value: i16) -> usize {
-    assert!(data.len() >= 2, "Unable to pack i16 into buffer. Not enough space");
+pub fn pack_smallint(data: &mut [u8], value: i16) -> Result<usize, Error> {
+    check_space(data, 2, "i16")?;
 
     data[0] = value as u8;
     data[1] = (value >> 8) as u8;
 
-    2
+    Ok(2)
 }
 
 #[test]
 fn test_pack_smallint_success() {
     let mut array: [u8; 2] = [0; 2];
-    assert_eq!(2, pack_smallint(&mut array, 42));
+    assert_eq!(2, pack_smallint(&mut array, 42).unwrap());
 }
 
 #[test]
-#[should_panic]
 fn test_pack_smallint_fail() {
     let mut array: [u8; 1] = [0; 1];
-    pack_smallint(&mut array, 42);
+    pack_smallint(&mut array, 42).unwrap_err();
 }
 
-pub fn pack_bigint(data: &mut [u8], value: i64) -> usize {
-    assert!(data.len() >= 8, "Unable to pack i64 into buffer. Not enough space");
+pub fn pack_bigint(data: &mut [u8], value: i64) -> Result<usize, Error> {
+    check_space(data, 8, "i64")?;
 
     data[0] = value as u8;
     data[1] = (value >> 8) as u8;
@@ -58,28 +69,25 @@ pub fn pack_bigint(data: &mut [u8], value: i64) -> usize {
     data[6] = (value >> 48) as u8;
     data[7] = (value >> 56) as u8;
 
-    8
+    Ok(8)
 }
 
 #[test]
 fn test_pack_bigint_success() {
     let mut array: [u8; 8] = [0; 8];
-    assert_eq!(8, pack_bigint(&mut array, 42));
+    assert_eq!(8, pack_bigint(&mut array, 42).unwrap());
 }
 
 #[test]
-#[should_panic]
 fn test_pack_bigint_fail() {
     let mut array: [u8; 7] = [0; 7];
-    pack_bigint(&mut array, 42);
+    pack_bigint(&mut array, 42).unwrap_err();
 }
 
-pub fn pack_float(data: &mut [u8], value: f64) -> usize {
-    assert!(data.len() >= 8, "Unable to pack f64 into buffer. Not enough space");
+pub fn pack_float(data: &mut [u8], value: f64) -> Result<usize, Error> {
+    check_space(data, 8, "f64")?;
 
-    let transumuted = unsafe {
-        transmute::<f64, i64>(value)
-    };
+    let transumuted = value.to_bits() as i64;
 
     data[0] = transumuted as u8;
     data[1] = (transumuted >> 8) as u8;
@@ -90,69 +98,66 @@ pub fn pack_float(data: &mut [u8], value: f64) -> usize {
     data[6] = (transumuted >> 48) as u8;
     data[7] = (transumuted >> 56) as u8;
 
-    8
+    Ok(8)
 }
 
 #[test]
 fn test_pack_float_success() {
     let mut array: [u8; 8] = [0; 8];
-    assert_eq!(8, pack_float(&mut array, 42.02));
+    assert_eq!(8, pack_float(&mut array, 42.02).unwrap());
 }
 
 #[test]
-#[should_panic]
 fn test_pack_float_fail() {
     let mut array: [u8; 7] = [0; 7];
-    pack_float(&mut array, 42.02);
+    pack_float(&mut array, 42.02).unwrap_err();
 }
 
-pub fn pack_bool(data: &mut [u8], value: bool) -> usize {
-    assert!(data.len() >= 1, "Unable to pack bool into buffer. Not enough space");
+pub fn pack_bool(data: &mut [u8], value: bool) -> Result<usize, Error> {
+    check_space(data, 1, "bool")?;
 
     data[0] = value as u8;
 
-    1
+    Ok(1)
 }
 
 #[test]
 fn test_pack_bool_success() {
     let mut array: [u8; 1] = [0; 1];
-    assert_eq!(1, pack_bool(&mut array, true));
+    assert_eq!(1, pack_bool(&mut array, true).unwrap());
 }
 
 #[test]
-#[should_panic]
 fn test_pack_bool_fail() {
     let mut array: [u8; 0] = [0; 0];
-    pack_bool(&mut array, true);
+    pack_bool(&mut array, true).unwrap_err();
 }
 
-pub fn pack_unsigned(data: &mut [u8], value: u32) -> usize {
-    assert!(data.len() >= 4, "Unable to pack u32 into buffer. Not enough space");
+pub fn pack_unsigned(data: &mut [u8], value: u32) -> Result<usize, Error> {
+    check_space(data, 4, "u32")?;
 
     data[0] = value as u8;
     data[1] = (value >> 8) as u8;
     data[2] = (value >> 16) as u8;
     data[3] = (value >> 24) as u8;
 
-    4
+    Ok(4)
 }
 
 #[test]
 fn test_pack_unsigned_success() {
     let mut array: [u8; 4] = [0; 4];
-    assert_eq!(4, pack_unsigned(&mut array, 42));
+    assert_eq!(4, pack_unsigned(&mut array, 42).unwrap());
 }
 
 #[test]
-#[should_panic]
 fn test_pack_unsigned_fail() {
     let mut array: [u8; 3] = [0; 3];
-    pack_unsigned(&mut array, 42);
+    pack_unsigned(&mut array, 42).unwrap_err();
 }
 
-pub fn pack_string(data: &mut [u8], value: &str) -> usize {
-    assert!(data.len() >= value.len() + 4, "Unable to pack string into buffer. Not enough space");
+pub fn pack_string(data: &mut [u8], value: &str) -> Result<usize, Error> {
+    check_space(data, value.len() + 4, "string")?;
 
     pack_array(data, value.as_bytes())
 }
@@ -161,49 +166,45 @@ pub fn pack_string(data: &mut [u8], value: &str) -> usize {
 fn test_pack_string_success() {
     let mut array: [u8; 8] = [0; 8];
     let test = "test";
-    assert_eq!(4 + test.len(), pack_string(&mut array, &test));
+    assert_eq!(4 + test.len(), pack_string(&mut array, test).unwrap());
 }
 
 #[test]
-#[should_panic]
 fn test_pack_string_fail() {
     let mut array: [u8; 7] = [0; 7];
-    pack_string(&mut array, "test");
+    pack_string(&mut array, "test").unwrap_err();
 }
 
-fn copy_array(dst: &mut [u8], src: &[u8]) {
-    assert!(dst.len() >= src.len(), "Not enough space in destination array.");
+fn copy_array(dst: &mut [u8], src: &[u8]) -> Result<(), Error> {
+    check_space(dst, src.len(), "array")?;
 
-    let mut pos = 0;
-    for b in src {
-        dst[pos] = *b;
-        pos += 1;
-    }
+    dst[..src.len()].copy_from_slice(src);
+
+    Ok(())
 }
 
 #[test]
 fn test_copy_array_success() {
     let mut dst: [u8; 8] = [0; 8];
     let src: [u8; 8] = [0; 8];
-    copy_array(&mut dst, &src);
+    copy_array(&mut dst, &src).unwrap();
 }
 
 #[test]
-#[should_panic]
 fn test_copy_array_fail() {
     let mut dst: [u8; 1] = [0; 1];
     let src: [u8; 8] = [0; 8];
-    copy_array(&mut dst, &src);
+    copy_array(&mut dst, &src).unwrap_err();
 }
 
-pub fn pack_array(data: &mut [u8], value: &[u8]) -> usize {
-    assert!(data.len() >= value.len() + 4, "Unable to pack binary into buffer. Not enough space");
+pub fn pack_array(data: &mut [u8], value: &[u8]) -> Result<usize, Error> {
+    check_space(data, value.len() + 4, "binary")?;
 
-    let array_len = pack_unsigned(data, value.len() as u32);
+    let array_len = pack_unsigned(data, value.len() as u32)?;
 
-    copy_array(&mut data[4..], value);
+    copy_array(&mut data[4..], value)?;
 
-    array_len + value.len()
+    Ok(array_len + value.len())
 }
 
 #[test]
@@ -211,14 +212,13 @@ fn test_pack_array_success() {
     let mut array: [u8; 8] = [0; 8];
     let test: [u8; 4] = [1; 4];
 
-    assert_eq!(4 + test.len(), pack_array(&mut array, &test));
+    assert_eq!(4 + test.len(), pack_array(&mut array, &test).unwrap());
 }
 
 #[test]
-#[should_panic]
 fn test_pack_array_fail() {
     let mut array: [u8; 7] = [0; 7];
     let test: [u8; 4] = [1; 4];
 
-    pack_array(&mut array, &test);
+    pack_array(&mut array, &test).unwrap_err();
 }
\ No newline at end of file
@@ -1,13 +1,23 @@
 use data_type::DataType;
 use storage::MemoryPage;
+use storage::OverflowWriter;
+use storage::OVERFLOW_FLAG;
+use storage::OVERFLOW_REF_LEN;
 use protocol::pack;
+use value::Value;
+use error::Error;
 
 /// Serializes and writes values to MemoryPage.
 /// Always writes values in the architertural endian currently.
+///
+/// If the stream has an overflow writer, VARCHAR and VARBINARY values which exceed its
+/// threshold are written to overflow pages, and only their length and id of the first
+/// overflow page are written to the stream.
 #[derive(Debug)]
 pub struct SerializeStream<'a> {
     position: usize,
     page: &'a mut MemoryPage,
+    overflow: Option<&'a OverflowWriter<'a>>,
 }
 
 impl<'a> SerializeStream<'a> {
@@ -15,125 +25,208 @@ impl<'a> SerializeStream<'a> {
     pub fn new(page: &'a mut MemoryPage, pos: usize) -> Self {
         SerializeStream {
             position: pos,
-            page: page,
+            page,
+            overflow: None,
         }
     }
 
-    fn check_available_space(&self, val: isize) -> Result<(), String> {
+    /// Creates new stream, which writes large values to overflow pages.
+    pub fn with_overflow(page: &'a mut MemoryPage,
+                         pos: usize,
+                         overflow: &'a OverflowWriter<'a>)
+                         -> Self {
+        SerializeStream {
+            position: pos,
+            page,
+            overflow: Some(overflow),
+        }
+    }
+
+    /// Get current position of the stream in the page.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    fn check_available_space(&self, val: isize) -> Result<(), Error> {
         let avail: isize = self.page.data().len() as isize - self.position as isize;
 
-        if avail < val as isize {
-            Err(format!("Not enough memory to serialize value: required={}, available={}",
-                        val,
-                        avail))
+        if avail < val {
+            Err(Error::OutOfSpace(format!("Not enough memory to serialize value: required={}, \
+                                           available={}",
+                                          val,
+                                          avail)))
         } else {
             Ok(())
         }
     }
 
     /// Check if we have enough memory in page to write a static typed value.
-    fn check_static_type_len(&self, field_type: DataType) -> Result<(), String> {
+    fn check_static_type_len(&self, field_type: DataType) -> Result<(), Error> {
         let field_len: isize = field_type.static_len() as isize;
 
         match self.check_available_space(field_len) {
-            Err(e) => Err(format!("Unable to write {:?}: {:?}", field_type, e)),
+            Err(e) => Err(Error::OutOfSpace(format!("Unable to write {:?}: {}", field_type, e))),
             Ok(_) => Ok(())
         }
     }
 
     /// Check if we have enough memory in page to write a dynamic typed value.
-    fn check_dynamic_type_len(&self, field_type: DataType, size: usize) -> Result<(), String> {
+    fn check_dynamic_type_len(&self, field_type: DataType, size: usize) -> Result<(), Error> {
         let field_len: isize = field_type.static_len() as isize;
 
         match self.check_available_space(field_len + size as isize) {
-            Err(e) => return Err(format!("Unable to write {:?}: {:?}", field_type, e)),
+            Err(e) => Err(Error::OutOfSpace(format!("Unable to write {:?}: {}", field_type, e))),
             Ok(_) => Ok(())
         }
     }
 
     /// Write INTEGER to stream.
-    pub fn write_int(&mut self, val: i32) -> Result<(), String> {
+    pub fn write_int(&mut self, val: i32) -> Result<(), Error> {
         self.check_static_type_len(DataType::INTEGER)?;
 
         let mem = self.page.data_mut();
         let len = mem.len();
 
-        self.position += pack::pack_int(&mut mem[self.position..len], val);
+        self.position += pack::pack_int(&mut mem[self.position..len], val)?;
 
         Ok(())
     }
 
     /// Write SMALLINT to stream.
-    pub fn write_smallint(&mut self, val: i16) -> Result<(), String> {
+    pub fn write_smallint(&mut self, val: i16) -> Result<(), Error> {
         self.check_static_type_len(DataType::SMALLINT)?;
 
         let mem = self.page.data_mut();
         let len = mem.len();
 
-        self.position += pack::pack_smallint(&mut mem[self.position..len], val);
+        self.position += pack::pack_smallint(&mut mem[self.position..len], val)?;
 
         Ok(())
     }
 
     /// Write BIGINT to stream.
-    pub fn write_bigint(&mut self, val: i64) -> Result<(), String> {
+    pub fn write_bigint(&mut self, val: i64) -> Result<(), Error> {
         self.check_static_type_len(DataType::BIGINT)?;
 
         let mem = self.page.data_mut();
         let len = mem.len();
 
-        self.position += pack::pack_bigint(&mut mem[self.position..len], val);
+        self.position += pack::pack_bigint(&mut mem[self.position..len], val)?;
 
         Ok(())
     }
 
     /// Write BOOLEAN to stream.
-    pub fn write_bool(&mut self, val: bool) -> Result<(), String> {
+    pub fn write_bool(&mut self, val: bool) -> Result<(), Error> {
         self.check_static_type_len(DataType::BOOLEAN)?;
 
         let mem = self.page.data_mut();
         let len = mem.len();
 
-        self.position += pack::pack_bool(&mut mem[self.position..len], val);
+        self.position += pack::pack_bool(&mut mem[self.position..len], val)?;
 
         Ok(())
     }
 
     /// Write VARCHAR to stream.
-    pub fn write_float(&mut self, val: f64) -> Result<(), String> {
+    pub fn write_float(&mut self, val: f64) -> Result<(), Error> {
         self.check_static_type_len(DataType::FLOAT)?;
 
         let mem = self.page.data_mut();
         let len = mem.len();
 
-        self.position += pack::pack_float(&mut mem[self.position..len], val);
+        self.position += pack::pack_float(&mut mem[self.position..len], val)?;
 
         Ok(())
     }
 
+    /// Write length of the value and id of its first overflow page to stream,
+    /// if the value has to be stored in overflow pages. Returns false otherwise.
+    fn write_overflow(&mut self, field_type: DataType, val: &[u8]) -> Result<bool, Error> {
+        let overflow = match self.overflow {
+            Some(overflow) if overflow.exceeds(field_type.static_len() + val.len()) => overflow,
+            _ => return Ok(false),
+        };
+
+        if val.len() as u32 & OVERFLOW_FLAG != 0 {
+            return Err(Error::OutOfSpace(format!("Unable to write {:?}: value of size {} is too \
+                                                  large",
+                                                 field_type,
+                                                 val.len())));
+        }
+
+        if let Err(e) = self.check_available_space(OVERFLOW_REF_LEN as isize) {
+            return Err(Error::OutOfSpace(format!("Unable to write {:?}: {}", field_type, e)));
+        }
+
+        let first = overflow.write(val)?;
+
+        let mem = self.page.data_mut();
+        let len = mem.len();
+
+        self.position += pack::pack_unsigned(&mut mem[self.position..len],
+                                             val.len() as u32 | OVERFLOW_FLAG)?;
+        self.position += pack::pack_unsigned(&mut mem[self.position..len], first)?;
+
+        Ok(true)
+    }
+
     /// Write VARCHAR to stream.
-    pub fn write_varchar(&mut self, val: &str) -> Result<(), String> {
+    pub fn write_varchar(&mut self, val: &str) -> Result<(), Error> {
+        if self.write_overflow(DataType::VARCHAR, val.as_bytes())? {
+            return Ok(());
+        }
+
         self.check_dynamic_type_len(DataType::VARCHAR, val.len())?;
 
         let mem = self.page.data_mut();
         let len = mem.len();
 
-        self.position += pack::pack_string(&mut mem[self.position..len], val);
+        self.position += pack::pack_string(&mut mem[self.position..len], val)?;
 
         Ok(())
     }
 
     /// Write VARBINARY to stream.
-    pub fn write_varbinary(&mut self, val: &[u8]) -> Result<(), String> {
+    pub fn write_varbinary(&mut self, val: &[u8]) -> Result<(), Error> {
+        if self.write_overflow(DataType::VARBINARY, val)? {
+            return Ok(());
+        }
+
         self.check_dynamic_type_len(DataType::VARBINARY, val.len())?;
 
         let mem = self.page.data_mut();
         let len = mem.len();
 
-        self.position += pack::pack_array(&mut mem[self.position..len], val);
+        self.position += pack::pack_array(&mut mem[self.position..len], val)?;
+
+        Ok(())
+    }
+
+    /// Write raw bytes to stream without any length information.
+    pub fn write_raw(&mut self, val: &[u8]) -> Result<(), Error> {
+        self.check_available_space(val.len() as isize)?;
+
+        let end = self.position + val.len();
+        self.page.data_mut()[self.position..end].copy_from_slice(val);
+        self.position = end;
 
         Ok(())
     }
+
+    /// Write value of any type to stream. NULL values can not be written.
+    pub fn write_value(&mut self, val: &Value) -> Result<(), Error> {
+        match *val {
+            Value::Null => Err(Error::InvalidArgument("Unable to write NULL value".to_owned())),
+            Value::Varchar(ref s) => self.write_varchar(s),
+            Value::Varbinary(ref b) => self.write_varbinary(b),
+            Value::Boolean(b) => self.write_bool(b),
+            Value::Smallint(v) => self.write_smallint(v),
+            Value::Integer(v) => self.write_int(v),
+            Value::Bigint(v) => self.write_bigint(v),
+            Value::Float(v) => self.write_float(v),
+        }
+    }
 }
 
 #[test]
@@ -207,7 +300,7 @@ fn write_single_bool() {
 
 #[test]
 fn write_several_bools() {
-    let mut page = MemoryPage::new(3 * 1);
+    let mut page = MemoryPage::new(3);
     let mut stream = SerializeStream::new(&mut page, 0);
 
     stream.write_bool(true).expect("Should not fail");
@@ -301,4 +394,20 @@ fn write_all_types() {
     stream.write_varbinary(&arr).expect("Should not fail");
     stream.write_varchar(test).expect("Should not fail");
     stream.write_varbinary(&[2, 2]).unwrap_err();
+}
+
+#[test]
+fn write_values() {
+    let values = [Value::Integer(7), Value::Varchar("test".to_owned()), Value::Boolean(false)];
+    let len = values.iter().map(|v| v.serialized_len()).sum();
+
+    let mut page = MemoryPage::new(len);
+    let mut stream = SerializeStream::new(&mut page, 0);
+
+    for val in values.iter() {
+        stream.write_value(val).expect("Should not fail");
+    }
+
+    assert_eq!(stream.position(), len);
+    stream.write_value(&Value::Boolean(true)).unwrap_err();
 }
\ No newline at end of file
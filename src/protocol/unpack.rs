@@ -1,79 +1,90 @@
-use std::mem::transmute;
 use std::str;
 
-pub fn unpack_int(data: &[u8]) -> i32 {
-    assert!(data.len() >= 4, "Unable to unpack i32 from provided buffer. Not enough space, available {}", data.len());
+use error::Error;
 
-    (data[0] as i32 & 0xFFi32) |
-    (data[1] as i32 & 0xFFi32) << 8 |
-    (data[2] as i32 & 0xFFi32) << 16 |
-    (data[3] as i32 & 0xFFi32) << 24
+/// Checks that the buffer has enough data for the value.
+fn check_data(data: &[u8], len: usize, what: &str) -> Result<(), Error> {
+    if data.len() < len {
+        return Err(Error::Corruption(format!("Unable to unpack {}: not enough data, \
+                                              required {}, available {}",
+                                             what,
+                                             len,
+                                             data.len())));
+    }
+
+    Ok(())
+}
+
+pub fn unpack_int(data: &[u8]) -> Result<i32, Error> {
+    check_data(data, 4, "i32")?;
+
+    Ok((data[0] as i32 & 0xFFi32) |
+       (data[1] as i32 & 0xFFi32) << 8 |
+       (data[2] as i32 & 0xFFi32) << 16 |
+       (data[3] as i32 & 0xFFi32) << 24)
 }
 
 #[test]
 fn test_unpack_int_success() {
     let array: [u8; 4] = [0x11, 0x22, 0x33, 0x44];
-    let res: i32 = unpack_int(&array);
+    let res: i32 = unpack_int(&array).unwrap();
     assert_eq!(res, 0x44332211);
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_int_fail() {
     let array: [u8; 3] = [0; 3];
-    unpack_int(&array);
+    unpack_int(&array).unwrap_err();
 }
 
-pub fn unpack_smallint(data: &[u8]) -> i16 {
-    assert!(data.len() >= 2, "Unable to unpack i16 from provided buffer. Not enough space");
+pub fn unpack_smallint(data: &[u8]) -> Result<i16, Error> {
+    check_data(data, 2, "i16")?;
 
-    (data[0] as i16 & 0xFFi16) |
-    (data[1] as i16 & 0xFFi16) << 8
+    Ok((data[0] as i16 & 0xFFi16) |
+       (data[1] as i16 & 0xFFi16) << 8)
 }
 
 #[test]
 fn test_unpack_smallint_success() {
     let array: [u8; 2] = [0x11, 0x22];
-    let res: i16 = unpack_smallint(&array);
+    let res: i16 = unpack_smallint(&array).unwrap();
     assert_eq!(res, 0x2211);
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_smallint_fail() {
     let array: [u8; 1] = [0; 1];
-    unpack_smallint(&array);
+    unpack_smallint(&array).unwrap_err();
 }
 
-pub fn unpack_bigint(data: &[u8]) -> i64 {
-    assert!(data.len() >= 8, "Unable to unpack i64 from provided buffer. Not enough space");
+pub fn unpack_bigint(data: &[u8]) -> Result<i64, Error> {
+    check_data(data, 8, "i64")?;
 
-    (data[0] as i64 & 0xFFi64) |
-    (data[1] as i64 & 0xFFi64) << 8 |
-    (data[2] as i64 & 0xFFi64) << 16 |
-    (data[3] as i64 & 0xFFi64) << 24 |
-    (data[4] as i64 & 0xFFi64) << 32 |
-    (data[5] as i64 & 0xFFi64) << 40 |
-    (data[6] as i64 & 0xFFi64) << 48 |
-    (data[7] as i64 & 0xFFi64) << 56
+    Ok((data[0] as i64 & 0xFFi64) |
+       (data[1] as i64 & 0xFFi64) << 8 |
+       (data[2] as i64 & 0xFFi64) << 16 |
+       (data[3] as i64 & 0xFFi64) << 24 |
+       (data[4] as i64 & 0xFFi64) << 32 |
+       (data[5] as i64 & 0xFFi64) << 40 |
+       (data[6] as i64 & 0xFFi64) << 48 |
+       (data[7] as i64 & 0xFFi64) << 56)
 }
 
 #[test]
 fn test_unpack_bigint_success() {
     let array: [u8; 8] = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
-    let res: i64 = unpack_bigint(&array);
+    let res: i64 = unpack_bigint(&array).unwrap();
     assert_eq!(res, 0x8877665544332211u64 as i64);
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_bigint_fail() {
     let array: [u8; 7] = [0; 7];
-    unpack_bigint(&array);
+    unpack_bigint(&array).unwrap_err();
 }
 
-pub fn unpack_float(data: &[u8]) -> f64 {
-    assert!(data.len() >= 8, "Unable to unpack f64 from provided buffer. Not enough space");
+pub fn unpack_float(data: &[u8]) -> Result<f64, Error> {
+    check_data(data, 8, "f64")?;
 
     let tmp: i64 = 
         (data[0] as i64 & 0xFFi64) |
@@ -84,146 +95,138 @@ pub fn unpack_float(data: &[u8]) -> f64 {
         (data[5] as i64 & 0xFFi64) << 40 |
         (data[6] as i64 & 0xFFi64) << 48 |
         (data[7] as i64 & 0xFFi64) << 56;
-    
-    let transumuted = unsafe {
-        transmute::<i64, f64>(tmp)
-    };
 
-    transumuted
+    Ok(f64::from_bits(tmp as u64))
 }
 
 #[test]
 fn test_unpack_float_success() {
     let array: [u8; 8] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE0, 0x3F];
-    let res: f64 = unpack_float(&array);
+    let res: f64 = unpack_float(&array).unwrap();
     assert_eq!(res, 0.5);
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_float_fail() {
     let array: [u8; 7] = [0; 7];
-    unpack_float(&array);
+    unpack_float(&array).unwrap_err();
 }
 
-pub fn unpack_bool(data: &[u8]) -> bool {
-    assert!(data.len() >= 1, "Unable to unpack bool from provided buffer. Not enough space");
+pub fn unpack_bool(data: &[u8]) -> Result<bool, Error> {
+    check_data(data, 1, "bool")?;
 
-    data[0] != 0
+    Ok(data[0] != 0)
 }
 
 #[test]
 fn test_unpack_bool_success_1() {
     let array: [u8; 1] = [0x01];
-    let res: bool = unpack_bool(&array);
-    assert_eq!(res, true);
+    let res: bool = unpack_bool(&array).unwrap();
+    assert!(res);
 }
 
 #[test]
 fn test_unpack_bool_success_2() {
     let array: [u8; 1] = [0x42];
-    let res: bool = unpack_bool(&array);
-    assert_eq!(res, true);
+    let res: bool = unpack_bool(&array).unwrap();
+    assert!(res);
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_bool_fail() {
     let array: [u8; 0] = [0; 0];
-    unpack_bool(&array);
+    unpack_bool(&array).unwrap_err();
 }
 
-pub fn unpack_unsigned(data: &[u8]) -> u32 {
-    assert!(data.len() >= 4, "Unable to unpack u32 from provided buffer. Not enough space, available {}", data.len());
+pub fn unpack_unsigned(data: &[u8]) -> Result<u32, Error> {
+    check_data(data, 4, "u32")?;
 
-    (data[0] as u32 & 0xFFu32) |
-    (data[1] as u32 & 0xFFu32) << 8 |
-    (data[2] as u32 & 0xFFu32) << 16 |
-    (data[3] as u32 & 0xFFu32) << 24
+    Ok((data[0] as u32 & 0xFFu32) |
+       (data[1] as u32 & 0xFFu32) << 8 |
+       (data[2] as u32 & 0xFFu32) << 16 |
+       (data[3] as u32 & 0xFFu32) << 24)
 }
 
 #[test]
 fn test_unpack_unsigned_success() {
     let array: [u8; 4] = [0x11, 0x22, 0x33, 0x44];
-    let res: u32 = unpack_unsigned(&array);
+    let res: u32 = unpack_unsigned(&array).unwrap();
     assert_eq!(res, 0x44332211);
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_unsigned_fail() {
     let array: [u8; 3] = [0; 3];
-    unpack_unsigned(&array);
+    unpack_unsigned(&array).unwrap_err();
 }
 
-pub fn unpack_string(data: &[u8]) -> &str {
-    assert!(data.len() >= 4, "Unable to unpack string length from provided buffer. Not enough space");
+pub fn unpack_string(data: &[u8]) -> Result<&str, Error> {
+    let len = unpack_unsigned(data)? as usize;
 
-    let len = unpack_unsigned(data) as usize;
+    check_data(&data[4..], len, "string")?;
 
-    assert!(data.len() - 4 >= len, "Unable to unpack string(with length: {}) from provided buffer. Not enough space", len);
-
-    str::from_utf8(&data[4..4 + len]).expect("Invalid UTF-8 sequence")
+    str::from_utf8(&data[4..4 + len])
+        .map_err(|e| Error::Corruption(format!("Unable to unpack string: {}", e)))
 }
 
 #[test]
 fn test_unpack_string_success() {
     let array: [u8; 8] = [4, 0, 0, 0, 240, 159, 146, 150];
-    let res = unpack_string(&array);
+    let res = unpack_string(&array).unwrap();
     assert_eq!(res, "💖");
 }
 
 #[test]
 fn test_unpack_string_success_ascii() {
-    let array: [u8; 8] = [4, 0, 0, 0, 't' as u8, 'e' as u8, 's' as u8, 't' as u8];
-    let res = unpack_string(&array);
+    let array: [u8; 8] = [4, 0, 0, 0, b't', b'e', b's', b't'];
+    let res = unpack_string(&array).unwrap();
     assert_eq!(res, "test");
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_string_fail_on_length() {
     let array: [u8; 3] = [0; 3];
-    unpack_string(&array);
+    unpack_string(&array).unwrap_err();
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_string_fail_on_string() {
     let array: [u8; 7] = [4, 0, 0, 0, 0, 0, 0];
-    unpack_string(&array);
+    unpack_string(&array).unwrap_err();
 }
 
-pub fn unpack_array(data: &[u8]) -> &[u8] {
-    assert!(data.len() >= 4, "Unable to unpack array size from provided buffer. Not enough space");
+#[test]
+fn test_unpack_string_fail_on_utf8() {
+    let array: [u8; 6] = [2, 0, 0, 0, 0xFF, 0xFE];
+    unpack_string(&array).unwrap_err();
+}
 
-    let len = unpack_unsigned(data) as usize;
+pub fn unpack_array(data: &[u8]) -> Result<&[u8], Error> {
+    let len = unpack_unsigned(data)? as usize;
 
-    assert!(data.len() - 4 >= len, "Unable to unpack array(with size: {}) from provided buffer. Not enough space", len);
+    check_data(&data[4..], len, "array")?;
 
-    &data[4..4 + len]
+    Ok(&data[4..4 + len])
 }
 
 #[test]
 fn test_unpack_array_success() {
     let array: [u8; 8] = [4, 0, 0, 0, 11, 22, 33, 44];
-    let res: &[u8] = unpack_array(&array);
+    let res: &[u8] = unpack_array(&array).unwrap();
 
     assert_eq!(&array[4..], res);
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_array_fail_on_size() {
     let array: [u8; 3] = [0, 0, 0];
 
-    unpack_array(&array);
+    unpack_array(&array).unwrap_err();
 }
 
 #[test]
-#[should_panic]
 fn test_unpack_array_fail_on_array() {
     let array: [u8; 7] = [4, 0, 0, 0, 0, 0, 0];
 
-    unpack_array(&array);
+    unpack_array(&array).unwrap_err();
 }
\ No newline at end of file
@@ -0,0 +1,163 @@
+use std::ops::Index;
+use std::slice;
+
+use value::Value;
+
+/// Row of a table: ordered list of values with the names of their columns.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Row {
+    columns: Vec<String>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// Creates new empty row.
+    pub fn new() -> Row {
+        Row {
+            columns: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Sets value of the column and returns the row. Useful to build rows in place.
+    pub fn with<V: Into<Value>>(mut self, column: &str, value: V) -> Row {
+        self.set(column, value);
+        self
+    }
+
+    /// Sets value of the column. Replaces previous value if the column is already present.
+    pub fn set<V: Into<Value>>(&mut self, column: &str, value: V) {
+        let value = value.into();
+
+        match self.position(column) {
+            Some(pos) => self.values[pos] = value,
+            None => {
+                self.columns.push(column.to_owned());
+                self.values.push(value);
+            }
+        }
+    }
+
+    /// Gets position of the column in the row.
+    pub fn position(&self, column: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c == column)
+    }
+
+    /// Gets value of the column by its name.
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        self.position(column).map(|pos| &self.values[pos])
+    }
+
+    /// Gets value of the column by its position.
+    pub fn get_at(&self, pos: usize) -> Option<&Value> {
+        self.values.get(pos)
+    }
+
+    /// Gets number of values in the row.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check if the row does not have any values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Gets names of the columns in the row order.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Gets values in the row order.
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Iterates over column names and values in the row order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            columns: self.columns.iter(),
+            values: self.values.iter(),
+        }
+    }
+}
+
+impl Index<usize> for Row {
+    type Output = Value;
+
+    fn index(&self, pos: usize) -> &Value {
+        &self.values[pos]
+    }
+}
+
+impl Index<&str> for Row {
+    type Output = Value;
+
+    fn index(&self, column: &str) -> &Value {
+        match self.get(column) {
+            Some(value) => value,
+            None => panic!("Row does not have column '{}'", column),
+        }
+    }
+}
+
+/// Iterator over column names and values of a row.
+#[derive(Debug)]
+pub struct Iter<'a> {
+    columns: slice::Iter<'a, String>,
+    values: slice::Iter<'a, Value>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match (self.columns.next(), self.values.next()) {
+            (Some(column), Some(value)) => Some((column, value)),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Row {
+    type Item = (&'a str, &'a Value);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use row::Row;
+    use value::Value;
+
+    #[test]
+    fn build_row() {
+        let mut row = Row::new().with("foo", 1).with("bar", "text").with("baz", None::<i64>);
+
+        assert_eq!(row.len(), 3);
+        assert_eq!(row["foo"], Value::Integer(1));
+        assert_eq!(row[1], Value::Varchar("text".to_owned()));
+        assert_eq!(row.get("baz"), Some(&Value::Null));
+        assert_eq!(row.get("qux"), None);
+        assert_eq!(row.get_at(3), None);
+
+        row.set("foo", 2i64);
+
+        assert_eq!(row.len(), 3);
+        assert_eq!(row.get_at(0), Some(&Value::Bigint(2)));
+        assert_eq!(row.columns(), &["foo", "bar", "baz"]);
+
+        let names: Vec<_> = row.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["foo", "bar", "baz"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_missing_column() {
+        let row = Row::new().with("foo", 1);
+        let _ = &row["bar"];
+    }
+}
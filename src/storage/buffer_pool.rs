@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use storage::MemoryPage;
+use storage::PageFile;
+use error::Error;
+
+/// Default number of pages cached by the buffer pool of a file-backed storage.
+pub const DEFAULT_BUFFER_POOL_SIZE: usize = 1024;
+
+/// Cached page.
+#[derive(Debug)]
+struct Frame {
+    page_id: u32,
+    page: Mutex<MemoryPage>,
+    pins: AtomicUsize,
+    dirty: AtomicBool,
+    referenced: AtomicBool,
+}
+
+impl Frame {
+    fn new(page_id: u32, page: MemoryPage, dirty: bool) -> Frame {
+        Frame {
+            page_id,
+            page: Mutex::new(page),
+            pins: AtomicUsize::new(0),
+            dirty: AtomicBool::new(dirty),
+            referenced: AtomicBool::new(true),
+        }
+    }
+}
+
+/// Handle of the page in the buffer pool. Page is pinned while there is a handle to it,
+/// so it can not be evicted.
+#[derive(Debug)]
+pub struct PageHandle {
+    frame: Arc<Frame>,
+}
+
+impl PageHandle {
+    fn pin(frame: Arc<Frame>) -> PageHandle {
+        frame.pins.fetch_add(1, Ordering::SeqCst);
+        frame.referenced.store(true, Ordering::SeqCst);
+
+        PageHandle { frame }
+    }
+
+    /// Creates handle of the page which does not belong to any buffer pool.
+    pub fn detached(page: MemoryPage) -> PageHandle {
+        PageHandle::pin(Arc::new(Frame::new(0, page, false)))
+    }
+
+    /// Gets id of the page.
+    pub fn page_id(&self) -> u32 {
+        self.frame.page_id
+    }
+
+    /// Locks the page. Page is marked as dirty once it is borrowed mutably.
+    pub fn lock(&self) -> PageGuard<'_> {
+        PageGuard {
+            frame: &self.frame,
+            page: self.frame.page.lock().unwrap(),
+        }
+    }
+
+    /// Check if the page was changed since it was written to the file last time.
+    pub fn is_dirty(&self) -> bool {
+        self.frame.dirty.load(Ordering::SeqCst)
+    }
+}
+
+impl Clone for PageHandle {
+    fn clone(&self) -> PageHandle {
+        PageHandle::pin(self.frame.clone())
+    }
+}
+
+impl Drop for PageHandle {
+    fn drop(&mut self) {
+        self.frame.pins.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Locked page.
+#[derive(Debug)]
+pub struct PageGuard<'a> {
+    frame: &'a Frame,
+    page: MutexGuard<'a, MemoryPage>,
+}
+
+impl<'a> PageGuard<'a> {
+    /// Gets id of the page.
+    pub fn page_id(&self) -> u32 {
+        self.frame.page_id
+    }
+
+    /// Marks page as written to the file.
+    fn clear_dirty(&self) {
+        self.frame.dirty.store(false, Ordering::SeqCst);
+    }
+}
+
+impl<'a> Deref for PageGuard<'a> {
+    type Target = MemoryPage;
+
+    fn deref(&self) -> &MemoryPage {
+        &self.page
+    }
+}
+
+impl<'a> DerefMut for PageGuard<'a> {
+    fn deref_mut(&mut self) -> &mut MemoryPage {
+        self.frame.dirty.store(true, Ordering::SeqCst);
+        &mut self.page
+    }
+}
+
+/// Statistics of the buffer pool.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Number of requests served from the cache.
+    pub hits: u64,
+    /// Number of requests which read the page from the file.
+    pub misses: u64,
+    /// Number of pages evicted from the cache.
+    pub evictions: u64,
+}
+
+/// Buffer pool, which caches a bounded number of pages of the file.
+///
+/// Pages are evicted with the clock policy: the clock hand sweeps over the cached pages, skipping
+/// pinned ones and clearing the reference bit of the recently used ones, until it finds a page
+/// which was not used since the last sweep. Dirty pages are written to the file when evicted.
+///
+/// Buffer pool without capacity never evicts pages and is used by the in-memory storage.
+#[derive(Debug)]
+pub struct BufferPool {
+    page_size: usize,
+    capacity: Option<usize>,
+    frames: Vec<Arc<Frame>>,
+    page_table: HashMap<u32, usize>,
+    hand: usize,
+    stats: BufferPoolStats,
+}
+
+impl BufferPool {
+    /// Creates new buffer pool. Pool without capacity is never evicted.
+    pub fn new(page_size: usize, capacity: Option<usize>) -> BufferPool {
+        assert!(capacity != Some(0), "Buffer pool capacity must be positive");
+
+        BufferPool {
+            page_size,
+            capacity,
+            frames: Vec::new(),
+            page_table: HashMap::new(),
+            hand: 0,
+            stats: BufferPoolStats::default(),
+        }
+    }
+
+    /// Gets number of cached pages.
+    pub fn len(&self) -> usize {
+        self.page_table.len()
+    }
+
+    /// Check if there are no cached pages.
+    pub fn is_empty(&self) -> bool {
+        self.page_table.is_empty()
+    }
+
+    /// Gets statistics of the buffer pool.
+    pub fn stats(&self) -> BufferPoolStats {
+        self.stats
+    }
+
+    /// Gets cached page.
+    pub fn get(&mut self, page_id: u32) -> Option<PageHandle> {
+        let index = *self.page_table.get(&page_id)?;
+        self.stats.hits += 1;
+
+        Some(PageHandle::pin(self.frames[index].clone()))
+    }
+
+    /// Caches page which was read from the file. The page is not cached yet and is clean.
+    /// File is used to write back the evicted page.
+    pub fn load(&mut self, page_id: u32, page: MemoryPage, file: &mut PageFile)
+                -> Result<PageHandle, Error> {
+        assert_eq!(page.data().len(), self.page_size);
+        assert!(!self.page_table.contains_key(&page_id), "Page {} is cached", page_id);
+
+        self.stats.misses += 1;
+
+        self.install(page_id, page, false, Some(file))
+    }
+
+    /// Replaces content of the page with the new one. The page is marked as dirty.
+    /// Handles of the previous content of the page stay valid, but are not cached anymore.
+    pub fn put(&mut self,
+               page_id: u32,
+               page: MemoryPage,
+               file: Option<&mut PageFile>)
+               -> Result<PageHandle, Error> {
+        assert_eq!(page.data().len(), self.page_size);
+
+        if let Some(&index) = self.page_table.get(&page_id) {
+            let frame = Arc::new(Frame::new(page_id, page, true));
+            self.frames[index] = frame.clone();
+
+            return Ok(PageHandle::pin(frame));
+        }
+
+        self.install(page_id, page, true, file)
+    }
+
+    /// Caches the page which is not cached yet.
+    fn install(&mut self,
+               page_id: u32,
+               page: MemoryPage,
+               dirty: bool,
+               file: Option<&mut PageFile>)
+               -> Result<PageHandle, Error> {
+        let frame = Arc::new(Frame::new(page_id, page, dirty));
+
+        let index = match self.capacity {
+            Some(capacity) if self.frames.len() >= capacity => {
+                let index = self.evict(file)?;
+                self.frames[index] = frame.clone();
+
+                index
+            }
+            _ => {
+                self.frames.push(frame.clone());
+                self.frames.len() - 1
+            }
+        };
+
+        self.page_table.insert(page_id, index);
+
+        Ok(PageHandle::pin(frame))
+    }
+
+    /// Evicts page which is neither pinned nor recently used and returns index of its frame.
+    fn evict(&mut self, file: Option<&mut PageFile>) -> Result<usize, Error> {
+        // Reference bits are cleared during the first sweep, so two sweeps are enough to find
+        // a page which is not pinned.
+        for _ in 0..2 * self.frames.len() {
+            let index = self.hand;
+            self.hand = (self.hand + 1) % self.frames.len();
+
+            let frame = &self.frames[index];
+
+            if frame.pins.load(Ordering::SeqCst) > 0 {
+                continue;
+            }
+
+            if frame.referenced.swap(false, Ordering::SeqCst) {
+                continue;
+            }
+
+            if frame.dirty.load(Ordering::SeqCst) {
+                // Page is not pinned, so nobody holds its lock.
+                let page = frame.page.lock().unwrap();
+
+                match file {
+                    Some(file) => file.write_page(frame.page_id, &page)?,
+                    None => {
+                        return Err(Error::OutOfSpace("Unable to evict dirty page: no file"
+                            .to_owned()))
+                    }
+                }
+            }
+
+            self.page_table.remove(&frame.page_id);
+            self.stats.evictions += 1;
+
+            return Ok(index);
+        }
+
+        Err(Error::OutOfSpace(format!("Unable to evict page from the buffer pool: all {} pages \
+                                       are pinned",
+                                      self.frames.len())))
+    }
+
+    /// Gets handles of all dirty cached pages.
+    pub fn dirty_pages(&self) -> Vec<PageHandle> {
+        let mut pages: Vec<_> = self.page_table
+            .values()
+            .map(|&index| &self.frames[index])
+            .filter(|frame| frame.dirty.load(Ordering::SeqCst))
+            .map(|frame| PageHandle::pin(frame.clone()))
+            .collect();
+
+        pages.sort_by_key(PageHandle::page_id);
+        pages
+    }
+}
+
+/// Writes the locked page to the file if it is dirty and marks it as clean.
+pub fn write_back(page: &PageGuard, file: &mut PageFile) -> Result<(), Error> {
+    if page.frame.dirty.load(Ordering::SeqCst) {
+        file.write_page(page.frame.page_id, page)?;
+        page.clear_dirty();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use storage::MemoryPage;
+    use storage::PageFile;
+    use storage::FileHeader;
+    use storage::buffer_pool::{BufferPool, PageHandle, BufferPoolStats, write_back};
+    use test_utils::temp_path;
+
+    fn page(value: u8) -> MemoryPage {
+        let mut page = MemoryPage::new(256);
+        page.data_mut()[100] = value;
+        page
+    }
+
+    #[test]
+    fn cache_pages_in_memory() {
+        let mut pool = BufferPool::new(256, None);
+
+        for id in 1..100 {
+            pool.put(id, page(id as u8), None).unwrap();
+        }
+
+        assert_eq!(pool.len(), 99);
+        assert_eq!(pool.get(42).unwrap().lock().data()[100], 42);
+        assert!(pool.get(100).is_none());
+
+        assert_eq!(pool.stats(),
+                   BufferPoolStats {
+                       hits: 1,
+                       misses: 0,
+                       evictions: 0,
+                   });
+    }
+
+    #[test]
+    fn evict_pages() {
+        let path = temp_path("evict_pages");
+        let mut file = PageFile::create(&path, &FileHeader::new(256)).unwrap();
+
+        let mut pool = BufferPool::new(256, Some(4));
+
+        for id in 1..11 {
+            pool.put(id, page(id as u8), Some(&mut file)).unwrap();
+        }
+
+        assert_eq!(pool.len(), 4);
+        assert_eq!(pool.stats().evictions, 6);
+
+        // Evicted dirty pages are written to the file and read back on demand.
+        for id in (1..11).rev() {
+            let handle = match pool.get(id) {
+                Some(handle) => handle,
+                None => {
+                    let mut page = MemoryPage::new(256);
+                    file.read_page(id, &mut page).unwrap();
+                    pool.load(id, page, &mut file).unwrap()
+                }
+            };
+
+            assert_eq!(handle.lock().data()[100], id as u8);
+        }
+
+        assert_eq!(pool.stats().misses, 6);
+        assert_eq!(pool.stats().hits, 4);
+
+        drop(file);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn pinned_pages_are_not_evicted() {
+        let path = temp_path("pinned_pages_are_not_evicted");
+        let mut file = PageFile::create(&path, &FileHeader::new(256)).unwrap();
+
+        let mut pool = BufferPool::new(256, Some(2));
+
+        let first = pool.put(1, page(1), Some(&mut file)).unwrap();
+        let second = pool.put(2, page(2), Some(&mut file)).unwrap().clone();
+
+        pool.put(3, page(3), Some(&mut file)).unwrap_err();
+
+        drop(second);
+        pool.put(3, page(3), Some(&mut file)).unwrap();
+
+        // The first page is still cached.
+        pool.get(1).unwrap();
+        assert_eq!(first.lock().data()[100], 1);
+
+        drop(file);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn track_dirty_pages() {
+        let path = temp_path("track_dirty_pages");
+        let mut file = PageFile::create(&path, &FileHeader::new(256)).unwrap();
+
+        let mut pool = BufferPool::new(256, Some(4));
+
+        for id in 1..4 {
+            let handle = pool.put(id, page(id as u8), Some(&mut file)).unwrap();
+            write_back(&handle.lock(), &mut file).unwrap();
+        }
+
+        assert!(pool.dirty_pages().is_empty());
+
+        // Reading the page does not make it dirty.
+        let handle = pool.get(2).unwrap();
+        assert_eq!(handle.lock().data()[100], 2);
+        assert!(!handle.is_dirty());
+
+        handle.lock().data_mut()[100] = 20;
+        assert!(handle.is_dirty());
+
+        let dirty = pool.dirty_pages();
+        assert_eq!(dirty.iter().map(PageHandle::page_id).collect::<Vec<_>>(), vec![2]);
+
+        write_back(&dirty[0].lock(), &mut file).unwrap();
+        assert!(pool.dirty_pages().is_empty());
+
+        let mut read = MemoryPage::new(256);
+        file.read_page(2, &mut read).unwrap();
+        assert_eq!(read.data()[100], 20);
+
+        drop(file);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn detached_page() {
+        let handle = PageHandle::detached(page(7));
+
+        assert_eq!(handle.clone().lock().data()[100], 7);
+        assert!(!handle.is_dirty());
+    }
+}
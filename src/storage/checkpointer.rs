@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use storage::Storage;
+use error::Error;
+
+/// Background thread, which periodically writes checkpoints of the storage.
+/// The thread is stopped when the checkpointer is dropped.
+///
+/// Checkpoint which failed with an I/O error, e.g. because the disk is full, is retried after
+/// the interval, the log keeps all the changes meanwhile. Any other failure, e.g. a corrupted
+/// page, can not be fixed by retrying, so the thread stops. Error of the last failed checkpoint
+/// is kept until a checkpoint succeeds, see `last_error` and `stop`.
+#[derive(Debug)]
+pub struct Checkpointer {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    error: Arc<Mutex<Option<Error>>>,
+}
+
+impl Checkpointer {
+    /// Starts thread, which writes a checkpoint of the storage every `interval`.
+    pub fn start(storage: Arc<Storage>, interval: Duration) -> Result<Checkpointer, Error> {
+        Checkpointer::spawn(interval, move || storage.checkpoint())
+    }
+
+    /// Starts thread, which calls `checkpoint` every `interval`.
+    fn spawn<F>(interval: Duration, mut checkpoint: F) -> Result<Checkpointer, Error>
+        where F: FnMut() -> Result<(), Error> + Send + 'static
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let error = Arc::new(Mutex::new(None));
+        let last_error = error.clone();
+
+        let thread = thread::Builder::new()
+            .name("reddb-checkpointer".to_owned())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    let res = checkpoint();
+                    let retry = match res {
+                        Ok(()) | Err(Error::Io { .. }) => true,
+                        Err(_) => false,
+                    };
+
+                    *last_error.lock().unwrap() = res.err();
+
+                    if !retry {
+                        break;
+                    }
+                }
+            })
+            .map_err(|e| Error::io("Unable to start checkpointer thread".to_owned(), e))?;
+
+        Ok(Checkpointer {
+            stop: Some(stop),
+            thread: Some(thread),
+            error,
+        })
+    }
+
+    /// Gets description of the error of the last failed checkpoint, `None` if the last
+    /// checkpoint has succeeded.
+    pub fn last_error(&self) -> Option<String> {
+        self.error.lock().unwrap().as_ref().map(|e| e.to_string())
+    }
+
+    /// Stops the thread. Returns error of the last failed checkpoint, if any.
+    pub fn stop(mut self) -> Result<(), Error> {
+        self.join();
+
+        match self.error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn join(&mut self) {
+        // Thread stops as soon as the channel is disconnected.
+        drop(self.stop.take());
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Checkpointer {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use storage::Storage;
+    use storage::PageType;
+    use storage::CorruptionPolicy;
+    use storage::wal_path;
+    use storage::checkpointer::Checkpointer;
+    use test_utils::{temp_path, remove_database};
+    use error::Error;
+
+    #[test]
+    fn write_checkpoints_periodically() {
+        let path = temp_path("write_checkpoints_periodically");
+        let storage = Arc::new(Storage::create(&path, 512, 16, CorruptionPolicy::Fail).unwrap());
+
+        let txn = storage.begin();
+        txn.allocate_page(PageType::Data, 1).unwrap();
+        txn.commit().unwrap();
+
+        assert!(fs::metadata(wal_path(&path)).unwrap().len() > 16);
+
+        let checkpointer = Checkpointer::start(storage.clone(), Duration::from_millis(10))
+            .unwrap();
+
+        let started = Instant::now();
+
+        while storage.checkpoint_lsn() == 0 {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        drop(checkpointer);
+
+        assert_eq!(fs::metadata(wal_path(&path)).unwrap().len(), 16);
+
+        drop(storage);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn report_failed_checkpoints() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+
+        // I/O error is retried, corruption stops the thread.
+        let checkpointer = Checkpointer::spawn(Duration::from_millis(5), move || {
+                match counter.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(Error::io("disk is full".to_owned(), io::Error::other("full"))),
+                    1 => Ok(()),
+                    _ => Err(Error::Corruption("page is corrupted".to_owned())),
+                }
+            })
+            .unwrap();
+
+        let started = Instant::now();
+
+        while checkpointer.last_error() != Some("page is corrupted".to_owned()) {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        match checkpointer.stop() {
+            Err(Error::Corruption(msg)) => assert_eq!(msg, "page is corrupted"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::fmt;
+
+use storage::MemoryPage;
+use storage::memory_page::CHECKSUM_OFFSET;
+use storage::file_header::{HEADER_LEN, HEADER_CHECKSUM_OFFSET, HEADER_PAGE};
+use protocol::unpack;
+
+/// Reversed CRC-32C (Castagnoli) polynomial.
+const POLYNOMIAL: u32 = 0x82F6_3B78;
+
+/// Lookup table for the byte-wise CRC-32C computation.
+const TABLE: [u32; 256] = make_table();
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+/// Continues CRC-32C checksum of the data, which starts with the bytes checksummed as `crc`.
+pub fn crc32c_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+
+    for &b in data {
+        crc = TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+
+    !crc
+}
+
+/// Computes CRC-32C checksum of the data.
+pub fn crc32c(data: &[u8]) -> u32 {
+    crc32c_update(0, data)
+}
+
+/// Computes checksum of the page data. The checksum field of the page header is skipped.
+pub fn page_checksum(data: &[u8]) -> u32 {
+    let crc = crc32c(&data[..CHECKSUM_OFFSET]);
+    crc32c_update(crc, &data[CHECKSUM_OFFSET + 4..])
+}
+
+/// Verifies checksum of the page read from the database file.
+/// Page which was never written to the file is zeroed, so it has no checksum. Zeroed page is
+/// valid only if `never_written` is set: its id is past the pages recorded in the file header.
+/// Otherwise the page was zeroed by a corruption.
+pub fn verify_page(page_id: u32, page: &MemoryPage, never_written: bool)
+                   -> Result<(), CorruptPage> {
+    let expected = page.checksum();
+    let actual = page_checksum(page.data());
+
+    if expected == actual ||
+       (never_written && expected == 0 && page.data().iter().all(|&b| b == 0)) {
+        return Ok(());
+    }
+
+    Err(CorruptPage {
+        page_id,
+        expected,
+        actual,
+    })
+}
+
+/// Computes checksum of the serialized file header.
+pub fn header_checksum(data: &[u8]) -> u32 {
+    crc32c(&data[..HEADER_LEN])
+}
+
+/// Verifies checksum of the file header read from the first page of the database file.
+/// Header page is reported as page 0.
+pub fn verify_header(data: &[u8]) -> Result<(), CorruptPage> {
+    let expected = unpack::unpack_unsigned(&data[HEADER_CHECKSUM_OFFSET..]).unwrap_or(0);
+    let actual = header_checksum(data);
+
+    if expected == actual {
+        return Ok(());
+    }
+
+    Err(CorruptPage {
+        page_id: HEADER_PAGE,
+        expected,
+        actual,
+    })
+}
+
+/// Error of the page whose content does not match its checksum, e.g. because of a bit rot
+/// or a torn write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptPage {
+    /// Id of the corrupted page.
+    pub page_id: u32,
+    /// Checksum stored in the page header.
+    pub expected: u32,
+    /// Checksum of the page content.
+    pub actual: u32,
+}
+
+impl fmt::Display for CorruptPage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "Page {} is corrupted: checksum mismatch, expected {:08x}, actual {:08x}",
+               self.page_id,
+               self.expected,
+               self.actual)
+    }
+}
+
+impl Error for CorruptPage {}
+
+/// What to do when a page of the database file is corrupted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum CorruptionPolicy {
+    /// Fail the read of the page.
+    #[default]
+    Fail,
+    /// Quarantine the page: it is excluded from the storage as if it was never allocated and
+    /// is never reused, so the rest of the database stays available.
+    Quarantine,
+}
+
+#[cfg(test)]
+mod test {
+    use storage::MemoryPage;
+    use storage::PageType;
+    use storage::checksum::{crc32c, crc32c_update, page_checksum, verify_page, CorruptPage};
+
+    #[test]
+    fn compute_crc32c() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(&[0; 32]), 0x8A91_36AA);
+        assert_eq!(crc32c_update(crc32c(b"1234"), b"56789"), crc32c(b"123456789"));
+    }
+
+    #[test]
+    fn verify_page_checksum() {
+        let mut page = MemoryPage::new(256);
+
+        // Page which was never written has no checksum.
+        verify_page(1, &page, true).unwrap();
+
+        // Written page which is zeroed is corrupted.
+        verify_page(1, &page, false).unwrap_err();
+
+        page.reset(1, PageType::Data, 2);
+        page.data_mut()[100] = 42;
+        verify_page(1, &page, false).unwrap_err();
+
+        let checksum = page_checksum(page.data());
+        page.set_checksum(checksum);
+        verify_page(1, &page, false).unwrap();
+
+        page.data_mut()[200] ^= 1;
+
+        assert_eq!(verify_page(1, &page, false),
+                   Err(CorruptPage {
+                       page_id: 1,
+                       expected: checksum,
+                       actual: page_checksum(page.data()),
+                   }));
+    }
+}
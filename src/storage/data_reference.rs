@@ -1,51 +1,205 @@
 use std::cmp::Ordering;
-use std::sync::Arc;
 
 use data_type::DataType;
-use storage::MemoryPage;
+use collation::Collation;
+use storage::PageHandle;
 use protocol::deserialize_stream::DeserializeStream;
+use value::Value;
+use error::Error;
 
-/// Reference to indexed data.
+/// Reference to indexed data. Referenced page is pinned in the buffer pool.
+#[derive(Debug)]
 pub struct DataReference {
     data_type: DataType,
-    page: Arc<MemoryPage>,
+    collation: Collation,
+    page: PageHandle,
     pos: usize,
 }
 
 impl DataReference {
-    /// Create new data reference.
-    pub fn new(page: Arc<MemoryPage>, pos: usize, data_type: DataType) -> Self {
+    /// Create new data reference. VARCHAR values are compared with the binary collation.
+    pub fn new(page: PageHandle, pos: usize, data_type: DataType) -> Self {
         DataReference {
-            data_type: data_type,
-            page: page,
-            pos: pos,
+            data_type,
+            collation: Collation::Binary,
+            page,
+            pos,
         }
     }
 
+    /// Set collation used to compare VARCHAR values.
+    pub fn with_collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    /// Gets type of the referenced value.
+    pub fn data_type(&self) -> DataType {
+        self.data_type
+    }
+
     /// Deserializes value as an integer.
     pub fn to_int(&self) -> i32 {
         assert!(self.data_type == DataType::INTEGER);
 
-        let mut rs = DeserializeStream::new(&self.page, self.pos);
+        let page = self.page.lock();
+        let mut rs = DeserializeStream::new(&page, self.pos);
         rs.read_int().unwrap()
     }
+
+    /// Deserializes referenced value.
+    pub fn to_value(&self) -> Result<Value, Error> {
+        let page = self.page.lock();
+        let mut rs = DeserializeStream::new(&page, self.pos);
+        rs.read_value(self.data_type)
+    }
+
+    /// Deserializes referenced value, which was serialized by the database itself.
+    /// Pages are locked one by one, so two references to the same page can be compared.
+    fn value(&self) -> Value {
+        self.to_value().expect("Referenced value is valid")
+    }
+}
+
+/// References are ordered by the type of the values first and by the collation second,
+/// so values of different types are never equal. Values of the same type are ordered
+/// by `Value::total_cmp` with the collation.
+impl Ord for DataReference {
+    fn cmp(&self, other: &DataReference) -> Ordering {
+        let res = self.data_type.cmp(&other.data_type).then(self.collation.cmp(&other.collation));
+
+        if res != Ordering::Equal {
+            return res;
+        }
+
+        self.value().total_cmp(&other.value(), self.collation)
+    }
 }
 
 impl PartialOrd for DataReference {
     fn partial_cmp(&self, other: &DataReference) -> Option<Ordering> {
-        if self.data_type != other.data_type {
-            None
-        } else {
-            match self.data_type {
-                DataType::INTEGER => self.to_int().partial_cmp(&other.to_int()),
-                _ => unimplemented!(),
-            }
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl PartialEq for DataReference {
     fn eq(&self, other: &DataReference) -> bool {
-        self.data_type == other.data_type
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DataReference {}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+    use std::f64;
+
+    use data_type::DataType;
+    use collation::Collation;
+    use value::Value;
+    use storage::MemoryPage;
+    use storage::PageHandle;
+    use storage::data_reference::DataReference;
+    use protocol::serialize_stream::SerializeStream;
+
+    /// Serializes values into a detached page and creates references to them.
+    fn references(values: &[Value]) -> Vec<DataReference> {
+        let mut page = MemoryPage::new(values.iter().map(Value::serialized_len).sum());
+        let mut positions = Vec::new();
+
+        {
+            let mut stream = SerializeStream::new(&mut page, 0);
+
+            for value in values {
+                positions.push(stream.position());
+                stream.write_value(value).unwrap();
+            }
+        }
+
+        let page = PageHandle::detached(page);
+
+        positions.into_iter()
+            .zip(values)
+            .map(|(pos, value)| {
+                DataReference::new(page.clone(), pos, value.data_type().unwrap())
+            })
+            .collect()
+    }
+
+    /// Check that the references are sorted in the strictly ascending order.
+    fn assert_ascending(values: &[Value]) {
+        let refs = references(values);
+
+        for (i, a) in refs.iter().enumerate() {
+            for (j, b) in refs.iter().enumerate() {
+                assert_eq!(a.cmp(b), i.cmp(&j), "{:?} and {:?}", values[i], values[j]);
+            }
+        }
+    }
+
+    #[test]
+    fn compare_integers() {
+        assert_ascending(&[Value::from(i16::MIN), Value::from(-1i16), Value::from(i16::MAX)]);
+        assert_ascending(&[Value::from(i32::MIN), Value::from(0), Value::from(7)]);
+        assert_ascending(&[Value::from(-1i64 << 40), Value::from(1i64), Value::from(i64::MAX)]);
+        assert_ascending(&[Value::from(false), Value::from(true)]);
+    }
+
+    #[test]
+    fn compare_floats() {
+        assert_ascending(&[Value::from(f64::NEG_INFINITY),
+                           Value::from(-1.5),
+                           Value::from(0.0),
+                           Value::from(f64::MIN_POSITIVE),
+                           Value::from(f64::INFINITY),
+                           Value::from(f64::NAN)]);
+
+        let refs = references(&[Value::from(0.0),
+                                Value::from(-0.0),
+                                Value::from(f64::NAN),
+                                Value::from(-f64::NAN)]);
+
+        assert_eq!(refs[0], refs[1]);
+        assert_eq!(refs[2], refs[3]);
+        assert_eq!(refs[2], refs[2]);
+    }
+
+    #[test]
+    fn compare_bytes() {
+        assert_ascending(&[Value::from(""), Value::from("B"), Value::from("a"), Value::from("é")]);
+        assert_ascending(&[Value::from(vec![]),
+                           Value::from(vec![0u8]),
+                           Value::from(vec![0u8, 0]),
+                           Value::from(vec![1u8])]);
+    }
+
+    #[test]
+    fn compare_with_collation() {
+        let refs: Vec<_> = references(&[Value::from("ABC"),
+                                        Value::from("abc"),
+                                        Value::from("abd"),
+                                        Value::from("B")])
+            .into_iter()
+            .map(|r| r.with_collation(Collation::CaseInsensitive))
+            .collect();
+
+        assert_eq!(refs[0], refs[1]);
+        assert!(refs[1] < refs[2]);
+        assert!(refs[2] < refs[3]);
+
+        let binary = references(&[Value::from("ABC")]).pop().unwrap();
+        assert!(binary < refs[0]);
+        assert!(binary.partial_cmp(&refs[0]) == Some(Ordering::Less));
+    }
+
+    #[test]
+    fn compare_types() {
+        let refs = references(&[Value::from("1"), Value::from(1i16), Value::from(1)]);
+
+        assert_eq!(refs[0].data_type(), DataType::VARCHAR);
+        assert!(refs[0] < refs[1]);
+        assert!(refs[1] < refs[2]);
+        assert_ne!(refs[1], refs[2]);
     }
 }
@@ -0,0 +1,156 @@
+use protocol::pack;
+use protocol::unpack;
+use error::Error;
+
+/// Magic number at the beginning of every database file.
+pub const MAGIC: &[u8; 8] = b"REDDBDAT";
+
+/// Version of the database file format.
+pub const FORMAT_VERSION: u32 = 13;
+
+/// Offset of the format version in the header.
+const VERSION_OFFSET: usize = 8;
+
+/// Offset of the page size in the header.
+const PAGE_SIZE_OFFSET: usize = 12;
+
+/// Offset of the number of pages in the header.
+const PAGE_COUNT_OFFSET: usize = 16;
+
+/// Offset of the id of the first catalog page in the header.
+pub const CATALOG_PAGE_OFFSET: usize = 20;
+
+/// Offset of the LSN of the last checkpoint in the header.
+const CHECKPOINT_LSN_OFFSET: usize = 24;
+
+/// Offset of the id of the next table in the header.
+pub const NEXT_TABLE_ID_OFFSET: usize = 32;
+
+/// Length of the serialized header.
+pub const HEADER_LEN: usize = 36;
+
+/// Offset of the checksum of the header in the header page. Like checksums of other pages,
+/// it is written only to the database file, see `PageFile::write_header`.
+pub const HEADER_CHECKSUM_OFFSET: usize = HEADER_LEN;
+
+/// Id of the page which holds the header.
+pub const HEADER_PAGE: u32 = 0;
+
+/// Smallest supported page size.
+pub const MIN_PAGE_SIZE: usize = 256;
+
+/// Header of the database file. Stored at the beginning of the first page of the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileHeader {
+    /// Size of the single page in bytes.
+    pub page_size: usize,
+    /// Number of pages in the file, including the header page.
+    pub page_count: u32,
+    /// Id of the first page of the catalog. Zero if the catalog is empty.
+    pub catalog_page: u32,
+    /// LSN of the last checkpoint. Changes logged before it are already in the file.
+    pub checkpoint_lsn: u64,
+    /// Id of the next table of the catalog. Ids of the dropped tables are never reused. Zero
+    /// if no table was created yet.
+    pub next_table_id: u32,
+}
+
+impl FileHeader {
+    /// Creates header of an empty database file.
+    pub fn new(page_size: usize) -> Self {
+        FileHeader {
+            page_size,
+            page_count: 1,
+            catalog_page: 0,
+            checkpoint_lsn: 0,
+            next_table_id: 0,
+        }
+    }
+
+    /// Reads and validates header.
+    pub fn read(data: &[u8]) -> Result<FileHeader, Error> {
+        if data.len() < HEADER_LEN || &data[..MAGIC.len()] != MAGIC {
+            return Err(Error::Corruption("Not a reddb database file: invalid magic number"
+                .to_owned()));
+        }
+
+        let version = unpack::unpack_unsigned(&data[VERSION_OFFSET..])?;
+
+        if version != FORMAT_VERSION {
+            return Err(Error::Corruption(format!("Unsupported database file format version: {}, \
+                                                  expected {}",
+                                                 version,
+                                                 FORMAT_VERSION)));
+        }
+
+        let page_size = unpack::unpack_unsigned(&data[PAGE_SIZE_OFFSET..])? as usize;
+
+        if page_size < MIN_PAGE_SIZE || !page_size.is_power_of_two() {
+            return Err(Error::Corruption(format!("Invalid page size in database file header: {}",
+                                                 page_size)));
+        }
+
+        Ok(FileHeader {
+            page_size,
+            page_count: unpack::unpack_unsigned(&data[PAGE_COUNT_OFFSET..])?,
+            catalog_page: unpack::unpack_unsigned(&data[CATALOG_PAGE_OFFSET..])?,
+            checkpoint_lsn: unpack::unpack_bigint(&data[CHECKPOINT_LSN_OFFSET..])? as u64,
+            next_table_id: unpack::unpack_unsigned(&data[NEXT_TABLE_ID_OFFSET..])?,
+        })
+    }
+
+    /// Writes header.
+    pub fn write(&self, data: &mut [u8]) -> Result<(), Error> {
+        if data.len() < HEADER_LEN {
+            return Err(Error::OutOfSpace(format!("Unable to write file header: buffer of {} \
+                                                  bytes is too small",
+                                                 data.len())));
+        }
+
+        data[..MAGIC.len()].copy_from_slice(MAGIC);
+
+        pack::pack_unsigned(&mut data[VERSION_OFFSET..], FORMAT_VERSION)?;
+        pack::pack_unsigned(&mut data[PAGE_SIZE_OFFSET..], self.page_size as u32)?;
+        pack::pack_unsigned(&mut data[PAGE_COUNT_OFFSET..], self.page_count)?;
+        pack::pack_unsigned(&mut data[CATALOG_PAGE_OFFSET..], self.catalog_page)?;
+        pack::pack_bigint(&mut data[CHECKPOINT_LSN_OFFSET..], self.checkpoint_lsn as i64)?;
+        pack::pack_unsigned(&mut data[NEXT_TABLE_ID_OFFSET..], self.next_table_id)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use storage::file_header::{FileHeader, HEADER_LEN, FORMAT_VERSION};
+    use protocol::pack;
+
+    #[test]
+    fn write_read_header() {
+        let mut header = FileHeader::new(4096);
+        header.page_count = 10;
+        header.catalog_page = 3;
+        header.checkpoint_lsn = 1 << 40;
+        header.next_table_id = 12;
+
+        let mut data = [0u8; HEADER_LEN];
+        header.write(&mut data).unwrap();
+
+        assert_eq!(FileHeader::read(&data).unwrap(), header);
+    }
+
+    #[test]
+    fn read_invalid_header() {
+        let mut data = [0u8; HEADER_LEN];
+
+        FileHeader::read(&data).unwrap_err();
+        FileHeader::read(&data[..4]).unwrap_err();
+
+        FileHeader::new(4096).write(&mut data).unwrap();
+        pack::pack_unsigned(&mut data[8..], FORMAT_VERSION + 1).unwrap();
+        FileHeader::read(&data).unwrap_err();
+
+        FileHeader::new(4095).write(&mut data).unwrap();
+        FileHeader::read(&data).unwrap_err();
+    }
+}
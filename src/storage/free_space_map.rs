@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+/// Tracks amount of free space in pages.
+/// Allows to find a page with enough free space without checking every page.
+#[derive(Debug, Default)]
+pub struct FreeSpaceMap {
+    by_page: BTreeMap<u32, usize>,
+    by_space: BTreeSet<(usize, u32)>,
+}
+
+impl FreeSpaceMap {
+    /// Creates new empty map.
+    pub fn new() -> Self {
+        FreeSpaceMap {
+            by_page: BTreeMap::new(),
+            by_space: BTreeSet::new(),
+        }
+    }
+
+    /// Sets amount of free space in the page.
+    pub fn update(&mut self, page_id: u32, space: usize) {
+        if let Some(prev) = self.by_page.insert(page_id, space) {
+            self.by_space.remove(&(prev, page_id));
+        }
+
+        self.by_space.insert((space, page_id));
+    }
+
+    /// Stops tracking the page.
+    pub fn remove(&mut self, page_id: u32) {
+        if let Some(prev) = self.by_page.remove(&page_id) {
+            self.by_space.remove(&(prev, page_id));
+        }
+    }
+
+    /// Gets amount of free space in the page.
+    pub fn get(&self, page_id: u32) -> Option<usize> {
+        self.by_page.get(&page_id).cloned()
+    }
+
+    /// Finds the page with the least amount of free space which is still not less than
+    /// requested.
+    pub fn find(&self, space: usize) -> Option<u32> {
+        self.by_space.range((space, 0)..).next().map(|&(_, page_id)| page_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use storage::free_space_map::FreeSpaceMap;
+
+    #[test]
+    fn find_best_fit() {
+        let mut fsm = FreeSpaceMap::new();
+
+        assert_eq!(fsm.find(0), None);
+
+        fsm.update(1, 100);
+        fsm.update(2, 50);
+        fsm.update(3, 200);
+
+        assert_eq!(fsm.find(0), Some(2));
+        assert_eq!(fsm.find(50), Some(2));
+        assert_eq!(fsm.find(51), Some(1));
+        assert_eq!(fsm.find(101), Some(3));
+        assert_eq!(fsm.find(201), None);
+        assert_eq!(fsm.get(1), Some(100));
+        assert_eq!(fsm.get(4), None);
+    }
+
+    #[test]
+    fn update_and_remove_pages() {
+        let mut fsm = FreeSpaceMap::new();
+
+        fsm.update(1, 100);
+        fsm.update(2, 100);
+        fsm.update(1, 10);
+
+        assert_eq!(fsm.find(11), Some(2));
+        assert_eq!(fsm.get(1), Some(10));
+
+        fsm.remove(2);
+        fsm.remove(5);
+
+        assert_eq!(fsm.find(11), None);
+        assert_eq!(fsm.find(10), Some(1));
+        assert_eq!(fsm.get(2), None);
+    }
+}
@@ -1,6 +1,65 @@
 use std::borrow::BorrowMut;
 use std::borrow::Borrow;
 
+use protocol::pack;
+use protocol::unpack;
+
+/// Offset of the page id in the page header.
+const PAGE_ID_OFFSET: usize = 0;
+
+/// Offset of the page type in the page header.
+const PAGE_TYPE_OFFSET: usize = 4;
+
+/// Offset of the id of the page owner in the page header.
+const OWNER_OFFSET: usize = 8;
+
+/// Offset of the id of the next page in the chain in the page header.
+const NEXT_PAGE_OFFSET: usize = 12;
+
+/// Offset of the LSN of the last change of the page in the page header.
+pub const LSN_OFFSET: usize = 16;
+
+/// Offset of the checksum of the page content in the page header.
+pub const CHECKSUM_OFFSET: usize = 24;
+
+/// Length of the header, common for all pages in the storage.
+pub const PAGE_HEADER_LEN: usize = 28;
+
+/// Type of the page in the storage.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PageType {
+    Free,
+    Data,
+    Catalog,
+    Overflow,
+    Index,
+}
+
+impl PageType {
+    /// Get code of the page type, stored in the page header.
+    pub fn code(self) -> u32 {
+        match self {
+            PageType::Free => 0,
+            PageType::Data => 1,
+            PageType::Catalog => 2,
+            PageType::Overflow => 3,
+            PageType::Index => 4,
+        }
+    }
+
+    /// Get page type by its code.
+    pub fn from_code(code: u32) -> Option<PageType> {
+        match code {
+            0 => Some(PageType::Free),
+            1 => Some(PageType::Data),
+            2 => Some(PageType::Catalog),
+            3 => Some(PageType::Overflow),
+            4 => Some(PageType::Index),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct MemoryPage {
     mem: Box<[u8]>,
@@ -22,4 +81,120 @@ impl MemoryPage {
     pub fn data_mut(&mut self) -> &mut [u8] {
         self.mem.borrow_mut()
     }
+
+    fn header_field(&self, offset: usize) -> u32 {
+        unpack::unpack_unsigned(&self.mem[offset..]).expect("Page is smaller than its header")
+    }
+
+    fn set_header_field(&mut self, offset: usize, val: u32) {
+        pack::pack_unsigned(&mut self.mem[offset..], val).expect("Page is smaller than its header");
+    }
+
+    /// Gets id of the page from the page header.
+    pub fn page_id(&self) -> u32 {
+        self.header_field(PAGE_ID_OFFSET)
+    }
+
+    /// Sets id of the page in the page header.
+    pub fn set_page_id(&mut self, page_id: u32) {
+        self.set_header_field(PAGE_ID_OFFSET, page_id);
+    }
+
+    /// Gets type of the page from the page header.
+    pub fn page_type(&self) -> Option<PageType> {
+        PageType::from_code(self.header_field(PAGE_TYPE_OFFSET))
+    }
+
+    /// Sets type of the page in the page header.
+    pub fn set_page_type(&mut self, page_type: PageType) {
+        self.set_header_field(PAGE_TYPE_OFFSET, page_type.code());
+    }
+
+    /// Gets id of the object which owns the page, e.g. id of the table.
+    pub fn owner(&self) -> u32 {
+        self.header_field(OWNER_OFFSET)
+    }
+
+    /// Sets id of the object which owns the page.
+    pub fn set_owner(&mut self, owner: u32) {
+        self.set_header_field(OWNER_OFFSET, owner);
+    }
+
+    /// Gets id of the next page in the chain. Zero means there is no next page.
+    pub fn next_page(&self) -> u32 {
+        self.header_field(NEXT_PAGE_OFFSET)
+    }
+
+    /// Sets id of the next page in the chain.
+    pub fn set_next_page(&mut self, page_id: u32) {
+        self.set_header_field(NEXT_PAGE_OFFSET, page_id);
+    }
+
+    /// Gets log sequence number of the last logged change of the page.
+    /// Zero means the page was never changed under the log.
+    pub fn lsn(&self) -> u64 {
+        let lsn = unpack::unpack_bigint(&self.mem[LSN_OFFSET..])
+            .expect("Page is smaller than its header");
+
+        lsn as u64
+    }
+
+    /// Sets log sequence number of the last logged change of the page.
+    pub fn set_lsn(&mut self, lsn: u64) {
+        pack::pack_bigint(&mut self.mem[LSN_OFFSET..], lsn as i64)
+            .expect("Page is smaller than its header");
+    }
+
+    /// Gets checksum of the page content, which was computed when the page was written
+    /// to the database file.
+    pub fn checksum(&self) -> u32 {
+        self.header_field(CHECKSUM_OFFSET)
+    }
+
+    /// Sets checksum of the page content.
+    pub fn set_checksum(&mut self, checksum: u32) {
+        self.set_header_field(CHECKSUM_OFFSET, checksum);
+    }
+
+    /// Fills page with zeroes and initializes the page header.
+    pub fn reset(&mut self, page_id: u32, page_type: PageType, owner: u32) {
+        for b in self.mem.iter_mut() {
+            *b = 0;
+        }
+
+        self.set_page_id(page_id);
+        self.set_page_type(page_type);
+        self.set_owner(owner);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use storage::MemoryPage;
+    use storage::PageType;
+
+    #[test]
+    fn page_header() {
+        let mut page = MemoryPage::new(64);
+        page.data_mut()[40] = 1;
+
+        page.reset(7, PageType::Data, 3);
+        page.set_next_page(12);
+        page.set_lsn(1 << 40);
+        page.set_checksum(0xdead_beef);
+
+        assert_eq!(page.page_id(), 7);
+        assert_eq!(page.page_type(), Some(PageType::Data));
+        assert_eq!(page.owner(), 3);
+        assert_eq!(page.next_page(), 12);
+        assert_eq!(page.lsn(), 1 << 40);
+        assert_eq!(page.checksum(), 0xdead_beef);
+        assert_eq!(page.data()[40], 0);
+
+        page.reset(8, PageType::Free, 0);
+
+        assert_eq!(page.page_id(), 8);
+        assert_eq!(page.page_type(), Some(PageType::Free));
+        assert_eq!(page.next_page(), 0);
+    }
 }
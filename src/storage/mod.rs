@@ -1,7 +1,44 @@
 mod memory_page;
+#[allow(clippy::module_inception)]
 mod storage;
 mod data_reference;
+mod slotted_page;
+mod free_space_map;
+mod file_header;
+mod page_file;
+mod buffer_pool;
+mod wal;
+mod transaction;
+mod recovery;
+mod checkpointer;
+mod overflow;
+mod checksum;
 
 pub use self::memory_page::MemoryPage;
+pub use self::memory_page::PageType;
+pub use self::memory_page::PAGE_HEADER_LEN;
 pub use self::storage::Storage;
-pub use self::data_reference::DataReference;
+pub use self::storage::DEFAULT_PAGE_SIZE;
+pub use self::slotted_page::SlottedPage;
+pub use self::slotted_page::SLOT_LEN;
+pub use self::free_space_map::FreeSpaceMap;
+pub use self::file_header::FileHeader;
+pub use self::page_file::PageFile;
+pub use self::buffer_pool::BufferPool;
+pub use self::buffer_pool::BufferPoolStats;
+pub use self::buffer_pool::PageHandle;
+pub use self::buffer_pool::DEFAULT_BUFFER_POOL_SIZE;
+pub use self::file_header::MIN_PAGE_SIZE;
+pub use self::buffer_pool::PageGuard;
+pub use self::wal::Wal;
+pub use self::wal::LogRecord;
+pub use self::wal::PageChange;
+pub use self::wal::wal_path;
+pub use self::transaction::Transaction;
+pub use self::checkpointer::Checkpointer;
+pub use self::overflow::OverflowWriter;
+pub use self::overflow::OVERFLOW_FLAG;
+pub use self::overflow::OVERFLOW_REF_LEN;
+pub use self::overflow::overflow_threshold;
+pub use self::checksum::CorruptPage;
+pub use self::checksum::CorruptionPolicy;
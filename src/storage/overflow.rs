@@ -0,0 +1,48 @@
+use storage::MemoryPage;
+use storage::PageType;
+use storage::SlottedPage;
+use storage::Transaction;
+use error::Error;
+
+/// Flag in the length of VARCHAR or VARBINARY value, which is stored in overflow pages.
+pub const OVERFLOW_FLAG: u32 = 0x8000_0000;
+
+/// Length of the in-row part of the value stored in overflow pages:
+/// length of the value and id of the first overflow page.
+pub const OVERFLOW_REF_LEN: usize = 8;
+
+/// Gets the largest serialized length of the value stored in the row itself.
+/// Larger values are stored in a chain of overflow pages, so that several rows with large
+/// values still share a page.
+pub fn overflow_threshold(page_size: usize) -> usize {
+    SlottedPage::<&MemoryPage>::max_record_len(page_size) / 4
+}
+
+/// Writes values, which exceed the threshold, to chains of overflow pages of the owner.
+#[derive(Debug)]
+pub struct OverflowWriter<'a> {
+    txn: &'a Transaction<'a>,
+    owner: u32,
+    threshold: usize,
+}
+
+impl<'a> OverflowWriter<'a> {
+    /// Creates new writer of overflow pages of the storage with the page size.
+    pub fn new(txn: &'a Transaction<'a>, owner: u32, page_size: usize) -> OverflowWriter<'a> {
+        OverflowWriter {
+            txn,
+            owner,
+            threshold: overflow_threshold(page_size),
+        }
+    }
+
+    /// Checks if the value of the serialized length must be stored in overflow pages.
+    pub fn exceeds(&self, len: usize) -> bool {
+        len > self.threshold
+    }
+
+    /// Writes data to a chain of new overflow pages and returns id of the first page.
+    pub fn write(&self, data: &[u8]) -> Result<u32, Error> {
+        self.txn.write_chain(PageType::Overflow, self.owner, data)
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use storage::MemoryPage;
+use storage::PAGE_HEADER_LEN;
+use storage::Wal;
+use storage::FileHeader;
+use storage::file_header;
+use storage::checksum;
+use storage::memory_page::CHECKSUM_OFFSET;
+use protocol::pack;
+use error::Error;
+
+/// Database file, which consists of fixed-size pages.
+/// The first page of the file holds the file header.
+///
+/// If the file has a write-ahead log, the log is flushed up to the LSN of the page
+/// before the page is written, so the log is always ahead of the file.
+///
+/// File opened read-only is never changed: pages written to it are kept in memory and are
+/// read back from there.
+#[derive(Debug)]
+pub struct PageFile {
+    file: File,
+    page_size: usize,
+    wal: Option<Arc<Wal>>,
+    /// Pages written to the read-only file by their ids, `None` if the file is writable.
+    written: Option<BTreeMap<u32, Vec<u8>>>,
+}
+
+impl PageFile {
+    /// Creates new database file with the header. Fails if the file already exists.
+    pub fn create(path: &Path, header: &FileHeader) -> Result<PageFile, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| {
+                Error::io(format!("Unable to create database file '{}'", path.display()), e)
+            })?;
+
+        let mut res = PageFile {
+            file,
+            page_size: header.page_size,
+            wal: None,
+            written: None,
+        };
+
+        res.write_header(header)?;
+        res.sync()?;
+
+        Ok(res)
+    }
+
+    /// Opens existing database file and reads its header. Read-only file is never changed.
+    pub fn open(path: &Path, read_only: bool) -> Result<(PageFile, FileHeader), Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(!read_only)
+            .open(path)
+            .map_err(|e| {
+                Error::io(format!("Unable to open database file '{}'", path.display()), e)
+            })?;
+
+        let mut data = [0u8; file_header::HEADER_CHECKSUM_OFFSET + 4];
+
+        file.read_exact(&mut data)
+            .map_err(|e| Error::io("Unable to read database file header".to_owned(), e))?;
+
+        // Checksum is verified first, so a corrupted field of the header is reported as
+        // a corruption. File without the magic number is not a database file at all.
+        if data.starts_with(file_header::MAGIC) {
+            checksum::verify_header(&data)?;
+        }
+
+        let header = FileHeader::read(&data)?;
+
+        let res = PageFile {
+            file,
+            page_size: header.page_size,
+            wal: None,
+            written: if read_only { Some(BTreeMap::new()) } else { None },
+        };
+
+        Ok((res, header))
+    }
+
+    /// Gets size of the single page in bytes.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Sets write-ahead log of the file.
+    pub fn set_wal(&mut self, wal: Arc<Wal>) {
+        self.wal = Some(wal);
+    }
+
+    /// Gets number of pages written to the file, including the header page.
+    pub fn page_count(&self) -> Result<u32, Error> {
+        let len = self.file
+            .metadata()
+            .map_err(|e| Error::io("Unable to get length of database file".to_owned(), e))?
+            .len();
+
+        let count = (len / self.page_size as u64) as u32;
+
+        match self.written.as_ref().and_then(|written| written.keys().next_back()) {
+            Some(&last) => Ok(count.max(last + 1)),
+            None => Ok(count),
+        }
+    }
+
+    /// Reads page from the file. Checksum of the page is not verified, see `checksum::verify_page`.
+    pub fn read_page(&mut self, page_id: u32, page: &mut MemoryPage) -> Result<(), Error> {
+        assert_eq!(page.data().len(), self.page_size);
+
+        if let Some(data) = self.written.as_ref().and_then(|written| written.get(&page_id)) {
+            page.data_mut().copy_from_slice(data);
+            return Ok(());
+        }
+
+        self.file
+            .seek(SeekFrom::Start(page_id as u64 * self.page_size as u64))
+            .and_then(|_| self.file.read_exact(page.data_mut()))
+            .map_err(|e| {
+                Error::io(format!("Unable to read page {} from database file", page_id), e)
+            })
+    }
+
+    /// Reads only the header of the page from the file, the rest of the page is left as is.
+    /// Checksum of the page can not be verified without its content.
+    pub fn read_page_header(&mut self, page_id: u32, page: &mut MemoryPage) -> Result<(), Error> {
+        assert_eq!(page.data().len(), self.page_size);
+
+        if let Some(data) = self.written.as_ref().and_then(|written| written.get(&page_id)) {
+            page.data_mut()[..PAGE_HEADER_LEN].copy_from_slice(&data[..PAGE_HEADER_LEN]);
+            return Ok(());
+        }
+
+        self.file
+            .seek(SeekFrom::Start(page_id as u64 * self.page_size as u64))
+            .and_then(|_| self.file.read_exact(&mut page.data_mut()[..PAGE_HEADER_LEN]))
+            .map_err(|e| {
+                Error::io(format!("Unable to read header of page {} from database file",
+                                  page_id),
+                          e)
+            })
+    }
+
+    /// Writes page to the file. Checksum of the page content is written to the page header.
+    pub fn write_page(&mut self, page_id: u32, page: &MemoryPage) -> Result<(), Error> {
+        assert_eq!(page.data().len(), self.page_size);
+
+        if let Some(ref wal) = self.wal {
+            wal.flush(page.lsn())?;
+        }
+
+        let mut data = page.data().to_vec();
+        let checksum = checksum::page_checksum(&data);
+        pack::pack_unsigned(&mut data[CHECKSUM_OFFSET..], checksum)?;
+
+        self.write_data(page_id, &data)
+    }
+
+    /// Writes header to the first page of the file. Checksum of the header follows it.
+    pub fn write_header(&mut self, header: &FileHeader) -> Result<(), Error> {
+        let mut data = vec![0u8; self.page_size];
+        header.write(&mut data)?;
+
+        let checksum = checksum::header_checksum(&data);
+        pack::pack_unsigned(&mut data[file_header::HEADER_CHECKSUM_OFFSET..], checksum)?;
+
+        self.write_data(file_header::HEADER_PAGE, &data)
+    }
+
+    fn write_data(&mut self, page_id: u32, data: &[u8]) -> Result<(), Error> {
+        if let Some(ref mut written) = self.written {
+            written.insert(page_id, data.to_vec());
+            return Ok(());
+        }
+
+        self.file
+            .seek(SeekFrom::Start(page_id as u64 * self.page_size as u64))
+            .and_then(|_| self.file.write_all(data))
+            .map_err(|e| Error::io(format!("Unable to write page {} to database file", page_id), e))
+    }
+
+    /// Flushes all written data to the disk.
+    pub fn sync(&mut self) -> Result<(), Error> {
+        if self.written.is_some() {
+            return Ok(());
+        }
+
+        self.file
+            .sync_all()
+            .map_err(|e| Error::io("Unable to sync database file".to_owned(), e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use storage::MemoryPage;
+    use storage::FileHeader;
+    use storage::page_file::PageFile;
+    use storage::checksum;
+    use test_utils::temp_path;
+    use error::Error;
+
+    #[test]
+    fn create_and_open_file() {
+        let path = temp_path("create_and_open_file");
+
+        let mut header = FileHeader::new(512);
+        header.page_count = 3;
+
+        {
+            let mut file = PageFile::create(&path, &header).unwrap();
+
+            let mut page = MemoryPage::new(512);
+            page.data_mut()[0] = 1;
+            page.data_mut()[511] = 2;
+
+            file.write_page(2, &page).unwrap();
+            file.sync().unwrap();
+        }
+
+        PageFile::create(&path, &header).unwrap_err();
+
+        let (mut file, read_header) = PageFile::open(&path, false).unwrap();
+
+        assert_eq!(read_header, header);
+        assert_eq!(file.page_size(), 512);
+        assert_eq!(file.page_count().unwrap(), 3);
+
+        let mut page = MemoryPage::new(512);
+        file.read_page(2, &mut page).unwrap();
+
+        assert_eq!(page.data()[0], 1);
+        assert_eq!(page.data()[511], 2);
+
+        // Page 1 was never written, so it reads as zeroes.
+        file.read_page(1, &mut page).unwrap();
+        assert!(page.data().iter().all(|&b| b == 0));
+
+        file.read_page(3, &mut page).unwrap_err();
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keep_writes_to_read_only_file_in_memory() {
+        let path = temp_path("keep_writes_to_read_only_file_in_memory");
+
+        let mut header = FileHeader::new(512);
+        header.page_count = 2;
+
+        {
+            let mut file = PageFile::create(&path, &header).unwrap();
+            file.write_page(1, &MemoryPage::new(512)).unwrap();
+        }
+
+        let data = fs::read(&path).unwrap();
+
+        {
+            let (mut file, _) = PageFile::open(&path, true).unwrap();
+
+            let mut page = MemoryPage::new(512);
+            page.data_mut()[300] = 1;
+
+            file.write_page(3, &page).unwrap();
+            header.page_count = 4;
+            file.write_header(&header).unwrap();
+            file.sync().unwrap();
+
+            // Written pages are read back from memory.
+            assert_eq!(file.page_count().unwrap(), 4);
+
+            let mut read = MemoryPage::new(512);
+            file.read_page(3, &mut read).unwrap();
+            checksum::verify_page(3, &read, false).unwrap();
+            assert_eq!(read.data()[300], 1);
+        }
+
+        assert_eq!(fs::read(&path).unwrap(), data);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn detect_corrupted_page() {
+        let path = temp_path("detect_corrupted_page");
+
+        {
+            let mut file = PageFile::create(&path, &FileHeader::new(512)).unwrap();
+
+            let mut page = MemoryPage::new(512);
+            page.data_mut()[300] = 1;
+
+            file.write_page(1, &page).unwrap();
+
+            // Checksum is computed on write, the page itself is not changed.
+            assert_eq!(page.checksum(), 0);
+        }
+
+        let (mut file, _) = PageFile::open(&path, false).unwrap();
+        let mut page = MemoryPage::new(512);
+
+        file.read_page(1, &mut page).unwrap();
+        checksum::verify_page(1, &page, false).unwrap();
+        assert!(page.checksum() != 0);
+
+        let mut data = fs::read(&path).unwrap();
+        data[512 + 300] = 3;
+        fs::write(&path, &data).unwrap();
+
+        file.read_page(1, &mut page).unwrap();
+        let err = checksum::verify_page(1, &page, false).unwrap_err();
+
+        assert_eq!(err.page_id, 1);
+        assert_eq!(err.expected, page.checksum());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn open_invalid_file() {
+        let path = temp_path("open_invalid_file");
+
+        PageFile::open(&path, false).unwrap_err();
+
+        fs::write(&path, b"definitely not a database file").unwrap();
+        PageFile::open(&path, false).unwrap_err();
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn detect_corrupted_header() {
+        let path = temp_path("detect_corrupted_header");
+
+        let mut header = FileHeader::new(512);
+        header.page_count = 3;
+
+        PageFile::create(&path, &header).unwrap();
+
+        // Page count is changed, the header is still valid otherwise.
+        let mut data = fs::read(&path).unwrap();
+        data[16] = 2;
+        fs::write(&path, &data).unwrap();
+
+        match PageFile::open(&path, false) {
+            Err(Error::CorruptPage(e)) => assert_eq!(e.page_id, 0),
+            res => panic!("Unexpected result: {:?}", res.map(|(_, header)| header)),
+        }
+
+        data[16] = 3;
+        fs::write(&path, &data).unwrap();
+
+        assert_eq!(PageFile::open(&path, false).unwrap().1, header);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
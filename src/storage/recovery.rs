@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use storage::MemoryPage;
+use storage::FileHeader;
+use storage::PageFile;
+use storage::Wal;
+use storage::LogRecord;
+use storage::PageChange;
+use storage::CorruptionPolicy;
+use storage::checksum;
+use storage::file_header::{HEADER_LEN, HEADER_PAGE};
+use error::Error;
+
+/// Pages of the database file changed during recovery.
+struct Pages<'a> {
+    file: &'a mut PageFile,
+    header: Vec<u8>,
+    cache: BTreeMap<u32, MemoryPage>,
+    policy: CorruptionPolicy,
+    corrupted: BTreeSet<u32>,
+    /// Number of pages recorded in the file header before the recovery.
+    page_count: u32,
+}
+
+impl<'a> Pages<'a> {
+    /// Gets page, reading it from the file. Pages which were never written are zeroed: they are
+    /// past the pages recorded in the file header, as the header is written after the pages.
+    /// Returns `None` if the page is corrupted and the policy allows to skip it. Such page is
+    /// left as is, so it is quarantined when the storage is opened.
+    fn get(&mut self, page_id: u32) -> Result<Option<&mut MemoryPage>, Error> {
+        if self.corrupted.contains(&page_id) {
+            return Ok(None);
+        }
+
+        if !self.cache.contains_key(&page_id) {
+            let mut page = MemoryPage::new(self.file.page_size());
+
+            if page_id < self.file.page_count()? {
+                self.file.read_page(page_id, &mut page)?;
+
+                let never_written = page_id >= self.page_count;
+
+                if let Err(e) = checksum::verify_page(page_id, &page, never_written) {
+                    if self.policy == CorruptionPolicy::Fail {
+                        return Err(e.into());
+                    }
+
+                    self.corrupted.insert(page_id);
+                    return Ok(None);
+                }
+            }
+
+            self.cache.insert(page_id, page);
+        }
+
+        Ok(self.cache.get_mut(&page_id))
+    }
+
+    /// Applies changes to the page. Changes of pages other than the header page are applied
+    /// only if the page is older than the change.
+    fn apply(&mut self, page_id: u32, changes: &[PageChange], lsn: u64) -> Result<(), Error> {
+        if page_id == HEADER_PAGE {
+            for change in changes {
+                change.apply(&mut self.header);
+            }
+
+            return Ok(());
+        }
+
+        let page = match self.get(page_id)? {
+            Some(page) => page,
+            None => return Ok(()),
+        };
+
+        if page.lsn() >= lsn {
+            return Ok(());
+        }
+
+        for change in changes {
+            change.apply(page.data_mut());
+        }
+
+        page.set_lsn(lsn);
+
+        Ok(())
+    }
+}
+
+/// Recovers the database file after a crash using the write-ahead log.
+///
+/// Recovery follows ARIES. The analysis pass finds transactions which have neither committed
+/// nor rolled back. The redo pass repeats history: every logged change is applied to the pages
+/// which are older than the change. The undo pass rolls back the unfinished transactions,
+/// logging every reverted change as a compensation record. Changed pages and the header are
+/// written to the file, so the log can be reset afterwards. Records logged before the last
+/// checkpoint are skipped, so recovery time is bounded by the interval between checkpoints.
+/// Corrupted pages are handled according to the policy.
+pub fn recover(file: &mut PageFile,
+               header: &mut FileHeader,
+               wal: &Wal,
+               policy: CorruptionPolicy)
+               -> Result<(), Error> {
+    // Changes logged before the checkpoint are already in the file. Such records remain
+    // in the log if the storage crashed before the log was reset after the checkpoint.
+    let records: Vec<_> = wal.records()?
+        .into_iter()
+        .filter(|&(lsn, _)| lsn >= header.checkpoint_lsn)
+        .collect();
+
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    // Analysis: last LSN of every unfinished transaction.
+    let mut active: HashMap<u64, u64> = HashMap::new();
+
+    for &(lsn, ref record) in &records {
+        match *record {
+            LogRecord::Commit { txn, .. } |
+            LogRecord::Abort { txn, .. } => {
+                active.remove(&txn);
+            }
+            _ => {
+                active.insert(record.txn(), lsn);
+            }
+        }
+    }
+
+    let mut header_data = vec![0u8; HEADER_LEN];
+    header.write(&mut header_data)?;
+
+    let mut pages = Pages {
+        file,
+        header: header_data,
+        cache: BTreeMap::new(),
+        policy,
+        corrupted: BTreeSet::new(),
+        page_count: header.page_count,
+    };
+
+    // Redo.
+    for &(lsn, ref record) in &records {
+        match *record {
+            LogRecord::Update { page_id, ref changes, .. } |
+            LogRecord::Compensation { page_id, ref changes, .. } => {
+                pages.apply(page_id, changes, lsn)?;
+            }
+            _ => (),
+        }
+    }
+
+    // Undo, starting from the latest change of all unfinished transactions.
+    let by_lsn: HashMap<u64, &LogRecord> = records.iter().map(|&(lsn, ref r)| (lsn, r)).collect();
+    let mut to_undo: BTreeSet<u64> = active.values().cloned().collect();
+
+    while let Some(lsn) = to_undo.iter().next_back().cloned() {
+        to_undo.remove(&lsn);
+
+        let record = match by_lsn.get(&lsn) {
+            Some(record) => *record,
+            None => return Err(Error::Corruption(format!("Log record {} is missing", lsn))),
+        };
+
+        let txn = record.txn();
+
+        let next = match *record {
+            LogRecord::Update { page_id, ref changes, prev_lsn, .. } => {
+                let changes: Vec<_> = changes.iter().rev().map(PageChange::inverse).collect();
+
+                let clr_lsn = wal.append(&LogRecord::Compensation {
+                    txn,
+                    prev_lsn: active[&txn],
+                    page_id,
+                    changes: changes.clone(),
+                    undo_next_lsn: prev_lsn,
+                });
+
+                pages.apply(page_id, &changes, clr_lsn)?;
+                active.insert(txn, clr_lsn);
+
+                prev_lsn
+            }
+            LogRecord::Compensation { undo_next_lsn, .. } => undo_next_lsn,
+            _ => 0,
+        };
+
+        if next != 0 {
+            to_undo.insert(next);
+        } else {
+            wal.append(&LogRecord::Abort {
+                txn,
+                prev_lsn: active[&txn],
+            });
+        }
+    }
+
+    wal.flush(wal.end_lsn())?;
+
+    let mut recovered = FileHeader::read(&pages.header)?;
+    recovered.page_count = header.page_count;
+    recovered.checkpoint_lsn = wal.end_lsn();
+
+    for (&page_id, page) in &pages.cache {
+        pages.file.write_page(page_id, page)?;
+        recovered.page_count = recovered.page_count.max(page_id + 1);
+    }
+
+    pages.file.write_header(&recovered)?;
+    pages.file.sync()?;
+
+    *header = recovered;
+
+    Ok(())
+}
@@ -0,0 +1,550 @@
+use std::borrow::Borrow;
+use std::borrow::BorrowMut;
+
+use storage::MemoryPage;
+use storage::PAGE_HEADER_LEN;
+use protocol::pack;
+use protocol::unpack;
+
+/// Offset of the page flags in the page header.
+const FLAGS_OFFSET: usize = PAGE_HEADER_LEN;
+
+/// Offset of the number of slots in the page header.
+const SLOT_COUNT_OFFSET: usize = PAGE_HEADER_LEN + 4;
+
+/// Offset of the free space pointer in the page header.
+/// Free space pointer is the offset of the first byte of the record data.
+const FREE_PTR_OFFSET: usize = PAGE_HEADER_LEN + 8;
+
+/// Length of the slotted page header, including the common page header.
+pub const HEADER_LEN: usize = PAGE_HEADER_LEN + 12;
+
+/// Length of the single slot directory entry: offset and length of the record.
+pub const SLOT_LEN: usize = 8;
+
+/// Page with the slotted record layout.
+///
+/// Page starts with the common page header and the slotted page header, which are followed by the slot directory growing towards
+/// the end of the page. Record data grows from the end of the page towards the slot directory.
+/// Records are addressed by the number of their slot, which does not change when the page
+/// is compacted. Slot with zero offset is free and can be reused by a new record.
+#[derive(Debug)]
+pub struct SlottedPage<P> {
+    page: P,
+}
+
+impl<P: Borrow<MemoryPage>> SlottedPage<P> {
+    /// Wraps page which is already initialized as a slotted page.
+    pub fn new(page: P) -> Self {
+        SlottedPage { page }
+    }
+
+    /// Gets maximum length of the record which fits into an empty page of the given size.
+    pub fn max_record_len(page_size: usize) -> usize {
+        page_size - HEADER_LEN - SLOT_LEN
+    }
+
+    fn data(&self) -> &[u8] {
+        self.page.borrow().data()
+    }
+
+    /// Reads field of the page header or of the slot directory.
+    /// Offset must be checked by the caller.
+    fn read(&self, offset: usize) -> usize {
+        let val = unpack::unpack_unsigned(&self.data()[offset..])
+            .expect("Field is outside of the page");
+
+        val as usize
+    }
+
+    /// Gets id of the page.
+    pub fn page_id(&self) -> u32 {
+        self.page.borrow().page_id()
+    }
+
+    /// Gets page flags.
+    pub fn flags(&self) -> u32 {
+        self.read(FLAGS_OFFSET) as u32
+    }
+
+    /// Gets number of slots in the slot directory, including free ones.
+    pub fn slot_count(&self) -> u32 {
+        self.read(SLOT_COUNT_OFFSET) as u32
+    }
+
+    fn free_ptr(&self) -> usize {
+        self.read(FREE_PTR_OFFSET)
+    }
+
+    fn slot_pos(slot: u32) -> usize {
+        HEADER_LEN + slot as usize * SLOT_LEN
+    }
+
+    /// Gets offset and length of the record in the slot, if the slot is used.
+    /// Slot which is outside of the page is never used.
+    fn slot(&self, slot: u32) -> Option<(usize, usize)> {
+        let pos = Self::slot_pos(slot);
+
+        if slot >= self.slot_count() || pos + SLOT_LEN > self.data().len() {
+            return None;
+        }
+
+        let offset = self.read(pos);
+
+        if offset == 0 {
+            None
+        } else {
+            Some((offset, self.read(pos + 4)))
+        }
+    }
+
+    /// Gets number of the first free slot in the directory.
+    fn free_slot(&self) -> Option<u32> {
+        (0..self.slot_count()).find(|&slot| self.slot(slot).is_none())
+    }
+
+    /// Gets offset of the record in the page.
+    pub fn record_offset(&self, slot: u32) -> Option<usize> {
+        self.slot(slot).map(|(offset, _)| offset)
+    }
+
+    /// Gets record data. Returns `None` if the record is outside of the page.
+    pub fn record(&self, slot: u32) -> Option<&[u8]> {
+        self.slot(slot).and_then(|(offset, len)| self.data().get(offset..offset + len))
+    }
+
+    /// Iterates over numbers of the used slots.
+    pub fn slots(&self) -> Vec<u32> {
+        (0..self.slot_count()).filter(|&slot| self.slot(slot).is_some()).collect()
+    }
+
+    /// Checks that the slot directory and the records fit into the page
+    /// and that the records do not overlap.
+    pub fn validate(&self) -> Result<(), String> {
+        let len = self.data().len();
+        let slot_count = self.slot_count();
+
+        if slot_count as usize > (len - HEADER_LEN) / SLOT_LEN {
+            return Err(format!("slot directory of {} slots does not fit into the page",
+                               slot_count));
+        }
+
+        let free_ptr = self.free_ptr();
+
+        if free_ptr < Self::slot_pos(slot_count) || free_ptr > len {
+            return Err(format!("free space pointer {} is outside of the page", free_ptr));
+        }
+
+        let mut records: Vec<_> = (0..slot_count)
+            .filter_map(|slot| self.slot(slot).map(|(offset, len)| (offset, len, slot)))
+            .collect();
+
+        for &(offset, record_len, slot) in &records {
+            if offset < free_ptr || offset + record_len > len {
+                return Err(format!("record in slot {} is outside of the record data", slot));
+            }
+        }
+
+        records.sort();
+
+        for pair in records.windows(2) {
+            if pair[0].0 + pair[0].1 > pair[1].0 {
+                return Err(format!("records in slots {} and {} overlap", pair[0].2, pair[1].2));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets contiguous free space between the slot directory and the record data.
+    pub fn free_space(&self) -> usize {
+        self.free_ptr().saturating_sub(Self::slot_pos(self.slot_count()))
+    }
+
+    /// Gets total free space in the page, including space of the deleted records,
+    /// which can be reclaimed by the compaction.
+    pub fn available_space(&self) -> usize {
+        let used: usize = (0..self.slot_count()).filter_map(|s| self.slot(s)).map(|(_, l)| l).sum();
+
+        self.data().len().saturating_sub(Self::slot_pos(self.slot_count()) + used)
+    }
+
+    /// Gets space needed to insert a record of the given length, including the new slot
+    /// if there is no free one.
+    fn required_space(&self, len: usize) -> usize {
+        match self.free_slot() {
+            Some(_) => len,
+            None => len + SLOT_LEN,
+        }
+    }
+
+    /// Check if a record of the given length can be inserted into the page.
+    pub fn fits(&self, len: usize) -> bool {
+        self.required_space(len) <= self.available_space()
+    }
+}
+
+impl<P: BorrowMut<MemoryPage> + Borrow<MemoryPage>> SlottedPage<P> {
+    /// Initializes empty slotted page. Common page header other than the page id is kept.
+    pub fn init(mut page: P, page_id: u32) -> Self {
+        let len = page.borrow().data().len();
+
+        assert!(len >= HEADER_LEN + SLOT_LEN,
+                "Page of size {} is too small for the slotted layout",
+                len);
+        assert!(len <= u32::MAX as usize,
+                "Page of size {} is too large for the slotted layout",
+                len);
+
+        for b in page.borrow_mut().data_mut()[PAGE_HEADER_LEN..HEADER_LEN].iter_mut() {
+            *b = 0;
+        }
+
+        page.borrow_mut().set_page_id(page_id);
+
+        let mut res = SlottedPage { page };
+
+        res.write(FREE_PTR_OFFSET, len);
+
+        res
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        self.page.borrow_mut().data_mut()
+    }
+
+    fn write(&mut self, offset: usize, val: usize) {
+        pack::pack_unsigned(&mut self.data_mut()[offset..], val as u32)
+            .expect("Field is outside of the page");
+    }
+
+    /// Sets page flags.
+    pub fn set_flags(&mut self, flags: u32) {
+        self.write(FLAGS_OFFSET, flags as usize);
+    }
+
+    fn set_slot(&mut self, slot: u32, offset: usize, len: usize) {
+        let pos = Self::slot_pos(slot);
+
+        self.write(pos, offset);
+        self.write(pos + 4, len);
+    }
+
+    /// Copies data to the free space and returns its offset. Space must be available.
+    fn place(&mut self, data: &[u8]) -> usize {
+        let offset = self.free_ptr() - data.len();
+
+        self.data_mut()[offset..offset + data.len()].copy_from_slice(data);
+        self.write(FREE_PTR_OFFSET, offset);
+
+        offset
+    }
+
+    /// Inserts record into the page and returns its slot number.
+    /// Compacts page if the contiguous free space is not enough.
+    /// Returns None if the record does not fit into the page.
+    pub fn insert(&mut self, data: &[u8]) -> Option<u32> {
+        if !self.fits(data.len()) {
+            return None;
+        }
+
+        if self.required_space(data.len()) > self.free_space() {
+            self.compact();
+        }
+
+        let slot = match self.free_slot() {
+            Some(slot) => slot,
+            None => {
+                let slot = self.slot_count();
+                self.write(SLOT_COUNT_OFFSET, slot as usize + 1);
+                slot
+            }
+        };
+
+        let offset = self.place(data);
+        self.set_slot(slot, offset, data.len());
+
+        Some(slot)
+    }
+
+    /// Deletes record from the page. Returns false if the slot is not used.
+    /// Space of the record is reclaimed by the compaction.
+    pub fn delete(&mut self, slot: u32) -> bool {
+        if self.slot(slot).is_none() {
+            return false;
+        }
+
+        self.set_slot(slot, 0, 0);
+
+        // Trailing free slots are removed from the directory.
+        let mut count = self.slot_count();
+        while count > 0 && self.slot(count - 1).is_none() {
+            count -= 1;
+        }
+
+        self.write(SLOT_COUNT_OFFSET, count as usize);
+
+        true
+    }
+
+    /// Replaces record data. Record is updated in place if the new data is not larger than
+    /// the old one, otherwise it is moved within the page.
+    /// Returns false if the slot is not used or the new data does not fit into the page.
+    pub fn update(&mut self, slot: u32, data: &[u8]) -> bool {
+        let (offset, len) = match self.slot(slot) {
+            Some(slot) => slot,
+            None => return false,
+        };
+
+        if data.len() <= len {
+            self.data_mut()[offset..offset + data.len()].copy_from_slice(data);
+            self.set_slot(slot, offset, data.len());
+
+            return true;
+        }
+
+        if self.available_space() + len < data.len() {
+            return false;
+        }
+
+        // Old data is released before the compaction so its space can be reused.
+        self.set_slot(slot, 0, 0);
+
+        if self.free_space() < data.len() {
+            self.compact();
+        }
+
+        let offset = self.place(data);
+        self.set_slot(slot, offset, data.len());
+
+        true
+    }
+
+    /// Moves all records to the end of the page, so all free space becomes contiguous.
+    /// Slot numbers of the records do not change.
+    pub fn compact(&mut self) {
+        let mut records: Vec<(u32, usize, usize)> = (0..self.slot_count())
+            .filter_map(|s| self.slot(s).map(|(offset, len)| (s, offset, len)))
+            .collect();
+
+        // Records closest to the end of the page are moved first, so a record is never
+        // overwritten before it is moved.
+        records.sort_by_key(|&(_, offset, _)| ::std::cmp::Reverse(offset));
+
+        let mut free_ptr = self.data().len();
+
+        for (slot, offset, len) in records {
+            free_ptr -= len;
+            self.data_mut().copy_within(offset..offset + len, free_ptr);
+            self.set_slot(slot, free_ptr, len);
+        }
+
+        self.write(FREE_PTR_OFFSET, free_ptr);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use storage::MemoryPage;
+    use storage::slotted_page::{SlottedPage, HEADER_LEN, SLOT_LEN, SLOT_COUNT_OFFSET};
+    use protocol::pack;
+
+    #[test]
+    fn init_page() {
+        let mut page = MemoryPage::new(128);
+        let mut sp = SlottedPage::init(&mut page, 42);
+
+        sp.set_flags(7);
+
+        assert_eq!(sp.page_id(), 42);
+        assert_eq!(sp.flags(), 7);
+        assert_eq!(sp.slot_count(), 0);
+        assert_eq!(sp.free_space(), 128 - HEADER_LEN);
+        assert_eq!(sp.available_space(), 128 - HEADER_LEN);
+        assert_eq!(sp.record(0), None);
+    }
+
+    #[test]
+    fn insert_records() {
+        let mut page = MemoryPage::new(128);
+        let mut sp = SlottedPage::init(&mut page, 0);
+
+        assert_eq!(sp.insert(b"first"), Some(0));
+        assert_eq!(sp.insert(b""), Some(1));
+        assert_eq!(sp.insert(b"third"), Some(2));
+
+        assert_eq!(sp.record(0), Some(&b"first"[..]));
+        assert_eq!(sp.record(1), Some(&b""[..]));
+        assert_eq!(sp.record(2), Some(&b"third"[..]));
+        assert_eq!(sp.slots(), vec![0, 1, 2]);
+        assert_eq!(sp.free_space(), 128 - HEADER_LEN - 3 * SLOT_LEN - 10);
+    }
+
+    #[test]
+    fn insert_up_to_page_boundary() {
+        let mut page = MemoryPage::new(64);
+        let mut sp = SlottedPage::init(&mut page, 0);
+
+        let max = SlottedPage::<&MemoryPage>::max_record_len(64);
+
+        assert!(!sp.fits(max + 1));
+        assert_eq!(sp.insert(&vec![1; max + 1]), None);
+        assert_eq!(sp.insert(&vec![1; max]), Some(0));
+        assert_eq!(sp.free_space(), 0);
+        assert_eq!(sp.insert(b""), None);
+
+        assert!(sp.delete(0));
+        assert_eq!(sp.slot_count(), 0);
+        assert_eq!(sp.insert(&vec![2; max]), Some(0));
+        assert_eq!(sp.record(0), Some(&vec![2; max][..]));
+    }
+
+    #[test]
+    fn fill_page_exactly() {
+        let mut page = MemoryPage::new(HEADER_LEN + 4 * (SLOT_LEN + 8));
+        let mut sp = SlottedPage::init(&mut page, 0);
+
+        for i in 0..4 {
+            assert_eq!(sp.insert(&[i; 8]), Some(i as u32));
+        }
+
+        assert_eq!(sp.available_space(), 0);
+        assert_eq!(sp.insert(&[]), None);
+
+        for i in 0..4 {
+            assert_eq!(sp.record(i), Some(&[i as u8; 8][..]));
+        }
+    }
+
+    #[test]
+    fn delete_records() {
+        let mut page = MemoryPage::new(128);
+        let mut sp = SlottedPage::init(&mut page, 0);
+
+        sp.insert(b"a").unwrap();
+        sp.insert(b"b").unwrap();
+        sp.insert(b"c").unwrap();
+
+        assert!(sp.delete(1));
+        assert!(!sp.delete(1));
+        assert!(!sp.delete(10));
+        assert_eq!(sp.record(1), None);
+        assert_eq!(sp.slots(), vec![0, 2]);
+        assert_eq!(sp.slot_count(), 3);
+
+        // Free slot is reused.
+        assert_eq!(sp.insert(b"d"), Some(1));
+
+        assert!(sp.delete(2));
+        assert_eq!(sp.slot_count(), 2);
+        assert!(sp.delete(0));
+        assert!(sp.delete(1));
+        assert_eq!(sp.slot_count(), 0);
+    }
+
+    #[test]
+    fn insert_compacts_fragmented_page() {
+        let mut page = MemoryPage::new(HEADER_LEN + 3 * (SLOT_LEN + 16));
+        let mut sp = SlottedPage::init(&mut page, 0);
+
+        sp.insert(&[1; 16]).unwrap();
+        sp.insert(&[2; 16]).unwrap();
+        sp.insert(&[3; 16]).unwrap();
+
+        assert!(sp.delete(1));
+        assert_eq!(sp.free_space(), 0);
+        assert_eq!(sp.available_space(), 16);
+
+        assert_eq!(sp.insert(&[4; 16]), Some(1));
+        assert_eq!(sp.record(0), Some(&[1; 16][..]));
+        assert_eq!(sp.record(1), Some(&[4; 16][..]));
+        assert_eq!(sp.record(2), Some(&[3; 16][..]));
+    }
+
+    #[test]
+    fn compact_keeps_slots() {
+        let mut page = MemoryPage::new(256);
+        let mut sp = SlottedPage::init(&mut page, 0);
+
+        for i in 0..10u8 {
+            sp.insert(&vec![i; i as usize + 1]).unwrap();
+        }
+
+        for i in (0..10).filter(|i| i % 3 == 0) {
+            assert!(sp.delete(i));
+        }
+
+        let available = sp.available_space();
+        sp.compact();
+
+        assert_eq!(sp.free_space(), available);
+        assert_eq!(sp.available_space(), available);
+
+        for i in 0..10u8 {
+            if i % 3 == 0 {
+                assert_eq!(sp.record(i as u32), None);
+            } else {
+                assert_eq!(sp.record(i as u32), Some(&vec![i; i as usize + 1][..]));
+            }
+        }
+    }
+
+    #[test]
+    fn update_records() {
+        let mut page = MemoryPage::new(HEADER_LEN + 2 * SLOT_LEN + 32);
+        let mut sp = SlottedPage::init(&mut page, 0);
+
+        sp.insert(&[1; 8]).unwrap();
+        sp.insert(&[2; 8]).unwrap();
+
+        let offset = sp.record_offset(0).unwrap();
+
+        // Smaller record is updated in place.
+        assert!(sp.update(0, &[3; 4]));
+        assert_eq!(sp.record_offset(0), Some(offset));
+        assert_eq!(sp.record(0), Some(&[3; 4][..]));
+
+        // Larger record is moved, using all available space.
+        assert!(sp.update(1, &[4; 28]));
+        assert_eq!(sp.record(1), Some(&[4; 28][..]));
+        assert_eq!(sp.record(0), Some(&[3; 4][..]));
+        assert_eq!(sp.available_space(), 0);
+
+        assert!(!sp.update(0, &[5; 5]));
+        assert!(!sp.update(2, &[5; 1]));
+        assert_eq!(sp.record(0), Some(&[3; 4][..]));
+    }
+
+    #[test]
+    fn validate_slot_directory() {
+        let mut page = MemoryPage::new(128);
+
+        {
+            let mut sp = SlottedPage::init(&mut page, 0);
+
+            sp.insert(b"first").unwrap();
+            sp.insert(b"").unwrap();
+            sp.insert(b"third").unwrap();
+
+            assert_eq!(sp.validate(), Ok(()));
+        }
+
+        // The third record points to the data of the first one.
+        let first = HEADER_LEN;
+        let third = HEADER_LEN + 2 * SLOT_LEN;
+        pack::pack_unsigned(&mut page.data_mut()[third..], 128 - 5).unwrap();
+        assert_eq!(SlottedPage::new(&page).validate(),
+                   Err("records in slots 0 and 2 overlap".to_owned()));
+
+        pack::pack_unsigned(&mut page.data_mut()[third..], 126).unwrap();
+        assert_eq!(SlottedPage::new(&page).validate(),
+                   Err("record in slot 2 is outside of the record data".to_owned()));
+
+        pack::pack_unsigned(&mut page.data_mut()[first..], 0).unwrap();
+        pack::pack_unsigned(&mut page.data_mut()[third..], 0).unwrap();
+        assert_eq!(SlottedPage::new(&page).validate(), Ok(()));
+
+        pack::pack_unsigned(&mut page.data_mut()[SLOT_COUNT_OFFSET..], 100).unwrap();
+        SlottedPage::new(&page).validate().unwrap_err();
+    }
+}
@@ -1,16 +1,1196 @@
-use std::sync::Mutex;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 
 use storage::MemoryPage;
+use storage::PageType;
+use storage::PAGE_HEADER_LEN;
+use storage::SlottedPage;
+use storage::FreeSpaceMap;
+use storage::FileHeader;
+use storage::PageFile;
+use storage::BufferPool;
+use storage::BufferPoolStats;
+use storage::PageHandle;
+use storage::Wal;
+use storage::PageChange;
+use storage::Transaction;
+use storage::wal_path;
+use storage::CorruptPage;
+use storage::CorruptionPolicy;
+use storage::buffer_pool;
+use storage::checksum;
+use storage::recovery;
+use storage::file_header::HEADER_LEN;
+use protocol::unpack;
+use verify::Problem;
+use verify::VerifyReport;
+use error::Error;
+
+/// Default size of the single page in bytes.
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+
+/// Offset of the data length in the page of a chain.
+pub const CHAIN_LEN_OFFSET: usize = PAGE_HEADER_LEN;
+
+/// Offset of the data in the page of a chain.
+pub const CHAIN_DATA_OFFSET: usize = PAGE_HEADER_LEN + 4;
 
+/// Storage of pages.
+///
+/// Pages are addressed by their id. Ids of freed pages are reused by new pages,
+/// so the storage does not grow while there are free pages. Every page has an owner,
+/// e.g. a table, and the free space is tracked separately for every owner.
+///
+/// Storage either lives in memory only or is backed by a database file. The first page of the
+/// file holds the file header, so page id 0 is never allocated. Pages of the file are cached in
+/// the buffer pool of the bounded size.
+///
+/// Pages are changed only by transactions, see `begin`. Changes of the file-backed storage are
+/// written to the write-ahead log, which is used to recover the file after a crash.
+///
+/// Every page written to the file has a checksum, which is verified when the page is read.
+/// Depending on the corruption policy, reading a corrupted page either fails or quarantines
+/// the page: it is excluded from the storage, but the rest of the storage stays available.
+///
+/// Storage opened read-only never changes its file and log, and its transactions can not be
+/// committed.
+///
+/// Storage never locks a page which may be used by someone else while holding its own lock,
+/// so it is safe to call storage methods while holding a page lock.
 #[derive(Debug)]
 pub struct Storage {
-    root: Vec<Arc<Mutex<MemoryPage>>>,
+    inner: Mutex<Inner>,
+    wal: Option<Arc<Wal>>,
+    writer: Mutex<()>,
+    next_txn: AtomicU64,
+    closed: AtomicBool,
+    read_only: bool,
+}
+
+#[derive(Debug)]
+struct Inner {
+    header: FileHeader,
+    file: Option<PageFile>,
+    pool: BufferPool,
+    free_pages: BTreeSet<u32>,
+    owners: BTreeMap<u32, BTreeSet<u32>>,
+    page_owners: HashMap<u32, u32>,
+    free_space: BTreeMap<u32, FreeSpaceMap>,
+    policy: CorruptionPolicy,
+    quarantined: BTreeSet<u32>,
+    /// Number of pages recorded in the header of the database file. Pages past them were never
+    /// written to the file, unless they were evicted from the buffer pool.
+    written_pages: u32,
+}
+
+impl Inner {
+    fn new(header: FileHeader,
+           file: Option<PageFile>,
+           pool_size: Option<usize>,
+           policy: CorruptionPolicy)
+           -> Inner {
+        Inner {
+            pool: BufferPool::new(header.page_size, pool_size),
+            written_pages: header.page_count,
+            header,
+            file,
+            free_pages: BTreeSet::new(),
+            owners: BTreeMap::new(),
+            page_owners: HashMap::new(),
+            free_space: BTreeMap::new(),
+            policy,
+            quarantined: BTreeSet::new(),
+        }
+    }
+
+    /// Handles corrupted page according to the corruption policy. Quarantined page is
+    /// unregistered and is never reused.
+    fn corrupted(&mut self, error: CorruptPage) -> Result<(), Error> {
+        if self.policy == CorruptionPolicy::Fail {
+            return Err(error.into());
+        }
+
+        let page_id = error.page_id;
+
+        if let Some(owner) = self.page_owners.remove(&page_id) {
+            self.owners.get_mut(&owner).unwrap().remove(&page_id);
+
+            if let Some(fsm) = self.free_space.get_mut(&owner) {
+                fsm.remove(page_id);
+            }
+        }
+
+        self.free_pages.remove(&page_id);
+        self.quarantined.insert(page_id);
+
+        Ok(())
+    }
+
+    /// Registers page by the type and the owner from its header.
+    /// Previous registration of the page is removed.
+    fn register_page(&mut self, page_id: u32, page_type: Option<PageType>, owner: u32)
+                     -> Result<(), Error> {
+        if let Some(prev) = self.page_owners.remove(&page_id) {
+            self.owners.get_mut(&prev).unwrap().remove(&page_id);
+
+            if let Some(fsm) = self.free_space.get_mut(&prev) {
+                fsm.remove(page_id);
+            }
+        }
+
+        self.free_pages.remove(&page_id);
+
+        match page_type {
+            Some(PageType::Free) => {
+                self.free_pages.insert(page_id);
+            }
+            Some(_) => {
+                self.owners.entry(owner).or_default().insert(page_id);
+                self.page_owners.insert(page_id, owner);
+            }
+            None => return Err(Error::Corruption(format!("Page {} has unknown type", page_id))),
+        }
+
+        if self.header.page_count <= page_id {
+            self.header.page_count = page_id + 1;
+        }
+
+        Ok(())
+    }
 }
 
 impl Storage {
     /// Create new Storage
     pub fn new() -> Self {
-        Storage { root: Vec::new() }
+        Storage::with_page_size(DEFAULT_PAGE_SIZE)
+    }
+
+    /// Create new Storage with the specific page size.
+    pub fn with_page_size(page_size: usize) -> Self {
+        let header = FileHeader::new(page_size);
+
+        Storage::with_inner(Inner::new(header, None, None, CorruptionPolicy::Fail), None, false)
+    }
+
+    fn with_inner(inner: Inner, wal: Option<Arc<Wal>>, read_only: bool) -> Self {
+        Storage {
+            inner: Mutex::new(inner),
+            wal,
+            writer: Mutex::new(()),
+            next_txn: AtomicU64::new(1),
+            closed: AtomicBool::new(false),
+            read_only,
+        }
+    }
+
+    /// Create new Storage backed by a new database file and its log.
+    /// Buffer pool caches up to `pool_size` pages of the file.
+    pub fn create(path: &Path, page_size: usize, pool_size: usize, policy: CorruptionPolicy)
+                  -> Result<Self, Error> {
+        let header = FileHeader::new(page_size);
+        let mut file = PageFile::create(path, &header)?;
+
+        let wal = Arc::new(Wal::create(&wal_path(path))?);
+        file.set_wal(wal.clone());
+
+        let inner = Inner::new(header, Some(file), Some(pool_size), policy);
+
+        Ok(Storage::with_inner(inner, Some(wal), false))
+    }
+
+    /// Open Storage backed by an existing database file. If the file was not closed properly,
+    /// it is recovered using its log. Buffer pool caches up to `pool_size` pages of the file.
+    ///
+    /// Only headers of the pages are read to find their owners, so opening does not read the
+    /// whole file. Checksum of an allocated page is verified when the page is read for the first
+    /// time, see `get_page`; use `verify_pages` to check all pages at once. Pages whose header
+    /// does not describe an allocated page, e.g. free pages which may be reused at any moment,
+    /// are read and verified at once.
+    pub fn open(path: &Path, pool_size: usize, policy: CorruptionPolicy) -> Result<Self, Error> {
+        Storage::open_file(path, pool_size, policy, false)
+    }
+
+    /// Open Storage backed by an existing database file without changing the file and its log.
+    /// If the file was not closed properly, it is recovered in memory. Transactions of the
+    /// storage can not be committed.
+    pub fn open_read_only(path: &Path, pool_size: usize, policy: CorruptionPolicy)
+                          -> Result<Self, Error> {
+        Storage::open_file(path, pool_size, policy, true)
+    }
+
+    fn open_file(path: &Path, pool_size: usize, policy: CorruptionPolicy, read_only: bool)
+                 -> Result<Self, Error> {
+        let (mut file, mut header) = PageFile::open(path, read_only)?;
+
+        // Missing log of the read-only file is not created: there is nothing to recover.
+        let log = wal_path(path);
+
+        let wal = if read_only && !log.exists() {
+            None
+        } else {
+            Some(Arc::new(Wal::open(&log, read_only)?))
+        };
+
+        if let Some(ref wal) = wal {
+            recovery::recover(&mut file, &mut header, wal, policy)?;
+            wal.reset(header.checkpoint_lsn)?;
+
+            file.set_wal(wal.clone());
+        }
+
+        let mut pages = Vec::new();
+        let mut corrupted = Vec::new();
+        let mut page = MemoryPage::new(header.page_size);
+
+        for page_id in 1..header.page_count {
+            file.read_page_header(page_id, &mut page)?;
+
+            let allocated = page.page_id() == page_id && page.page_type().is_some() &&
+                            page.page_type() != Some(PageType::Free);
+
+            if !allocated {
+                file.read_page(page_id, &mut page)?;
+
+                // Header of the recovered file records all its pages, so none of them is zeroed.
+                if let Err(e) = checksum::verify_page(page_id, &page, false) {
+                    corrupted.push(e);
+                    continue;
+                }
+
+                // Page whose allocation was rolled back is zeroed, so it is free.
+                let zeroed = page.page_id() == 0 && page.page_type() == Some(PageType::Free);
+
+                if page.page_id() != page_id && !zeroed {
+                    return Err(Error::Corruption(format!("Page {} has invalid id in the \
+                                                          header: {}",
+                                                         page_id,
+                                                         page.page_id())));
+                }
+            }
+
+            pages.push((page_id, page.page_type(), page.owner()));
+        }
+
+        let mut inner = Inner::new(header, Some(file), Some(pool_size), policy);
+
+        for (page_id, page_type, owner) in pages {
+            inner.register_page(page_id, page_type, owner)?;
+        }
+
+        for e in corrupted {
+            inner.corrupted(e)?;
+        }
+
+        Ok(Storage::with_inner(inner, wal, read_only))
+    }
+
+    /// Check whether the storage is opened read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Get size of the single page in bytes.
+    pub fn page_size(&self) -> usize {
+        self.inner.lock().unwrap().header.page_size
+    }
+
+    /// Get number of allocated pages.
+    pub fn page_count(&self) -> usize {
+        self.inner.lock().unwrap().page_owners.len()
+    }
+
+    /// Get ids of all pages of the owner in ascending order.
+    pub fn pages(&self, owner: u32) -> Vec<u32> {
+        let inner = self.inner.lock().unwrap();
+
+        match inner.owners.get(&owner) {
+            Some(pages) => pages.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Get ids of all owners of the allocated pages in ascending order.
+    pub fn owners(&self) -> Vec<u32> {
+        let inner = self.inner.lock().unwrap();
+
+        inner.owners
+            .iter()
+            .filter(|&(_, pages)| !pages.is_empty())
+            .map(|(&owner, _)| owner)
+            .collect()
+    }
+
+    /// Get ids of the quarantined corrupted pages in ascending order.
+    pub fn quarantined_pages(&self) -> Vec<u32> {
+        self.inner.lock().unwrap().quarantined.iter().cloned().collect()
+    }
+
+    /// Get statistics of the buffer pool.
+    pub fn buffer_pool_stats(&self) -> BufferPoolStats {
+        self.inner.lock().unwrap().pool.stats()
+    }
+
+    /// Begin new transaction. Waits until the active transaction, if any, is finished.
+    pub fn begin(&self) -> Transaction<'_> {
+        let lock = self.writer.lock().unwrap();
+        let id = self.next_txn.fetch_add(1, Ordering::SeqCst);
+
+        Transaction::new(self, id, self.wal.clone(), lock)
+    }
+
+    /// Get id of the page to allocate next: the lowest free page id, if any,
+    /// or the id past the last page.
+    pub fn next_free_page(&self) -> u32 {
+        let inner = self.inner.lock().unwrap();
+
+        match inner.free_pages.iter().next() {
+            Some(&page_id) => page_id,
+            None => inner.header.page_count,
+        }
+    }
+
+    /// Get page by its id. Returns `None` if the page is not allocated or is quarantined
+    /// because it is corrupted.
+    pub fn get_page(&self, page_id: u32) -> Result<Option<PageHandle>, Error> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if !inner.page_owners.contains_key(&page_id) {
+            return Ok(None);
+        }
+
+        Storage::load_page(&mut inner, page_id)
+    }
+
+    /// Get any page except the header page, including the free ones.
+    /// Page past the last page of the storage is zeroed. Fails if the page is quarantined.
+    pub fn fetch_page(&self, page_id: u32) -> Result<PageHandle, Error> {
+        assert!(page_id != 0, "Unable to fetch the header page");
+
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+
+        if page_id >= inner.header.page_count {
+            let page = MemoryPage::new(inner.header.page_size);
+            let handle = inner.pool.put(page_id, page, inner.file.as_mut())?;
+
+            inner.header.page_count = page_id + 1;
+
+            return Ok(handle);
+        }
+
+        match Storage::load_page(inner, page_id)? {
+            Some(page) => Ok(page),
+            None => Err(Error::Corruption(format!("Page {} is quarantined", page_id))),
+        }
+    }
+
+    /// Get page from the buffer pool, reading it from the file if it is not cached.
+    /// Returns `None` if the page is quarantined.
+    fn load_page(inner: &mut Inner, page_id: u32) -> Result<Option<PageHandle>, Error> {
+        if inner.quarantined.contains(&page_id) {
+            return Ok(None);
+        }
+
+        if let Some(page) = inner.pool.get(page_id) {
+            return Ok(Some(page));
+        }
+
+        let file = match inner.file.as_mut() {
+            Some(file) => file,
+            None => {
+                return Err(Error::InvalidArgument(format!("Page {} is not in the buffer pool",
+                                                          page_id)))
+            }
+        };
+
+        let mut page = MemoryPage::new(inner.header.page_size);
+        file.read_page(page_id, &mut page)?;
+
+        let never_written = page_id >= inner.written_pages;
+
+        if let Err(e) = checksum::verify_page(page_id, &page, never_written) {
+            inner.corrupted(e)?;
+            return Ok(None);
+        }
+
+        inner.pool.load(page_id, page, file).map(Some)
+    }
+
+    /// Register page by the type and the owner from its header after the page was changed.
+    pub fn register_page(&self, page_id: u32, page_type: Option<PageType>, owner: u32)
+                         -> Result<(), Error> {
+        self.inner.lock().unwrap().register_page(page_id, page_type, owner)
+    }
+
+    /// Record amount of free space in the allocated page.
+    pub fn update_free_space(&self, page_id: u32, space: usize) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(&owner) = inner.page_owners.get(&page_id) {
+            inner.free_space.entry(owner).or_default().update(page_id, space);
+        }
+    }
+
+    /// Get amount of free space in the page, if it was recorded.
+    pub fn free_space(&self, page_id: u32) -> Option<usize> {
+        let inner = self.inner.lock().unwrap();
+
+        let owner = inner.page_owners.get(&page_id)?;
+
+        inner.free_space.get(owner)?.get(page_id)
+    }
+
+    /// Find page of the owner which has at least the requested amount of free space.
+    pub fn find_page_with_space(&self, owner: u32, space: usize) -> Option<u32> {
+        let inner = self.inner.lock().unwrap();
+
+        inner.free_space.get(&owner).and_then(|fsm| fsm.find(space))
+    }
+
+    /// Get id of the first page of the catalog.
+    pub fn catalog_page(&self) -> u32 {
+        self.inner.lock().unwrap().header.catalog_page
+    }
+
+    /// Get id of the next table of the catalog.
+    pub fn next_table_id(&self) -> u32 {
+        self.inner.lock().unwrap().header.next_table_id
+    }
+
+    /// Get the serialized file header.
+    pub fn header_data(&self) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_LEN];
+        self.inner.lock().unwrap().header.write(&mut data).expect("Buffer is large enough");
+
+        data
+    }
+
+    /// Apply changes to the serialized file header.
+    pub fn apply_header_changes(&self, changes: &[PageChange]) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let mut data = vec![0u8; HEADER_LEN];
+        inner.header.write(&mut data)?;
+
+        for change in changes {
+            change.apply(&mut data);
+        }
+
+        inner.header = FileHeader::read(&data)?;
+
+        Ok(())
+    }
+
+    /// Get allocated page of the chain.
+    fn chain_page(&self, page_id: u32) -> Result<PageHandle, Error> {
+        match self.get_page(page_id)? {
+            Some(page) => Ok(page),
+            None => {
+                Err(Error::Corruption(format!("Page {} of the chain is not allocated", page_id)))
+            }
+        }
+    }
+
+    /// Get ids of all pages of the chain.
+    pub fn chain_pages(&self, first: u32) -> Result<Vec<u32>, Error> {
+        let mut pages = Vec::new();
+        let mut page_id = first;
+
+        while page_id != 0 {
+            if pages.contains(&page_id) {
+                return Err(Error::Corruption(format!("Page chain starting at page {} has a cycle",
+                                                     first)));
+            }
+
+            let page = self.chain_page(page_id)?;
+
+            pages.push(page_id);
+            page_id = page.lock().next_page();
+        }
+
+        Ok(pages)
+    }
+
+    /// Read data from the chain of pages.
+    pub fn read_chain(&self, first: u32) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+
+        for page_id in self.chain_pages(first)? {
+            let page = self.chain_page(page_id)?;
+            let page = page.lock();
+
+            let len = unpack::unpack_unsigned(&page.data()[CHAIN_LEN_OFFSET..])? as usize;
+
+            if CHAIN_DATA_OFFSET + len > page.data().len() {
+                return Err(Error::Corruption(format!("Page {} of the chain has invalid data \
+                                                      length: {}",
+                                                     page_id,
+                                                     len)));
+            }
+
+            data.extend_from_slice(&page.data()[CHAIN_DATA_OFFSET..CHAIN_DATA_OFFSET + len]);
+        }
+
+        Ok(data)
+    }
+
+    /// Write all dirty pages and the file header to the database file.
+    /// Does nothing if the storage is not backed by a file.
+    pub fn flush(&self) -> Result<(), Error> {
+        let pages = {
+            let inner = self.inner.lock().unwrap();
+
+            if inner.file.is_none() {
+                return Ok(());
+            }
+
+            inner.pool.dirty_pages()
+        };
+
+        // Page is locked before the storage, as the page may be locked by someone
+        // who waits for the storage.
+        for handle in pages {
+            let page = handle.lock();
+            let mut inner = self.inner.lock().unwrap();
+
+            buffer_pool::write_back(&page, inner.file.as_mut().unwrap())?;
+        }
+
+        // Changes of the header are logged, but the header has no LSN,
+        // so the whole log is flushed before it.
+        if let Some(ref wal) = self.wal {
+            wal.flush(wal.end_lsn())?;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let header = inner.header.clone();
+        let file = inner.file.as_mut().unwrap();
+        file.write_header(&header)?;
+        file.sync()?;
+
+        inner.written_pages = header.page_count;
+
+        Ok(())
+    }
+
+    /// Check every page of the storage: checksum of the page in the database file must match
+    /// its content, the page header must be valid and the slot directory of a data page must
+    /// be consistent. Dirty pages are written to the file first, so the file is checked as is.
+    /// Changes made during the check may be reported as problems.
+    pub fn verify_pages(&self, report: &mut VerifyReport) -> Result<(), Error> {
+        self.flush()?;
+
+        let page_count = self.inner.lock().unwrap().header.page_count;
+
+        for page_id in 1..page_count {
+            report.pages += 1;
+
+            let page = self.read_stored_page(page_id)?;
+
+            let (file, never_written) = {
+                let inner = self.inner.lock().unwrap();
+                (inner.file.is_some(), page_id >= inner.written_pages)
+            };
+
+            if file {
+                if let Err(e) = checksum::verify_page(page_id, &page, never_written) {
+                    report.problems.push(Problem::CorruptPage(e));
+                    continue;
+                }
+            }
+
+            let invalid = |reason: String| Problem::InvalidPage { page_id, reason };
+
+            // Page which was never written or whose allocation was rolled back is zeroed,
+            // so it is free.
+            if page.page_id() == 0 && page.page_type() == Some(PageType::Free) {
+                continue;
+            }
+
+            if page.page_id() != page_id {
+                let reason = format!("invalid id in the header: {}", page.page_id());
+                report.problems.push(invalid(reason));
+                continue;
+            }
+
+            match page.page_type() {
+                Some(PageType::Data) => {
+                    if let Err(reason) = SlottedPage::new(&page).validate() {
+                        report.problems.push(invalid(reason));
+                    }
+                }
+                Some(_) => (),
+                None => report.problems.push(invalid("unknown page type".to_owned())),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read copy of the page as it is stored: in the database file or in memory.
+    fn read_stored_page(&self, page_id: u32) -> Result<MemoryPage, Error> {
+        let handle = {
+            let mut inner = self.inner.lock().unwrap();
+            let inner = &mut *inner;
+
+            let mut page = MemoryPage::new(inner.header.page_size);
+
+            if let Some(file) = inner.file.as_mut() {
+                // Page past the end of the file was never written.
+                if page_id < file.page_count()? {
+                    file.read_page(page_id, &mut page)?;
+                }
+
+                return Ok(page);
+            }
+
+            match inner.pool.get(page_id) {
+                Some(handle) => handle,
+                None => return Ok(page),
+            }
+        };
+
+        let page = handle.lock();
+        let mut copy = MemoryPage::new(page.data().len());
+        copy.data_mut().copy_from_slice(page.data());
+
+        Ok(copy)
+    }
+
+    /// Get LSN of the last checkpoint.
+    pub fn checkpoint_lsn(&self) -> u64 {
+        self.inner.lock().unwrap().header.checkpoint_lsn
+    }
+
+    /// Write a checkpoint: write all dirty pages to the database file, record the end of
+    /// the log as the checkpoint LSN in the file header and discard the log records before it.
+    /// Recovery after a crash replays only the records written after the last checkpoint.
+    /// Waits until the active transaction, if any, is finished.
+    /// Does nothing if the storage is not backed by a file or is closed.
+    pub fn checkpoint(&self) -> Result<(), Error> {
+        let _lock = self.writer.lock().unwrap();
+
+        if self.closed.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        self.write_checkpoint()
+    }
+
+    /// Write a checkpoint. There must be no active transaction.
+    fn write_checkpoint(&self) -> Result<(), Error> {
+        let wal = match self.wal {
+            Some(ref wal) => wal,
+            None => return Ok(()),
+        };
+
+        let lsn = wal.end_lsn();
+        self.inner.lock().unwrap().header.checkpoint_lsn = lsn;
+
+        // Header is written after the pages, so the checkpoint LSN is recorded only when
+        // all the changes before it are in the file.
+        self.flush()?;
+
+        wal.reset(lsn)
+    }
+
+    /// Close the storage: write a checkpoint, so the file does not need recovery when it is
+    /// opened next time. If there is an active transaction, the pages are written, but the log
+    /// is kept. Does nothing if the storage is already closed.
+    pub fn close(&self) -> Result<(), Error> {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        match self.writer.try_lock() {
+            Ok(_lock) => self.write_checkpoint(),
+            Err(_) => self.flush(),
+        }
+    }
+
+    /// Mark the storage as closed without writing anything, as if the process has crashed.
+    #[cfg(test)]
+    pub fn abandon(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use storage::Storage;
+    use storage::PageType;
+    use storage::CorruptionPolicy;
+    use storage::wal_path;
+    use test_utils::{temp_path, remove_database};
+    use verify::VerifyReport;
+    use error::Error;
+
+    #[test]
+    fn allocate_pages() {
+        let storage = Storage::with_page_size(64);
+        let txn = storage.begin();
+
+        assert_eq!(txn.allocate_page(PageType::Data, 1).unwrap(), 1);
+        assert_eq!(txn.allocate_page(PageType::Data, 2).unwrap(), 2);
+        assert_eq!(txn.allocate_page(PageType::Data, 1).unwrap(), 3);
+
+        txn.commit().unwrap();
+
+        assert_eq!(storage.page_count(), 3);
+        assert_eq!(storage.pages(1), vec![1, 3]);
+        assert_eq!(storage.pages(2), vec![2]);
+        assert_eq!(storage.pages(3), vec![]);
+
+        let page = storage.get_page(3).unwrap().unwrap();
+        let page = page.lock();
+
+        assert_eq!(page.data().len(), 64);
+        assert_eq!(page.page_id(), 3);
+        assert_eq!(page.page_type(), Some(PageType::Data));
+        assert_eq!(page.owner(), 1);
+
+        assert!(storage.get_page(0).unwrap().is_none());
+        assert!(storage.get_page(4).unwrap().is_none());
+    }
+
+    #[test]
+    fn recycle_freed_pages() {
+        let storage = Storage::with_page_size(64);
+        let txn = storage.begin();
+
+        for _ in 0..4 {
+            txn.allocate_page(PageType::Data, 1).unwrap();
+        }
+
+        txn.write(&storage.get_page(2).unwrap().unwrap()).data_mut()[40] = 42;
+
+        txn.free_page(2).unwrap();
+        txn.free_page(1).unwrap();
+        txn.free_page(1).unwrap_err();
+        txn.free_page(0).unwrap_err();
+        txn.free_page(10).unwrap_err();
+
+        assert!(storage.get_page(2).unwrap().is_none());
+        assert_eq!(storage.pages(1), vec![3, 4]);
+        assert_eq!(storage.page_count(), 2);
+
+        assert_eq!(txn.allocate_page(PageType::Data, 1).unwrap(), 1);
+        assert_eq!(txn.allocate_page(PageType::Data, 1).unwrap(), 2);
+        assert_eq!(txn.allocate_page(PageType::Data, 1).unwrap(), 5);
+
+        txn.commit().unwrap();
+
+        // Recycled page is zeroed.
+        assert_eq!(storage.get_page(2).unwrap().unwrap().lock().data()[40], 0);
+    }
+
+    #[test]
+    fn find_page_with_space() {
+        let storage = Storage::with_page_size(64);
+        let txn = storage.begin();
+
+        for _ in 0..3 {
+            txn.allocate_page(PageType::Data, 1).unwrap();
+        }
+
+        txn.allocate_page(PageType::Data, 2).unwrap();
+
+        storage.update_free_space(1, 10);
+        storage.update_free_space(2, 40);
+        storage.update_free_space(3, 20);
+        storage.update_free_space(4, 50);
+        storage.update_free_space(5, 64);
+
+        assert_eq!(storage.find_page_with_space(1, 15), Some(3));
+        assert_eq!(storage.find_page_with_space(1, 30), Some(2));
+        assert_eq!(storage.find_page_with_space(1, 41), None);
+        assert_eq!(storage.find_page_with_space(2, 41), Some(4));
+        assert_eq!(storage.free_space(2), Some(40));
+
+        txn.free_page(2).unwrap();
+        txn.commit().unwrap();
+
+        assert_eq!(storage.find_page_with_space(1, 30), None);
+        assert_eq!(storage.free_space(2), None);
+    }
+
+    #[test]
+    fn write_read_chains() {
+        let storage = Storage::with_page_size(256);
+        let txn = storage.begin();
+
+        let small = txn.write_chain(PageType::Catalog, 0, b"small").unwrap();
+        let empty = txn.write_chain(PageType::Catalog, 0, b"").unwrap();
+
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let large = txn.write_chain(PageType::Catalog, 0, &data).unwrap();
+
+        assert_eq!(storage.read_chain(small).unwrap(), b"small");
+        assert_eq!(storage.read_chain(empty).unwrap(), b"");
+        assert_eq!(storage.read_chain(large).unwrap(), data);
+        assert_eq!(storage.page_count(), 2 + 5);
+
+        txn.free_chain(large).unwrap();
+        txn.commit().unwrap();
+
+        assert_eq!(storage.page_count(), 2);
+        storage.read_chain(large).unwrap_err();
+    }
+
+    #[test]
+    fn persist_pages() {
+        let path = temp_path("persist_pages");
+
+        {
+            let storage = Storage::create(&path, 512, 2, CorruptionPolicy::Fail).unwrap();
+            let txn = storage.begin();
+
+            for owner in 1..4 {
+                let page_id = txn.allocate_page(PageType::Data, owner).unwrap();
+                let page = storage.get_page(page_id).unwrap().unwrap();
+                txn.write(&page).data_mut()[100] = owner as u8;
+            }
+
+            let chain = txn.write_chain(PageType::Catalog, 0, &[7; 1000]).unwrap();
+            txn.set_catalog_page(chain).unwrap();
+
+            txn.free_page(2).unwrap();
+            txn.commit().unwrap();
+
+            storage.close().unwrap();
+        }
+
+        Storage::create(&path, 512, 2, CorruptionPolicy::Fail).unwrap_err();
+
+        // Log of the closed storage has no records.
+        assert_eq!(fs::metadata(wal_path(&path)).unwrap().len(), 16);
+
+        let storage = Storage::open(&path, 2, CorruptionPolicy::Fail).unwrap();
+
+        assert_eq!(storage.page_size(), 512);
+        assert_eq!(storage.pages(1), vec![1]);
+        assert_eq!(storage.pages(2), vec![]);
+        assert_eq!(storage.pages(3), vec![3]);
+        assert_eq!(storage.get_page(3).unwrap().unwrap().lock().data()[100], 3);
+        assert_eq!(storage.read_chain(storage.catalog_page()).unwrap(), vec![7; 1000]);
+
+        // Freed page is reused after reopening.
+        let txn = storage.begin();
+        assert_eq!(txn.allocate_page(PageType::Data, 1).unwrap(), 2);
+        txn.commit().unwrap();
+
+        drop(storage);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn handle_corrupted_pages() {
+        let path = temp_path("handle_corrupted_pages");
+
+        let corrupt_page = |policy: CorruptionPolicy| {
+            let storage = Storage::create(&path, 512, 2, policy).unwrap();
+            let txn = storage.begin();
+
+            for i in 0..5 {
+                let page_id = txn.allocate_page(PageType::Data, 1).unwrap();
+                txn.write(&storage.get_page(page_id).unwrap().unwrap()).data_mut()[100] = i;
+            }
+
+            txn.commit().unwrap();
+            storage.checkpoint().unwrap();
+
+            // Page 1 is evicted, so it is read from the file.
+            let mut data = fs::read(&path).unwrap();
+            data[512 + 100] = 42;
+            fs::write(&path, &data).unwrap();
+
+            storage
+        };
+
+        let storage = corrupt_page(CorruptionPolicy::Fail);
+        match storage.get_page(1) {
+            Err(Error::CorruptPage(e)) => assert_eq!(e.page_id, 1),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert_eq!(storage.pages(1), vec![1, 2, 3, 4, 5]);
+
+        drop(storage);
+        remove_database(&path);
+
+        let storage = corrupt_page(CorruptionPolicy::Quarantine);
+        assert!(storage.get_page(1).unwrap().is_none());
+        assert_eq!(storage.get_page(2).unwrap().unwrap().lock().data()[100], 1);
+        assert_eq!(storage.quarantined_pages(), vec![1]);
+        assert_eq!(storage.pages(1), vec![2, 3, 4, 5]);
+        storage.fetch_page(1).unwrap_err();
+
+        // Quarantined page is never reused.
+        let txn = storage.begin();
+        assert_eq!(txn.allocate_page(PageType::Data, 1).unwrap(), 6);
+        txn.commit().unwrap();
+
+        drop(storage);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn verify_pages_on_first_read() {
+        let path = temp_path("verify_pages_on_first_read");
+
+        {
+            let storage = Storage::create(&path, 512, 2, CorruptionPolicy::Fail).unwrap();
+            let txn = storage.begin();
+
+            for i in 0..3 {
+                let page_id = txn.allocate_page(PageType::Overflow, 1).unwrap();
+                txn.write(&storage.get_page(page_id).unwrap().unwrap()).data_mut()[100] = i;
+            }
+
+            txn.free_page(3).unwrap();
+            txn.commit().unwrap();
+            storage.close().unwrap();
+        }
+
+        // Content of an allocated page is corrupted, its header is intact.
+        let mut data = fs::read(&path).unwrap();
+        data[2 * 512 + 100] = 42;
+        fs::write(&path, &data).unwrap();
+
+        let storage = Storage::open(&path, 2, CorruptionPolicy::Fail).unwrap();
+        assert_eq!(storage.pages(1), vec![1, 2]);
+        assert_eq!(storage.get_page(1).unwrap().unwrap().lock().data()[100], 0);
+
+        match storage.get_page(2) {
+            Err(Error::CorruptPage(e)) => assert_eq!(e.page_id, 2),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let mut report = VerifyReport::default();
+        storage.verify_pages(&mut report).unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.has_page_problem(2));
+
+        drop(storage);
+
+        // Free page may be reused at any moment, so it is verified when the file is opened.
+        data[3 * 512 + 100] = 42;
+        fs::write(&path, &data).unwrap();
+
+        match Storage::open(&path, 2, CorruptionPolicy::Fail) {
+            Err(Error::CorruptPage(e)) => assert_eq!(e.page_id, 3),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        remove_database(&path);
+    }
+
+    #[test]
+    fn detect_zeroed_pages() {
+        let path = temp_path("detect_zeroed_pages");
+
+        {
+            let storage = Storage::create(&path, 512, 2, CorruptionPolicy::Fail).unwrap();
+            let txn = storage.begin();
+
+            for _ in 0..3 {
+                txn.allocate_page(PageType::Overflow, 1).unwrap();
+            }
+
+            txn.commit().unwrap();
+            storage.close().unwrap();
+        }
+
+        // Page recorded in the file header was written, so a zeroed one is corrupted.
+        let mut data = fs::read(&path).unwrap();
+        for b in &mut data[2 * 512..3 * 512] {
+            *b = 0;
+        }
+        fs::write(&path, &data).unwrap();
+
+        match Storage::open(&path, 2, CorruptionPolicy::Fail) {
+            Err(Error::CorruptPage(e)) => assert_eq!(e.page_id, 2),
+            res => panic!("Unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let storage = Storage::open(&path, 2, CorruptionPolicy::Quarantine).unwrap();
+        assert_eq!(storage.quarantined_pages(), vec![2]);
+        assert_eq!(storage.pages(1), vec![1, 3]);
+
+        drop(storage);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn undo_unfinished_transaction_after_crash() {
+        let path = temp_path("undo_unfinished_transaction_after_crash");
+
+        {
+            let storage = Storage::create(&path, 512, 4, CorruptionPolicy::Fail).unwrap();
+
+            let txn = storage.begin();
+
+            for i in 0..10 {
+                let page_id = txn.allocate_page(PageType::Data, 1).unwrap();
+                txn.write(&storage.get_page(page_id).unwrap().unwrap()).data_mut()[100] = i;
+            }
+
+            let chain = txn.write_chain(PageType::Catalog, 0, &[7; 1000]).unwrap();
+            txn.set_catalog_page(chain).unwrap();
+            txn.commit().unwrap();
+
+            // Changes of the unfinished transaction reach the file and must be undone.
+            let txn = storage.begin();
+
+            for page_id in 1..6 {
+                txn.write(&storage.get_page(page_id).unwrap().unwrap()).data_mut()[100] = 42;
+            }
+
+            txn.free_page(7).unwrap();
+            txn.free_chain(chain).unwrap();
+            txn.set_catalog_page(0).unwrap();
+            txn.allocate_page(PageType::Data, 2).unwrap();
+
+            storage.flush().unwrap();
+            txn.abandon();
+            storage.abandon();
+        }
+
+        let storage = Storage::open(&path, 4, CorruptionPolicy::Fail).unwrap();
+
+        assert_eq!(storage.pages(1), (1..11).collect::<Vec<_>>());
+        assert_eq!(storage.pages(2), vec![]);
+        assert_eq!(storage.read_chain(storage.catalog_page()).unwrap(), vec![7; 1000]);
+
+        for page_id in 1..11 {
+            let page = storage.get_page(page_id).unwrap().unwrap();
+            assert_eq!(page.lock().data()[100], page_id as u8 - 1);
+        }
+
+        drop(storage);
+        remove_database(&path);
+    }
+
+    #[test]
+    fn recover_from_truncated_log() {
+        let path = temp_path("recover_from_truncated_log");
+
+        {
+            let storage = Storage::create(&path, 512, 100, CorruptionPolicy::Fail).unwrap();
+
+            // Pages are never written to the file, so the log is the only copy of the changes.
+            for i in 0..10 {
+                let txn = storage.begin();
+                let page_id = txn.allocate_page(PageType::Data, 1).unwrap();
+                txn.write(&storage.get_page(page_id).unwrap().unwrap()).data_mut()[100] = i;
+                txn.commit().unwrap();
+            }
+
+            storage.abandon();
+        }
+
+        let data = fs::read(&path).unwrap();
+        let log = fs::read(wal_path(&path)).unwrap();
+
+        let copy = temp_path("recover_from_truncated_log_copy");
+        let mut recovered = 0;
+
+        for len in (16..log.len()).step_by(7).chain(Some(log.len())) {
+            fs::write(&copy, &data).unwrap();
+            fs::write(wal_path(&copy), &log[..len]).unwrap();
+
+            let storage = Storage::open(&copy, 100, CorruptionPolicy::Fail).unwrap();
+            let pages = storage.pages(1);
+
+            // Committed transactions survive, unfinished ones are rolled back.
+            assert!(pages.len() >= recovered);
+            assert_eq!(pages, (1..pages.len() as u32 + 1).collect::<Vec<_>>());
+
+            for &page_id in &pages {
+                let page = storage.get_page(page_id).unwrap().unwrap();
+                assert_eq!(page.lock().data()[100], page_id as u8 - 1);
+            }
+
+            recovered = pages.len();
+        }
+
+        assert_eq!(recovered, 10);
+
+        remove_database(&path);
+        remove_database(&copy);
+    }
+
+    #[test]
+    fn recover_after_checkpoint() {
+        let path = temp_path("recover_after_checkpoint");
+
+        let write_pages = |storage: &Storage, count: u8| for _ in 0..count {
+            let txn = storage.begin();
+            let page_id = txn.allocate_page(PageType::Data, 1).unwrap();
+            txn.write(&storage.get_page(page_id).unwrap().unwrap()).data_mut()[100] =
+                page_id as u8;
+            txn.commit().unwrap();
+        };
+
+        let log = {
+            let storage = Storage::create(&path, 512, 100, CorruptionPolicy::Fail).unwrap();
+
+            write_pages(&storage, 5);
+
+            let log = fs::read(wal_path(&path)).unwrap();
+
+            storage.checkpoint().unwrap();
+
+            assert_eq!(fs::metadata(wal_path(&path)).unwrap().len(), 16);
+            assert!(storage.checkpoint_lsn() > 0);
+
+            write_pages(&storage, 5);
+            storage.abandon();
+
+            log
+        };
+
+        {
+            let storage = Storage::open(&path, 100, CorruptionPolicy::Fail).unwrap();
+
+            assert_eq!(storage.pages(1), (1..11).collect::<Vec<_>>());
+
+            for page_id in 1..11 {
+                let page = storage.get_page(page_id).unwrap().unwrap();
+                assert_eq!(page.lock().data()[100], page_id as u8);
+            }
+
+            storage.checkpoint().unwrap();
+            storage.abandon();
+        }
+
+        // Crash after the checkpoint was recorded, but before the log was reset.
+        // Records before the checkpoint are skipped and new LSNs continue after it.
+        fs::write(wal_path(&path), &log).unwrap();
+
+        let storage = Storage::open(&path, 100, CorruptionPolicy::Fail).unwrap();
+        let checkpoint_lsn = storage.checkpoint_lsn();
+
+        assert_eq!(storage.pages(1), (1..11).collect::<Vec<_>>());
+
+        write_pages(&storage, 1);
+        storage.abandon();
+        drop(storage);
+
+        let storage = Storage::open(&path, 100, CorruptionPolicy::Fail).unwrap();
+
+        assert_eq!(storage.pages(1), (1..12).collect::<Vec<_>>());
+        assert!(storage.checkpoint_lsn() > checkpoint_lsn);
+
+        drop(storage);
+        remove_database(&path);
     }
 }
@@ -0,0 +1,437 @@
+use std::cell::RefCell;
+use std::mem;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::sync::MutexGuard;
+
+use storage::MemoryPage;
+use storage::PageType;
+use storage::PageHandle;
+use storage::PageGuard;
+use storage::Storage;
+use storage::Wal;
+use storage::LogRecord;
+use storage::PageChange;
+use storage::memory_page::{LSN_OFFSET, CHECKSUM_OFFSET};
+use storage::file_header::{HEADER_PAGE, CATALOG_PAGE_OFFSET, NEXT_TABLE_ID_OFFSET};
+use storage::storage::{CHAIN_LEN_OFFSET, CHAIN_DATA_OFFSET};
+use protocol::pack;
+use error::Error;
+
+/// Change of the page made by the transaction, which is reverted on rollback.
+#[derive(Debug)]
+struct UndoEntry {
+    page_id: u32,
+    changes: Vec<PageChange>,
+    prev_lsn: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    last_lsn: u64,
+    undo: Vec<UndoEntry>,
+}
+
+/// Part of the transaction shared with the page writers.
+#[derive(Debug)]
+struct Context {
+    id: u64,
+    wal: Option<Arc<Wal>>,
+    state: RefCell<State>,
+}
+
+impl Context {
+    /// Appends record to the log, if the storage has one, and returns its LSN.
+    fn append(&self, record: &LogRecord) -> u64 {
+        let lsn = match self.wal {
+            Some(ref wal) => wal.append(record),
+            None => 0,
+        };
+
+        if lsn != 0 {
+            self.state.borrow_mut().last_lsn = lsn;
+        }
+
+        lsn
+    }
+
+    /// Logs change of the page and returns LSN of the log record.
+    fn log_update(&self, page_id: u32, changes: Vec<PageChange>) -> u64 {
+        let prev_lsn = self.state.borrow().last_lsn;
+
+        let lsn = self.append(&LogRecord::Update {
+            txn: self.id,
+            prev_lsn,
+            page_id,
+            changes: changes.clone(),
+        });
+
+        self.state.borrow_mut().undo.push(UndoEntry {
+            page_id,
+            changes,
+            prev_lsn,
+        });
+
+        lsn
+    }
+}
+
+/// Transaction, which groups changes of pages made by a single operation.
+///
+/// Only one transaction of the storage is active at a time. Every change of a page is
+/// appended to the write-ahead log as a diff of the page before and after the change, and is
+/// reverted on rollback. Transaction which is dropped without a commit is rolled back.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    storage: &'a Storage,
+    context: Context,
+    finished: bool,
+    _lock: MutexGuard<'a, ()>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Starts new transaction. Lock guarantees that there are no other active transactions.
+    pub fn new(storage: &'a Storage,
+               id: u64,
+               wal: Option<Arc<Wal>>,
+               lock: MutexGuard<'a, ()>)
+               -> Transaction<'a> {
+        let txn = Transaction {
+            storage,
+            context: Context {
+                id,
+                wal,
+                state: RefCell::new(State::default()),
+            },
+            finished: false,
+            _lock: lock,
+        };
+
+        txn.context.append(&LogRecord::Begin { txn: id });
+
+        txn
+    }
+
+    /// Gets id of the transaction.
+    pub fn id(&self) -> u64 {
+        self.context.id
+    }
+
+    /// Locks page for writing. Changes of the page are logged when the writer is dropped.
+    pub fn write<'t>(&'t self, page: &'t PageHandle) -> PageWriter<'t> {
+        let guard = page.lock();
+        let before = guard.data().to_vec();
+
+        PageWriter {
+            context: &self.context,
+            page: guard,
+            before,
+        }
+    }
+
+    /// Allocates new zeroed page with initialized page header and returns its id.
+    /// Ids of the freed pages are reused first.
+    pub fn allocate_page(&self, page_type: PageType, owner: u32) -> Result<u32, Error> {
+        assert!(page_type != PageType::Free, "Unable to allocate a free page");
+
+        let page_id = self.storage.next_free_page();
+        let page = self.storage.fetch_page(page_id)?;
+
+        self.write(&page).reset(page_id, page_type, owner);
+        self.storage.register_page(page_id, Some(page_type), owner)?;
+
+        Ok(page_id)
+    }
+
+    /// Frees page, so its id can be reused by a new page.
+    /// The page must not be locked by the caller.
+    pub fn free_page(&self, page_id: u32) -> Result<(), Error> {
+        let page = match self.storage.get_page(page_id)? {
+            Some(page) => page,
+            None => {
+                return Err(Error::InvalidArgument(format!("Unable to free page {}: page is not \
+                                                           allocated",
+                                                          page_id)))
+            }
+        };
+
+        self.write(&page).reset(page_id, PageType::Free, 0);
+        self.storage.register_page(page_id, Some(PageType::Free), 0)
+    }
+
+    /// Writes data to a chain of new pages and returns id of the first page.
+    pub fn write_chain(&self, page_type: PageType, owner: u32, data: &[u8]) -> Result<u32, Error> {
+        let capacity = self.storage.page_size() - CHAIN_DATA_OFFSET;
+
+        let mut chunks: Vec<&[u8]> = data.chunks(capacity).collect();
+
+        if chunks.is_empty() {
+            chunks.push(&[]);
+        }
+
+        let mut next = 0;
+
+        // Pages are written from the end, so every page knows id of the next one.
+        for chunk in chunks.into_iter().rev() {
+            let page_id = self.allocate_page(page_type, owner)?;
+            let page = self.storage.fetch_page(page_id)?;
+            let mut page = self.write(&page);
+
+            page.set_next_page(next);
+            pack::pack_unsigned(&mut page.data_mut()[CHAIN_LEN_OFFSET..], chunk.len() as u32)?;
+            page.data_mut()[CHAIN_DATA_OFFSET..CHAIN_DATA_OFFSET + chunk.len()]
+                .copy_from_slice(chunk);
+
+            next = page_id;
+        }
+
+        Ok(next)
+    }
+
+    /// Frees all pages of the chain.
+    pub fn free_chain(&self, first: u32) -> Result<(), Error> {
+        for page_id in self.storage.chain_pages(first)? {
+            self.free_page(page_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets id of the first page of the catalog in the file header.
+    pub fn set_catalog_page(&self, page_id: u32) -> Result<(), Error> {
+        self.set_header_field(CATALOG_PAGE_OFFSET, page_id)
+    }
+
+    /// Sets id of the next table of the catalog in the file header.
+    pub fn set_next_table_id(&self, table_id: u32) -> Result<(), Error> {
+        self.set_header_field(NEXT_TABLE_ID_OFFSET, table_id)
+    }
+
+    /// Changes field of the file header at the offset. The change is logged like a change of
+    /// a page.
+    fn set_header_field(&self, offset: usize, val: u32) -> Result<(), Error> {
+        let before = self.storage.header_data();
+        let mut after = before.clone();
+
+        pack::pack_unsigned(&mut after[offset..], val)?;
+
+        let changes = PageChange::diff(&before, &after, (0, 0));
+
+        self.storage.apply_header_changes(&changes)?;
+        self.context.log_update(HEADER_PAGE, changes);
+
+        Ok(())
+    }
+
+    /// Commits transaction. Log is flushed, so the changes survive a crash.
+    /// Transaction of the read-only storage is rolled back instead.
+    pub fn commit(mut self) -> Result<(), Error> {
+        self.finished = true;
+
+        if self.storage.is_read_only() {
+            self.undo()?;
+            return Err(Error::InvalidArgument("Database is opened read-only".to_owned()));
+        }
+
+        let prev_lsn = self.context.state.borrow().last_lsn;
+        let lsn = self.context.append(&LogRecord::Commit {
+            txn: self.id(),
+            prev_lsn,
+        });
+
+        let res = match self.context.wal {
+            Some(ref wal) => wal.flush(lsn),
+            None => Ok(()),
+        };
+
+        if let Err(e) = res {
+            self.undo()?;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Rolls transaction back and returns ids of the pages whose changes were reverted.
+    pub fn rollback(mut self) -> Result<Vec<u32>, Error> {
+        self.finished = true;
+
+        self.undo()
+    }
+
+    /// Reverts all changes of the transaction in reverse order. Every reverted change is logged
+    /// as a compensation record, so it is not undone again during recovery.
+    fn undo(&mut self) -> Result<Vec<u32>, Error> {
+        let undo = mem::take(&mut self.context.state.borrow_mut().undo);
+        let mut pages = Vec::new();
+
+        for entry in undo.into_iter().rev() {
+            let changes: Vec<_> = entry.changes.iter().rev().map(PageChange::inverse).collect();
+
+            let prev_lsn = self.context.state.borrow().last_lsn;
+            let lsn = self.context.append(&LogRecord::Compensation {
+                txn: self.id(),
+                prev_lsn,
+                page_id: entry.page_id,
+                changes: changes.clone(),
+                undo_next_lsn: entry.prev_lsn,
+            });
+
+            if entry.page_id == HEADER_PAGE {
+                self.storage.apply_header_changes(&changes)?;
+                continue;
+            }
+
+            let page = self.storage.fetch_page(entry.page_id)?;
+            let (page_type, owner) = {
+                let mut page = page.lock();
+
+                for change in &changes {
+                    change.apply(page.data_mut());
+                }
+
+                if lsn != 0 {
+                    page.set_lsn(lsn);
+                }
+
+                (page.page_type(), page.owner())
+            };
+
+            self.storage.register_page(entry.page_id, page_type, owner)?;
+
+            if !pages.contains(&entry.page_id) {
+                pages.push(entry.page_id);
+            }
+        }
+
+        let prev_lsn = self.context.state.borrow().last_lsn;
+        self.context.append(&LogRecord::Abort {
+            txn: self.id(),
+            prev_lsn,
+        });
+
+        Ok(pages)
+    }
+
+    /// Finishes transaction without rolling it back, as if the process has crashed.
+    #[cfg(test)]
+    pub fn abandon(mut self) {
+        self.finished = true;
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.undo();
+        }
+    }
+}
+
+/// Page locked for writing by the transaction.
+#[derive(Debug)]
+pub struct PageWriter<'t> {
+    context: &'t Context,
+    page: PageGuard<'t>,
+    before: Vec<u8>,
+}
+
+impl<'t> Deref for PageWriter<'t> {
+    type Target = MemoryPage;
+
+    fn deref(&self) -> &MemoryPage {
+        &self.page
+    }
+}
+
+impl<'t> DerefMut for PageWriter<'t> {
+    fn deref_mut(&mut self) -> &mut MemoryPage {
+        &mut self.page
+    }
+}
+
+impl<'t> Drop for PageWriter<'t> {
+    fn drop(&mut self) {
+        // LSN and checksum are maintained by the storage rather than logged.
+        let changes = PageChange::diff(&self.before,
+                                       self.page.data(),
+                                       (LSN_OFFSET, CHECKSUM_OFFSET + 4));
+
+        if changes.is_empty() {
+            return;
+        }
+
+        let lsn = self.context.log_update(self.page.page_id(), changes);
+
+        if lsn != 0 {
+            self.page.set_lsn(lsn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use storage::Storage;
+    use storage::PageType;
+
+    #[test]
+    fn commit_changes() {
+        let storage = Storage::with_page_size(256);
+
+        let txn = storage.begin();
+        let page_id = txn.allocate_page(PageType::Data, 1).unwrap();
+
+        {
+            let page = storage.get_page(page_id).unwrap().unwrap();
+            txn.write(&page).data_mut()[100] = 42;
+        }
+
+        txn.commit().unwrap();
+
+        assert_eq!(storage.pages(1), vec![page_id]);
+        assert_eq!(storage.get_page(page_id).unwrap().unwrap().lock().data()[100], 42);
+    }
+
+    #[test]
+    fn rollback_changes() {
+        let storage = Storage::with_page_size(256);
+
+        let txn = storage.begin();
+        let first = txn.allocate_page(PageType::Data, 1).unwrap();
+        let chain = txn.write_chain(PageType::Catalog, 0, &[1; 1000]).unwrap();
+        txn.set_catalog_page(chain).unwrap();
+        txn.commit().unwrap();
+
+        let txn = storage.begin();
+
+        {
+            let page = storage.get_page(first).unwrap().unwrap();
+            txn.write(&page).data_mut()[100] = 42;
+        }
+
+        let second = txn.allocate_page(PageType::Data, 1).unwrap();
+        txn.free_chain(chain).unwrap();
+        let other = txn.write_chain(PageType::Catalog, 0, &[2; 10]).unwrap();
+        txn.set_catalog_page(other).unwrap();
+
+        assert_eq!(storage.pages(1), vec![first, second]);
+
+        let mut pages = txn.rollback().unwrap();
+        pages.sort();
+
+        assert_eq!(pages, (1..8).collect::<Vec<_>>());
+        assert_eq!(storage.pages(1), vec![first]);
+        assert_eq!(storage.get_page(first).unwrap().unwrap().lock().data()[100], 0);
+        assert_eq!(storage.catalog_page(), chain);
+        assert_eq!(storage.read_chain(chain).unwrap(), vec![1; 1000]);
+
+        // Transaction which is dropped without commit is rolled back.
+        {
+            let txn = storage.begin();
+            txn.free_page(first).unwrap();
+        }
+
+        assert_eq!(storage.pages(1), vec![first]);
+    }
+}
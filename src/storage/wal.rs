@@ -0,0 +1,758 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use protocol::pack;
+use protocol::unpack;
+use error::Error;
+
+/// Magic number at the beginning of every log file.
+pub const WAL_MAGIC: &[u8; 8] = b"REDDBWAL";
+
+/// Length of the log file header: magic number and LSN of the first record.
+pub const WAL_HEADER_LEN: usize = 16;
+
+/// LSN of the first record of a new log.
+const FIRST_LSN: u64 = 1;
+
+/// Length of the common part of every record: length, kind, transaction id and previous LSN.
+const RECORD_HEADER_LEN: usize = 4 + 1 + 8 + 8;
+
+const BEGIN: u8 = 1;
+const COMMIT: u8 = 2;
+const ABORT: u8 = 3;
+const UPDATE: u8 = 4;
+const COMPENSATION: u8 = 5;
+
+/// Gets path to the log file of the database file.
+pub fn wal_path(path: &Path) -> PathBuf {
+    let mut res = OsString::from(path.as_os_str());
+    res.push("-wal");
+
+    PathBuf::from(res)
+}
+
+/// Change of a continuous range of bytes of the page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageChange {
+    pub offset: usize,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+impl PageChange {
+    /// Gets changes which turn the `before` image into the `after` one.
+    /// Bytes in the `skip` range are not compared. Close changes are merged.
+    pub fn diff(before: &[u8], after: &[u8], skip: (usize, usize)) -> Vec<PageChange> {
+        /// Changes separated by fewer equal bytes are merged into one.
+        const MAX_GAP: usize = 16;
+
+        assert_eq!(before.len(), after.len());
+
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+        for i in 0..before.len() {
+            if before[i] == after[i] || (i >= skip.0 && i < skip.1) {
+                continue;
+            }
+
+            match ranges.last_mut() {
+                Some(range) if i - range.1 <= MAX_GAP => range.1 = i + 1,
+                _ => ranges.push((i, i + 1)),
+            }
+        }
+
+        ranges.into_iter()
+            .map(|(start, end)| {
+                PageChange {
+                    offset: start,
+                    before: before[start..end].to_vec(),
+                    after: after[start..end].to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    /// Gets change which reverts this one.
+    pub fn inverse(&self) -> PageChange {
+        PageChange {
+            offset: self.offset,
+            before: self.after.clone(),
+            after: self.before.clone(),
+        }
+    }
+
+    /// Writes the after image of the change.
+    pub fn apply(&self, data: &mut [u8]) {
+        data[self.offset..self.offset + self.after.len()].copy_from_slice(&self.after);
+    }
+}
+
+/// Record of the write-ahead log.
+///
+/// Every record of a transaction holds LSN of the previous record of the same transaction,
+/// so records of a transaction can be walked backwards during undo.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogRecord {
+    /// Transaction has started.
+    Begin { txn: u64 },
+    /// Transaction has committed.
+    Commit { txn: u64, prev_lsn: u64 },
+    /// Transaction was rolled back completely.
+    Abort { txn: u64, prev_lsn: u64 },
+    /// Page was changed by the transaction.
+    Update {
+        txn: u64,
+        prev_lsn: u64,
+        page_id: u32,
+        changes: Vec<PageChange>,
+    },
+    /// Change of the page was undone. `undo_next_lsn` is LSN of the next record to undo.
+    Compensation {
+        txn: u64,
+        prev_lsn: u64,
+        page_id: u32,
+        changes: Vec<PageChange>,
+        undo_next_lsn: u64,
+    },
+}
+
+impl LogRecord {
+    /// Gets id of the transaction which wrote the record.
+    pub fn txn(&self) -> u64 {
+        match *self {
+            LogRecord::Begin { txn } |
+            LogRecord::Commit { txn, .. } |
+            LogRecord::Abort { txn, .. } |
+            LogRecord::Update { txn, .. } |
+            LogRecord::Compensation { txn, .. } => txn,
+        }
+    }
+
+    /// Gets LSN of the previous record of the same transaction.
+    pub fn prev_lsn(&self) -> u64 {
+        match *self {
+            LogRecord::Begin { .. } => 0,
+            LogRecord::Commit { prev_lsn, .. } |
+            LogRecord::Abort { prev_lsn, .. } |
+            LogRecord::Update { prev_lsn, .. } |
+            LogRecord::Compensation { prev_lsn, .. } => prev_lsn,
+        }
+    }
+
+    /// Serializes record.
+    fn encode(&self) -> Vec<u8> {
+        let mut data = vec![0u8; RECORD_HEADER_LEN];
+
+        let kind = match *self {
+            LogRecord::Begin { .. } => BEGIN,
+            LogRecord::Commit { .. } => COMMIT,
+            LogRecord::Abort { .. } => ABORT,
+            LogRecord::Update { page_id, ref changes, .. } => {
+                encode_changes(&mut data, page_id, changes);
+                UPDATE
+            }
+            LogRecord::Compensation { page_id, ref changes, undo_next_lsn, .. } => {
+                encode_changes(&mut data, page_id, changes);
+                push_u64(&mut data, undo_next_lsn);
+                COMPENSATION
+            }
+        };
+
+        let len = data.len() as u32;
+        pack::pack_unsigned(&mut data[0..], len).expect("Buffer is large enough");
+        data[4] = kind;
+        pack::pack_bigint(&mut data[5..], self.txn() as i64).expect("Buffer is large enough");
+        pack::pack_bigint(&mut data[13..], self.prev_lsn() as i64).expect("Buffer is large enough");
+
+        data
+    }
+
+    /// Deserializes record. Returns `None` if the record is incomplete or malformed,
+    /// which happens to the last record if the log was not written completely.
+    fn decode(data: &[u8]) -> Option<LogRecord> {
+        if data.len() < RECORD_HEADER_LEN {
+            return None;
+        }
+
+        let txn = unpack::unpack_bigint(&data[5..]).ok()? as u64;
+        let prev_lsn = unpack::unpack_bigint(&data[13..]).ok()? as u64;
+        let mut reader = Reader {
+            data,
+            pos: RECORD_HEADER_LEN,
+        };
+
+        let record = match data[4] {
+            BEGIN => LogRecord::Begin { txn },
+            COMMIT => LogRecord::Commit { txn, prev_lsn },
+            ABORT => LogRecord::Abort { txn, prev_lsn },
+            UPDATE => {
+                let (page_id, changes) = reader.changes()?;
+
+                LogRecord::Update {
+                    txn,
+                    prev_lsn,
+                    page_id,
+                    changes,
+                }
+            }
+            COMPENSATION => {
+                let (page_id, changes) = reader.changes()?;
+
+                LogRecord::Compensation {
+                    txn,
+                    prev_lsn,
+                    page_id,
+                    changes,
+                    undo_next_lsn: reader.u64()?,
+                }
+            }
+            _ => return None,
+        };
+
+        if reader.pos != data.len() {
+            return None;
+        }
+
+        Some(record)
+    }
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    let pos = data.len();
+    data.resize(pos + 4, 0);
+    pack::pack_unsigned(&mut data[pos..], value).expect("Buffer is large enough");
+}
+
+fn push_u64(data: &mut Vec<u8>, value: u64) {
+    let pos = data.len();
+    data.resize(pos + 8, 0);
+    pack::pack_bigint(&mut data[pos..], value as i64).expect("Buffer is large enough");
+}
+
+fn encode_changes(data: &mut Vec<u8>, page_id: u32, changes: &[PageChange]) {
+    push_u32(data, page_id);
+    push_u32(data, changes.len() as u32);
+
+    for change in changes {
+        push_u32(data, change.offset as u32);
+        push_u32(data, change.after.len() as u32);
+        data.extend_from_slice(&change.before);
+        data.extend_from_slice(&change.after);
+    }
+}
+
+/// Reader of the record fields, which checks bounds of the record.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() - self.pos < len {
+            return None;
+        }
+
+        self.pos += len;
+        Some(&self.data[self.pos - len..self.pos])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).and_then(|data| unpack::unpack_unsigned(data).ok())
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8).and_then(|data| unpack::unpack_bigint(data).ok()).map(|v| v as u64)
+    }
+
+    fn changes(&mut self) -> Option<(u32, Vec<PageChange>)> {
+        let page_id = self.u32()?;
+        let count = self.u32()?;
+        let mut changes = Vec::new();
+
+        for _ in 0..count {
+            let offset = self.u32()? as usize;
+            let len = self.u32()? as usize;
+
+            changes.push(PageChange {
+                offset,
+                before: self.bytes(len)?.to_vec(),
+                after: self.bytes(len)?.to_vec(),
+            });
+        }
+
+        Some((page_id, changes))
+    }
+}
+
+/// Write-ahead log.
+///
+/// Records are appended to the in-memory buffer and written to the log file when the log is
+/// flushed: on commit and before a changed page is written to the database file. LSN of the
+/// record is its position in the log, so LSNs grow monotonically. When the log is reset,
+/// the file is truncated, but LSNs continue from the end of the previous log.
+///
+/// Log opened read-only is never changed: its records are read into memory, and the records
+/// appended to it are kept there.
+#[derive(Debug)]
+pub struct Wal {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    file: File,
+    start_lsn: u64,
+    flushed_lsn: u64,
+    buffer: Vec<u8>,
+    /// Records of the read-only log, `None` if the log is writable.
+    written: Option<Vec<u8>>,
+}
+
+impl Inner {
+    fn end_lsn(&self) -> u64 {
+        self.flushed_lsn + self.buffer.len() as u64
+    }
+
+    /// Truncates the file and writes the header.
+    fn truncate(&mut self, start_lsn: u64) -> Result<(), Error> {
+        let mut header = [0u8; WAL_HEADER_LEN];
+        header[..WAL_MAGIC.len()].copy_from_slice(WAL_MAGIC);
+        pack::pack_bigint(&mut header[WAL_MAGIC.len()..], start_lsn as i64)?;
+
+        match self.written {
+            Some(ref mut written) => written.clear(),
+            None => {
+                self.file
+                    .set_len(0)
+                    .and_then(|_| self.file.seek(SeekFrom::Start(0)))
+                    .and_then(|_| self.file.write_all(&header))
+                    .and_then(|_| self.file.sync_all())
+                    .map_err(|e| Error::io("Unable to reset log file".to_owned(), e))?;
+            }
+        }
+
+        self.start_lsn = start_lsn;
+        self.flushed_lsn = start_lsn;
+        self.buffer.clear();
+
+        Ok(())
+    }
+
+    /// Reads records written to the log.
+    fn read(&mut self) -> Result<Vec<u8>, Error> {
+        if let Some(ref written) = self.written {
+            return Ok(written.clone());
+        }
+
+        let mut data = Vec::new();
+
+        self.file
+            .seek(SeekFrom::Start(WAL_HEADER_LEN as u64))
+            .and_then(|_| self.file.read_to_end(&mut data))
+            .map_err(|e| Error::io("Unable to read log file".to_owned(), e))?;
+
+        Ok(data)
+    }
+}
+
+impl Wal {
+    /// Creates new empty log file. Existing file is truncated.
+    pub fn create(path: &Path) -> Result<Wal, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| Error::io(format!("Unable to create log file '{}'", path.display()), e))?;
+
+        let mut inner = Inner {
+            file,
+            start_lsn: FIRST_LSN,
+            flushed_lsn: FIRST_LSN,
+            buffer: Vec::new(),
+            written: None,
+        };
+
+        inner.truncate(FIRST_LSN)?;
+
+        Ok(Wal { inner: Mutex::new(inner) })
+    }
+
+    /// Opens existing log file or creates new one if it does not exist. Read-only log must exist
+    /// and is never changed. Complete records of the log can be read with `records`.
+    pub fn open(path: &Path, read_only: bool) -> Result<Wal, Error> {
+        if !path.exists() && !read_only {
+            return Wal::create(path);
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(!read_only)
+            .open(path)
+            .map_err(|e| Error::io(format!("Unable to open log file '{}'", path.display()), e))?;
+
+        let mut header = [0u8; WAL_HEADER_LEN];
+
+        file.read_exact(&mut header)
+            .map_err(|e| Error::io("Unable to read log file header".to_owned(), e))?;
+
+        if &header[..WAL_MAGIC.len()] != WAL_MAGIC {
+            return Err(Error::Corruption("Not a reddb log file: invalid magic number".to_owned()));
+        }
+
+        let start_lsn = unpack::unpack_bigint(&header[WAL_MAGIC.len()..])? as u64;
+
+        let mut inner = Inner {
+            file,
+            start_lsn,
+            flushed_lsn: start_lsn,
+            buffer: Vec::new(),
+            written: None,
+        };
+
+        if read_only {
+            inner.written = Some(inner.read()?);
+        }
+
+        let wal = Wal { inner: Mutex::new(inner) };
+
+        // Log ends after the last complete record, the rest is a partially written record.
+        let end_lsn = match wal.records()?.last() {
+            Some(&(lsn, ref record)) => lsn + record.encode().len() as u64,
+            None => start_lsn,
+        };
+
+        {
+            let mut inner = wal.inner.lock().unwrap();
+            let len = end_lsn - start_lsn;
+
+            match inner.written {
+                Some(ref mut written) => written.truncate(len as usize),
+                None => {
+                    inner.file
+                        .set_len(WAL_HEADER_LEN as u64 + len)
+                        .map_err(|e| Error::io("Unable to truncate log file".to_owned(), e))?;
+                }
+            }
+
+            inner.flushed_lsn = end_lsn;
+        }
+
+        Ok(wal)
+    }
+
+    /// Gets LSN of the first record of the log.
+    pub fn start_lsn(&self) -> u64 {
+        self.inner.lock().unwrap().start_lsn
+    }
+
+    /// Gets LSN which the next appended record will have.
+    pub fn end_lsn(&self) -> u64 {
+        self.inner.lock().unwrap().end_lsn()
+    }
+
+    /// Gets LSN up to which the log is written to the disk.
+    pub fn flushed_lsn(&self) -> u64 {
+        self.inner.lock().unwrap().flushed_lsn
+    }
+
+    /// Appends record to the log buffer and returns its LSN.
+    pub fn append(&self, record: &LogRecord) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+
+        let lsn = inner.end_lsn();
+        inner.buffer.extend_from_slice(&record.encode());
+
+        lsn
+    }
+
+    /// Writes buffered records to the disk, so the record with the LSN becomes durable.
+    pub fn flush(&self, lsn: u64) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if lsn < inner.flushed_lsn || inner.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let pos = WAL_HEADER_LEN as u64 + (inner.flushed_lsn - inner.start_lsn);
+        let inner = &mut *inner;
+
+        match inner.written {
+            Some(ref mut written) => written.extend_from_slice(&inner.buffer),
+            None => {
+                inner.file
+                    .seek(SeekFrom::Start(pos))
+                    .and_then(|_| inner.file.write_all(&inner.buffer))
+                    .and_then(|_| inner.file.sync_data())
+                    .map_err(|e| Error::io("Unable to write log file".to_owned(), e))?;
+            }
+        }
+
+        inner.flushed_lsn += inner.buffer.len() as u64;
+        inner.buffer.clear();
+
+        Ok(())
+    }
+
+    /// Reads all complete records written to the log file, along with their LSNs.
+    pub fn records(&self) -> Result<Vec<(u64, LogRecord)>, Error> {
+        let mut inner = self.inner.lock().unwrap();
+        let data = inner.read()?;
+
+        let mut records = Vec::new();
+        let mut pos = 0;
+
+        while data.len() - pos >= 4 {
+            let len = unpack::unpack_unsigned(&data[pos..])? as usize;
+
+            if len < RECORD_HEADER_LEN || data.len() - pos < len {
+                break;
+            }
+
+            match LogRecord::decode(&data[pos..pos + len]) {
+                Some(record) => records.push((inner.start_lsn + pos as u64, record)),
+                None => break,
+            }
+
+            pos += len;
+        }
+
+        Ok(records)
+    }
+
+    /// Discards all records of the log. LSNs of the new records continue from the end of the log,
+    /// but are not less than `min_lsn`.
+    /// Must be called only when all changed pages are written to the database file.
+    pub fn reset(&self, min_lsn: u64) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap();
+        let start_lsn = inner.end_lsn().max(min_lsn);
+
+        inner.truncate(start_lsn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use storage::wal::{Wal, LogRecord, PageChange, WAL_HEADER_LEN};
+    use test_utils::temp_path;
+
+    fn records() -> Vec<LogRecord> {
+        vec![LogRecord::Begin { txn: 1 },
+             LogRecord::Update {
+                 txn: 1,
+                 prev_lsn: 1,
+                 page_id: 3,
+                 changes: vec![PageChange {
+                                   offset: 10,
+                                   before: vec![0, 0],
+                                   after: vec![1, 2],
+                               },
+                               PageChange {
+                                   offset: 100,
+                                   before: vec![3],
+                                   after: vec![4],
+                               }],
+             },
+             LogRecord::Compensation {
+                 txn: 1,
+                 prev_lsn: 22,
+                 page_id: 3,
+                 changes: vec![],
+                 undo_next_lsn: 1,
+             },
+             LogRecord::Abort {
+                 txn: 1,
+                 prev_lsn: 60,
+             },
+             LogRecord::Commit {
+                 txn: 2,
+                 prev_lsn: 0,
+             }]
+    }
+
+    #[test]
+    fn diff_pages() {
+        let before = vec![0u8; 100];
+        let mut after = before.clone();
+
+        assert_eq!(PageChange::diff(&before, &after, (0, 0)), vec![]);
+
+        after[5] = 1;
+        after[10] = 2;
+        after[50] = 3;
+        after[90] = 4;
+
+        let changes = PageChange::diff(&before, &after, (90, 92));
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].offset, 5);
+        assert_eq!(changes[0].after, vec![1, 0, 0, 0, 0, 2]);
+        assert_eq!(changes[1].offset, 50);
+
+        let mut data = before.clone();
+        for change in &changes {
+            change.apply(&mut data);
+        }
+
+        after[90] = 0;
+        assert_eq!(data, after);
+
+        for change in changes.iter().rev() {
+            change.inverse().apply(&mut data);
+        }
+
+        assert_eq!(data, before);
+    }
+
+    #[test]
+    fn write_read_records() {
+        let path = temp_path("write_read_records");
+
+        {
+            let wal = Wal::create(&path).unwrap();
+            let mut lsns = Vec::new();
+
+            for record in records() {
+                lsns.push(wal.append(&record));
+            }
+
+            assert_eq!(lsns[0], wal.start_lsn());
+            assert!(lsns.windows(2).all(|w| w[0] < w[1]));
+
+            // Records are not written until the log is flushed.
+            assert_eq!(wal.records().unwrap(), vec![]);
+
+            wal.flush(lsns[4]).unwrap();
+
+            assert_eq!(wal.flushed_lsn(), wal.end_lsn());
+            assert_eq!(wal.records().unwrap(),
+                       lsns.into_iter().zip(records()).collect::<Vec<_>>());
+        }
+
+        let wal = Wal::open(&path, false).unwrap();
+
+        assert_eq!(wal.records().unwrap().len(), 5);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ignore_incomplete_records() {
+        let path = temp_path("ignore_incomplete_records");
+
+        {
+            let wal = Wal::create(&path).unwrap();
+
+            for record in records() {
+                let lsn = wal.append(&record);
+                wal.flush(lsn).unwrap();
+            }
+        }
+
+        let data = fs::read(&path).unwrap();
+        let mut counts = Vec::new();
+
+        for truncated in WAL_HEADER_LEN..data.len() + 1 {
+            fs::write(&path, &data[..truncated]).unwrap();
+
+            let wal = Wal::open(&path, false).unwrap();
+            let read = wal.records().unwrap();
+
+            assert_eq!(read.iter().map(|r| r.1.clone()).collect::<Vec<_>>(),
+                       records()[..read.len()].to_vec());
+
+            // New records are appended after the last complete record.
+            let lsn = wal.append(&LogRecord::Begin { txn: 10 });
+            wal.flush(lsn).unwrap();
+            assert_eq!(wal.records().unwrap().last().unwrap(),
+                       &(lsn, LogRecord::Begin { txn: 10 }));
+
+            counts.push(read.len());
+        }
+
+        assert_eq!(counts.first(), Some(&0));
+        assert_eq!(counts.last(), Some(&5));
+        assert!(counts.contains(&4));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keep_records_of_read_only_log_in_memory() {
+        let path = temp_path("keep_records_of_read_only_log_in_memory");
+
+        Wal::open(&path, true).unwrap_err();
+
+        {
+            let wal = Wal::create(&path).unwrap();
+
+            for record in records() {
+                wal.append(&record);
+            }
+
+            wal.flush(wal.end_lsn()).unwrap();
+        }
+
+        // Incomplete record is ignored, but not truncated.
+        let mut data = fs::read(&path).unwrap();
+        data.pop();
+        fs::write(&path, &data).unwrap();
+
+        let wal = Wal::open(&path, true).unwrap();
+        assert_eq!(wal.records().unwrap().len(), 4);
+
+        let lsn = wal.append(&LogRecord::Begin { txn: 10 });
+        wal.flush(lsn).unwrap();
+        assert_eq!(wal.records().unwrap().last().unwrap(),
+                   &(lsn, LogRecord::Begin { txn: 10 }));
+
+        wal.reset(0).unwrap();
+        assert_eq!(wal.records().unwrap(), vec![]);
+
+        assert_eq!(fs::read(&path).unwrap(), data);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reset_log() {
+        let path = temp_path("reset_log");
+
+        let wal = Wal::create(&path).unwrap();
+
+        let lsn = wal.append(&LogRecord::Begin { txn: 1 });
+        wal.flush(lsn).unwrap();
+
+        wal.reset(0).unwrap();
+
+        assert_eq!(wal.records().unwrap(), vec![]);
+        assert!(wal.start_lsn() > lsn);
+
+        let next = wal.append(&LogRecord::Begin { txn: 2 });
+        wal.flush(next).unwrap();
+
+        assert!(next > lsn);
+        drop(wal);
+
+        let wal = Wal::open(&path, false).unwrap();
+        assert_eq!(wal.records().unwrap(), vec![(next, LogRecord::Begin { txn: 2 })]);
+
+        // LSNs may be moved forward, e.g. to continue from the last checkpoint.
+        wal.reset(1000).unwrap();
+        assert_eq!(wal.append(&LogRecord::Begin { txn: 3 }), 1000);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
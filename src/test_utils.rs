@@ -0,0 +1,23 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+
+use storage::wal_path;
+
+/// Gets path to a file in the temporary directory, unique for the test.
+/// Removes the database file and its log if they are left from the previous run.
+pub fn temp_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("reddb-{}-{}.db", process::id(), name));
+
+    remove_database(&path);
+
+    path
+}
+
+/// Removes the database file and its log.
+pub fn remove_database(path: &Path) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(wal_path(path));
+}
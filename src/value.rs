@@ -0,0 +1,355 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+use data_type::DataType;
+use collation::Collation;
+use error::Error;
+
+/// Single value stored in a table cell.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Varchar(String),
+    Varbinary(Vec<u8>),
+    Boolean(bool),
+    Smallint(i16),
+    Integer(i32),
+    Bigint(i64),
+    Float(f64),
+}
+
+impl Value {
+    /// Get type of the value. NULL does not have a type.
+    pub fn data_type(&self) -> Option<DataType> {
+        match *self {
+            Value::Null => None,
+            Value::Varchar(_) => Some(DataType::VARCHAR),
+            Value::Varbinary(_) => Some(DataType::VARBINARY),
+            Value::Boolean(_) => Some(DataType::BOOLEAN),
+            Value::Smallint(_) => Some(DataType::SMALLINT),
+            Value::Integer(_) => Some(DataType::INTEGER),
+            Value::Bigint(_) => Some(DataType::BIGINT),
+            Value::Float(_) => Some(DataType::FLOAT),
+        }
+    }
+
+    /// Check if the value is NULL.
+    pub fn is_null(&self) -> bool {
+        matches!(*self, Value::Null)
+    }
+
+    /// Get number of bytes needed to serialize the value. NULL is not serialized.
+    pub fn serialized_len(&self) -> usize {
+        match *self {
+            Value::Null => 0,
+            Value::Varchar(ref s) => DataType::VARCHAR.static_len() + s.len(),
+            Value::Varbinary(ref b) => DataType::VARBINARY.static_len() + b.len(),
+            _ => self.data_type().unwrap().static_len(),
+        }
+    }
+
+    /// Get string slice if the value is VARCHAR.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::Varchar(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get byte slice if the value is VARBINARY.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Value::Varbinary(ref b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Convert integer value to the wider integer type, see `DataType::widens_to`.
+    /// Other values are returned as they are.
+    pub fn widen(self, data_type: DataType) -> Value {
+        match (self, data_type) {
+            (Value::Smallint(v), DataType::INTEGER) => Value::Integer(i32::from(v)),
+            (Value::Smallint(v), DataType::BIGINT) => Value::Bigint(i64::from(v)),
+            (Value::Integer(v), DataType::BIGINT) => Value::Bigint(i64::from(v)),
+            (value, _) => value,
+        }
+    }
+
+    /// Compares values of the same type in a total order: integers and floats by their numeric
+    /// value, `false` before `true`, VARCHAR by the collation and VARBINARY byte by byte.
+    /// Floats are ordered by `compare_floats`. Panics if the values have different types.
+    pub fn total_cmp(&self, other: &Value, collation: Collation) -> Ordering {
+        match (self, other) {
+            (Value::Varchar(a), Value::Varchar(b)) => collation.compare(a, b),
+            (Value::Varbinary(a), Value::Varbinary(b)) => a.cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::Smallint(a), Value::Smallint(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Bigint(a), Value::Bigint(b)) => a.cmp(b),
+            (Value::Float(a), Value::Float(b)) => compare_floats(*a, *b),
+            (a, b) => panic!("Unable to compare {:?} and {:?}", a, b),
+        }
+    }
+
+    /// Get value of any integer type widened to i64.
+    fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Smallint(v) => Some(i64::from(v)),
+            Value::Integer(v) => Some(i64::from(v)),
+            Value::Bigint(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Compares floats in a total order: -0.0 is equal to 0.0 and NaN is equal to NaN and greater
+/// than any other value, including infinity.
+fn compare_floats(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.partial_cmp(&b).expect("Numbers are ordered"),
+    }
+}
+
+/// NULL is equal to NULL and less than any other value.
+/// Integers of different width are compared by their numeric value.
+/// Values of other different types are not comparable.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            return a.partial_cmp(&b);
+        }
+
+        match (self, other) {
+            (Value::Null, Value::Null) => Some(Ordering::Equal),
+            (Value::Null, _) => Some(Ordering::Less),
+            (_, Value::Null) => Some(Ordering::Greater),
+            (Value::Varchar(a), Value::Varchar(b)) => a.partial_cmp(b),
+            (Value::Varbinary(a), Value::Varbinary(b)) => a.partial_cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(val: bool) -> Value {
+        Value::Boolean(val)
+    }
+}
+
+impl From<i16> for Value {
+    fn from(val: i16) -> Value {
+        Value::Smallint(val)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(val: i32) -> Value {
+        Value::Integer(val)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(val: i64) -> Value {
+        Value::Bigint(val)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(val: f64) -> Value {
+        Value::Float(val)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(val: &'a str) -> Value {
+        Value::Varchar(val.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(val: String) -> Value {
+        Value::Varchar(val)
+    }
+}
+
+impl<'a> From<&'a [u8]> for Value {
+    fn from(val: &'a [u8]) -> Value {
+        Value::Varbinary(val.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(val: Vec<u8>) -> Value {
+        Value::Varbinary(val)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(val: Option<T>) -> Value {
+        match val {
+            Some(val) => val.into(),
+            None => Value::Null,
+        }
+    }
+}
+
+fn conversion_error(val: &Value, target: &str) -> Error {
+    Error::InvalidArgument(format!("Unable to convert {:?} to {}", val, target))
+}
+
+impl TryFrom<Value> for bool {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<bool, Error> {
+        match val {
+            Value::Boolean(v) => Ok(v),
+            _ => Err(conversion_error(&val, "bool")),
+        }
+    }
+}
+
+impl TryFrom<Value> for i16 {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<i16, Error> {
+        match val {
+            Value::Smallint(v) => Ok(v),
+            _ => Err(conversion_error(&val, "i16")),
+        }
+    }
+}
+
+impl TryFrom<Value> for i32 {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<i32, Error> {
+        match val {
+            Value::Smallint(v) => Ok(i32::from(v)),
+            Value::Integer(v) => Ok(v),
+            _ => Err(conversion_error(&val, "i32")),
+        }
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<i64, Error> {
+        match val.as_i64() {
+            Some(v) => Ok(v),
+            None => Err(conversion_error(&val, "i64")),
+        }
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<f64, Error> {
+        match val {
+            Value::Float(v) => Ok(v),
+            _ => Err(conversion_error(&val, "f64")),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<String, Error> {
+        match val {
+            Value::Varchar(v) => Ok(v),
+            _ => Err(conversion_error(&val, "String")),
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<u8> {
+    type Error = Error;
+
+    fn try_from(val: Value) -> Result<Vec<u8>, Error> {
+        match val {
+            Value::Varbinary(v) => Ok(v),
+            _ => Err(conversion_error(&val, "Vec<u8>")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+    use std::convert::TryFrom;
+
+    use data_type::DataType;
+    use value::Value;
+
+    #[test]
+    fn data_types() {
+        assert_eq!(Value::Null.data_type(), None);
+        assert_eq!(Value::from("a").data_type(), Some(DataType::VARCHAR));
+        assert_eq!(Value::from(vec![1u8]).data_type(), Some(DataType::VARBINARY));
+        assert_eq!(Value::from(true).data_type(), Some(DataType::BOOLEAN));
+        assert_eq!(Value::from(1i16).data_type(), Some(DataType::SMALLINT));
+        assert_eq!(Value::from(1i32).data_type(), Some(DataType::INTEGER));
+        assert_eq!(Value::from(1i64).data_type(), Some(DataType::BIGINT));
+        assert_eq!(Value::from(1.0).data_type(), Some(DataType::FLOAT));
+        assert!(Value::from(None::<i32>).is_null());
+    }
+
+    #[test]
+    fn convert_to_primitives() {
+        assert_eq!(i32::try_from(Value::from(7i16)).unwrap(), 7);
+        assert_eq!(i64::try_from(Value::from(7i32)).unwrap(), 7);
+        assert_eq!(String::try_from(Value::from("foo")).unwrap(), "foo");
+        assert_eq!(Vec::<u8>::try_from(Value::from(&[1u8, 2][..])).unwrap(), vec![1, 2]);
+        assert!(bool::try_from(Value::from(true)).unwrap());
+        assert_eq!(f64::try_from(Value::from(0.5)).unwrap(), 0.5);
+
+        i16::try_from(Value::from(7i32)).unwrap_err();
+        i32::try_from(Value::Null).unwrap_err();
+        String::try_from(Value::from(vec![1u8])).unwrap_err();
+    }
+
+    #[test]
+    fn widen_values() {
+        assert_eq!(Value::from(7i16).widen(DataType::INTEGER).data_type(), Some(DataType::INTEGER));
+        assert_eq!(Value::from(7i16).widen(DataType::BIGINT).data_type(), Some(DataType::BIGINT));
+        assert_eq!(Value::from(-7i32).widen(DataType::BIGINT), Value::Bigint(-7));
+        assert_eq!(Value::from(7i64).widen(DataType::SMALLINT).data_type(),
+                   Some(DataType::BIGINT));
+        assert!(Value::Null.widen(DataType::BIGINT).is_null());
+
+        assert!(DataType::SMALLINT.widens_to(DataType::BIGINT));
+        assert!(!DataType::BIGINT.widens_to(DataType::INTEGER));
+        assert!(!DataType::INTEGER.widens_to(DataType::INTEGER));
+        assert!(!DataType::INTEGER.widens_to(DataType::FLOAT));
+    }
+
+    #[test]
+    fn compare_values() {
+        assert!(Value::from(1i16) < Value::from(2i64));
+        assert_eq!(Value::from(3i32), Value::from(3i64));
+        assert!(Value::from("abc") < Value::from("abd"));
+        assert!(Value::from(vec![1u8]) < Value::from(vec![1u8, 0]));
+        assert!(Value::from(false) < Value::from(true));
+        assert!(Value::from(-0.5) < Value::from(0.5));
+        assert!(Value::Null < Value::from(i64::MIN));
+        assert_eq!(Value::Null, Value::Null);
+
+        assert_eq!(Value::from("1").partial_cmp(&Value::from(1)), None);
+        assert_eq!(Value::from(1.0).partial_cmp(&Value::from(1)), None);
+        assert!(Value::from(f64::NAN) != Value::from(f64::NAN));
+        assert_eq!(Value::from(1).partial_cmp(&Value::from(1)), Some(Ordering::Equal));
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+
+use database::RowId;
+use storage::CorruptPage;
+
+/// Problem found by the integrity check of the database.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// Content of the page in the database file does not match its checksum.
+    CorruptPage(CorruptPage),
+    /// Page header or slot directory of the page is invalid.
+    InvalidPage { page_id: u32, reason: String },
+    /// Page is allocated, but does not belong to any table.
+    OrphanPage { page_id: u32, owner: u32 },
+    /// Catalog can not be read or does not match the tables.
+    InvalidCatalog(String),
+    /// Row can not be read.
+    InvalidRow {
+        table: String,
+        row: RowId,
+        reason: String,
+    },
+    /// Tree of the index is invalid.
+    InvalidIndex {
+        table: String,
+        index: String,
+        reason: String,
+    },
+    /// Entries of the index do not match the rows of the table: a row is missing from
+    /// the index or an entry refers to a row which does not exist.
+    IndexMismatch {
+        table: String,
+        index: String,
+        row: RowId,
+        reason: String,
+    },
+}
+
+impl Problem {
+    /// Gets id of the page with the problem, if the problem is limited to a single page.
+    pub fn page_id(&self) -> Option<u32> {
+        match *self {
+            Problem::CorruptPage(ref e) => Some(e.page_id),
+            Problem::InvalidPage { page_id, .. } |
+            Problem::OrphanPage { page_id, .. } => Some(page_id),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::CorruptPage(ref e) => write!(f, "{}", e),
+            Problem::InvalidPage { page_id, ref reason } => {
+                write!(f, "Page {} is invalid: {}", page_id, reason)
+            }
+            Problem::OrphanPage { page_id, owner } => {
+                write!(f, "Page {} is owned by unknown object {}", page_id, owner)
+            }
+            Problem::InvalidCatalog(ref reason) => write!(f, "Catalog is invalid: {}", reason),
+            Problem::InvalidRow { ref table, row, ref reason } => {
+                write!(f, "Row {:?} of table '{}' is invalid: {}", row, table, reason)
+            }
+            Problem::InvalidIndex { ref table, ref index, ref reason } => {
+                write!(f, "Index '{}' of table '{}' is invalid: {}", index, table, reason)
+            }
+            Problem::IndexMismatch { ref table, ref index, row, ref reason } => {
+                write!(f,
+                       "Index '{}' of table '{}' does not match row {:?}: {}",
+                       index,
+                       table,
+                       row,
+                       reason)
+            }
+        }
+    }
+}
+
+/// Report of the integrity check of the database, see `Database::verify`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerifyReport {
+    /// Number of checked pages, not including the header page.
+    pub pages: usize,
+    /// Number of checked tables.
+    pub tables: usize,
+    /// Number of checked rows which are not deleted.
+    pub rows: usize,
+    /// Problems found by the check.
+    pub problems: Vec<Problem>,
+}
+
+impl VerifyReport {
+    /// Check if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Check if a problem was found in the page.
+    pub fn has_page_problem(&self, page_id: u32) -> bool {
+        self.problems.iter().any(|p| p.page_id() == Some(page_id))
+    }
+
+    /// Check if a problem was found in an index, which is fixed by rebuilding the indexes,
+    /// see `Database::repair_indexes`.
+    pub fn has_index_problem(&self) -> bool {
+        self.problems.iter().any(|p| {
+            matches!(*p, Problem::InvalidIndex { .. } | Problem::IndexMismatch { .. })
+        })
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Pages checked: {}", self.pages)?;
+        writeln!(f, "Tables checked: {}", self.tables)?;
+        writeln!(f, "Rows checked: {}", self.rows)?;
+
+        if self.is_ok() {
+            return writeln!(f, "No problems found");
+        }
+
+        writeln!(f, "Problems found: {}", self.problems.len())?;
+
+        for problem in &self.problems {
+            writeln!(f, "  {}", problem)?;
+        }
+
+        Ok(())
+    }
+}